@@ -0,0 +1,50 @@
+use bazuka::blockchain::verify_pow;
+use bazuka::core::{Header, ProofOfWork};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// Easiest possible target, so that every header passes and none of them
+// short-circuits the verification
+const TARGET: u32 = 0x00ffffff;
+const POW_KEY: &[u8] = b"BAZUKA BASE KEY";
+
+fn headers(count: u64) -> (Vec<Header>, Vec<Vec<u8>>) {
+    let headers = (1..=count)
+        .map(|number| Header {
+            number,
+            proof_of_work: ProofOfWork {
+                target: TARGET,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let keys = vec![POW_KEY.to_vec(); headers.len()];
+    (headers, keys)
+}
+
+// What `will_extend` used to do, one header after the other
+fn verify_pow_serial(headers: &[Header], pow_keys: &[Vec<u8>]) -> Option<u64> {
+    headers
+        .iter()
+        .zip(pow_keys.iter())
+        .map(|(h, key)| h.meets_target(key).then(|| h.power()))
+        .sum()
+}
+
+fn header_verification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("header_pow");
+    group.sample_size(10);
+    for count in [64, 512] {
+        let (headers, keys) = headers(count);
+        group.bench_with_input(BenchmarkId::new("serial", count), &count, |b, _| {
+            b.iter(|| verify_pow_serial(&headers, &keys).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", count), &count, |b, _| {
+            b.iter(|| verify_pow(&headers, &keys).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, header_verification);
+criterion_main!(benches);
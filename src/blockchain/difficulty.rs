@@ -0,0 +1,152 @@
+use crate::config;
+use crate::utils;
+
+// Median of the timestamps of the last MEDIAN_TIMESTAMP_COUNT blocks
+pub fn median_time_past(timestamps: &[u32]) -> u32 {
+    let mut sorted = timestamps.to_vec();
+    sorted.sort_unstable();
+    utils::median(&sorted)
+}
+
+// Median-time-past of the block at `index`, given a lookup of block timestamps
+pub fn median_time_past_at<E, F: Fn(usize) -> Result<u32, E>>(
+    index: usize,
+    timestamp: F,
+) -> Result<u32, E> {
+    Ok(median_time_past(
+        &(0..std::cmp::min(index + 1, config::MEDIAN_TIMESTAMP_COUNT))
+            .map(|i| timestamp(index - i))
+            .collect::<Result<Vec<u32>, E>>()?,
+    ))
+}
+
+// Target the block at `height` should have. It only changes every
+// DIFFICULTY_CALC_INTERVAL blocks, otherwise the last target is kept.
+pub fn next_target<E, F: Fn(usize) -> Result<u32, E>>(
+    height: usize,
+    last_target: u32,
+    timestamp: F,
+) -> Result<u32, E> {
+    if height == 0 || height % config::DIFFICULTY_CALC_INTERVAL != 0 {
+        return Ok(last_target);
+    }
+    let timespan = retarget_timespan(
+        median_time_past_at(height - config::DIFFICULTY_CALC_INTERVAL, &timestamp)?,
+        median_time_past_at(height - 1, &timestamp)?,
+    );
+    Ok(retarget(last_target, timespan))
+}
+
+// Time DIFFICULTY_CALC_INTERVAL blocks are expected to take
+pub fn expected_timespan() -> u32 {
+    (config::BLOCK_TIME * (config::DIFFICULTY_CALC_INTERVAL - 1)) as u32
+}
+
+// The measured timespan of a retarget window, between the median-time-past of its
+// endpoints, so that a miner can't warp it by lying about a single timestamp. It's
+// also clamped, so that a window can't crash (Or spike) the difficulty at once.
+pub fn retarget_timespan(start_mtp: u32, end_mtp: u32) -> u32 {
+    let expected = expected_timespan();
+    end_mtp.saturating_sub(start_mtp).clamp(
+        expected / config::RETARGET_CLAMP_FACTOR,
+        expected * config::RETARGET_CLAMP_FACTOR,
+    )
+}
+
+// New difficulty target, given the previous target and the measured timespan of
+// the last retarget window.
+pub fn retarget(target: u32, timespan: u32) -> u32 {
+    let expected = expected_timespan();
+    let timespan = timespan.clamp(
+        expected / config::RETARGET_CLAMP_FACTOR,
+        expected * config::RETARGET_CLAMP_FACTOR,
+    );
+    let diff_change = expected as f32 / timespan as f32;
+    rust_randomx::Difficulty::new(target)
+        .scale(diff_change)
+        .to_u32()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mtp_at(timestamps: &[u32], index: usize) -> u32 {
+        median_time_past_at(index, |i| Ok::<u32, ()>(timestamps[i])).unwrap()
+    }
+
+    #[test]
+    fn test_timespan_is_clamped() {
+        let expected = expected_timespan();
+        assert_eq!(retarget_timespan(100, 100 + expected), expected);
+        assert_eq!(
+            retarget_timespan(0, u32::MAX),
+            expected * config::RETARGET_CLAMP_FACTOR
+        );
+        assert_eq!(
+            retarget_timespan(0, 1),
+            expected / config::RETARGET_CLAMP_FACTOR
+        );
+        // Timestamps going backwards can't underflow
+        assert_eq!(
+            retarget_timespan(1000, 0),
+            expected / config::RETARGET_CLAMP_FACTOR
+        );
+    }
+
+    #[test]
+    fn test_time_warp() {
+        let interval = config::DIFFICULTY_CALC_INTERVAL;
+        let block_time = config::BLOCK_TIME as u32;
+        let mut timestamps = (0..interval as u32 * 2)
+            .map(|i| i * block_time)
+            .collect::<Vec<_>>();
+
+        // Attacker sets the first block of the window to the smallest timestamp
+        // allowed, and the last block of the window far in the future.
+        let start = interval;
+        let end = 2 * interval - 1;
+        timestamps[start] = mtp_at(&timestamps, start - 1);
+        timestamps[end] += 7 * 24 * 3600;
+
+        // Using the raw endpoints, difficulty would be dropped as much as possible
+        let naive = timestamps[end] - timestamps[start];
+        assert_eq!(
+            retarget_timespan(0, naive),
+            expected_timespan() * config::RETARGET_CLAMP_FACTOR
+        );
+
+        // Median-time-past endpoints ignore the lies
+        let timespan = retarget_timespan(mtp_at(&timestamps, start), mtp_at(&timestamps, end));
+        assert!(timespan.abs_diff(expected_timespan()) <= block_time);
+    }
+
+    #[test]
+    fn test_next_target() {
+        let interval = config::DIFFICULTY_CALC_INTERVAL;
+        let block_time = config::BLOCK_TIME as u32;
+        let target = 0x02ffffff;
+        let timestamps = |speedup: u32| {
+            (0..interval as u32 * 3)
+                .map(|i| i * block_time / speedup)
+                .collect::<Vec<_>>()
+        };
+        let next = |height: usize, timestamps: &[u32]| {
+            next_target(height, target, |i| Ok::<u32, ()>(timestamps[i])).unwrap()
+        };
+        let power = |target: u32| rust_randomx::Difficulty::new(target).power();
+
+        // Only changes on retarget heights
+        assert_eq!(next(2 * interval + 1, &timestamps(2)), target);
+        let same = next(2 * interval, &timestamps(1));
+        assert!(power(same).abs_diff(power(target)) <= power(target) / 50);
+
+        // Blocks twice as fast, twice as hard
+        let harder = next(2 * interval, &timestamps(2));
+        assert!(power(harder) > power(target) * 19 / 10);
+
+        // At most 4x at once, no matter how fast the blocks were
+        let capped = next(2 * interval, &timestamps(100));
+        assert!(power(capped) <= power(target) * 41 / 10);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::core::hash::Hash;
+use crate::core::Hasher;
+use serde::{Deserialize, Serialize};
+
+// End of a known branch, either the current chain or a side-chain whose blocks
+// are kept in the fork store
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChainTip {
+    pub hash: <Hasher as Hash>::Output,
+    pub number: u64,
+    // Blocks shared with the current chain
+    pub fork_height: usize,
+    // Blocks of the branch after the fork point, 0 for the current chain
+    pub branch_length: usize,
+    pub active: bool,
+}
+
+pub(crate) fn fork_key(hash: &[u8]) -> String {
+    format!("fork_{}", hex::encode(hash))
+}
+
+// Stored blocks by number, for pruning the store without reading the blocks
+pub(crate) fn fork_number_key(number: u64, hash: &[u8]) -> String {
+    format!("forknumber_{:010}_{}", number, hex::encode(hash))
+}
+
+// Stored blocks no other stored block builds on
+pub(crate) fn fork_tip_key(hash: &[u8]) -> String {
+    format!("forktip_{}", hex::encode(hash))
+}
+
+// Hash of a block, given one of the keys above
+pub(crate) fn fork_key_hash(key: &str) -> Option<Vec<u8>> {
+    hex::decode(key.rsplit('_').next()?).ok()
+}
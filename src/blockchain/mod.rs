@@ -2,11 +2,65 @@ use thiserror::Error;
 
 use crate::config;
 use crate::config::{genesis, TOTAL_SUPPLY};
-use crate::core::{Account, Address, Block, Header, Transaction, TransactionData};
-use crate::db::{KvStore, KvStoreError, RamMirrorKvStore, StringKey, WriteOp};
-use crate::utils;
-use crate::wallet::Wallet;
-use crate::zk::ZkState;
+use crate::core::hash::Hash;
+use crate::core::{
+    is_approved, tx_weight, Account, AccountV0, Address, Block, BlockMetadata, Checkpoint,
+    Contract, ContractId, GovernanceParam, Hasher, Header, LockedFunds, Money, PaymentDirection,
+    Proposal, ProposalId, Signer, Transaction, TransactionData, TransactionId, TransactionLocation,
+    TxReceipt, Unbonding, Validator, VRF_PUBLIC_KEY_LENGTH,
+};
+use crate::crypto::SignatureScheme;
+use crate::db::{Blob, DbStats, KvStore, KvStoreError, RamMirrorKvStore, StringKey, WriteOp};
+use crate::zk::{self, ZkCompressedState, ZkState};
+use std::collections::HashMap;
+
+#[cfg(feature = "pow")]
+mod proof;
+#[cfg(feature = "pow")]
+pub use proof::*;
+
+#[cfg(feature = "pow")]
+pub mod relay;
+
+#[cfg(feature = "pow")]
+mod difficulty;
+#[cfg(feature = "pow")]
+pub use difficulty::*;
+
+#[cfg(feature = "pos")]
+mod proposal;
+#[cfg(feature = "pos")]
+pub use proposal::*;
+#[cfg(feature = "pos")]
+use {
+    crate::consensus::pos::{validator_epoch, Authority, Epoch, Slot},
+    crate::crypto::VRFPublicKey,
+};
+
+mod reward;
+pub use reward::*;
+
+mod reorgs;
+use reorgs::reorg_key;
+pub use reorgs::ReorgRecord;
+
+mod export;
+pub use export::*;
+
+mod randomness;
+pub use randomness::*;
+
+mod forks;
+pub use forks::ChainTip;
+use forks::{fork_key, fork_key_hash, fork_number_key, fork_tip_key};
+
+mod verify;
+pub use verify::*;
+
+#[cfg(feature = "pow")]
+mod stats;
+#[cfg(feature = "pow")]
+pub use stats::*;
 
 #[derive(Error, Debug)]
 pub enum BlockchainError {
@@ -20,34 +74,198 @@ pub enum BlockchainError {
     Inconsistency,
     #[error("block not found")]
     BlockNotFound,
+    #[error("block body has been pruned")]
+    BlockPruned,
     #[error("cannot extend from the genesis block")]
     ExtendFromGenesis,
     #[error("cannot extend from very future blocks")]
     ExtendFromFuture,
+    #[error("cannot rewind the chain to height {0}")]
+    InvalidRewindHeight(usize),
     #[error("block number invalid")]
     InvalidBlockNumber,
     #[error("parent hash invalid")]
     InvalidParentHash,
     #[error("merkle root invalid")]
     InvalidMerkleRoot,
+    #[error("transaction count invalid")]
+    InvalidTransactionCount,
     #[error("transaction nonce invalid")]
     InvalidTransactionNonce,
     #[error("block timestamp is in past")]
     InvalidTimestamp,
     #[error("unmet difficulty target")]
     DifficultyTargetUnmet,
+    #[error("difficulty target invalid")]
+    InvalidDifficultyTarget,
+    #[error("chain proof invalid")]
+    InvalidChainProof,
+    #[error("relay header invalid")]
+    InvalidRelayHeader,
+    #[error("checkpoint invalid")]
+    InvalidCheckpoint,
+    #[error("block conflicts with a checkpoint")]
+    CheckpointConflict,
+    #[error("transaction type not supported by this version")]
+    UnsupportedTransaction,
+    #[error("database was written by an incompatible version, a resync is required")]
+    IncompatibleDatabase,
+    #[error("block reward invalid")]
+    InvalidReward,
+    #[error("chain is not heavier than the current one")]
+    WeakerChain,
+    #[error("contract not found")]
+    ContractNotFound,
+    #[error("update circuit not found")]
+    CircuitNotFound,
+    #[error("contract balance insufficient")]
+    ContractBalanceInsufficient,
+    #[error("contract payment invalid")]
+    InvalidContractPayment,
+    #[error("zk proof invalid")]
+    InvalidZkProof,
+    #[error("contract state transitions are not supported by this build")]
+    ZkProofsUnsupported,
+    #[error("validator VRF public key invalid")]
+    InvalidValidatorKey,
+    #[error("the Treasury can't be a validator")]
+    TreasuryValidator,
+    #[error("validator not found")]
+    ValidatorNotFound,
+    #[error("stake insufficient")]
+    StakeInsufficient,
+    #[error("slash evidence invalid")]
+    InvalidSlashEvidence,
+    #[error("slash evidence already used")]
+    SlashEvidenceUsed,
+    #[error("governance proposal invalid")]
+    InvalidGovernanceProposal,
+    #[error("governance proposal not found")]
+    ProposalNotFound,
+    #[error("batch send invalid")]
+    InvalidBatchSend,
+    #[error("block weight exceeds the limit")]
+    BlockTooHeavy,
+    #[error("funds are locked (immature block rewards, unbonding stake or lockups)")]
+    FundsLocked,
+    #[error("state export failed: {0}")]
+    ExportFailed(#[from] std::io::Error),
+    #[error("export format {0} unknown")]
+    UnknownExportFormat(String),
+    #[error("verification level {0} unknown")]
+    UnknownVerifyLevel(String),
+    #[error("transaction {0} of the block is invalid: {1}")]
+    InvalidBlockTransaction(usize, Box<BlockchainError>),
+    #[error("branch completed with stored side-chain blocks is invalid: {0}")]
+    InvalidSideBranch(Box<BlockchainError>),
+    #[cfg(feature = "pos")]
+    #[error("block proposal invalid: {0}")]
+    InvalidProposal(#[from] crate::consensus::pos::Error),
+}
+
+impl BlockchainError {
+    // Whether the error proves the submitted block(s) to be invalid, rather
+    // than being a local failure or a race with another chain update
+    pub fn is_invalid_block(&self) -> bool {
+        match self {
+            BlockchainError::KvStoreError(_)
+            | BlockchainError::Inconsistency
+            | BlockchainError::BlockNotFound
+            | BlockchainError::BlockPruned
+            | BlockchainError::ExtendFromFuture
+            | BlockchainError::WeakerChain
+            // Might be due to blocks sent by others, see `drop_side_branch`
+            | BlockchainError::InvalidSideBranch(_) => false,
+            // Might be fine a few seconds later
+            #[cfg(feature = "pos")]
+            BlockchainError::InvalidProposal(crate::consensus::pos::Error::SlotInFuture(_)) => {
+                false
+            }
+            BlockchainError::InvalidBlockTransaction(_, e) => e.is_invalid_block(),
+            _ => true,
+        }
+    }
+    // Whether the block is invalid whichever chain it gets applied on, so that
+    // it can be remembered by its hash. A block not fitting where it was tried
+    // (Wrong number or parent) might still be valid elsewhere.
+    pub fn is_context_free(&self) -> bool {
+        match self {
+            BlockchainError::SignatureError
+            | BlockchainError::DifficultyTargetUnmet
+            | BlockchainError::InvalidMerkleRoot
+            | BlockchainError::InvalidBatchSend
+            | BlockchainError::UnsupportedTransaction
+            | BlockchainError::BlockTooHeavy => true,
+            BlockchainError::InvalidBlockTransaction(_, e) => e.is_context_free(),
+            _ => false,
+        }
+    }
+}
+
+// Index of the block whose hash is used as the PoW key of the block at `index`,
+// None means the base key is used.
+#[cfg(feature = "pow")]
+pub fn pow_key_reference(index: usize) -> Option<usize> {
+    if index < config::POW_KEY_CHANGE_DELAY {
+        None
+    } else {
+        Some(
+            ((index - config::POW_KEY_CHANGE_DELAY) / config::POW_KEY_CHANGE_INTERVAL)
+                * config::POW_KEY_CHANGE_INTERVAL,
+        )
+    }
+}
+
+// Whether the headers are all sealed with the VRF key of the validator
+#[cfg(feature = "pos")]
+fn sealed_by(vrf_public_key: &[u8], headers: &[&Header]) -> bool {
+    match VRFPublicKey::from_bytes(vrf_public_key) {
+        Ok(key) => headers.iter().all(|h| {
+            h.seal()
+                .map(|seal| key.verify_seal(&h.pre_seal_hash(), seal))
+                .unwrap_or(false)
+        }),
+        Err(_) => false,
+    }
+}
+
+// PoW headers aren't signed by anyone
+#[cfg(not(feature = "pos"))]
+fn sealed_by(_vrf_public_key: &[u8], _headers: &[&Header]) -> bool {
+    false
+}
+
+fn checkpoint_authorities() -> Result<Vec<<Signer as SignatureScheme>::Pub>, BlockchainError> {
+    config::checkpoint::CHECKPOINT_AUTHORITIES
+        .iter()
+        .map(|s| s.parse().map_err(|_| BlockchainError::InvalidCheckpoint))
+        .collect()
 }
 
 pub trait Blockchain {
     fn get_account(&self, addr: Address) -> Result<Account, BlockchainError>;
+    // Part of the balance that can't be spent in the next block
+    fn get_locked_balance(&self, addr: Address) -> Result<Money, BlockchainError>;
+    // Registered validators, sorted by address
+    fn get_validators(&self) -> Result<Vec<(Address, Validator)>, BlockchainError>;
+    // Epoch of the slot claimed by the given block
+    #[cfg(feature = "pos")]
+    fn epoch_at(&self, number: u64, slot: Slot) -> Result<Epoch<VRFPublicKey>, BlockchainError>;
     fn will_extend(&self, from: usize, headers: &Vec<Header>) -> Result<bool, BlockchainError>;
-    fn extend(&mut self, from: usize, blocks: &Vec<Block>) -> Result<(), BlockchainError>;
+    fn extend(
+        &mut self,
+        from: usize,
+        blocks: &Vec<Block>,
+    ) -> Result<Vec<BlockExecutionResult>, BlockchainError>;
     fn draft_block(
         &self,
         timestamp: u32,
         mempool: &Vec<Transaction>,
-        wallet: &Wallet,
+        payouts: &PayoutSplit,
     ) -> Result<Block, BlockchainError>;
+    // Rolls the chain back to its first `height` blocks, the removed blocks are
+    // dropped. Returns the number of removed blocks.
+    fn rewind(&mut self, height: usize) -> Result<usize, BlockchainError>;
     fn get_height(&self) -> Result<usize, BlockchainError>;
     fn get_headers(
         &self,
@@ -56,63 +274,408 @@ pub trait Blockchain {
     ) -> Result<Vec<Header>, BlockchainError>;
     fn get_blocks(&self, since: usize, until: Option<usize>)
         -> Result<Vec<Block>, BlockchainError>;
+    fn get_checkpoint(&self) -> Result<Option<Checkpoint>, BlockchainError>;
+    fn get_block_metadata(&self, index: usize) -> Result<BlockMetadata, BlockchainError>;
+    // Height of a block of the current chain, given its hash
+    fn get_block_height(&self, hash: &[u8]) -> Result<Option<usize>, BlockchainError>;
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError>;
 
-    #[cfg(feature = "pow")]
+    // Side-chain blocks, kept so that switching to their branch later doesn't
+    // need downloading them again. Only for blocks whose headers passed
+    // `will_extend`, the bodies are verified once applied. Blocks that don't
+    // connect to a known block are ignored.
+    fn store_side_blocks(&mut self, blocks: &[Block]) -> Result<(), BlockchainError>;
+    // Blocks of the side-chain ending with the given block, after its fork point
+    fn get_side_branch(&self, tip: &[u8]) -> Result<Option<(usize, Vec<Block>)>, BlockchainError>;
+    // Removes the blocks of the side-chain ending with the given block, e.g.
+    // once they turned out to be invalid
+    fn drop_side_branch(&mut self, tip: &[u8]) -> Result<(), BlockchainError>;
+    // The current tip first, then the side-chain tips from the highest
+    fn get_chain_tips(&self) -> Result<Vec<ChainTip>, BlockchainError>;
+    // Looked up by `Transaction::txid`, which doesn't change on re-signing
+    fn get_transaction(
+        &self,
+        txid: TransactionId,
+    ) -> Result<Option<(Transaction, TransactionLocation)>, BlockchainError>;
+    fn get_receipt(&self, txid: TransactionId) -> Result<Option<TxReceipt>, BlockchainError>;
+    fn apply_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), BlockchainError>;
+    // Checks the stored blocks from genesis on, stopping at the first one that
+    // fails. For finding out whether the database survived a crash.
+    fn verify_chain(&self, level: VerifyLevel) -> Result<VerifyReport, BlockchainError>;
+    fn db_stats(&self) -> Result<DbStats, BlockchainError>;
+    fn compact(&mut self) -> Result<(), BlockchainError>;
+
+    // Journal of the reorgs seen by the node, oldest first
+    fn get_reorgs(&self) -> Result<Vec<ReorgRecord>, BlockchainError>;
+    // The oldest records are dropped once there are more than `capacity`
+    fn record_reorg(&mut self, record: ReorgRecord, capacity: usize)
+        -> Result<(), BlockchainError>;
+
+    // Beacon value at the given height, derived from the blocks before it. See
+    // `RandomnessSource` for how far it can be trusted.
+    fn randomness(&self, height: usize) -> Result<Randomness, BlockchainError>;
+
+    // Current value of a governance parameter
+    fn get_param(&self, param: GovernanceParam) -> Result<u64, BlockchainError>;
+
+    // Dumps the accounts and the contracts as of `height` blocks, see
+    // `ExportFormat` for the layout
+    fn export_state(
+        &self,
+        writer: &mut dyn std::io::Write,
+        height: usize,
+        format: ExportFormat,
+    ) -> Result<ExportSummary, BlockchainError>;
+
+    // Opaque records the node keeps next to the chain (e.g. known peers), stored
+    // under `<namespace>_<id>` keys. Removed when the value is None.
+    fn get_records(&self, namespace: &str) -> Result<Vec<(String, Vec<u8>)>, BlockchainError>;
+    fn update_records(
+        &mut self,
+        namespace: &str,
+        records: Vec<(String, Option<Vec<u8>>)>,
+    ) -> Result<(), BlockchainError>;
+
+    // Accumulated work (PoW) or proposer stake (PoS) of the chain
     fn get_power(&self) -> Result<u64, BlockchainError>;
     #[cfg(feature = "pow")]
     fn pow_key(&self, index: usize) -> Result<Vec<u8>, BlockchainError>;
+    #[cfg(feature = "pow")]
+    fn generate_chain_proof(&self) -> Result<ChainProof, BlockchainError>;
+    #[cfg(feature = "pow")]
+    fn export_relay_headers(
+        &self,
+        since: usize,
+        until: Option<usize>,
+    ) -> Result<Vec<relay::RelayHeader>, BlockchainError>;
+}
+
+// Everything learnt while applying a block, so that consumers don't have to
+// recompute it
+#[derive(Debug, Clone)]
+pub struct BlockExecutionResult {
+    pub receipts: Vec<TxReceipt>,
+    // Fees paid to the block producer
+    pub fees: Money,
+    pub weight: u64,
+    // Hash of the state changes made by the block. Not a commitment to the
+    // whole state, `Header::state_root` is not checked against it.
+    pub write_set_hash: <Hasher as Hash>::Output,
+}
+
+// Independent of the order in which the changes were made
+fn write_set_hash(mut ops: Vec<WriteOp>) -> <Hasher as Hash>::Output {
+    ops.sort_by(|a, b| a.key().as_str().cmp(b.key().as_str()));
+    Hasher::hash(&bincode::serialize(&ops).unwrap())
+}
+
+// Version of the format of the stored chain. Databases written with any other
+// version can't be used, the chain has to be synced again. Bumped on every
+// change of the encoding or the hashes of the stored blocks:
+// 2: Domain-separated header and transaction hashes
+// 3: Headers commit to the cumulative transaction count
+const DB_VERSION: usize = 3;
+
+// Version of the formula used for the stored cumulative powers
+#[cfg(feature = "pow")]
+const POWER_VERSION: usize = 1;
+
+// Checks the PoW of the headers (Against their corresponding keys) on all
+// cores, returning the total power. RandomX hashing is what makes verifying
+// headers during the initial sync CPU-bound.
+#[cfg(feature = "pow")]
+pub fn verify_pow(headers: &[Header], pow_keys: &[Vec<u8>]) -> Result<u64, BlockchainError> {
+    use rayon::prelude::*;
+    headers
+        .par_iter()
+        .zip(pow_keys.par_iter())
+        .map(|(h, key)| {
+            if h.meets_target(key) {
+                Ok(h.power())
+            } else {
+                Err(BlockchainError::DifficultyTargetUnmet)
+            }
+        })
+        .try_reduce(|| 0, |a, b| Ok(a.saturating_add(b)))
 }
 
 pub struct KvStoreChain<K: KvStore> {
     database: K,
+    // Whether the transaction index and the block metadata are maintained
+    indexed: bool,
+    // Bodies of the blocks this deep behind the tip are dropped, along with
+    // their rollback records. None keeps everything.
+    pruning: Option<usize>,
+    // Contract operators whose Update transactions get a reserved slice of
+    // the drafted blocks
+    aggregators: Vec<Address>,
 }
 
 impl<K: KvStore> KvStoreChain<K> {
     pub fn new(kv_store: K) -> Result<KvStoreChain<K>, BlockchainError> {
-        let mut chain = KvStoreChain::<K> { database: kv_store };
+        Self::open(kv_store, true)
+    }
+
+    pub fn open(kv_store: K, indexed: bool) -> Result<KvStoreChain<K>, BlockchainError> {
+        let mut chain = KvStoreChain::<K> {
+            database: kv_store,
+            indexed,
+            pruning: None,
+            aggregators: Vec::new(),
+        };
+        chain.database.recover()?;
         if chain.get_height()? == 0 {
+            // Nothing to migrate in a fresh database
+            chain.database.update(&vec![
+                WriteOp::Put("db_version".into(), DB_VERSION.into()),
+                WriteOp::Put("accounts_version".into(), 1usize.into()),
+            ])?;
             chain.apply_block(&genesis::get_genesis_block(), false)?;
         }
+        // Checked before anything else tries to decode the stored blocks
+        chain.check_db_version()?;
+        #[cfg(feature = "pow")]
+        chain.migrate_power()?;
+        chain.migrate_tx_index()?;
+        chain.migrate_block_hash_index()?;
+        chain.migrate_accounts()?;
         Ok(chain)
     }
 
+    pub fn with_pruning(mut self, depth: usize) -> Self {
+        self.pruning = Some(depth);
+        self
+    }
+
+    pub fn with_aggregators(mut self, aggregators: Vec<Address>) -> Self {
+        self.aggregators = aggregators;
+        self
+    }
+
+    // Blocks below this height (Genesis excluded) only have their headers
+    fn pruned_height(&self) -> Result<usize, BlockchainError> {
+        Ok(match self.database.get("pruned_height".into())? {
+            Some(b) => b.try_into()?,
+            None => 0,
+        })
+    }
+
+    fn check_body(&self, index: usize) -> Result<(), BlockchainError> {
+        if index > 0 && index < self.pruned_height()? {
+            return Err(BlockchainError::BlockPruned);
+        }
+        Ok(())
+    }
+
+    fn check_db_version(&self) -> Result<(), BlockchainError> {
+        let version: usize = match self.database.get("db_version".into())? {
+            Some(b) => b.try_into()?,
+            None => 0,
+        };
+        if version != DB_VERSION {
+            return Err(BlockchainError::IncompatibleDatabase);
+        }
+        Ok(())
+    }
+
+    // Accounts stored before they could hold a lock are re-encoded, along with
+    // the ones kept in the rollback records, which get restored on a reorg
+    fn migrate_accounts(&mut self) -> Result<(), BlockchainError> {
+        if self.database.get("accounts_version".into())?.is_some() {
+            return Ok(());
+        }
+        let upgrade = |v: Blob| -> Result<Blob, BlockchainError> {
+            let account: AccountV0 = v.try_into()?;
+            Ok(Account::from(account).into())
+        };
+        let mut ops = Vec::new();
+        for (k, v) in self.database.pairs("account_".into())? {
+            ops.push(WriteOp::Put(k, upgrade(v)?));
+        }
+        for (k, v) in self.database.pairs("rollback_".into())? {
+            let record: Vec<WriteOp> = v.try_into()?;
+            let record = record
+                .into_iter()
+                .map(|op| match op {
+                    WriteOp::Put(k, v) if k.as_str().starts_with("account_") => {
+                        Ok(WriteOp::Put(k, upgrade(v)?))
+                    }
+                    op => Ok(op),
+                })
+                .collect::<Result<Vec<_>, BlockchainError>>()?;
+            ops.push(WriteOp::Put(k, record.into()));
+        }
+        ops.push(WriteOp::Put("accounts_version".into(), 1usize.into()));
+        self.database.update(&ops)?;
+        Ok(())
+    }
+
+    // Blocks applied before the hash index existed are indexed at once
+    fn migrate_block_hash_index(&mut self) -> Result<(), BlockchainError> {
+        if self.database.get("blockhash_index".into())?.is_some() {
+            return Ok(());
+        }
+        let mut ops = Vec::new();
+        for i in 0..self.get_height()? {
+            ops.push(Self::block_hash_op(&self.get_block(i)?.header));
+        }
+        ops.push(WriteOp::Put("blockhash_index".into(), 1usize.into()));
+        self.database.update(&ops)?;
+        Ok(())
+    }
+
+    fn get_side_block(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError> {
+        Ok(match self.database.get(fork_key(hash).into())? {
+            Some(b) => Some(b.try_into()?),
+            None => None,
+        })
+    }
+
+    // Header of a block of the current chain or of the fork store
+    fn get_known_header(&self, hash: &[u8]) -> Result<Option<Header>, BlockchainError> {
+        if let Some(height) = self.get_block_height(hash)? {
+            return Ok(self.get_headers(height, Some(height + 1))?.pop());
+        }
+        Ok(self.get_side_block(hash)?.map(|b| b.header))
+    }
+
+    fn side_block_ops(block: &Block, tip: bool) -> Vec<WriteOp> {
+        let hash = block.header.hash();
+        let number = block.header.number;
+        let mut ops = vec![
+            WriteOp::Put(fork_key(&hash).into(), block.into()),
+            WriteOp::Put(fork_number_key(number, &hash).into(), number.into()),
+        ];
+        if tip {
+            ops.push(WriteOp::Put(fork_tip_key(&hash).into(), number.into()));
+        }
+        ops
+    }
+
+    fn remove_side_block_ops(header: &Header) -> Vec<WriteOp> {
+        let hash = header.hash();
+        vec![
+            WriteOp::Remove(fork_key(&hash).into()),
+            WriteOp::Remove(fork_number_key(header.number, &hash).into()),
+            WriteOp::Remove(fork_tip_key(&hash).into()),
+        ]
+    }
+
+    fn block_hash_op(header: &Header) -> WriteOp {
+        WriteOp::Put(
+            format!("blockhash_{}", hex::encode(header.hash())).into(),
+            (header.number as usize).into(),
+        )
+    }
+
+    // Databases older than the transaction index are indexed at once. The
+    // marker is dropped while running unindexed, so that the blocks applied
+    // meanwhile get indexed once the index is enabled again.
+    fn migrate_tx_index(&mut self) -> Result<(), BlockchainError> {
+        let marked = self.database.get("tx_index".into())?.is_some();
+        if !self.indexed {
+            if marked {
+                self.database
+                    .update(&vec![WriteOp::Remove("tx_index".into())])?;
+            }
+            return Ok(());
+        }
+        if marked {
+            return Ok(());
+        }
+        let mut ops = Vec::new();
+        for i in 0..self.get_height()? {
+            self.check_body(i)?;
+            ops.extend(Self::tx_index_ops(&self.get_block(i)?));
+        }
+        ops.push(WriteOp::Put("tx_index".into(), 1usize.into()));
+        self.database.update(&ops)?;
+        Ok(())
+    }
+
+    fn tx_index_ops(block: &Block) -> Vec<WriteOp> {
+        block
+            .body
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                WriteOp::Put(
+                    format!("tx_{}", hex::encode(tx.txid::<Hasher>())).into(),
+                    TransactionLocation {
+                        block: block.header.number,
+                        index,
+                    }
+                    .into(),
+                )
+            })
+            .collect()
+    }
+
+    // Cumulative powers used to be calculated from the hash values, recalculate
+    // them from the targets if the database is older.
+    #[cfg(feature = "pow")]
+    fn migrate_power(&mut self) -> Result<(), BlockchainError> {
+        let version: usize = match self.database.get("power_version".into())? {
+            Some(b) => b.try_into()?,
+            None => 0,
+        };
+        if version >= POWER_VERSION {
+            return Ok(());
+        }
+        let mut ops = Vec::new();
+        let mut power = 0u64;
+        for i in 0..self.get_height()? {
+            power += self.get_block(i)?.header.power();
+            ops.push(WriteOp::Put(
+                format!("power_{:010}", i).into(),
+                power.into(),
+            ));
+        }
+        ops.push(WriteOp::Put("power_version".into(), POWER_VERSION.into()));
+        self.database.update(&ops)?;
+        Ok(())
+    }
+
+    // Number of reorgs recorded so far, including the dropped ones
+    fn reorg_count(&self) -> Result<usize, BlockchainError> {
+        Ok(match self.database.get("reorgs".into())? {
+            Some(b) => b.try_into()?,
+            None => 0,
+        })
+    }
+
     fn fork_on_ram<'a>(&'a self) -> KvStoreChain<RamMirrorKvStore<'a, K>> {
         KvStoreChain {
             database: RamMirrorKvStore::new(&self.database),
+            indexed: self.indexed,
+            pruning: self.pruning,
+            aggregators: self.aggregators.clone(),
         }
     }
 
     #[cfg(feature = "pow")]
     fn median_timestamp(&self, index: usize) -> Result<u32, BlockchainError> {
-        Ok(utils::median(
-            &(0..std::cmp::min(index + 1, config::MEDIAN_TIMESTAMP_COUNT))
-                .map(|i| {
-                    self.get_block(index - i)
-                        .map(|b| b.header.proof_of_work.timestamp)
-                })
-                .collect::<Result<Vec<u32>, BlockchainError>>()?,
-        ))
+        median_time_past_at(index, |i| {
+            self.get_block(i).map(|b| b.header.proof_of_work.timestamp)
+        })
     }
 
     #[cfg(feature = "pow")]
     fn next_difficulty(&self) -> Result<u32, BlockchainError> {
         let height = self.get_height()?;
         let last_block = self.get_block(height - 1)?.header;
-        if height % config::DIFFICULTY_CALC_INTERVAL == 0 {
-            let prev_block = self
-                .get_block(height - config::DIFFICULTY_CALC_INTERVAL)?
-                .header;
-            let time_delta =
-                last_block.proof_of_work.timestamp - prev_block.proof_of_work.timestamp;
-            let avg_block_time = time_delta / (config::DIFFICULTY_CALC_INTERVAL - 1) as u32;
-            let diff_change =
-                (config::BLOCK_TIME as f32 / avg_block_time as f32).clamp(0.5f32, 2f32);
-            let new_diff =
-                rust_randomx::Difficulty::new(last_block.proof_of_work.target).scale(diff_change);
-            Ok(new_diff.to_u32())
-        } else {
-            Ok(last_block.proof_of_work.target)
+        next_target(height, last_block.proof_of_work.target, |i| {
+            self.get_block(i).map(|b| b.header.proof_of_work.timestamp)
+        })
+    }
+
+    fn check_checkpoint(&self, header: &Header) -> Result<(), BlockchainError> {
+        if let Some(checkpoint) = self.get_checkpoint()? {
+            if checkpoint.number == header.number && checkpoint.hash != header.hash() {
+                return Err(BlockchainError::CheckpointConflict);
+            }
         }
+        Ok(())
     }
 
     fn get_block(&self, index: usize) -> Result<Block, BlockchainError> {
@@ -128,10 +691,189 @@ impl<K: KvStore> KvStoreChain<K> {
         })
     }
 
-    fn apply_tx(&mut self, tx: &Transaction) -> Result<(), BlockchainError> {
+    fn get_contract(&self, contract_id: &ContractId) -> Result<Contract, BlockchainError> {
+        match self
+            .database
+            .get(format!("contract_{}", contract_id).into())?
+        {
+            Some(b) => Ok(b.try_into()?),
+            None => Err(BlockchainError::ContractNotFound),
+        }
+    }
+
+    fn get_contract_state(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<ZkCompressedState, BlockchainError> {
+        match self
+            .database
+            .get(format!("contract_{}_state", contract_id).into())?
+        {
+            Some(b) => Ok(b.try_into()?),
+            None => Err(BlockchainError::ContractNotFound),
+        }
+    }
+
+    // Funds of the address that are still locked at the given height
+    fn locked_funds(
+        &self,
+        addr: &Address,
+        height: u64,
+    ) -> Result<Vec<LockedFunds>, BlockchainError> {
+        let locks: Vec<LockedFunds> = match self.database.get(format!("locked_{}", addr).into())? {
+            Some(b) => b.try_into()?,
+            None => Vec::new(),
+        };
+        Ok(locks
+            .into_iter()
+            .filter(|lock| !lock.is_unlocked(height))
+            .collect())
+    }
+
+    fn lock_funds_op(
+        &self,
+        addr: &Address,
+        height: u64,
+        lock: LockedFunds,
+    ) -> Result<WriteOp, BlockchainError> {
+        let mut locks = self.locked_funds(addr, height)?;
+        locks.push(lock);
+        Ok(WriteOp::Put(
+            format!("locked_{}", addr).into(),
+            locks.into(),
+        ))
+    }
+
+    // Stake unstaked by the address that is still unbonding at the given height
+    fn unbonding(&self, addr: &Address, height: u64) -> Result<Option<Unbonding>, BlockchainError> {
+        Ok(
+            match self.database.get(format!("unbonding_{}", addr).into())? {
+                Some(b) => {
+                    let mut unbonding: Unbonding = b.try_into()?;
+                    unbonding.locks.retain(|lock| !lock.is_unlocked(height));
+                    Some(unbonding).filter(|u| !u.locks.is_empty())
+                }
+                None => None,
+            },
+        )
+    }
+
+    fn get_validator(&self, addr: &Address) -> Result<Option<Validator>, BlockchainError> {
+        Ok(
+            match self.database.get(format!("validator_{}", addr).into())? {
+                Some(b) => Some(b.try_into()?),
+                None => None,
+            },
+        )
+    }
+
+    // Decides the proposals that have been open for a whole governance epoch,
+    // at the end of the given block. Votes are weighted by the stakes at the
+    // time of the tally, so stake moved around after voting doesn't count twice.
+    fn tally_proposals_ops(&self, number: u64) -> Result<Vec<WriteOp>, BlockchainError> {
+        let stakes = self
+            .get_validators()?
+            .into_iter()
+            .map(|(addr, v)| (addr.to_string(), v.stake))
+            .collect::<HashMap<_, _>>();
+        let total_stake = stakes.values().sum::<Money>();
+
+        let mut due = Vec::new();
+        for (k, v) in self.database.pairs("proposal_".into())? {
+            let proposal: Proposal = v.try_into()?;
+            if proposal.created + config::GOVERNANCE_EPOCH_LENGTH <= number + 1 {
+                due.push((k.as_str()["proposal_".len()..].to_string(), proposal));
+            }
+        }
+        // Among the approved proposals on the same parameter, the latest wins
+        due.sort_by(|a, b| (a.1.created, &a.0).cmp(&(b.1.created, &b.0)));
+
+        let mut ops = Vec::new();
+        for (id, proposal) in due {
+            let prefix = format!("vote_{}_", id);
+            let mut approving: Money = 0;
+            for (k, v) in self.database.pairs(prefix.clone().into())? {
+                let approve: bool = v.try_into()?;
+                if approve {
+                    approving += stakes.get(&k.as_str()[prefix.len()..]).unwrap_or(&0);
+                }
+                ops.push(WriteOp::Remove(k));
+            }
+            if is_approved(approving, total_stake) {
+                ops.push(WriteOp::Put(
+                    format!("param_{}", proposal.param.name()).into(),
+                    proposal.value.into(),
+                ));
+            }
+            ops.push(WriteOp::Remove(format!("proposal_{}", id).into()));
+        }
+        Ok(ops)
+    }
+
+    // Validators of the next validator epoch, i.e. everyone with some stake
+    #[cfg(feature = "pos")]
+    fn validator_snapshot_op(&self, epoch: u64) -> Result<WriteOp, BlockchainError> {
+        let validators = self
+            .get_validators()?
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect::<Vec<_>>();
+        Ok(WriteOp::Put(
+            format!("validators_{:010}", epoch).into(),
+            validators.into(),
+        ))
+    }
+
+    // Proposers of the given block, elected from the validator set snapshotted
+    // at the end of the previous validator epoch. Genesis authorities are in
+    // charge when there was no snapshot or nobody had staked.
+    #[cfg(feature = "pos")]
+    fn authorities(&self, number: u64) -> Result<Vec<Authority<VRFPublicKey>>, BlockchainError> {
+        let validators: Vec<Validator> = match self
+            .database
+            .get(format!("validators_{:010}", validator_epoch(number)).into())?
+        {
+            Some(b) => b.try_into()?,
+            None => Vec::new(),
+        };
+        let authorities: Vec<_> = validators
+            .into_iter()
+            .filter_map(|v| {
+                Some(Authority {
+                    weight: std::num::NonZeroU64::new(v.stake)?,
+                    public_key: VRFPublicKey::from_bytes(&v.vrf_public_key).ok()?,
+                })
+            })
+            .collect();
+        Ok(if authorities.is_empty() {
+            genesis::get_genesis_authorities()
+        } else {
+            authorities
+        })
+    }
+
+    // Money deposited into a contract and not withdrawn yet
+    fn get_contract_balance(&self, contract_id: &ContractId) -> Result<Money, BlockchainError> {
+        Ok(
+            match self
+                .database
+                .get(format!("contract_{}_balance", contract_id).into())?
+            {
+                Some(b) => b.try_into()?,
+                None => 0,
+            },
+        )
+    }
+
+    // Pure state transition: the changes of the transaction are returned as a
+    // write set, to be applied by the caller
+    fn apply_tx(&self, tx: &Transaction) -> Result<(Vec<WriteOp>, TxReceipt), BlockchainError> {
         let mut ops = Vec::new();
+        let mut receipt = TxReceipt::default();
 
         let mut acc_src = self.get_account(tx.src.clone())?;
+        // The height of the block the transaction is going to be included in
+        let height = self.get_height()? as u64;
 
         if !tx.verify_signature() {
             return Err(BlockchainError::SignatureError);
@@ -147,6 +889,19 @@ impl<K: KvStore> KvStoreChain<K> {
 
         acc_src.balance -= tx.fee;
         acc_src.nonce += 1;
+        if tx.src != Address::Treasury {
+            receipt.fee = tx.fee;
+        }
+
+        // Everything the Treasury collects from this transaction
+        let mut collected = tx.fee;
+
+        // What the source should still have after the transaction
+        let mut locked = self
+            .locked_funds(&tx.src, height)?
+            .iter()
+            .map(|lock| lock.amount)
+            .sum::<Money>();
 
         match &tx.data {
             TransactionData::RegularSend { dst, amount } => {
@@ -166,72 +921,472 @@ impl<K: KvStore> KvStoreChain<K> {
                         format!("account_{}", dst).into(),
                         acc_dst.into(),
                     ));
+
+                    // Only block rewards are paid by the Treasury
+                    if tx.src == Address::Treasury {
+                        ops.push(self.lock_funds_op(
+                            dst,
+                            height,
+                            LockedFunds {
+                                until: height + config::COINBASE_MATURITY,
+                                amount: *amount,
+                            },
+                        )?);
+                    }
                 }
             }
-            TransactionData::CreateContract {
-                deposit_withdraw_circuit,
-                update_circuits,
-                state_model,
-                initial_state,
-            } => {
-                ops.push(WriteOp::Put(
-                    format!("contract_dw_{}", tx.uid()).into(),
-                    deposit_withdraw_circuit.clone().into(),
-                ));
-                for (i, c) in update_circuits.iter().enumerate() {
+            TransactionData::RegularSendMany { outputs } => {
+                // Block rewards are single sends, see `validate_reward`
+                if tx.src == Address::Treasury || outputs.is_empty() {
+                    return Err(BlockchainError::InvalidBatchSend);
+                }
+                let mut total: Money = 0;
+                for (_, amount) in outputs.iter() {
+                    total = total
+                        .checked_add(*amount)
+                        .ok_or(BlockchainError::InvalidBatchSend)?;
+                }
+                if acc_src.balance < total {
+                    return Err(BlockchainError::BalanceInsufficient);
+                }
+
+                // A destination may appear more than once
+                let mut accounts = HashMap::<String, (Address, Account)>::new();
+                for (dst, amount) in outputs.iter() {
+                    if *dst == tx.src {
+                        continue;
+                    }
+                    acc_src.balance -= amount;
+                    let key = dst.to_string();
+                    if !accounts.contains_key(&key) {
+                        accounts.insert(key.clone(), (dst.clone(), self.get_account(dst.clone())?));
+                    }
+                    accounts.get_mut(&key).unwrap().1.balance += amount;
+                }
+                for (_, (addr, acc)) in accounts.into_iter() {
+                    ops.push(WriteOp::Put(format!("account_{}", addr).into(), acc.into()));
+                }
+            }
+            TransactionData::LockedSend { dst, amount, until } => {
+                if acc_src.balance < *amount {
+                    return Err(BlockchainError::BalanceInsufficient);
+                }
+                let lock = LockedFunds {
+                    until: *until,
+                    amount: *amount,
+                };
+                // Locks that don't fit in the account go to the locked funds
+                if *dst == tx.src {
+                    if let Some(lock) = acc_src.add_lock(height, lock) {
+                        locked += lock.amount;
+                        ops.push(self.lock_funds_op(dst, height, lock)?);
+                    }
+                } else {
+                    acc_src.balance -= *amount;
+                    let mut acc_dst = self.get_account(dst.clone())?;
+                    acc_dst.balance += amount;
+                    if let Some(lock) = acc_dst.add_lock(height, lock) {
+                        ops.push(self.lock_funds_op(dst, height, lock)?);
+                    }
                     ops.push(WriteOp::Put(
-                        format!("contract_update_{}_{}", tx.uid(), i).into(),
-                        c.clone().into(),
+                        format!("account_{}", dst).into(),
+                        acc_dst.into(),
                     ));
                 }
+            }
+            TransactionData::RegisterValidator {
+                vrf_public_key,
+                amount,
+            } => {
+                if tx.src == Address::Treasury {
+                    return Err(BlockchainError::TreasuryValidator);
+                }
+                if vrf_public_key.len() != VRF_PUBLIC_KEY_LENGTH {
+                    return Err(BlockchainError::InvalidValidatorKey);
+                }
+                #[cfg(feature = "pos")]
+                VRFPublicKey::from_bytes(vrf_public_key)
+                    .map_err(|_| BlockchainError::InvalidValidatorKey)?;
+
+                if acc_src.balance < *amount {
+                    return Err(BlockchainError::BalanceInsufficient);
+                }
+                acc_src.balance -= amount;
+
+                let mut validator = self.get_validator(&tx.src)?.unwrap_or(Validator {
+                    vrf_public_key: Vec::new(),
+                    stake: 0,
+                });
+                validator.vrf_public_key = vrf_public_key.clone();
+                validator.stake += amount;
                 ops.push(WriteOp::Put(
-                    format!("contract_state_model_{}", tx.uid()).into(),
-                    state_model.clone().into(),
-                ));
-                ops.push(WriteOp::Put(
-                    format!("contract_initial_state_{}", tx.uid()).into(),
-                    initial_state.clone().into(),
+                    format!("validator_{}", tx.src).into(),
+                    validator.into(),
                 ));
-                let compressed_state =
-                    ZkState::new(state_model.clone(), initial_state.clone()).compress();
+            }
+            TransactionData::Stake { amount } => {
+                let mut validator = self
+                    .get_validator(&tx.src)?
+                    .ok_or(BlockchainError::ValidatorNotFound)?;
+                if acc_src.balance < *amount {
+                    return Err(BlockchainError::BalanceInsufficient);
+                }
+                acc_src.balance -= amount;
+                validator.stake += amount;
                 ops.push(WriteOp::Put(
-                    format!("contract_compressed_state_{}", tx.uid()).into(),
-                    compressed_state.into(),
+                    format!("validator_{}", tx.src).into(),
+                    validator.into(),
                 ));
-                unimplemented!();
+            }
+            TransactionData::Unstake { amount } => {
+                let mut validator = self
+                    .get_validator(&tx.src)?
+                    .ok_or(BlockchainError::ValidatorNotFound)?;
+                if validator.stake < *amount {
+                    return Err(BlockchainError::StakeInsufficient);
+                }
+                validator.stake -= amount;
+                let vrf_public_key = validator.vrf_public_key.clone();
+                ops.push(if validator.stake == 0 {
+                    WriteOp::Remove(format!("validator_{}", tx.src).into())
+                } else {
+                    WriteOp::Put(format!("validator_{}", tx.src).into(), validator.into())
+                });
+
+                // Back in the balance, but not spendable before unbonding
+                acc_src.balance += amount;
+                locked += amount;
+                let lock = LockedFunds {
+                    until: height + config::UNBONDING_PERIOD,
+                    amount: *amount,
+                };
+                ops.push(self.lock_funds_op(&tx.src, height, lock.clone())?);
+
+                // Slashable until then
+                let mut locks = self
+                    .unbonding(&tx.src, height)?
+                    .map(|u| u.locks)
+                    .unwrap_or_default();
+                locks.push(lock);
+                ops.push(WriteOp::Put(
+                    format!("unbonding_{}", tx.src).into(),
+                    Unbonding {
+                        vrf_public_key,
+                        locks,
+                    }
+                    .into(),
+                ));
+            }
+            TransactionData::SlashEvidence {
+                offender,
+                first,
+                second,
+            } => {
+                if first.number != second.number || first == second {
+                    return Err(BlockchainError::InvalidSlashEvidence);
+                }
+                let validator = self.get_validator(offender)?;
+                let unbonding = self.unbonding(offender, height)?;
+                if validator.is_none() && unbonding.is_none() {
+                    return Err(BlockchainError::ValidatorNotFound);
+                }
+                // The offender might have left, or registered another key since
+                let keys = validator
+                    .iter()
+                    .map(|v| &v.vrf_public_key)
+                    .chain(unbonding.iter().map(|u| &u.vrf_public_key));
+                if !keys.into_iter().any(|k| sealed_by(k, &[first, second])) {
+                    return Err(BlockchainError::InvalidSlashEvidence);
+                }
+
+                // The offender might register again, the same evidence
+                // shouldn't be usable against them twice
+                let evidence_key = format!("slashed_{}_{:010}", offender, first.number);
+                if self.database.get(evidence_key.clone().into())?.is_some() {
+                    return Err(BlockchainError::SlashEvidenceUsed);
+                }
+
+                let (num, den) = config::DOUBLE_SIGN_SLASH;
+                let slash = |amount: Money| (amount as u128 * num as u128 / den as u128) as Money;
+                let stake = validator.map(|v| v.stake).unwrap_or_default();
+                let burnt_stake = slash(stake);
+                let remaining = stake - burnt_stake;
+                if stake > 0 {
+                    ops.push(WriteOp::Remove(format!("validator_{}", offender).into()));
+                }
+
+                // Unbonding stake is already back in the balance, the burnt
+                // part is taken out of both the balance and its lock
+                let mut locks = self.locked_funds(offender, height)?;
+                let mut burnt_unbonding: Money = 0;
+                if let Some(unbonding) = unbonding {
+                    for unbonding_lock in unbonding.locks.iter() {
+                        let burnt = slash(unbonding_lock.amount);
+                        if let Some(lock) = locks.iter_mut().find(|l| *l == unbonding_lock) {
+                            lock.amount -= burnt;
+                            burnt_unbonding += burnt;
+                        }
+                    }
+                    ops.push(WriteOp::Remove(format!("unbonding_{}", offender).into()));
+                }
+                ops.push(WriteOp::Put(
+                    evidence_key.into(),
+                    (burnt_stake + burnt_unbonding).into(),
+                ));
+
+                if *offender == tx.src {
+                    acc_src.balance = acc_src.balance + remaining - burnt_unbonding;
+                    locked = locked + remaining - burnt_unbonding;
+                } else {
+                    let mut acc_offender = self.get_account(offender.clone())?;
+                    acc_offender.balance = acc_offender.balance + remaining - burnt_unbonding;
+                    ops.push(WriteOp::Put(
+                        format!("account_{}", offender).into(),
+                        acc_offender.into(),
+                    ));
+                }
+                locks.push(LockedFunds {
+                    until: height + config::UNBONDING_PERIOD,
+                    amount: remaining,
+                });
+                locks.retain(|lock| lock.amount > 0);
+                ops.push(WriteOp::Put(
+                    format!("locked_{}", offender).into(),
+                    locks.into(),
+                ));
+            }
+            TransactionData::Propose { param, value } => {
+                if self.get_validator(&tx.src)?.is_none() {
+                    return Err(BlockchainError::ValidatorNotFound);
+                }
+                if !param.is_valid(*value) {
+                    return Err(BlockchainError::InvalidGovernanceProposal);
+                }
+                let proposal_id = ProposalId::new(tx);
+                ops.push(WriteOp::Put(
+                    format!("proposal_{}", proposal_id).into(),
+                    Proposal {
+                        param: *param,
+                        value: *value,
+                        created: height,
+                    }
+                    .into(),
+                ));
+                ops.push(WriteOp::Put(
+                    format!("vote_{}_{}", proposal_id, tx.src).into(),
+                    true.into(),
+                ));
+            }
+            TransactionData::Vote { proposal, approve } => {
+                if self.get_validator(&tx.src)?.is_none() {
+                    return Err(BlockchainError::ValidatorNotFound);
+                }
+                if self
+                    .database
+                    .get(format!("proposal_{}", proposal).into())?
+                    .is_none()
+                {
+                    return Err(BlockchainError::ProposalNotFound);
+                }
+                ops.push(WriteOp::Put(
+                    format!("vote_{}_{}", proposal, tx.src).into(),
+                    (*approve).into(),
+                ));
+            }
+            TransactionData::CreateContract {
+                deposit_withdraw_circuit,
+                update_circuits,
+                state_model,
+                initial_state,
+            } => {
+                let contract = Contract {
+                    deposit_withdraw_circuit: deposit_withdraw_circuit.clone(),
+                    update_circuits: update_circuits.clone(),
+                    state_model: state_model.clone(),
+                };
+                let creation_fee = (contract.circuits_size() as Money)
+                    .saturating_mul(config::CONTRACT_CREATION_FEE_PER_BYTE);
+                if acc_src.balance < creation_fee {
+                    return Err(BlockchainError::BalanceInsufficient);
+                }
+                acc_src.balance -= creation_fee;
+                collected += creation_fee;
+
+                // These are part of the rollback record of the block, so they
+                // are removed when the block is rolled back.
+                let contract_id = ContractId::new(tx);
+                receipt.created_contract = Some(contract_id.clone());
+                ops.push(WriteOp::Put(
+                    format!("contract_{}", contract_id).into(),
+                    contract.into(),
+                ));
+                ops.push(WriteOp::Put(
+                    format!("contract_{}_initial_state", contract_id).into(),
+                    initial_state.clone().into(),
+                ));
+                let compressed_state =
+                    ZkState::new(state_model.clone(), initial_state.clone()).compress();
+                ops.push(WriteOp::Put(
+                    format!("contract_{}_state", contract_id).into(),
+                    compressed_state.into(),
+                ));
+            }
+            // No proof could ever be accepted without a proving system
+            TransactionData::DepositWithdraw { .. } | TransactionData::Update { .. }
+                if !cfg!(feature = "zk") =>
+            {
+                return Err(BlockchainError::ZkProofsUnsupported);
             }
             TransactionData::DepositWithdraw {
-                contract_id: _,
-                deposit_withdraws: _,
-                next_state: _,
-                proof: _,
+                contract_id,
+                deposit_withdraws,
+                next_state,
+                proof,
             } => {
-                unimplemented!();
+                let contract = self.get_contract(contract_id)?;
+                let state = self.get_contract_state(contract_id)?;
+                let payments_hash = Hasher::hash(&bincode::serialize(deposit_withdraws).unwrap());
+                if !zk::check_proof(
+                    &contract.deposit_withdraw_circuit,
+                    state.state_hash(),
+                    &payments_hash,
+                    next_state,
+                    proof,
+                ) {
+                    return Err(BlockchainError::InvalidZkProof);
+                }
+
+                // Accounts touched by the payments, the executor is acc_src
+                let mut accounts = HashMap::<String, (Address, Account)>::new();
+                let mut contract_balance = self.get_contract_balance(contract_id)?;
+                for payment in deposit_withdraws.iter() {
+                    if payment.contract_id != *contract_id || !payment.verify_signature() {
+                        return Err(BlockchainError::InvalidContractPayment);
+                    }
+                    let nonce_key: StringKey =
+                        format!("contract_{}_nonce_{}", contract_id, payment.initiator).into();
+                    let nonce: usize = match self.database.get(nonce_key.clone())? {
+                        Some(b) => b.try_into()?,
+                        None => 0,
+                    };
+                    if payment.nonce != nonce + 1 {
+                        return Err(BlockchainError::InvalidContractPayment);
+                    }
+                    ops.push(WriteOp::Put(nonce_key, payment.nonce.into()));
+
+                    let initiator = payment.initiator.to_string();
+                    if !accounts.contains_key(&initiator) && payment.initiator != tx.src {
+                        accounts.insert(
+                            initiator.clone(),
+                            (
+                                payment.initiator.clone(),
+                                self.get_account(payment.initiator.clone())?,
+                            ),
+                        );
+                    }
+                    let acc = if payment.initiator == tx.src {
+                        &mut acc_src
+                    } else {
+                        &mut accounts.get_mut(&initiator).unwrap().1
+                    };
+                    match payment.direction {
+                        PaymentDirection::Deposit => {
+                            let total = payment
+                                .amount
+                                .checked_add(payment.fee)
+                                .ok_or(BlockchainError::InvalidContractPayment)?;
+                            if acc.balance < total {
+                                return Err(BlockchainError::BalanceInsufficient);
+                            }
+                            acc.balance -= total;
+                            contract_balance += payment.amount;
+                        }
+                        PaymentDirection::Withdraw => {
+                            if contract_balance < payment.amount {
+                                return Err(BlockchainError::ContractBalanceInsufficient);
+                            }
+                            if payment.amount < payment.fee {
+                                return Err(BlockchainError::InvalidContractPayment);
+                            }
+                            contract_balance -= payment.amount;
+                            acc.balance += payment.amount - payment.fee;
+                        }
+                    }
+                    acc_src.balance += payment.fee;
+                }
+                for (_, (addr, acc)) in accounts.into_iter() {
+                    ops.push(WriteOp::Put(format!("account_{}", addr).into(), acc.into()));
+                }
+                ops.push(WriteOp::Put(
+                    format!("contract_{}_balance", contract_id).into(),
+                    contract_balance.into(),
+                ));
+                ops.push(WriteOp::Put(
+                    format!("contract_{}_state", contract_id).into(),
+                    state.with_state_hash(next_state.clone()).into(),
+                ));
             }
             TransactionData::Update {
-                contract_id: _,
-                circuit_index: _,
-                next_state: _,
-                proof: _,
+                contract_id,
+                circuit_index,
+                next_state,
+                proof,
             } => {
-                unimplemented!();
+                let contract = self.get_contract(contract_id)?;
+                let vk = contract
+                    .update_circuits
+                    .get(*circuit_index as usize)
+                    .ok_or(BlockchainError::CircuitNotFound)?;
+                let state = self.get_contract_state(contract_id)?;
+                if !zk::check_proof(vk, state.state_hash(), &[], next_state, proof) {
+                    return Err(BlockchainError::InvalidZkProof);
+                }
+                ops.push(WriteOp::Put(
+                    format!("contract_{}_state", contract_id).into(),
+                    state.with_state_hash(next_state.clone()).into(),
+                ));
             }
-            _ => {
-                unimplemented!();
+            TransactionData::Unknown { .. } => {
+                return Err(BlockchainError::UnsupportedTransaction);
             }
         }
 
+        locked += acc_src.locked_at(height);
+        if tx.src != Address::Treasury && acc_src.balance < locked {
+            return Err(BlockchainError::FundsLocked);
+        }
+        if acc_src.locked_at(height) == 0 {
+            acc_src.locked = None;
+        }
+
         ops.push(WriteOp::Put(
             format!("account_{}", tx.src).into(),
             acc_src.into(),
         ));
 
-        self.database.update(&ops)?;
-        Ok(())
+        // Fees are collected by the Treasury, and paid to the block producer as
+        // a part of the block reward
+        if collected > 0 && tx.src != Address::Treasury {
+            let treasury_key: StringKey = format!("account_{}", Address::Treasury).into();
+            // The Treasury might have been paid by the transaction itself
+            let mut treasury: Account = match ops.iter().rev().find_map(|op| match op {
+                WriteOp::Put(k, v) if k.as_str() == treasury_key.as_str() => Some(v.clone()),
+                _ => None,
+            }) {
+                Some(v) => v.try_into()?,
+                None => self.get_account(Address::Treasury)?,
+            };
+            treasury.balance += collected;
+            ops.push(WriteOp::Put(treasury_key, treasury.into()));
+        }
+        receipt.collected = collected;
+
+        Ok((ops, receipt))
     }
 
     pub fn rollback_block(&mut self) -> Result<(), BlockchainError> {
         let height = self.get_height()?;
+        self.check_body(height - 1)?;
         let rollback_key: StringKey = format!("rollback_{:010}", height - 1).into();
         let mut rollback: Vec<WriteOp> = match self.database.get(rollback_key.clone())? {
             Some(b) => b.try_into()?,
@@ -239,6 +1394,10 @@ impl<K: KvStore> KvStoreChain<K> {
                 return Err(BlockchainError::Inconsistency);
             }
         };
+        let hash = self.get_block(height - 1)?.header.hash();
+        rollback.push(WriteOp::Remove(
+            format!("blockhash_{}", hex::encode(hash)).into(),
+        ));
         rollback.push(WriteOp::Remove(format!("block_{:010}", height - 1).into()));
         rollback.push(WriteOp::Remove(format!("merkle_{:010}", height - 1).into()));
         rollback.push(WriteOp::Remove(
@@ -248,28 +1407,74 @@ impl<K: KvStore> KvStoreChain<K> {
         Ok(())
     }
 
+    // Splits off the transactions going to the reserved slice of the block
+    fn aggregator_lane(&self, txs: Vec<Transaction>) -> (Vec<Transaction>, Vec<Transaction>) {
+        txs.into_iter().partition(|tx| {
+            matches!(tx.data, TransactionData::Update { .. }) && self.aggregators.contains(&tx.src)
+        })
+    }
+
     fn select_transactions(
         &self,
         txs: &Vec<Transaction>,
-    ) -> Result<Vec<Transaction>, BlockchainError> {
+        max_weight: u64,
+    ) -> Result<Vec<(Transaction, TxReceipt)>, BlockchainError> {
+        // Among transactions competing for the same nonce, the one paying the
+        // highest fee is tried first (Replace-by-fee)
         let mut sorted = txs.clone();
-        sorted.sort_by(|t1, t2| t1.nonce.cmp(&t2.nonce));
+        sorted.sort_by_key(|tx| (tx.nonce, std::cmp::Reverse(tx.fee)));
+        let (lane, mut rest) = self.aggregator_lane(sorted);
+        let (num, den) = config::lanes::AGGREGATOR_LANE_SHARE;
+        let reserved = max_weight.saturating_mul(num) / den;
+
         let mut fork = self.fork_on_ram();
         let mut result = Vec::new();
-        for tx in sorted.into_iter() {
-            if fork.apply_tx(&tx).is_ok() {
-                result.push(tx);
+        let mut weight = 0;
+        // Aggregator updates go first, within the reserved slice. Whatever doesn't
+        // fit competes with everything else for the rest of the block.
+        for tx in lane.into_iter() {
+            let tx_weight = tx_weight(&tx);
+            if weight + tx_weight <= reserved {
+                if let Ok((ops, receipt)) = fork.apply_tx(&tx) {
+                    weight += tx_weight;
+                    fork.database.update(&ops)?;
+                    result.push((tx, receipt));
+                    continue;
+                }
+            }
+            rest.push(tx);
+        }
+        rest.sort_by_key(|tx| (tx.nonce, std::cmp::Reverse(tx.fee)));
+        for tx in rest.into_iter() {
+            let tx_weight = tx_weight(&tx);
+            if weight + tx_weight > max_weight {
+                continue;
+            }
+            if let Ok((ops, receipt)) = fork.apply_tx(&tx) {
+                weight += tx_weight;
+                fork.database.update(&ops)?;
+                result.push((tx, receipt));
             }
         }
         Ok(result)
     }
 
-    fn apply_block(&mut self, block: &Block, draft: bool) -> Result<(), BlockchainError> {
+    fn apply_block(
+        &mut self,
+        block: &Block,
+        draft: bool,
+    ) -> Result<BlockExecutionResult, BlockchainError> {
         let curr_height = self.get_height()?;
 
         #[cfg(feature = "pow")]
         let pow_key = self.pow_key(block.header.number as usize)?;
 
+        #[cfg(feature = "pow")]
+        let block_power = block.header.power();
+        // Drafts are sealed by the proposer later
+        #[cfg(feature = "pos")]
+        let mut block_power = 0;
+
         if curr_height > 0 {
             let last_block = self.get_block(curr_height - 1)?;
 
@@ -278,11 +1483,25 @@ impl<K: KvStore> KvStoreChain<K> {
                 return Err(BlockchainError::InvalidTimestamp);
             }
 
+            #[cfg(feature = "pow")]
+            if block.header.proof_of_work.target != self.next_difficulty()? {
+                return Err(BlockchainError::InvalidDifficultyTarget);
+            }
+
             if !draft {
                 #[cfg(feature = "pow")]
                 if !block.header.meets_target(&pow_key) {
                     return Err(BlockchainError::DifficultyTargetUnmet);
                 }
+
+                #[cfg(feature = "pos")]
+                {
+                    block_power = proposal_weight(
+                        &block.header,
+                        &last_block.header,
+                        &self.authorities(block.header.number)?,
+                    )?;
+                }
             }
 
             if block.header.number as usize != curr_height {
@@ -296,26 +1515,104 @@ impl<K: KvStore> KvStoreChain<K> {
             if block.header.block_root != block.merkle_tree().root() {
                 return Err(BlockchainError::InvalidMerkleRoot);
             }
+
+            if block.header.tx_count != last_block.header.tx_count + block.body.len() as u64 {
+                return Err(BlockchainError::InvalidTransactionCount);
+            }
+
+            self.check_checkpoint(&block.header)?;
+
+            validate_reward(block)?;
+
+            if BlockMetadata::new(block).weight > self.get_param(GovernanceParam::MaxBlockWeight)? {
+                return Err(BlockchainError::BlockTooHeavy);
+            }
+        }
+
+        // Transaction types of newer versions can't be validated by this one
+        if let Some(i) = block.body.iter().position(|tx| !tx.data.is_known()) {
+            return Err(BlockchainError::InvalidBlockTransaction(
+                i,
+                Box::new(BlockchainError::UnsupportedTransaction),
+            ));
         }
 
         let mut fork = self.fork_on_ram();
-        for tx in block.body.iter() {
-            fork.apply_tx(tx)?;
+        let mut receipts = Vec::with_capacity(block.body.len());
+        for (i, tx) in block.body.iter().enumerate() {
+            let (ops, receipt) = fork
+                .apply_tx(tx)
+                .map_err(|e| BlockchainError::InvalidBlockTransaction(i, Box::new(e)))?;
+            fork.database.update(&ops)?;
+            receipts.push(receipt);
         }
+
+        // Stake changes only take effect in the next validator epoch, so that
+        // they can't be used for grinding the ongoing elections
+        #[cfg(feature = "pos")]
+        if (block.header.number + 1) % config::VALIDATOR_EPOCH_LENGTH == 0 {
+            let snapshot = fork.validator_snapshot_op(validator_epoch(block.header.number) + 1)?;
+            fork.database.update(&vec![snapshot])?;
+        }
+
+        if (block.header.number + 1) % config::GOVERNANCE_EPOCH_LENGTH == 0 {
+            let ops = fork.tally_proposals_ops(block.header.number)?;
+            fork.database.update(&ops)?;
+        }
+
         let mut changes = fork.database.to_ops();
 
+        let metadata = BlockMetadata::new(block);
+        let result = BlockExecutionResult {
+            fees: receipts.iter().map(|r| r.fee).sum(),
+            weight: metadata.weight,
+            write_set_hash: write_set_hash(changes.clone()),
+            receipts,
+        };
+
         changes.push(WriteOp::Put("height".into(), (curr_height + 1).into()));
 
-        #[cfg(feature = "pow")]
+        // Being part of the rollback record, the index entries and the
+        // metadata are removed by rollback_block too
+        if self.indexed {
+            changes.extend(Self::tx_index_ops(block));
+            for (tx, receipt) in block.body.iter().zip(result.receipts.iter()) {
+                changes.push(WriteOp::Put(
+                    format!("receipt_{}", hex::encode(tx.txid::<Hasher>())).into(),
+                    receipt.clone().into(),
+                ));
+            }
+            changes.push(WriteOp::Put(
+                format!("meta_{:010}", block.header.number).into(),
+                metadata.into(),
+            ));
+        }
+
         changes.push(WriteOp::Put(
             format!("power_{:010}", block.header.number).into(),
-            (block.header.power(&pow_key) + self.get_power()?).into(),
+            (block_power + self.get_power()?).into(),
         ));
 
         changes.push(WriteOp::Put(
             format!("rollback_{:010}", block.header.number).into(),
             self.database.rollback_of(&changes)?.into(),
         ));
+        // Not part of the rollback record, reorgs never go that deep
+        if let Some(old) = self.pruning.and_then(|depth| {
+            (block.header.number as usize)
+                .checked_sub(depth)
+                .filter(|old| *old > 0)
+        }) {
+            let mut stripped = self.get_block(old)?;
+            stripped.body.clear();
+            changes.push(WriteOp::Put(
+                format!("block_{:010}", old).into(),
+                (&stripped).into(),
+            ));
+            changes.push(WriteOp::Remove(format!("merkle_{:010}", old).into()));
+            changes.push(WriteOp::Remove(format!("rollback_{:010}", old).into()));
+            changes.push(WriteOp::Put("pruned_height".into(), (old + 1).into()));
+        }
         changes.push(WriteOp::Put(
             format!("block_{:010}", block.header.number).into(),
             block.into(),
@@ -324,9 +1621,10 @@ impl<K: KvStore> KvStoreChain<K> {
             format!("merkle_{:010}", block.header.number).into(),
             block.merkle_tree().into(),
         ));
+        changes.push(Self::block_hash_op(&block.header));
 
         self.database.update(&changes)?;
-        Ok(())
+        Ok(result)
     }
 }
 
@@ -342,13 +1640,93 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
                     0
                 },
                 nonce: 0,
+                locked: None,
             },
         })
     }
+    fn get_locked_balance(&self, addr: Address) -> Result<Money, BlockchainError> {
+        let height = self.get_height()? as u64;
+        Ok(self
+            .locked_funds(&addr, height)?
+            .iter()
+            .map(|lock| lock.amount)
+            .sum::<Money>()
+            + self.get_account(addr)?.locked_at(height))
+    }
+    fn get_validators(&self) -> Result<Vec<(Address, Validator)>, BlockchainError> {
+        let mut validators = Vec::new();
+        for (k, v) in self.database.pairs("validator_".into())? {
+            let addr = k.as_str()["validator_".len()..]
+                .parse()
+                .map_err(|_| BlockchainError::Inconsistency)?;
+            validators.push((addr, v.try_into()?));
+        }
+        Ok(validators)
+    }
+    #[cfg(feature = "pos")]
+    fn epoch_at(&self, number: u64, slot: Slot) -> Result<Epoch<VRFPublicKey>, BlockchainError> {
+        Ok(build_epoch(slot, self.authorities(number)?))
+    }
 
+    // Fork choice by the accumulated stake of the proposers
     #[cfg(feature = "pos")]
-    fn will_extend(&self, _from: usize, _headers: &Vec<Header>) -> Result<bool, BlockchainError> {
-        unimplemented!();
+    fn will_extend(&self, from: usize, headers: &Vec<Header>) -> Result<bool, BlockchainError> {
+        let current_power = self.get_power()?;
+
+        if from == 0 {
+            return Err(BlockchainError::ExtendFromGenesis);
+        } else if from > self.get_height()? {
+            return Err(BlockchainError::ExtendFromFuture);
+        }
+
+        let mut new_power: u64 = self
+            .database
+            .get(format!("power_{:010}", from - 1).into())?
+            .ok_or(BlockchainError::Inconsistency)?
+            .try_into()?;
+
+        let mut authorities: Option<(u64, Vec<Authority<VRFPublicKey>>)> = None;
+        let mut last_header = self.get_block(from - 1)?.header;
+        for h in headers.iter() {
+            if h.number != last_header.number + 1 {
+                return Err(BlockchainError::InvalidBlockNumber);
+            }
+
+            if h.parent_hash != last_header.hash() {
+                return Err(BlockchainError::InvalidParentHash);
+            }
+
+            // Bodies aren't known yet, the count can only be checked to not decrease
+            if h.tx_count < last_header.tx_count {
+                return Err(BlockchainError::InvalidTransactionCount);
+            }
+
+            self.check_checkpoint(h)?;
+
+            // Validator sets of the epochs starting after the fork point depend
+            // on the bodies of the new branch. Those headers are weighted with
+            // the last set both branches agree on, stake changes take a whole
+            // epoch to apply anyway. They get verified against their actual
+            // sets once the blocks are applied.
+            let epoch = validator_epoch(h.number).min(validator_epoch(from as u64));
+            if authorities.as_ref().map(|(e, _)| *e) != Some(epoch) {
+                authorities = Some((
+                    epoch,
+                    self.authorities(epoch * config::VALIDATOR_EPOCH_LENGTH)?,
+                ));
+            }
+            let (_, authorities) = authorities.as_ref().unwrap();
+            let weight = match proposal_weight(h, &last_header, authorities) {
+                Ok(weight) => weight,
+                // Sealed by a validator that joined on the new branch
+                Err(_) if epoch < validator_epoch(h.number) => 0,
+                Err(e) => return Err(e),
+            };
+            new_power = new_power.saturating_add(weight);
+            last_header = h.clone();
+        }
+
+        Ok(new_power > current_power)
     }
 
     #[cfg(feature = "pow")]
@@ -366,16 +1744,30 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
             .get(format!("power_{:010}", from - 1).into())?
             .ok_or(BlockchainError::Inconsistency)?
             .try_into()?;
+
+        // Linkage is checked sequentially, the expensive PoW checks are done
+        // in parallel afterwards
+        let mut pow_keys = Vec::with_capacity(headers.len());
         let mut last_header = self.get_block(from - 1)?.header;
         for h in headers.iter() {
-            let pow_key = self.pow_key(h.number as usize)?;
+            pow_keys.push(self.pow_key(h.number as usize)?);
 
             if h.proof_of_work.timestamp < self.median_timestamp(from - 1)? {
                 return Err(BlockchainError::InvalidTimestamp);
             }
 
-            if !h.meets_target(&pow_key) {
-                return Err(BlockchainError::DifficultyTargetUnmet);
+            let target = next_target(h.number as usize, last_header.proof_of_work.target, |i| {
+                if i < from {
+                    self.get_block(i).map(|b| b.header.proof_of_work.timestamp)
+                } else {
+                    headers
+                        .get(i - from)
+                        .map(|h| h.proof_of_work.timestamp)
+                        .ok_or(BlockchainError::Inconsistency)
+                }
+            })?;
+            if h.proof_of_work.target != target {
+                return Err(BlockchainError::InvalidDifficultyTarget);
             }
 
             if h.number != last_header.number + 1 {
@@ -386,13 +1778,25 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
                 return Err(BlockchainError::InvalidParentHash);
             }
 
+            // Bodies aren't known yet, the count can only be checked to not decrease
+            if h.tx_count < last_header.tx_count {
+                return Err(BlockchainError::InvalidTransactionCount);
+            }
+
+            self.check_checkpoint(h)?;
+
             last_header = h.clone();
-            new_power += h.power(&pow_key);
         }
+        // Only the work of fully verified headers counts
+        new_power = new_power.saturating_add(verify_pow(headers, &pow_keys)?);
 
         Ok(new_power > current_power)
     }
-    fn extend(&mut self, from: usize, blocks: &Vec<Block>) -> Result<(), BlockchainError> {
+    fn extend(
+        &mut self,
+        from: usize,
+        blocks: &Vec<Block>,
+    ) -> Result<Vec<BlockExecutionResult>, BlockchainError> {
         let curr_height = self.get_height()?;
 
         if from == 0 {
@@ -407,13 +1811,55 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
             forked.rollback_block()?;
         }
 
+        let mut results = Vec::with_capacity(blocks.len());
         for block in blocks.iter() {
-            forked.apply_block(block, false)?;
+            results.push(forked.apply_block(block, false)?);
+        }
+        let mut ops = forked.database.to_ops();
+
+        // The replaced blocks become a side-chain, in case their branch wins
+        // again later. The applied ones leave the fork store.
+        let replaced = self.get_blocks(from, None)?;
+        for (i, block) in replaced.iter().enumerate() {
+            ops.extend(Self::side_block_ops(block, i + 1 == replaced.len()));
+        }
+        for block in blocks.iter() {
+            if self.get_side_block(&block.header.hash())?.is_some() {
+                ops.extend(Self::remove_side_block_ops(&block.header));
+            }
         }
-        let ops = forked.database.to_ops();
 
         self.database.update(&ops)?;
-        Ok(())
+        if from < curr_height {
+            tracing::warn!(
+                fork_height = from,
+                rolled_back = curr_height - from,
+                "Chain reorganized"
+            );
+        }
+        tracing::debug!(
+            height = from + blocks.len(),
+            blocks = blocks.len(),
+            "Chain extended"
+        );
+        Ok(results)
+    }
+    fn rewind(&mut self, height: usize) -> Result<usize, BlockchainError> {
+        let curr_height = self.get_height()?;
+        if height == 0 || height > curr_height {
+            return Err(BlockchainError::InvalidRewindHeight(height));
+        }
+
+        // All or nothing, like a reorg
+        let mut forked = self.fork_on_ram();
+        while forked.get_height()? > height {
+            forked.rollback_block()?;
+        }
+        let ops = forked.database.to_ops();
+        self.database.update(&ops)?;
+
+        tracing::warn!(height, rolled_back = curr_height - height, "Chain rewound");
+        Ok(curr_height - height)
     }
     fn get_height(&self) -> Result<usize, BlockchainError> {
         Ok(match self.database.get("height".into())? {
@@ -426,11 +1872,11 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
         since: usize,
         until: Option<usize>,
     ) -> Result<Vec<Header>, BlockchainError> {
-        Ok(self
-            .get_blocks(since, until)?
-            .into_iter()
-            .map(|b| b.header)
-            .collect())
+        // Headers of the pruned blocks are still there
+        let height = self.get_height()?;
+        (since..until.unwrap_or(height).min(height))
+            .map(|i| self.get_block(i).map(|b| b.header))
+            .collect()
     }
     fn get_blocks(
         &self,
@@ -443,6 +1889,7 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
             if i >= height {
                 break;
             }
+            self.check_body(i)?;
             blks.push(
                 self.database
                     .get(format!("block_{:010}", i).into())?
@@ -452,51 +1899,1653 @@ impl<K: KvStore> Blockchain for KvStoreChain<K> {
         }
         Ok(blks)
     }
-    fn draft_block(
-        &self,
-        timestamp: u32,
-        mempool: &Vec<Transaction>,
-        _wallet: &Wallet,
-    ) -> Result<Block, BlockchainError> {
+    fn get_block_metadata(&self, index: usize) -> Result<BlockMetadata, BlockchainError> {
+        // Blocks applied before the metadata was introduced don't have it stored
+        Ok(
+            match self.database.get(format!("meta_{:010}", index).into())? {
+                Some(b) => b.try_into()?,
+                None => {
+                    self.check_body(index)?;
+                    BlockMetadata::new(&self.get_block(index)?)
+                }
+            },
+        )
+    }
+    fn get_block_height(&self, hash: &[u8]) -> Result<Option<usize>, BlockchainError> {
+        let height: usize = match self
+            .database
+            .get(format!("blockhash_{}", hex::encode(hash)).into())?
+        {
+            Some(b) => b.try_into()?,
+            None => {
+                return Ok(None);
+            }
+        };
+        if height >= self.get_height()? {
+            return Ok(None);
+        }
+        // Entries written by the migration are not part of the rollback records
+        // and might point to a block that has been replaced since.
+        let indexed = self.get_headers(height, Some(height + 1))?.pop();
+        Ok(Some(height).filter(|_| indexed.map(|h| h.hash() == hash) == Some(true)))
+    }
+    fn get_block_by_hash(&self, hash: &[u8]) -> Result<Option<Block>, BlockchainError> {
+        self.get_block_height(hash)?
+            .map(|height| {
+                self.check_body(height)?;
+                self.get_block(height)
+            })
+            .transpose()
+    }
+    fn store_side_blocks(&mut self, blocks: &[Block]) -> Result<(), BlockchainError> {
         let height = self.get_height()?;
-        let last_block = self.get_block(height - 1)?;
-        let mut blk = Block {
-            header: Default::default(),
-            body: self.select_transactions(mempool)?,
+        let mut ops = Vec::new();
+        let mut count = 0;
+        for (k, v) in self.database.pairs("forknumber_".into())? {
+            let number: u64 = v.try_into()?;
+            if (number as usize).saturating_add(config::FORK_STORE_DEPTH) < height {
+                let hash = fork_key_hash(k.as_str()).ok_or(BlockchainError::Inconsistency)?;
+                ops.push(WriteOp::Remove(fork_key(&hash).into()));
+                ops.push(WriteOp::Remove(fork_tip_key(&hash).into()));
+                ops.push(WriteOp::Remove(k));
+            } else {
+                count += 1;
+            }
+        }
+        let mut parent = match blocks.first() {
+            Some(first) => self.get_known_header(&first.header.parent_hash)?,
+            None => None,
         };
-        blk.header.number = height as u64;
-        blk.header.parent_hash = last_block.header.hash();
-        blk.header.block_root = blk.merkle_tree().root();
-        #[cfg(feature = "pow")]
-        {
-            blk.header.proof_of_work.timestamp = timestamp;
-            blk.header.proof_of_work.target = self.next_difficulty()?;
+        // Headers sealed in the validator epochs after the fork point can't be
+        // verified before the bodies are applied (See `will_extend`)
+        #[cfg(feature = "pos")]
+        let last_epoch = match (blocks.first(), &parent) {
+            (Some(first), Some(parent)) => {
+                validator_epoch(match self.get_side_branch(&first.header.parent_hash)? {
+                    Some((fork_height, _)) => fork_height as u64,
+                    None => parent.number.saturating_add(1),
+                })
+            }
+            _ => 0,
+        };
+        for block in blocks.iter() {
+            let linked = matches!(&parent, Some(parent)
+                if block.header.parent_hash == parent.hash()
+                    && parent.number.checked_add(1) == Some(block.header.number));
+            if !linked
+                || count >= config::FORK_STORE_MAX_BLOCKS
+                || (block.header.number as usize).saturating_add(config::FORK_STORE_DEPTH) < height
+            {
+                break;
+            }
+            #[cfg(feature = "pos")]
+            if validator_epoch(block.header.number) > last_epoch {
+                break;
+            }
+            parent = Some(block.header.clone());
+            let hash = block.header.hash();
+            if self.get_block_height(&hash)?.is_some() || self.get_side_block(&hash)?.is_some() {
+                continue;
+            }
+            // Its parent is no longer a tip, if it was one
+            ops.push(WriteOp::Remove(
+                fork_tip_key(&block.header.parent_hash).into(),
+            ));
+            ops.extend(Self::side_block_ops(block, true));
+            count += 1;
         }
-        self.fork_on_ram().apply_block(&blk, true)?; // Check if everything is ok
-        Ok(blk)
+        self.database.update(&ops)?;
+        Ok(())
     }
-    #[cfg(feature = "pow")]
-    fn get_power(&self) -> Result<u64, BlockchainError> {
+    fn get_side_branch(&self, tip: &[u8]) -> Result<Option<(usize, Vec<Block>)>, BlockchainError> {
+        let mut branch = Vec::new();
+        let mut hash = tip.to_vec();
+        loop {
+            if let Some(height) = self.get_block_height(&hash)? {
+                branch.reverse();
+                return Ok(Some((height + 1, branch)).filter(|(_, b)| !b.is_empty()));
+            }
+            match self.get_side_block(&hash)? {
+                Some(block) => {
+                    hash = block.header.parent_hash.to_vec();
+                    branch.push(block);
+                }
+                // Its ancestors have been pruned
+                None => {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+    fn drop_side_branch(&mut self, tip: &[u8]) -> Result<(), BlockchainError> {
+        let mut ops = Vec::new();
+        if let Some((_, branch)) = self.get_side_branch(tip)? {
+            for block in branch.iter() {
+                ops.extend(Self::remove_side_block_ops(&block.header));
+            }
+        }
+        self.database.update(&ops)?;
+        Ok(())
+    }
+    fn get_chain_tips(&self) -> Result<Vec<ChainTip>, BlockchainError> {
         let height = self.get_height()?;
-        if height == 0 {
-            Ok(0)
-        } else {
-            Ok(self
-                .database
-                .get(format!("power_{:010}", height - 1).into())?
-                .ok_or(BlockchainError::Inconsistency)?
-                .try_into()?)
+        let active = self.get_block(height - 1)?.header;
+        let mut tips = vec![ChainTip {
+            hash: active.hash(),
+            number: active.number,
+            fork_height: height,
+            branch_length: 0,
+            active: true,
+        }];
+        let mut side_tips = Vec::new();
+        for (k, _) in self.database.pairs("forktip_".into())? {
+            let hash = fork_key_hash(k.as_str()).ok_or(BlockchainError::Inconsistency)?;
+            if let Some((fork_height, branch)) = self.get_side_branch(&hash)? {
+                if let Some(tip) = branch.last() {
+                    side_tips.push(ChainTip {
+                        hash: tip.header.hash(),
+                        number: tip.header.number,
+                        fork_height,
+                        branch_length: branch.len(),
+                        active: false,
+                    });
+                }
+            }
         }
+        side_tips.sort_by_key(|t| std::cmp::Reverse(t.number));
+        tips.extend(side_tips);
+        Ok(tips)
     }
-    #[cfg(feature = "pow")]
-    fn pow_key(&self, index: usize) -> Result<Vec<u8>, BlockchainError> {
-        Ok(if index < config::POW_KEY_CHANGE_DELAY {
-            config::POW_BASE_KEY.to_vec()
+    fn get_checkpoint(&self) -> Result<Option<Checkpoint>, BlockchainError> {
+        Ok(match self.database.get("checkpoint".into())? {
+            Some(b) => Some(b.try_into()?),
+            None => None,
+        })
+    }
+    fn get_transaction(
+        &self,
+        txid: TransactionId,
+    ) -> Result<Option<(Transaction, TransactionLocation)>, BlockchainError> {
+        let location: TransactionLocation = match self
+            .database
+            .get(format!("tx_{}", hex::encode(txid)).into())?
+        {
+            Some(b) => b.try_into()?,
+            None => {
+                return Ok(None);
+            }
+        };
+        if location.block as usize >= self.get_height()? {
+            return Ok(None);
+        }
+        // Entries written by the migration are not part of the rollback records
+        // and might be stale.
+        Ok(self
+            .get_block(location.block as usize)?
+            .body
+            .get(location.index)
+            .filter(|tx| tx.txid::<Hasher>() == txid)
+            .cloned()
+            .map(|tx| (tx, location)))
+    }
+    fn get_receipt(&self, txid: TransactionId) -> Result<Option<TxReceipt>, BlockchainError> {
+        // Like the location, only valid as long as the transaction is
+        Ok(match self.get_transaction(txid)? {
+            Some(_) => match self
+                .database
+                .get(format!("receipt_{}", hex::encode(txid)).into())?
+            {
+                Some(b) => Some(b.try_into()?),
+                None => None,
+            },
+            None => None,
+        })
+    }
+    fn db_stats(&self) -> Result<DbStats, BlockchainError> {
+        Ok(self.database.stats()?)
+    }
+    fn compact(&mut self) -> Result<(), BlockchainError> {
+        Ok(self.database.compact()?)
+    }
+    fn randomness(&self, height: usize) -> Result<Randomness, BlockchainError> {
+        if height == 0 || height > self.get_height()? {
+            return Err(BlockchainError::BlockNotFound);
+        }
+        let start = randomness_window_start(height as u64) as usize;
+        let headers = self.get_headers(start, Some(height))?;
+        Ok(Randomness::mix(height as u64, &headers))
+    }
+    fn get_param(&self, param: GovernanceParam) -> Result<u64, BlockchainError> {
+        Ok(
+            match self
+                .database
+                .get(format!("param_{}", param.name()).into())?
+            {
+                Some(b) => b.try_into()?,
+                None => param.default_value(),
+            },
+        )
+    }
+    fn export_state(
+        &self,
+        writer: &mut dyn std::io::Write,
+        height: usize,
+        format: ExportFormat,
+    ) -> Result<ExportSummary, BlockchainError> {
+        if height == 0 || height > self.get_height()? {
+            return Err(BlockchainError::BlockNotFound);
+        }
+        // Older states are reconstructed in memory, by undoing the blocks after
+        let mut fork = self.fork_on_ram();
+        while fork.get_height()? > height {
+            fork.rollback_block()?;
+        }
+        let mut exporter = StateExporter::new(writer, format)?;
+
+        let mut accounts = Vec::new();
+        for (k, v) in fork.database.pairs("account_".into())? {
+            let account: Account = v.try_into()?;
+            accounts.push((k.as_str()["account_".len()..].to_string(), account));
+        }
+        // Untouched Treasury isn't stored, but holds the whole supply
+        let treasury = Address::Treasury.to_string();
+        if !accounts.iter().any(|(id, _)| *id == treasury) {
+            accounts.push((treasury, fork.get_account(Address::Treasury)?));
+        }
+        accounts.sort_by(|a, b| a.0.cmp(&b.0));
+        for (id, account) in accounts {
+            exporter.write(&StateRecord::Account {
+                id,
+                balance: account.balance,
+                nonce: account.nonce,
+            })?;
+        }
+
+        // The other `contract_<id>_*` keys are skipped
+        let mut contract_ids = fork
+            .database
+            .pairs("contract_".into())?
+            .into_iter()
+            .filter_map(
+                |(k, _)| match k.as_str()["contract_".len()..].split_once('_') {
+                    Some((id, "state")) => Some(id.to_string()),
+                    _ => None,
+                },
+            )
+            .collect::<Vec<_>>();
+        contract_ids.sort();
+        for id in contract_ids {
+            let contract_id: ContractId = id.parse().map_err(|_| BlockchainError::Inconsistency)?;
+            exporter.write(&StateRecord::Contract {
+                balance: fork.get_contract_balance(&contract_id)?,
+                state: hex::encode(
+                    bincode::serialize(&fork.get_contract_state(&contract_id)?).unwrap(),
+                ),
+                id,
+            })?;
+        }
+
+        exporter.finish(height)
+    }
+    fn get_records(&self, namespace: &str) -> Result<Vec<(String, Vec<u8>)>, BlockchainError> {
+        let prefix = format!("{}_", namespace);
+        let mut records = Vec::new();
+        for (k, v) in self.database.pairs(prefix.clone().into())? {
+            records.push((k.as_str()[prefix.len()..].to_string(), v.try_into()?));
+        }
+        Ok(records)
+    }
+    fn update_records(
+        &mut self,
+        namespace: &str,
+        records: Vec<(String, Option<Vec<u8>>)>,
+    ) -> Result<(), BlockchainError> {
+        let ops = records
+            .into_iter()
+            .map(|(id, v)| {
+                let k = format!("{}_{}", namespace, id).into();
+                match v {
+                    Some(v) => WriteOp::Put(k, v.into()),
+                    None => WriteOp::Remove(k),
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(self.database.update(&ops)?)
+    }
+    fn verify_chain(&self, level: VerifyLevel) -> Result<VerifyReport, BlockchainError> {
+        self.verify_blocks(level)
+    }
+    fn apply_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), BlockchainError> {
+        if !checkpoint.verify(
+            &checkpoint_authorities()?,
+            config::checkpoint::CHECKPOINT_THRESHOLD,
+        ) {
+            return Err(BlockchainError::InvalidCheckpoint);
+        }
+        if let Some(curr) = self.get_checkpoint()? {
+            if curr.number >= checkpoint.number {
+                return Ok(());
+            }
+        }
+
+        // We are on a chain which contradicts the checkpoint, rollback
+        // to the checkpoint so that the correct chain can be synced.
+        let number = checkpoint.number as usize;
+        if number < self.get_height()? && self.get_block(number)?.header.hash() != checkpoint.hash {
+            if number == 0 {
+                return Err(BlockchainError::CheckpointConflict);
+            }
+            tracing::warn!(height = number, "Rolling back to a conflicting checkpoint");
+            while self.get_height()? > number {
+                self.rollback_block()?;
+            }
+        }
+
+        tracing::info!(height = number, "Checkpoint applied");
+        self.database.update(&vec![WriteOp::Put(
+            "checkpoint".into(),
+            checkpoint.clone().into(),
+        )])?;
+        Ok(())
+    }
+    fn get_reorgs(&self) -> Result<Vec<ReorgRecord>, BlockchainError> {
+        // The kept records are contiguous, walk back from the latest one
+        let mut reorgs = Vec::new();
+        for i in (0..self.reorg_count()?).rev() {
+            match self.database.get(reorg_key(i).into())? {
+                Some(b) => reorgs.push(b.try_into()?),
+                None => break,
+            }
+        }
+        reorgs.reverse();
+        Ok(reorgs)
+    }
+    fn record_reorg(
+        &mut self,
+        record: ReorgRecord,
+        capacity: usize,
+    ) -> Result<(), BlockchainError> {
+        let count = self.reorg_count()?;
+        let mut ops = vec![
+            WriteOp::Put(reorg_key(count).into(), record.into()),
+            WriteOp::Put("reorgs".into(), (count + 1).into()),
+        ];
+        if count >= capacity {
+            ops.push(WriteOp::Remove(reorg_key(count - capacity).into()));
+        }
+        self.database.update(&ops)?;
+        Ok(())
+    }
+    fn draft_block(
+        &self,
+        timestamp: u32,
+        mempool: &Vec<Transaction>,
+        payouts: &PayoutSplit,
+    ) -> Result<Block, BlockchainError> {
+        let height = self.get_height()?;
+        let last_block = self.get_block(height - 1)?;
+        // Only the block producer may spend from the Treasury
+        let mempool = mempool
+            .iter()
+            .filter(|tx| tx.src != Address::Treasury)
+            .cloned()
+            .collect();
+        let treasury = self.get_account(Address::Treasury)?;
+        // The amounts of the rewards aren't known yet, but they are fixed-size
+        // and don't change the weight
+        let reserved = BlockMetadata::new(&Block {
+            header: Default::default(),
+            body: payouts.reward_transactions(treasury.nonce, 0),
+        })
+        .weight;
+        let max_weight = self
+            .get_param(GovernanceParam::MaxBlockWeight)?
+            .saturating_sub(reserved);
+        let (txs, receipts): (Vec<_>, Vec<_>) = self
+            .select_transactions(&mempool, max_weight)?
+            .into_iter()
+            .unzip();
+        let fees = receipts.iter().map(|r| r.fee).sum::<Money>();
+        let mut body =
+            payouts.reward_transactions(treasury.nonce, block_reward(height as u64) + fees);
+        body.extend(txs);
+        let mut blk = Block {
+            header: Default::default(),
+            body,
+        };
+        blk.header.number = height as u64;
+        blk.header.parent_hash = last_block.header.hash();
+        blk.header.block_root = blk.merkle_tree().root();
+        blk.header.tx_count = last_block.header.tx_count + blk.body.len() as u64;
+        #[cfg(feature = "pow")]
+        {
+            blk.header.proof_of_work.timestamp = timestamp;
+            blk.header.proof_of_work.target = self.next_difficulty()?;
+        }
+        self.fork_on_ram().apply_block(&blk, true)?; // Check if everything is ok
+        Ok(blk)
+    }
+    fn get_power(&self) -> Result<u64, BlockchainError> {
+        let height = self.get_height()?;
+        if height == 0 {
+            Ok(0)
         } else {
-            let reference = ((index - config::POW_KEY_CHANGE_DELAY)
-                / config::POW_KEY_CHANGE_INTERVAL)
-                * config::POW_KEY_CHANGE_INTERVAL;
-            self.get_block(reference)?.header.hash().to_vec()
+            Ok(self
+                .database
+                .get(format!("power_{:010}", height - 1).into())?
+                .ok_or(BlockchainError::Inconsistency)?
+                .try_into()?)
+        }
+    }
+    #[cfg(feature = "pow")]
+    fn pow_key(&self, index: usize) -> Result<Vec<u8>, BlockchainError> {
+        Ok(match pow_key_reference(index) {
+            Some(reference) => self.get_block(reference)?.header.hash().to_vec(),
+            None => config::POW_BASE_KEY.to_vec(),
         })
     }
+    #[cfg(feature = "pow")]
+    fn generate_chain_proof(&self) -> Result<ChainProof, BlockchainError> {
+        let height = self.get_height()?;
+        let suffix_start = height.saturating_sub(config::CHAIN_PROOF_SUFFIX);
+        let suffix = self.get_headers(suffix_start, None)?;
+        let tip_hash = suffix.last().ok_or(BlockchainError::BlockNotFound)?.hash();
+        let samples = sample_heights(&tip_hash, suffix_start, config::CHAIN_PROOF_SAMPLES)
+            .into_iter()
+            .map(|i| self.get_block(i).map(|b| b.header))
+            .collect::<Result<Vec<Header>, BlockchainError>>()?;
+
+        let mut references = samples
+            .iter()
+            .chain(suffix.iter())
+            .filter_map(|h| pow_key_reference(h.number as usize))
+            .collect::<Vec<_>>();
+        references.sort_unstable();
+        references.dedup();
+        let key_headers = references
+            .into_iter()
+            .map(|i| self.get_block(i).map(|b| b.header))
+            .collect::<Result<Vec<Header>, BlockchainError>>()?;
+
+        Ok(ChainProof {
+            samples,
+            suffix,
+            key_headers,
+        })
+    }
+    #[cfg(feature = "pow")]
+    fn export_relay_headers(
+        &self,
+        since: usize,
+        until: Option<usize>,
+    ) -> Result<Vec<relay::RelayHeader>, BlockchainError> {
+        Ok(self
+            .get_headers(since, until)?
+            .iter()
+            .map(|h| {
+                let work = if h.number == 0 { 0 } else { h.power() };
+                relay::encode_relay_header(h, work)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Signature;
+    use crate::db::RamKvStore;
+    use crate::wallet::Wallet;
+    use crate::zk::ZkProof;
+
+    // A chain in which `wallet` holds `amount` of mature coins
+    fn funded_chain(wallet: &mut Wallet, amount: Money) -> KvStoreChain<RamKvStore> {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let funding = Transaction {
+            src: Address::Treasury,
+            data: TransactionData::RegularSend {
+                dst: wallet.get_address(),
+                amount,
+            },
+            nonce: chain.get_account(Address::Treasury).unwrap().nonce + 1,
+            fee: 0,
+            sig: Signature::Unsigned,
+        };
+        let (mut ops, _) = chain.apply_tx(&funding).unwrap();
+        ops.push(WriteOp::Put(
+            "height".into(),
+            (1 + config::COINBASE_MATURITY as usize).into(),
+        ));
+        chain.database.update(&ops).unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        chain
+    }
+
+    fn apply(
+        chain: &mut KvStoreChain<RamKvStore>,
+        tx: &Transaction,
+    ) -> Result<(), BlockchainError> {
+        let (ops, _) = chain.apply_tx(tx)?;
+        chain.database.update(&ops)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tx_index() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        // Signed transactions are indexed by their id, not by their hash
+        let tx = Wallet::new(b"ABC".to_vec()).create_transaction(Address::Treasury, 100, 1);
+        let mut block = chain.get_block(0).unwrap();
+        block.body = vec![tx.clone()];
+        let mut ops = KvStoreChain::<RamKvStore>::tx_index_ops(&block);
+        ops.push(WriteOp::Put(
+            format!("block_{:010}", 0).into(),
+            (&block).into(),
+        ));
+        chain.database.update(&ops).unwrap();
+        assert_eq!(
+            chain
+                .get_transaction(tx.txid::<Hasher>())
+                .unwrap()
+                .map(|(tx, location)| (tx, location.block)),
+            Some((tx.clone(), 0))
+        );
+        assert!(chain
+            .get_transaction(tx.hash::<Hasher>())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_apply_tx_is_pure() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let dst = Wallet::new(b"DST".to_vec()).get_address();
+        let treasury = chain.get_account(Address::Treasury).unwrap();
+        let before = chain.get_account(dst.clone()).unwrap();
+        let tx = Transaction {
+            src: Address::Treasury,
+            data: TransactionData::RegularSend {
+                dst: dst.clone(),
+                amount: 100,
+            },
+            nonce: treasury.nonce + 1,
+            fee: 1,
+            sig: Signature::Unsigned,
+        };
+
+        let (ops, receipt) = chain.apply_tx(&tx).unwrap();
+        assert_eq!(receipt.collected, 1);
+        assert!(receipt.created_contract.is_none());
+        assert_eq!(
+            chain.get_account(dst.clone()).unwrap().balance,
+            before.balance
+        );
+
+        chain.database.update(&ops).unwrap();
+        assert_eq!(
+            chain.get_account(dst).unwrap().balance,
+            before.balance + 100
+        );
+        let after = chain.get_account(Address::Treasury).unwrap();
+        assert_eq!(after.balance, treasury.balance - 101);
+        assert_eq!(after.nonce, treasury.nonce + 1);
+    }
+
+    #[test]
+    fn test_block_execution_result() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        // Not from the Treasury, which only pays out immature coins
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                format!("account_{}", wallet.get_address()).into(),
+                Account {
+                    balance: 1000,
+                    nonce: 0,
+                    locked: None,
+                }
+                .into(),
+            )])
+            .unwrap();
+        let tx = wallet.create_transaction(Address::Treasury, 100, 2);
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        let block = chain
+            .draft_block(crate::utils::local_timestamp(), &vec![tx.clone()], &payouts)
+            .unwrap();
+        let result = chain.apply_block(&block, true).unwrap();
+        assert_eq!(result.receipts.len(), block.body.len());
+        assert_eq!(result.fees, 2);
+        assert_eq!(result.weight, BlockMetadata::new(&block).weight);
+
+        // Stored under the id of the transaction
+        let receipt = chain.get_receipt(tx.txid::<Hasher>()).unwrap().unwrap();
+        assert_eq!(receipt.fee, 2);
+        assert!(chain.get_receipt(tx.hash::<Hasher>()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unknown_transaction_rejected() {
+        let chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let treasury = chain.get_account(Address::Treasury).unwrap();
+        let tx = Transaction {
+            src: Address::Treasury,
+            data: TransactionData::Unknown {
+                tag: 1000,
+                payload: vec![1, 2, 3],
+            },
+            nonce: treasury.nonce + 1,
+            fee: 1,
+            sig: Signature::Unsigned,
+        };
+        assert!(matches!(
+            chain.apply_tx(&tx),
+            Err(BlockchainError::UnsupportedTransaction)
+        ));
+    }
+
+    #[test]
+    fn test_incompatible_database() {
+        let reopen = |version: Option<usize>| {
+            let mut db = KvStoreChain::new(RamKvStore::new()).unwrap().database;
+            db.update(&vec![match version {
+                Some(v) => WriteOp::Put("db_version".into(), v.into()),
+                None => WriteOp::Remove("db_version".into()),
+            }])
+            .unwrap();
+            KvStoreChain::new(db)
+        };
+        assert!(matches!(
+            reopen(None),
+            Err(BlockchainError::IncompatibleDatabase)
+        ));
+        // Written by an older version, e.g. with headers lacking `tx_count`
+        assert!(matches!(
+            reopen(Some(DB_VERSION - 1)),
+            Err(BlockchainError::IncompatibleDatabase)
+        ));
+        assert!(reopen(Some(DB_VERSION)).is_ok());
+    }
+
+    #[test]
+    fn test_export_state() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let dst = Wallet::new(b"DST".to_vec()).get_address();
+        let tx = Transaction {
+            src: Address::Treasury,
+            data: TransactionData::RegularSend {
+                dst: dst.clone(),
+                amount: 100,
+            },
+            nonce: chain.get_account(Address::Treasury).unwrap().nonce + 1,
+            fee: 1,
+            sig: Signature::Unsigned,
+        };
+        let (ops, _) = chain.apply_tx(&tx).unwrap();
+        chain.database.update(&ops).unwrap();
+
+        let mut out = Vec::new();
+        let summary = chain
+            .export_state(&mut out, 1, ExportFormat::JsonLines)
+            .unwrap();
+        // Money is only moved around, the fee is yet to be paid out by the block
+        assert_eq!(summary.total_balance, TOTAL_SUPPLY - tx.fee);
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), summary.records + 1);
+        assert!(lines.iter().any(|l| l.contains(&dst.to_string())));
+
+        // The root is reproducible from the lines alone
+        let mut root = <Hasher as Hash>::Output::default();
+        for line in &lines[..summary.records] {
+            root = Hasher::hash(&[&root[..], line.as_bytes(), b"\n"].concat());
+        }
+        assert_eq!(hex::encode(root), summary.root);
+        let last: serde_json::Value = serde_json::from_str(lines[summary.records]).unwrap();
+        assert_eq!(last["kind"], "root");
+        assert_eq!(last["root"], summary.root);
+
+        let mut csv = Vec::new();
+        let csv_summary = chain.export_state(&mut csv, 1, ExportFormat::Csv).unwrap();
+        assert_eq!(csv_summary.total_balance, summary.total_balance);
+        assert!(chain
+            .export_state(&mut Vec::new(), 2, ExportFormat::Csv)
+            .is_err());
+    }
+
+    #[test]
+    fn test_coinbase_maturity() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let mut wallet = Wallet::new(b"MINER".to_vec());
+        let reward = Transaction {
+            src: Address::Treasury,
+            data: TransactionData::RegularSend {
+                dst: wallet.get_address(),
+                amount: 100,
+            },
+            nonce: chain.get_account(Address::Treasury).unwrap().nonce + 1,
+            fee: 0,
+            sig: Signature::Unsigned,
+        };
+        let (ops, _) = chain.apply_tx(&reward).unwrap();
+        chain.database.update(&ops).unwrap();
+        assert!(chain.get_locked_balance(wallet.get_address()).unwrap() >= 100);
+
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let tx = wallet.create_transaction(Address::Treasury, 50, 0);
+        assert!(matches!(
+            chain.apply_tx(&tx),
+            Err(BlockchainError::FundsLocked)
+        ));
+
+        let mature_height = 1 + config::COINBASE_MATURITY as usize;
+        chain
+            .database
+            .update(&vec![WriteOp::Put("height".into(), mature_height.into())])
+            .unwrap();
+        assert_eq!(chain.get_locked_balance(wallet.get_address()).unwrap(), 0);
+        assert!(chain.apply_tx(&tx).is_ok());
+    }
+
+    #[test]
+    fn test_block_hash_index() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let genesis = chain.get_block(0).unwrap();
+        let hash = genesis.header.hash();
+        assert_eq!(chain.get_block_height(&hash).unwrap(), Some(0));
+        assert_eq!(
+            chain
+                .get_block_by_hash(&hash)
+                .unwrap()
+                .map(|b| b.header.hash()),
+            Some(hash)
+        );
+        assert!(chain.get_block_by_hash(&[0u8; 32]).unwrap().is_none());
+
+        // Stale entries, pointing to a block with another hash, are ignored
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                format!("blockhash_{}", hex::encode([1u8; 32])).into(),
+                0usize.into(),
+            )])
+            .unwrap();
+        assert_eq!(chain.get_block_height(&[1u8; 32]).unwrap(), None);
+
+        chain.rollback_block().unwrap();
+        assert_eq!(chain.get_block_height(&hash).unwrap(), None);
+        assert!(chain
+            .database
+            .get(format!("blockhash_{}", hex::encode(hash)).into())
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "pow")]
+    #[test]
+    fn test_will_extend_claimed_power() {
+        let chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        let header = chain
+            .draft_block(crate::utils::local_timestamp(), &vec![], &payouts)
+            .unwrap()
+            .header;
+        assert!(chain.will_extend(1, &vec![header.clone()]).unwrap());
+
+        // The hardest targets possible, summing up to more than u64::MAX
+        let mut forged = header;
+        forged.proof_of_work.target = 0;
+        let mut next = forged.clone();
+        next.number += 1;
+        next.parent_hash = forged.hash();
+        assert!(matches!(
+            chain.will_extend(1, &vec![forged, next]),
+            Err(BlockchainError::InvalidDifficultyTarget)
+        ));
+    }
+
+    #[test]
+    fn test_pruning() {
+        let mut chain = KvStoreChain::new(RamKvStore::new())
+            .unwrap()
+            .with_pruning(2);
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        for _ in 0..4 {
+            let block = chain
+                .draft_block(crate::utils::local_timestamp(), &vec![], &payouts)
+                .unwrap();
+            chain.apply_block(&block, true).unwrap();
+        }
+        assert_eq!(chain.get_height().unwrap(), 5);
+        assert_eq!(chain.pruned_height().unwrap(), 3);
+
+        assert!(matches!(
+            chain.get_blocks(0, None),
+            Err(BlockchainError::BlockPruned)
+        ));
+        assert_eq!(chain.get_blocks(0, Some(1)).unwrap().len(), 1);
+        assert_eq!(chain.get_blocks(3, None).unwrap().len(), 2);
+        let headers = chain.get_headers(0, None).unwrap();
+        assert_eq!(headers.len(), 5);
+        assert!(matches!(
+            chain.get_block_by_hash(&headers[1].hash()),
+            Err(BlockchainError::BlockPruned)
+        ));
+        assert!(chain
+            .database
+            .get("rollback_0000000002".into())
+            .unwrap()
+            .is_none());
+
+        chain.rollback_block().unwrap();
+        chain.rollback_block().unwrap();
+        assert!(matches!(
+            chain.rollback_block(),
+            Err(BlockchainError::BlockPruned)
+        ));
+        assert_eq!(chain.get_height().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        let mut hashes = Vec::new();
+        for _ in 0..2 {
+            let block = chain
+                .draft_block(crate::utils::local_timestamp(), &vec![], &payouts)
+                .unwrap();
+            chain.apply_block(&block, true).unwrap();
+            hashes.push(block.header.hash());
+        }
+        assert_eq!(chain.get_height().unwrap(), 3);
+
+        assert!(matches!(
+            chain.rewind(0),
+            Err(BlockchainError::InvalidRewindHeight(0))
+        ));
+        assert!(chain.rewind(4).is_err());
+        assert_eq!(chain.rewind(3).unwrap(), 0);
+
+        assert_eq!(chain.rewind(1).unwrap(), 2);
+        assert_eq!(chain.get_height().unwrap(), 1);
+        assert_eq!(chain.get_block_height(&hashes[0]).unwrap(), None);
+        assert_eq!(
+            chain.get_account(Address::Treasury).unwrap(),
+            KvStoreChain::new(RamKvStore::new())
+                .unwrap()
+                .get_account(Address::Treasury)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_chain() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let miner = Wallet::new(b"MINER".to_vec()).get_address();
+        let payouts = PayoutSplit::single(miner.clone());
+        for _ in 0..2 {
+            let block = chain
+                .draft_block(crate::utils::local_timestamp(), &vec![], &payouts)
+                .unwrap();
+            chain.apply_block(&block, true).unwrap();
+        }
+        let verify = |chain: &KvStoreChain<RamKvStore>, level| {
+            chain.verify_chain(level).unwrap().inconsistency
+        };
+        assert_eq!(verify(&chain, VerifyLevel::Full), None);
+
+        // A corrupted account is only caught by re-executing the blocks
+        let mut account = chain.get_account(miner.clone()).unwrap();
+        account.balance += 1;
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                format!("account_{}", miner).into(),
+                account.into(),
+            )])
+            .unwrap();
+        assert_eq!(verify(&chain, VerifyLevel::Merkle), None);
+        assert_eq!(
+            verify(&chain, VerifyLevel::Full),
+            Some((2, format!("state differs at account_{}", miner)))
+        );
+
+        // A body not matching its header, the header itself is fine
+        let mut block = chain.get_block(1).unwrap();
+        block.body.clear();
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                "block_0000000001".into(),
+                (&block).into(),
+            )])
+            .unwrap();
+        assert_eq!(verify(&chain, VerifyLevel::Headers), None);
+        assert_eq!(
+            verify(&chain, VerifyLevel::Merkle),
+            Some((1, "merkle root does not match the body".into()))
+        );
+        let report = chain.verify_chain(VerifyLevel::Full).unwrap();
+        assert_eq!(report.verified, 1);
+    }
+
+    #[test]
+    fn test_fork_store() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let genesis = chain.get_block(0).unwrap();
+        let side_block = |parent: &Block, tag: u8| {
+            let mut block = parent.clone();
+            block.header.parent_hash = parent.header.hash();
+            block.header.number = parent.header.number + 1;
+            block.header.state_root = [tag; 32];
+            block
+        };
+        let a1 = side_block(&genesis, 1);
+        let a2 = side_block(&a1, 2);
+        let b1 = side_block(&genesis, 3);
+        let orphan = side_block(&side_block(&genesis, 4), 5);
+        chain.store_side_blocks(&[a1.clone(), a2.clone()]).unwrap();
+        chain.store_side_blocks(&[b1.clone()]).unwrap();
+        chain.store_side_blocks(&[orphan.clone()]).unwrap();
+        // Blocks must be numbered after their parents
+        let mut skipping = side_block(&b1, 6);
+        skipping.header.number = u64::MAX;
+        chain.store_side_blocks(&[skipping.clone()]).unwrap();
+        assert!(chain
+            .get_side_branch(&skipping.header.hash())
+            .unwrap()
+            .is_none());
+        assert_eq!(chain.database.pairs("forktip_".into()).unwrap().len(), 2);
+
+        let (fork_height, branch) = chain.get_side_branch(&a2.header.hash()).unwrap().unwrap();
+        assert_eq!(fork_height, 1);
+        assert_eq!(
+            branch.iter().map(|b| b.header.hash()).collect::<Vec<_>>(),
+            vec![a1.header.hash(), a2.header.hash()]
+        );
+        assert!(chain
+            .get_side_branch(&orphan.header.hash())
+            .unwrap()
+            .is_none());
+        assert!(chain
+            .get_side_branch(&genesis.header.hash())
+            .unwrap()
+            .is_none());
+
+        let tips = chain.get_chain_tips().unwrap();
+        assert_eq!(tips.len(), 3);
+        assert!(tips[0].active);
+        assert_eq!(tips[0].hash, genesis.header.hash());
+        assert_eq!(
+            tips[1],
+            ChainTip {
+                hash: a2.header.hash(),
+                number: 2,
+                fork_height: 1,
+                branch_length: 2,
+                active: false,
+            }
+        );
+        assert_eq!(tips[2].hash, b1.header.hash());
+
+        chain.drop_side_branch(&b1.header.hash()).unwrap();
+        assert_eq!(chain.get_chain_tips().unwrap().len(), 2);
+
+        // Branches falling too far behind are pruned
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                "height".into(),
+                (3 + config::FORK_STORE_DEPTH).into(),
+            )])
+            .unwrap();
+        chain.store_side_blocks(&[]).unwrap();
+        for prefix in ["fork_", "forknumber_", "forktip_"] {
+            assert!(chain.database.pairs(prefix.into()).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_randomness() {
+        let chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let randomness = chain.randomness(1).unwrap();
+        assert_eq!(randomness, chain.randomness(1).unwrap());
+        assert_eq!(
+            randomness,
+            Randomness::mix(1, &chain.get_headers(0, None).unwrap())
+        );
+        // Genesis has no VRF output
+        assert_eq!(randomness.source, RandomnessSource::BlockHashes);
+        assert_ne!(randomness.value, Randomness::mix(2, &[]).value);
+        assert!(chain.randomness(2).is_err());
+    }
+
+    #[test]
+    fn test_batch_send() {
+        let mut wallet = Wallet::new(b"EXCHANGE".to_vec());
+        let chain = funded_chain(&mut wallet, 100);
+        let acc = chain.get_account(wallet.get_address()).unwrap();
+        let treasury = chain.get_account(Address::Treasury).unwrap();
+
+        // All or nothing
+        let tx = wallet.clone().create_batch_transaction(
+            vec![(Address::Treasury, acc.balance), (Address::Treasury, 1)],
+            0,
+        );
+        assert!(matches!(
+            chain.apply_tx(&tx),
+            Err(BlockchainError::BalanceInsufficient)
+        ));
+        let tx = wallet.clone().create_batch_transaction(vec![], 1);
+        assert!(matches!(
+            chain.apply_tx(&tx),
+            Err(BlockchainError::InvalidBatchSend)
+        ));
+
+        let tx = wallet
+            .create_batch_transaction(vec![(Address::Treasury, 30), (Address::Treasury, 20)], 1);
+        assert_eq!(wallet.balance().spendable, acc.balance - 51);
+        let (ops, _) = chain.apply_tx(&tx).unwrap();
+        let after = |key: &str| -> Account {
+            ops.iter()
+                .rev()
+                .find_map(|op| match op {
+                    WriteOp::Put(k, v) if k.as_str() == key => Some(v.clone().try_into().unwrap()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let src = after(&format!("account_{}", wallet.get_address()));
+        assert_eq!((src.balance, src.nonce), (acc.balance - 51, acc.nonce + 1));
+        // The fee is collected by the Treasury too
+        assert_eq!(
+            after(&format!("account_{}", Address::Treasury)).balance,
+            treasury.balance + 51
+        );
+    }
+
+    #[test]
+    fn test_create_contract() {
+        let create = |wallet: &mut Wallet| {
+            wallet.create_contract(
+                serde_json::from_str("[1, 2, 3]").unwrap(),
+                vec![serde_json::from_str("[4, 5]").unwrap()],
+                serde_json::from_str("{}").unwrap(),
+                serde_json::from_str(r#"{"leaf_size": 1, "tree_depth": 8}"#).unwrap(),
+                1,
+            )
+        };
+        // 5 bytes of verifier keys
+        let creation_fee = 5 * config::CONTRACT_CREATION_FEE_PER_BYTE;
+
+        let mut poor = Wallet::new(b"POOR".to_vec());
+        let chain = funded_chain(&mut poor, creation_fee);
+        assert!(matches!(
+            chain.apply_tx(&create(&mut poor).0),
+            Err(BlockchainError::BalanceInsufficient)
+        ));
+
+        let mut wallet = Wallet::new(b"CREATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, creation_fee + 1);
+        let (tx, contract_id) = create(&mut wallet);
+        let (ops, receipt) = chain.apply_tx(&tx).unwrap();
+        assert_eq!(receipt.created_contract, Some(contract_id.clone()));
+        assert_eq!(receipt.collected, creation_fee + 1);
+        let rollback = chain.database.rollback_of(&ops).unwrap();
+        chain.database.update(&ops).unwrap();
+
+        let (model, data) = match &tx.data {
+            TransactionData::CreateContract {
+                state_model,
+                initial_state,
+                ..
+            } => (state_model.clone(), initial_state.clone()),
+            _ => unreachable!(),
+        };
+        let contract = chain.get_contract(&contract_id).unwrap();
+        assert_eq!(contract.circuits_size(), 5);
+        assert_eq!(contract.state_model, model);
+        assert_eq!(
+            chain.get_contract_state(&contract_id).unwrap(),
+            ZkState::new(model, data).compress()
+        );
+        assert_eq!(chain.get_account(wallet.get_address()).unwrap().balance, 0);
+
+        // Everything is removed when the creation is rolled back
+        chain.database.update(&rollback).unwrap();
+        assert!(matches!(
+            chain.get_contract(&contract_id),
+            Err(BlockchainError::ContractNotFound)
+        ));
+        assert!(matches!(
+            chain.get_contract_state(&contract_id),
+            Err(BlockchainError::ContractNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_contract_transitions() {
+        let mut wallet = Wallet::new(b"EXECUTOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 10 * config::CONTRACT_CREATION_FEE_PER_BYTE);
+        let (tx, contract_id) = wallet.create_contract(
+            serde_json::from_str("[1]").unwrap(),
+            vec![serde_json::from_str("[2]").unwrap()],
+            serde_json::from_str("{}").unwrap(),
+            serde_json::from_str(r#"{"leaf_size": 1, "tree_depth": 8}"#).unwrap(),
+            1,
+        );
+        apply(&mut chain, &tx).unwrap();
+        let state = chain.get_contract_state(&contract_id).unwrap();
+        let next_state = state.state_hash().clone();
+        let proof: ZkProof = serde_json::from_str("[1, 2, 3]").unwrap();
+
+        let payment = wallet.create_contract_payment(
+            contract_id.clone(),
+            1,
+            100,
+            1,
+            PaymentDirection::Deposit,
+        );
+        // Both use the next nonce
+        let deposit = wallet.clone().deposit_withdraw(
+            contract_id.clone(),
+            vec![payment],
+            next_state.clone(),
+            proof.clone(),
+            1,
+        );
+        let update = wallet
+            .clone()
+            .update_contract(contract_id.clone(), 0, next_state, proof, 1);
+        for tx in [deposit, update] {
+            let result = chain.apply_tx(&tx);
+            if cfg!(feature = "zk") {
+                assert!(matches!(result, Err(BlockchainError::InvalidZkProof)));
+            } else {
+                // Rejected explicitly, rather than as an invalid proof
+                assert!(matches!(result, Err(BlockchainError::ZkProofsUnsupported)));
+            }
+        }
+        assert_eq!(chain.get_contract_state(&contract_id).unwrap(), state);
+        assert_eq!(chain.get_contract_balance(&contract_id).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_locked_send() {
+        let mut wallet = Wallet::new(b"VESTING".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let mut grantee = Wallet::new(b"GRANTEE".to_vec());
+        let height = 1 + config::COINBASE_MATURITY;
+
+        let tx = wallet.create_locked_transaction(grantee.get_address(), 40, height + 10, 1);
+        apply(&mut chain, &tx).unwrap();
+        let acc = chain.get_account(grantee.get_address()).unwrap();
+        assert_eq!(acc.balance, 40);
+        assert_eq!(chain.get_locked_balance(grantee.get_address()).unwrap(), 40);
+        grantee.update_account(&acc);
+        assert!(matches!(
+            chain.apply_tx(&grantee.clone().create_transaction(Address::Treasury, 1, 0)),
+            Err(BlockchainError::FundsLocked)
+        ));
+
+        // Grants expiring at the same height are merged, others are kept along
+        // with the other locked funds
+        let tx = wallet.create_locked_transaction(grantee.get_address(), 5, height + 10, 1);
+        apply(&mut chain, &tx).unwrap();
+        let acc = chain.get_account(grantee.get_address()).unwrap();
+        assert_eq!(acc.locked.map(|lock| lock.amount), Some(45));
+        let tx = wallet.create_locked_transaction(grantee.get_address(), 5, height + 20, 1);
+        apply(&mut chain, &tx).unwrap();
+        assert_eq!(chain.get_locked_balance(grantee.get_address()).unwrap(), 50);
+
+        // Locking one's own funds, e.g. as a validator
+        let tx = wallet.create_locked_transaction(wallet.get_address(), 40, height + 5, 0);
+        apply(&mut chain, &tx).unwrap();
+        assert_eq!(chain.get_account(wallet.get_address()).unwrap().balance, 47);
+        assert!(matches!(
+            chain.apply_tx(&wallet.clone().create_transaction(Address::Treasury, 14, 0)),
+            Err(BlockchainError::FundsLocked)
+        ));
+
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                "height".into(),
+                (height as usize + 10).into(),
+            )])
+            .unwrap();
+        grantee.update_account(&chain.get_account(grantee.get_address()).unwrap());
+        apply(
+            &mut chain,
+            &grantee.create_transaction(Address::Treasury, 44, 1),
+        )
+        .unwrap();
+        let acc = chain.get_account(grantee.get_address()).unwrap();
+        assert_eq!((acc.balance, acc.locked), (5, None));
+        assert_eq!(chain.get_locked_balance(grantee.get_address()).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_migrate_accounts() {
+        let chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let addr = Wallet::new(b"OLD".to_vec()).get_address();
+        let old = AccountV0 {
+            balance: 100,
+            nonce: 2,
+        };
+        let mut db = chain.database;
+        db.update(&vec![
+            WriteOp::Remove("accounts_version".into()),
+            WriteOp::Put(format!("account_{}", addr).into(), old.into()),
+        ])
+        .unwrap();
+        let chain = KvStoreChain::new(db).unwrap();
+        let acc = chain.get_account(addr).unwrap();
+        assert_eq!((acc.balance, acc.nonce, acc.locked), (100, 2, None));
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let mut wallet = Wallet::new(b"STUCK".to_vec());
+        let chain = funded_chain(&mut wallet, 100);
+
+        let stuck = wallet.create_transaction(Address::Treasury, 10, 1);
+        let bumped = wallet.bump_fee(&stuck.txid::<Hasher>(), 5).unwrap();
+        for txs in [
+            vec![stuck.clone(), bumped.clone()],
+            vec![bumped.clone(), stuck.clone()],
+        ] {
+            let selected = chain.select_transactions(&txs, u64::MAX).unwrap();
+            assert_eq!(selected.len(), 1);
+            assert_eq!(selected[0].0, bumped);
+        }
+    }
+
+    #[test]
+    fn test_aggregator_lane() {
+        let mut aggregator = Wallet::new(b"AGGREGATOR".to_vec());
+        let mut chain = funded_chain(&mut aggregator, 10 * config::CONTRACT_CREATION_FEE_PER_BYTE)
+            .with_aggregators(vec![aggregator.get_address()]);
+        let (tx, contract_id) = aggregator.create_contract(
+            serde_json::from_str("[1]").unwrap(),
+            vec![serde_json::from_str("[2]").unwrap()],
+            serde_json::from_str("{}").unwrap(),
+            serde_json::from_str(r#"{"leaf_size": 1, "tree_depth": 8}"#).unwrap(),
+            1,
+        );
+        apply(&mut chain, &tx).unwrap();
+        let next_state = chain
+            .get_contract_state(&contract_id)
+            .unwrap()
+            .state_hash()
+            .clone();
+        let proof: ZkProof = serde_json::from_str("[1, 2, 3]").unwrap();
+        let update = |wallet: &mut Wallet| {
+            wallet.update_contract(contract_id.clone(), 0, next_state.clone(), proof.clone(), 1)
+        };
+
+        // Only the updates of the known aggregators are in the lane
+        let mut stranger = Wallet::new(b"STRANGER".to_vec());
+        let ours = update(&mut aggregator.clone());
+        let theirs = update(&mut stranger);
+        let send = aggregator
+            .clone()
+            .create_transaction(Address::Treasury, 10, 1);
+        let (lane, rest) = chain.aggregator_lane(vec![ours.clone(), theirs.clone(), send.clone()]);
+        assert_eq!(lane, vec![ours.clone()]);
+        assert_eq!(rest, vec![theirs, send.clone()]);
+        let (lane, _) = KvStoreChain::new(RamKvStore::new())
+            .unwrap()
+            .aggregator_lane(vec![ours.clone()]);
+        assert!(lane.is_empty());
+
+        // The lane is tried first, within its share of the block. No update
+        // can be executed without a proving system though, so the slice is
+        // left to the rest, which still gets the whole block.
+        let (num, den) = config::lanes::AGGREGATOR_LANE_SHARE;
+        let max_weight = tx_weight(&ours) * den / num;
+        let selected = chain
+            .select_transactions(&vec![send.clone(), ours], max_weight)
+            .unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].0, send);
+    }
+
+    #[test]
+    fn test_governance() {
+        let mut wallet = Wallet::new(b"VALIDATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let param = GovernanceParam::MinRelayFee;
+
+        // Only validators can propose, and only within the bounds
+        assert!(matches!(
+            chain.apply_tx(&wallet.clone().propose(param, 10, 0).0),
+            Err(BlockchainError::ValidatorNotFound)
+        ));
+        apply(
+            &mut chain,
+            &wallet.register_validator(vec![0; VRF_PUBLIC_KEY_LENGTH], 10, 0),
+        )
+        .unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        assert!(matches!(
+            chain.apply_tx(&wallet.clone().propose(param, u64::MAX, 0).0),
+            Err(BlockchainError::InvalidGovernanceProposal)
+        ));
+
+        let (tx, approved) = wallet.propose(param, 10, 0);
+        apply(&mut chain, &tx).unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let (tx, rejected) = wallet.propose(param, 20, 0);
+        apply(&mut chain, &tx).unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        apply(&mut chain, &wallet.vote(rejected, false, 0)).unwrap();
+
+        // Not open for a whole epoch yet
+        let created = chain.get_height().unwrap() as u64;
+        let ops = chain.tally_proposals_ops(created).unwrap();
+        assert!(ops.is_empty());
+
+        let ops = chain
+            .tally_proposals_ops(created + config::GOVERNANCE_EPOCH_LENGTH)
+            .unwrap();
+        chain.database.update(&ops).unwrap();
+        assert_eq!(chain.get_param(param).unwrap(), 10);
+        assert_eq!(
+            chain.get_param(GovernanceParam::MaxBlockWeight).unwrap(),
+            config::MAX_BLOCK_WEIGHT
+        );
+        // Decided proposals are gone
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        assert!(matches!(
+            chain.apply_tx(&wallet.vote(approved, true, 0)),
+            Err(BlockchainError::ProposalNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_stake_unstake() {
+        let mut wallet = Wallet::new(b"STAKER".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let height = 1 + config::COINBASE_MATURITY;
+        let stake_of = |chain: &KvStoreChain<RamKvStore>, wallet: &Wallet| {
+            chain
+                .get_validator(&wallet.get_address())
+                .unwrap()
+                .map(|v| v.stake)
+        };
+
+        // Only registered validators can stake (rejected transactions are
+        // built on clones, so that they don't take up nonces)
+        assert!(matches!(
+            chain.apply_tx(&wallet.clone().stake(10, 0)),
+            Err(BlockchainError::ValidatorNotFound)
+        ));
+
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let register = wallet.register_validator(vec![0; VRF_PUBLIC_KEY_LENGTH], 10, 0);
+        apply(&mut chain, &register).unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        apply(&mut chain, &wallet.stake(20, 0)).unwrap();
+        assert_eq!(stake_of(&chain, &wallet), Some(30));
+
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        assert!(matches!(
+            chain.apply_tx(&wallet.clone().unstake(31, 0)),
+            Err(BlockchainError::StakeInsufficient)
+        ));
+        let before = chain.get_account(wallet.get_address()).unwrap().balance;
+        apply(&mut chain, &wallet.unstake(30, 0)).unwrap();
+        assert_eq!(stake_of(&chain, &wallet), None);
+        assert_eq!(
+            chain.get_account(wallet.get_address()).unwrap().balance,
+            before + 30
+        );
+        assert_eq!(chain.get_locked_balance(wallet.get_address()).unwrap(), 30);
+
+        // Unbonding funds can't be spent
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let tx = wallet.create_transaction(Address::Treasury, before + 1, 0);
+        assert!(matches!(
+            chain.apply_tx(&tx),
+            Err(BlockchainError::FundsLocked)
+        ));
+
+        chain
+            .database
+            .update(&vec![WriteOp::Put(
+                "height".into(),
+                ((height + config::UNBONDING_PERIOD) as usize).into(),
+            )])
+            .unwrap();
+        assert_eq!(chain.get_locked_balance(wallet.get_address()).unwrap(), 0);
+        assert!(chain.apply_tx(&tx).is_ok());
+    }
+
+    #[cfg(feature = "pos")]
+    #[test]
+    fn test_double_sign_slashing() {
+        use crate::core::digest::Digest;
+        use crate::crypto::{VRFPair, VerifiableRandomFunction};
+
+        let mut wallet = Wallet::new(b"VALIDATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let pair = VRFPair::generate(b"12345678901234567890123456789012").unwrap();
+        let stranger = VRFPair::generate(b"00000000000000000000000000000000").unwrap();
+
+        let register = wallet.register_validator(pair.to_public().as_ref().to_vec(), 10, 0);
+        apply(&mut chain, &register).unwrap();
+
+        let sealed = |state_root: u8, pair: &VRFPair| {
+            let mut header = Header {
+                number: 5,
+                state_root: [state_root; 32],
+                ..Default::default()
+            };
+            header
+                .digests
+                .push(Digest::Seal(pair.seal(&header.pre_seal_hash())));
+            header
+        };
+        let (first, second) = (sealed(1, &pair), sealed(2, &pair));
+        let offender = wallet.get_address();
+
+        // Rejected ones are built on clones, so that they don't take up nonces
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        for (a, b) in [
+            (first.clone(), first.clone()),
+            (first.clone(), sealed(2, &stranger)),
+        ] {
+            let tx = wallet
+                .clone()
+                .report_double_signing(offender.clone(), a, b, 0);
+            assert!(matches!(
+                chain.apply_tx(&tx),
+                Err(BlockchainError::InvalidSlashEvidence)
+            ));
+        }
+
+        let before = chain.get_account(offender.clone()).unwrap().balance;
+        let report =
+            wallet.report_double_signing(offender.clone(), first.clone(), second.clone(), 0);
+        apply(&mut chain, &report).unwrap();
+        assert!(chain.get_validators().unwrap().is_empty());
+        assert_eq!(
+            chain.get_account(offender.clone()).unwrap().balance,
+            before + 5
+        );
+        assert_eq!(chain.get_locked_balance(offender.clone()).unwrap(), 5);
+
+        // Registering again doesn't make the evidence reusable
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let register = wallet.register_validator(pair.to_public().as_ref().to_vec(), 10, 0);
+        apply(&mut chain, &register).unwrap();
+        wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+        let replay = wallet.report_double_signing(offender, first, second, 0);
+        assert!(matches!(
+            chain.apply_tx(&replay),
+            Err(BlockchainError::SlashEvidenceUsed)
+        ));
+    }
+
+    #[cfg(feature = "pos")]
+    #[test]
+    fn test_unbonding_slashing() {
+        use crate::core::digest::Digest;
+        use crate::crypto::{VRFPair, VerifiableRandomFunction};
+
+        let mut wallet = Wallet::new(b"VALIDATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let pair = VRFPair::generate(b"12345678901234567890123456789012").unwrap();
+        let register = wallet.register_validator(pair.to_public().as_ref().to_vec(), 10, 0);
+        apply(&mut chain, &register).unwrap();
+
+        let sealed = |state_root: u8| {
+            let mut header = Header {
+                number: 5,
+                state_root: [state_root; 32],
+                ..Default::default()
+            };
+            header
+                .digests
+                .push(Digest::Seal(pair.seal(&header.pre_seal_hash())));
+            header
+        };
+        let offender = wallet.get_address();
+        let before = chain.get_account(offender.clone()).unwrap().balance;
+
+        // Leaving right after double-signing doesn't save the stake
+        for amount in [4, 6] {
+            wallet.update_account(&chain.get_account(offender.clone()).unwrap());
+            apply(&mut chain, &wallet.unstake(amount, 0)).unwrap();
+        }
+        assert!(chain.get_validators().unwrap().is_empty());
+        assert_eq!(
+            chain.get_account(offender.clone()).unwrap().balance,
+            before + 10
+        );
+        let locked = chain.get_locked_balance(offender.clone()).unwrap();
+        assert!(locked >= 10);
+
+        wallet.update_account(&chain.get_account(offender.clone()).unwrap());
+        let report = wallet.report_double_signing(offender.clone(), sealed(1), sealed(2), 0);
+        apply(&mut chain, &report).unwrap();
+        assert_eq!(
+            chain.get_account(offender.clone()).unwrap().balance,
+            before + 5
+        );
+        assert_eq!(
+            chain.get_locked_balance(offender.clone()).unwrap(),
+            locked - 5
+        );
+
+        // Nothing left to slash
+        wallet.update_account(&chain.get_account(offender.clone()).unwrap());
+        let report = wallet.report_double_signing(offender, sealed(3), sealed(4), 0);
+        assert!(matches!(
+            chain.apply_tx(&report),
+            Err(BlockchainError::ValidatorNotFound)
+        ));
+    }
+
+    #[cfg(feature = "pos")]
+    #[test]
+    fn test_will_extend_across_epochs() {
+        use crate::consensus::pos::propose;
+        use crate::crypto::{VRFPair, VerifiableRandomFunction};
+
+        let mut wallet = Wallet::new(b"VALIDATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let pair = VRFPair::generate(b"12345678901234567890123456789012").unwrap();
+        let register = wallet.register_validator(pair.to_public().as_ref().to_vec(), 5, 0);
+        apply(&mut chain, &register).unwrap();
+        let snapshot = chain.validator_snapshot_op(0).unwrap();
+        chain.database.update(&vec![snapshot]).unwrap();
+
+        // Our chain is one block (Weighing 5) ahead of the fork point, which is
+        // right before the end of the first validator epoch
+        let boundary = config::VALIDATOR_EPOCH_LENGTH;
+        let mut fork_point = chain.get_block(0).unwrap();
+        fork_point.header.number = boundary - 2;
+        let epoch = chain.epoch_at(boundary - 2, Slot(5)).unwrap();
+        assert!(propose(&mut fork_point.header, Slot(5), &epoch, &pair));
+        chain
+            .database
+            .update(&vec![
+                WriteOp::Put(
+                    format!("block_{:010}", boundary - 2).into(),
+                    (&fork_point).into(),
+                ),
+                WriteOp::Put(format!("power_{:010}", boundary - 2).into(), 100u64.into()),
+                WriteOp::Put(format!("power_{:010}", boundary - 1).into(), 105u64.into()),
+                WriteOp::Put("height".into(), (boundary as usize).into()),
+            ])
+            .unwrap();
+
+        // The second block of the branch is in the next epoch
+        let mut headers = Vec::new();
+        let mut last = fork_point.header;
+        for (number, slot) in [(boundary - 1, 10), (boundary, 11)] {
+            let mut header = Header {
+                number,
+                parent_hash: last.hash(),
+                tx_count: last.tx_count,
+                ..Default::default()
+            };
+            let epoch = chain.epoch_at(boundary - 1, Slot(slot)).unwrap();
+            assert!(propose(&mut header, Slot(slot), &epoch, &pair));
+            last = header.clone();
+            headers.push(header);
+        }
+        let from = boundary as usize - 1;
+        assert!(!chain.will_extend(from, &headers[..1].to_vec()).unwrap());
+        assert!(chain.will_extend(from, &headers).unwrap());
+    }
+
+    #[cfg(feature = "pos")]
+    #[test]
+    fn test_validator_election() {
+        use crate::consensus::pos::propose;
+        use crate::crypto::{VRFPair, VerifiableRandomFunction};
+
+        let mut wallet = Wallet::new(b"VALIDATOR".to_vec());
+        let mut chain = funded_chain(&mut wallet, 100);
+        let pair = VRFPair::generate(b"12345678901234567890123456789012").unwrap();
+        let register = wallet.register_validator(pair.to_public().as_ref().to_vec(), 5, 0);
+        apply(&mut chain, &register).unwrap();
+        assert_eq!(chain.get_validators().unwrap().len(), 1);
+
+        // Not in charge before the next validator epoch
+        let slot = Slot(10);
+        let number = config::VALIDATOR_EPOCH_LENGTH;
+        assert_eq!(
+            chain.epoch_at(number - 1, slot).unwrap().authorities.len(),
+            2
+        );
+        let snapshot = chain.validator_snapshot_op(1).unwrap();
+        chain.database.update(&vec![snapshot]).unwrap();
+        assert_eq!(
+            chain.epoch_at(number - 1, slot).unwrap().authorities.len(),
+            2
+        );
+
+        let epoch = chain.epoch_at(number, slot).unwrap();
+        assert_eq!(epoch.authorities.len(), 1);
+        let parent = chain.get_block(0).unwrap().header;
+        let mut header = Header::default();
+        header.number = number;
+        header.parent_hash = parent.hash();
+        assert!(propose(&mut header, slot, &epoch, &pair));
+        assert_eq!(
+            proposal_weight(&header, &parent, &epoch.authorities).unwrap(),
+            5
+        );
+        assert!(check_slot_drift(&header, 1000).is_ok());
+        assert!(check_slot_drift(&header, 0).is_err());
+
+        // Genesis validators are not in charge anymore
+        let stranger = VRFPair::generate(b"00000000000000000000000000000000").unwrap();
+        assert!(!propose(&mut Header::default(), slot, &epoch, &stranger));
+    }
 }
@@ -0,0 +1,217 @@
+use super::{pow_key_reference, BlockchainError};
+use crate::config;
+use crate::core::hash::Hash;
+use crate::core::{Hasher, Header};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// A FlyClient-style succinct proof of the cumulative work of a chain. Instead of
+// downloading every header, a light client checks a handful of headers sampled
+// pseudo-randomly (Fiat-Shamir, seeded by the tip hash) plus a fully linked suffix.
+//
+// NOTE: Headers do not commit to their ancestors beyond the parent hash (There is
+// no MMR yet), so ancestry of the sampled headers is not proven, only their work.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChainProof {
+    pub samples: Vec<Header>,
+    pub suffix: Vec<Header>,
+    // Headers of the blocks whose hashes are used as RandomX keys
+    pub key_headers: Vec<Header>,
+}
+
+// Heights of the sampled headers, derived from the tip hash so that the prover
+// can't choose which headers will be checked.
+pub fn sample_heights(tip_hash: &[u8], prefix_len: usize, count: usize) -> Vec<usize> {
+    // Genesis block is not proven by work
+    if prefix_len <= 1 {
+        return Vec::new();
+    }
+    let mut heights = (0..count as u64)
+        .map(|i| {
+            let mut inp = tip_hash.to_vec();
+            inp.extend(i.to_le_bytes());
+            let mut rnd = [0u8; 8];
+            rnd.copy_from_slice(&Hasher::hash(&inp)[..8]);
+            1 + (u64::from_le_bytes(rnd) as usize) % (prefix_len - 1)
+        })
+        .collect::<Vec<_>>();
+    heights.sort_unstable();
+    heights.dedup();
+    heights
+}
+
+fn key_of(index: usize, key_headers: &HashMap<usize, &Header>) -> Result<Vec<u8>, BlockchainError> {
+    Ok(match pow_key_reference(index) {
+        Some(reference) => key_headers
+            .get(&reference)
+            .ok_or(BlockchainError::InvalidChainProof)?
+            .hash()
+            .to_vec(),
+        None => config::POW_BASE_KEY.to_vec(),
+    })
+}
+
+impl ChainProof {
+    pub fn height(&self) -> usize {
+        self.suffix
+            .last()
+            .map(|h| h.number as usize + 1)
+            .unwrap_or(0)
+    }
+
+    // Verifies the proof and returns the estimated power of the proven chain.
+    pub fn verify(&self) -> Result<u64, BlockchainError> {
+        let tip = self
+            .suffix
+            .last()
+            .ok_or(BlockchainError::InvalidChainProof)?;
+        let height = tip.number as usize + 1;
+        let prefix_len = height
+            .checked_sub(self.suffix.len())
+            .ok_or(BlockchainError::InvalidChainProof)?;
+
+        let key_headers = self
+            .key_headers
+            .iter()
+            .map(|h| (h.number as usize, h))
+            .collect::<HashMap<_, _>>();
+
+        let mut suffix_power = 0u64;
+        for (i, h) in self.suffix.iter().enumerate() {
+            if i > 0 {
+                let prev = &self.suffix[i - 1];
+                if h.number != prev.number + 1 {
+                    return Err(BlockchainError::InvalidBlockNumber);
+                }
+                if h.parent_hash != prev.hash() {
+                    return Err(BlockchainError::InvalidParentHash);
+                }
+            }
+            if h.number == 0 {
+                continue;
+            }
+            let key = key_of(h.number as usize, &key_headers)?;
+            if !h.meets_target(&key) {
+                return Err(BlockchainError::DifficultyTargetUnmet);
+            }
+            suffix_power += h.power();
+        }
+
+        let heights = sample_heights(&tip.hash(), prefix_len, config::CHAIN_PROOF_SAMPLES);
+        if heights.len() != self.samples.len() {
+            return Err(BlockchainError::InvalidChainProof);
+        }
+        let mut sampled_power = 0u64;
+        for (height, h) in heights.into_iter().zip(self.samples.iter()) {
+            if h.number as usize != height {
+                return Err(BlockchainError::InvalidChainProof);
+            }
+            let key = key_of(height, &key_headers)?;
+            if !h.meets_target(&key) {
+                return Err(BlockchainError::DifficultyTargetUnmet);
+            }
+            sampled_power += h.power();
+        }
+
+        let prefix_power = if self.samples.is_empty() {
+            0
+        } else {
+            sampled_power / self.samples.len() as u64 * (prefix_len - 1) as u64
+        };
+        Ok(prefix_power + suffix_power)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A linked chain of easy headers, all mined with the base key
+    fn chain(len: usize) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for i in 0..len {
+            let mut h = Header::default();
+            h.number = i as u64;
+            h.proof_of_work.target = 0x00ffffff;
+            if let Some(prev) = headers.last() {
+                h.parent_hash = prev.hash();
+            }
+            while !h.meets_target(config::POW_BASE_KEY) {
+                h.proof_of_work.nonce += 1;
+            }
+            headers.push(h);
+        }
+        headers
+    }
+
+    fn prove(headers: &[Header], suffix_len: usize) -> ChainProof {
+        let suffix = headers[headers.len() - suffix_len..].to_vec();
+        let tip_hash = suffix.last().unwrap().hash();
+        let samples = sample_heights(
+            &tip_hash,
+            headers.len() - suffix_len,
+            config::CHAIN_PROOF_SAMPLES,
+        )
+        .into_iter()
+        .map(|i| headers[i].clone())
+        .collect();
+        ChainProof {
+            samples,
+            suffix,
+            key_headers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sample_heights() {
+        assert!(sample_heights(b"tip", 0, 10).is_empty());
+        assert!(sample_heights(b"tip", 1, 10).is_empty());
+
+        let heights = sample_heights(b"tip", 50, 10);
+        assert!(!heights.is_empty() && heights.len() <= 10);
+        assert!(heights.windows(2).all(|w| w[0] < w[1]));
+        assert!(heights.iter().all(|h| (1..50).contains(h)));
+        // Decided by the tip alone
+        assert_eq!(heights, sample_heights(b"tip", 50, 10));
+        assert_ne!(heights, sample_heights(b"other tip", 50, 10));
+    }
+
+    #[test]
+    fn test_chain_proof() {
+        let headers = chain(40);
+        let power = headers[1..].iter().map(|h| h.power()).sum::<u64>();
+        let proof = prove(&headers, 8);
+        assert_eq!(proof.height(), 40);
+        // Samples are all as heavy as the rest of the prefix
+        assert_eq!(proof.verify().unwrap(), power);
+
+        let mut unlinked = proof.clone();
+        unlinked.suffix.remove(3);
+        assert!(matches!(
+            unlinked.verify(),
+            Err(BlockchainError::InvalidBlockNumber)
+        ));
+
+        let mut forged = proof.clone();
+        forged.suffix[5].parent_hash = Default::default();
+        assert!(matches!(
+            forged.verify(),
+            Err(BlockchainError::InvalidParentHash)
+        ));
+
+        let mut missing = proof.clone();
+        missing.samples.pop();
+        assert!(matches!(
+            missing.verify(),
+            Err(BlockchainError::InvalidChainProof)
+        ));
+
+        // Samples can't be picked by the prover
+        let mut swapped = proof;
+        swapped.samples[0] = headers[0].clone();
+        assert!(matches!(
+            swapped.verify(),
+            Err(BlockchainError::InvalidChainProof)
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use super::BlockchainError;
+use crate::config;
+use crate::config::genesis;
+use crate::consensus::pos::{self, AllowSlot, Authority, Epoch, Slot};
+use crate::core::Header;
+use crate::crypto::VRFPublicKey;
+
+// Epoch of a slot, whose proposers are elected from the given authorities
+pub fn build_epoch(slot: Slot, authorities: Vec<Authority<VRFPublicKey>>) -> Epoch<VRFPublicKey> {
+    let index = *slot / config::EPOCH_DURATION;
+    Epoch {
+        index,
+        start_slot_number: Slot(index * config::EPOCH_DURATION),
+        duration: config::EPOCH_DURATION,
+        authorities,
+        randomness: genesis::GENESIS_RANDOMNESS,
+        c: config::PRIMARY_SLOT_PROBABILITY,
+        allow_slots: AllowSlot::PrimaryAndSecondaryVFR,
+    }
+}
+
+// Latest slot a block may claim at the given time
+pub fn max_slot(timestamp: u32) -> Slot {
+    Slot(((timestamp + config::MAX_SLOT_DRIFT) / config::SLOT_DURATION) as u64)
+}
+
+// Blocks from the future are refused by the node (Against its own clock) before
+// they reach the chain, see `proposal_weight`
+pub fn check_slot_drift(header: &Header, timestamp: u32) -> Result<(), BlockchainError> {
+    let slot = pos::find_pre_digest(header)?.slot();
+    if slot > max_slot(timestamp) {
+        return Err(pos::Error::SlotInFuture(slot).into());
+    }
+    Ok(())
+}
+
+// Verifies the slot claim and the seal of a header, returning the stake weight
+// it adds to the chain. Only depends on the headers, so that every node gets the
+// same result whenever it applies the block.
+pub fn proposal_weight(
+    header: &Header,
+    parent: &Header,
+    authorities: &[Authority<VRFPublicKey>],
+) -> Result<u64, BlockchainError> {
+    let slot = pos::find_pre_digest(header)?.slot();
+    Ok(pos::verify_header(
+        header,
+        parent,
+        &build_epoch(slot, authorities.to_vec()),
+        slot,
+    )?)
+}
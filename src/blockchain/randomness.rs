@@ -0,0 +1,73 @@
+use crate::config;
+use crate::core::hash::{domains, Hash};
+use crate::core::{Hasher, Header};
+use serde::{Deserialize, Serialize};
+
+// Where the entropy of a beacon value came from, which determines how much it
+// can be biased.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum RandomnessSource {
+    // Hashes of the mixed blocks (PoW, or PoS blocks without a VRF output).
+    // The producer of the last block may throw away solutions (Or skip its
+    // slot) whose outcome it doesn't like, at the cost of the block reward.
+    // Fine for lotteries worth less than a reward, NOT for anything more
+    // valuable.
+    BlockHashes,
+    // VRF outputs of the mixed blocks (PoS). A proposer can't choose its VRF
+    // output, only withhold its block, so the bias is limited to one bit per
+    // colluding proposer among the last ones.
+    VrfOutputs,
+}
+
+// Public randomness of the chain at some height, i.e. mixed from the entropy of
+// the RANDOMNESS_WINDOW blocks before it. It's known by everyone as soon as the
+// block before `height` is, so it must not decide anything that can still be
+// influenced after that block (E.g. bets placed in the same block).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Randomness {
+    pub height: u64,
+    pub source: RandomnessSource,
+    pub value: <Hasher as Hash>::Output,
+}
+
+// Entropy a header contributes to the beacon
+#[cfg(feature = "pos")]
+fn entropy(header: &Header) -> (Vec<u8>, RandomnessSource) {
+    use crate::consensus::pos::digest::PreDigest;
+    match crate::consensus::pos::find_pre_digest(header) {
+        Ok(PreDigest::Primary(pre)) => (pre.vrf_output.to_vec(), RandomnessSource::VrfOutputs),
+        Ok(PreDigest::SecondaryVRF(pre)) => (pre.vrf_output.to_vec(), RandomnessSource::VrfOutputs),
+        _ => (header.hash().to_vec(), RandomnessSource::BlockHashes),
+    }
+}
+
+#[cfg(not(feature = "pos"))]
+fn entropy(header: &Header) -> (Vec<u8>, RandomnessSource) {
+    (header.hash().to_vec(), RandomnessSource::BlockHashes)
+}
+
+impl Randomness {
+    // `headers` are the ones right before `height`, oldest first. A single
+    // header without a VRF output weakens the whole value to `BlockHashes`.
+    pub fn mix(height: u64, headers: &[Header]) -> Self {
+        let mut data = height.to_le_bytes().to_vec();
+        let mut source = RandomnessSource::VrfOutputs;
+        for header in headers {
+            let (bytes, header_source) = entropy(header);
+            if header_source == RandomnessSource::BlockHashes {
+                source = RandomnessSource::BlockHashes;
+            }
+            data.extend(bytes);
+        }
+        Self {
+            height,
+            source,
+            value: domains::hash_randomness::<Hasher>(&data),
+        }
+    }
+}
+
+// First height of the window mixed into the randomness at a height
+pub fn randomness_window_start(height: u64) -> u64 {
+    height.saturating_sub(config::RANDOMNESS_WINDOW)
+}
@@ -0,0 +1,128 @@
+use super::BlockchainError;
+use crate::core::{Header, ProofOfWork};
+
+// Fixed-size, big-endian encoding of headers for relaying to smart contracts on
+// other chains (e.g. an EVM bridge), where variable-length bincode is a pain to parse.
+//
+// Layout:
+//   0..32    parent_hash
+//   32..40   number (u64, big-endian)
+//   40..72   state_root
+//   72..104  block_root
+//   104..112 tx_count (u64, big-endian)
+//   112..116 proof_of_work.timestamp (u32, big-endian)
+//   116..120 proof_of_work.target (u32, big-endian)
+//   120..128 proof_of_work.nonce (u64, big-endian)
+//   128..136 work, approximate number of hashes behind the header (u64, big-endian)
+//
+// NOTE: RandomX can't be evaluated on-chain, so `work` is only meaningful when the
+// relayer is trusted or the bridge allows fraud proofs against it.
+pub const RELAY_HEADER_SIZE: usize = 136;
+
+pub type RelayHeader = [u8; RELAY_HEADER_SIZE];
+
+pub fn encode_relay_header(header: &Header, work: u64) -> RelayHeader {
+    let mut out = [0u8; RELAY_HEADER_SIZE];
+    out[0..32].copy_from_slice(&header.parent_hash);
+    out[32..40].copy_from_slice(&header.number.to_be_bytes());
+    out[40..72].copy_from_slice(&header.state_root);
+    out[72..104].copy_from_slice(&header.block_root);
+    out[104..112].copy_from_slice(&header.tx_count.to_be_bytes());
+    out[112..116].copy_from_slice(&header.proof_of_work.timestamp.to_be_bytes());
+    out[116..120].copy_from_slice(&header.proof_of_work.target.to_be_bytes());
+    out[120..128].copy_from_slice(&header.proof_of_work.nonce.to_be_bytes());
+    out[128..136].copy_from_slice(&work.to_be_bytes());
+    out
+}
+
+pub fn decode_relay_header(bytes: &[u8]) -> Result<(Header, u64), BlockchainError> {
+    if bytes.len() != RELAY_HEADER_SIZE {
+        return Err(BlockchainError::InvalidRelayHeader);
+    }
+    let mut header = Header {
+        number: u64::from_be_bytes(bytes[32..40].try_into().unwrap()),
+        tx_count: u64::from_be_bytes(bytes[104..112].try_into().unwrap()),
+        proof_of_work: ProofOfWork {
+            timestamp: u32::from_be_bytes(bytes[112..116].try_into().unwrap()),
+            target: u32::from_be_bytes(bytes[116..120].try_into().unwrap()),
+            nonce: u64::from_be_bytes(bytes[120..128].try_into().unwrap()),
+        },
+        ..Default::default()
+    };
+    header.parent_hash.copy_from_slice(&bytes[0..32]);
+    header.state_root.copy_from_slice(&bytes[40..72]);
+    header.block_root.copy_from_slice(&bytes[72..104]);
+    let work = u64::from_be_bytes(bytes[128..136].try_into().unwrap());
+    Ok((header, work))
+}
+
+// Checks that the relayed headers form a chain (Optionally on top of an already
+// trusted header) and returns their total work. This is the same check a bridge
+// contract has to perform.
+pub fn verify_relay_headers(
+    trusted: Option<&Header>,
+    relayed: &[RelayHeader],
+) -> Result<u64, BlockchainError> {
+    let mut last = trusted.cloned();
+    let mut total_work = 0u64;
+    for bytes in relayed.iter() {
+        let (header, work) = decode_relay_header(bytes)?;
+        if let Some(prev) = &last {
+            if header.number != prev.number + 1 {
+                return Err(BlockchainError::InvalidBlockNumber);
+            }
+            if header.parent_hash != prev.hash() {
+                return Err(BlockchainError::InvalidParentHash);
+            }
+        }
+        total_work += work;
+        last = Some(header);
+    }
+    Ok(total_work)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(len: usize) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for i in 0..len {
+            let mut h = Header::default();
+            h.number = i as u64;
+            h.tx_count = 2 * i as u64 + 1;
+            h.proof_of_work.timestamp = 60 * i as u32;
+            if let Some(prev) = headers.last() {
+                h.parent_hash = prev.hash();
+            }
+            headers.push(h);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_relay_header_roundtrip() {
+        for h in chain(3) {
+            let (decoded, work) = decode_relay_header(&encode_relay_header(&h, 1234)).unwrap();
+            assert_eq!(decoded.hash(), h.hash());
+            assert_eq!(work, 1234);
+        }
+    }
+
+    #[test]
+    fn test_relay_header_linkage() {
+        let headers = chain(4);
+        let relayed = headers[1..]
+            .iter()
+            .map(|h| encode_relay_header(h, 10))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verify_relay_headers(Some(&headers[0]), &relayed).unwrap(),
+            30
+        );
+
+        let mut broken = relayed.clone();
+        broken.swap(0, 1);
+        assert!(verify_relay_headers(Some(&headers[0]), &broken).is_err());
+    }
+}
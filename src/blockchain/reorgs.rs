@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+// A reorg seen by the node. Kept next to the chain for auditing, it's not part
+// of the chain state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReorgRecord {
+    pub old_tip: String,
+    pub new_tip: String,
+    pub fork_height: usize,
+    // Number of blocks rolled back
+    pub depth: usize,
+    pub timestamp: u32,
+    // `ip:port` of the peer whose blocks replaced ours, None for local ones
+    pub peer: Option<String>,
+}
+
+// Records are numbered in the order they were added
+pub(crate) fn reorg_key(index: usize) -> String {
+    format!("reorg_{:010}", index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Blockchain, KvStoreChain};
+    use super::*;
+    use crate::db::RamKvStore;
+
+    #[test]
+    fn test_reorg_journal() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let record = |tip: &str| ReorgRecord {
+            old_tip: "aa".into(),
+            new_tip: tip.into(),
+            fork_height: 5,
+            depth: 2,
+            timestamp: 10,
+            peer: Some("127.0.0.1:3030".into()),
+        };
+        chain.record_reorg(record("bb"), 2).unwrap();
+        chain.record_reorg(record("cc"), 2).unwrap();
+        assert_eq!(
+            chain.get_reorgs().unwrap(),
+            vec![record("bb"), record("cc")]
+        );
+
+        // The oldest one goes
+        chain.record_reorg(record("dd"), 2).unwrap();
+        assert_eq!(
+            chain.get_reorgs().unwrap(),
+            vec![record("cc"), record("dd")]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use super::BlockchainError;
+use crate::config;
+use crate::core::{Address, Block, Money, Signature, Transaction, TransactionData};
+use std::str::FromStr;
+use thiserror::Error;
+
+// Emission schedule, the block subsidy halves every REWARD_HALVING_INTERVAL blocks.
+pub fn block_reward(height: u64) -> Money {
+    let halvings = height / config::REWARD_HALVING_INTERVAL;
+    if halvings >= Money::BITS as u64 {
+        0
+    } else {
+        config::INITIAL_BLOCK_REWARD >> halvings
+    }
+}
+
+// Rewards are paid as unsigned RegularSend transactions from the Treasury. The
+// sum of everything a block takes from the Treasury may not exceed the subsidy
+// of the emission schedule plus the fees of the block's transactions.
+pub fn validate_reward(block: &Block) -> Result<(), BlockchainError> {
+    let mut fees: Money = 0;
+    for tx in block.body.iter().filter(|tx| tx.src != Address::Treasury) {
+        fees = fees
+            .checked_add(tx.fee)
+            .ok_or(BlockchainError::InvalidReward)?;
+    }
+    let mut total: Money = 0;
+    for tx in block.body.iter().filter(|tx| tx.src == Address::Treasury) {
+        match &tx.data {
+            TransactionData::RegularSend { amount, .. } if tx.fee == 0 => {
+                total = total
+                    .checked_add(*amount)
+                    .ok_or(BlockchainError::InvalidReward)?;
+            }
+            _ => {
+                return Err(BlockchainError::InvalidReward);
+            }
+        }
+    }
+    if total > block_reward(block.header.number).saturating_add(fees) {
+        return Err(BlockchainError::InvalidReward);
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum PayoutSplitError {
+    #[error("payout split is empty")]
+    Empty,
+    #[error("payout percentages should add up to 100")]
+    InvalidTotal,
+    #[error("payout share should be in <address>:<percent> format")]
+    InvalidFormat,
+    #[error("payout address invalid")]
+    InvalidAddress,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutShare {
+    pub address: Address,
+    pub percent: u8,
+}
+
+impl FromStr for PayoutShare {
+    type Err = PayoutSplitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, percent) = s.rsplit_once(':').ok_or(PayoutSplitError::InvalidFormat)?;
+        Ok(PayoutShare {
+            address: address
+                .parse()
+                .map_err(|_| PayoutSplitError::InvalidAddress)?,
+            percent: percent
+                .parse()
+                .map_err(|_| PayoutSplitError::InvalidFormat)?,
+        })
+    }
+}
+
+// How a solo-miner's reward is divided between several addresses
+// (e.g. operator fee + owner).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutSplit(Vec<PayoutShare>);
+
+impl PayoutSplit {
+    pub fn new(shares: Vec<PayoutShare>) -> Result<Self, PayoutSplitError> {
+        if shares.is_empty() {
+            return Err(PayoutSplitError::Empty);
+        }
+        if shares.iter().map(|s| s.percent as u32).sum::<u32>() != 100 {
+            return Err(PayoutSplitError::InvalidTotal);
+        }
+        Ok(Self(shares))
+    }
+    pub fn single(address: Address) -> Self {
+        Self(vec![PayoutShare {
+            address,
+            percent: 100,
+        }])
+    }
+    pub fn shares(&self) -> &[PayoutShare] {
+        &self.0
+    }
+    // Divides the amount between the shares, the rounding remainder goes to the
+    // first share.
+    pub fn outputs(&self, amount: Money) -> Vec<(Address, Money)> {
+        let mut outputs = self
+            .0
+            .iter()
+            .map(|s| {
+                (
+                    s.address.clone(),
+                    (amount as u128 * s.percent as u128 / 100) as Money,
+                )
+            })
+            .collect::<Vec<_>>();
+        let paid = outputs.iter().map(|(_, a)| a).sum::<Money>();
+        outputs[0].1 += amount - paid;
+        outputs.retain(|(_, a)| *a > 0);
+        outputs
+    }
+    pub fn reward_transactions(&self, treasury_nonce: u32, amount: Money) -> Vec<Transaction> {
+        self.outputs(amount)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (dst, amount))| Transaction {
+                src: Address::Treasury,
+                nonce: treasury_nonce + 1 + i as u32,
+                data: TransactionData::RegularSend { dst, amount },
+                fee: 0,
+                sig: Signature::Unsigned,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_payout_split() {
+        let owner = Wallet::new(b"owner".to_vec()).get_address();
+        let split = PayoutSplit::new(vec![
+            PayoutShare {
+                address: owner.clone(),
+                percent: 90,
+            },
+            PayoutShare {
+                address: Address::Treasury,
+                percent: 10,
+            },
+        ])
+        .unwrap();
+        let outputs = split.outputs(1001);
+        assert_eq!(outputs, vec![(owner, 901), (Address::Treasury, 100)]);
+
+        assert!(PayoutSplit::new(vec![]).is_err());
+        assert!(PayoutSplit::new(vec![PayoutShare {
+            address: Address::Treasury,
+            percent: 99,
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn test_reward_validation() {
+        let owner = Wallet::new(b"owner".to_vec()).get_address();
+        let mut block = Block {
+            header: Default::default(),
+            body: Vec::new(),
+        };
+        block.header.number = 1;
+        let reward = block_reward(1);
+
+        block.body = PayoutSplit::single(owner.clone()).reward_transactions(0, reward);
+        assert!(validate_reward(&block).is_ok());
+
+        block.body = PayoutSplit::single(owner.clone()).reward_transactions(0, reward + 1);
+        assert!(validate_reward(&block).is_err());
+
+        // Fees of the included transactions may be claimed too
+        let mut wallet = Wallet::new(b"sender".to_vec());
+        block
+            .body
+            .push(wallet.create_transaction(owner.clone(), 10, 1));
+        assert!(validate_reward(&block).is_ok());
+        block.body[0] = PayoutSplit::single(owner).reward_transactions(0, reward + 2)[0].clone();
+        assert!(validate_reward(&block).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use crate::config;
+use crate::core::Header;
+use serde::{Deserialize, Serialize};
+
+// Distribution of the intervals between consecutive solved blocks, useful for
+// diagnosing a broken difficulty retargeting.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BlockTimeStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: i64,
+    pub min: i64,
+    pub max: i64,
+    pub std_dev: f64,
+}
+
+impl BlockTimeStats {
+    pub fn from_headers(headers: &[Header]) -> Option<Self> {
+        let mut intervals = headers
+            .windows(2)
+            .map(|w| w[1].proof_of_work.timestamp as i64 - w[0].proof_of_work.timestamp as i64)
+            .collect::<Vec<_>>();
+        if intervals.is_empty() {
+            return None;
+        }
+        let count = intervals.len();
+        let mean = intervals.iter().sum::<i64>() as f64 / count as f64;
+        let variance = intervals
+            .iter()
+            .map(|i| (*i as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count as f64;
+        intervals.sort_unstable();
+        Some(Self {
+            count,
+            mean,
+            median: intervals[count / 2],
+            min: intervals[0],
+            max: intervals[count - 1],
+            std_dev: variance.sqrt(),
+        })
+    }
+
+    // Whether the average block time is too far from the expected one
+    pub fn is_drifting(&self) -> bool {
+        let expected = config::BLOCK_TIME as f64;
+        self.mean > expected * config::BLOCK_TIME_DRIFT_FACTOR
+            || self.mean < expected / config::BLOCK_TIME_DRIFT_FACTOR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(timestamps: &[u32]) -> Vec<Header> {
+        timestamps
+            .iter()
+            .map(|t| {
+                let mut h = Header::default();
+                h.proof_of_work.timestamp = *t;
+                h
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_block_time_stats() {
+        assert_eq!(BlockTimeStats::from_headers(&headers(&[10])), None);
+
+        let stats = BlockTimeStats::from_headers(&headers(&[0, 60, 120, 180, 240])).unwrap();
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.median, 60);
+        assert_eq!(stats.std_dev, 0.0);
+        assert!(!stats.is_drifting());
+
+        let stats = BlockTimeStats::from_headers(&headers(&[0, 10, 20, 30])).unwrap();
+        assert_eq!(stats.mean, 10.0);
+        assert!(stats.is_drifting());
+    }
+}
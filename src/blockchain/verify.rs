@@ -0,0 +1,196 @@
+use super::{Blockchain, BlockchainError, KvStoreChain};
+use crate::config::genesis;
+use crate::core::{Block, Hasher};
+use crate::crypto::merkle::MerkleTree;
+use crate::db::{KvStore, RamKvStore, StringKey};
+use serde_derive::Serialize;
+use std::str::FromStr;
+
+// Each level includes the checks of the ones before it
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyLevel {
+    // Numbering, linkage, PoW and the hash index
+    Headers,
+    // Bodies against the merkle roots and the transaction counts
+    Merkle,
+    // Re-executes every block from genesis and compares the resulting state
+    // with the stored one. The state is rebuilt in memory.
+    Full,
+}
+
+impl FromStr for VerifyLevel {
+    type Err = BlockchainError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "headers" => Ok(VerifyLevel::Headers),
+            "merkle" => Ok(VerifyLevel::Merkle),
+            "full" => Ok(VerifyLevel::Full),
+            _ => Err(BlockchainError::UnknownVerifyLevel(s.into())),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub level: VerifyLevel,
+    // Blocks that passed, genesis included
+    pub verified: usize,
+    // Height of the first block failing a check, and why
+    pub inconsistency: Option<(usize, String)>,
+}
+
+// Keys making up the state, the rest is derived from the blocks (Indexes,
+// rollback records) or kept by the node
+const STATE_NAMESPACES: &[&str] = &[
+    "account_",
+    "contract_",
+    "locked_",
+    "param_",
+    "proposal_",
+    "slashed_",
+    "unbonding_",
+    "validator_",
+    "validators_",
+    "vote_",
+];
+
+impl<K: KvStore> KvStoreChain<K> {
+    fn verify_header(&self, index: usize, block: &Block, prev: &Block) -> Result<(), String> {
+        if block.header.number as usize != index {
+            return Err(format!("block number is {}", block.header.number));
+        }
+        if block.header.parent_hash != prev.header.hash() {
+            return Err("parent hash does not match the previous block".into());
+        }
+        #[cfg(feature = "pow")]
+        {
+            let pow_key = self.pow_key(index).map_err(|e| e.to_string())?;
+            if !block.header.meets_target(&pow_key) {
+                return Err("difficulty target unmet".into());
+            }
+        }
+        if self.get_block_height(&block.header.hash()).ok().flatten() != Some(index) {
+            return Err("block is missing from the hash index".into());
+        }
+        Ok(())
+    }
+
+    fn verify_body(&self, index: usize, block: &Block, prev: &Block) -> Result<(), String> {
+        if block.header.block_root != block.merkle_tree().root() {
+            return Err("merkle root does not match the body".into());
+        }
+        if block.header.tx_count != prev.header.tx_count + block.body.len() as u64 {
+            return Err("transaction count does not match the body".into());
+        }
+        let stored: Option<MerkleTree<Hasher>> = self
+            .database
+            .get(format!("merkle_{:010}", index).into())
+            .map_err(|e| e.to_string())?
+            .map(|b| b.try_into())
+            .transpose()
+            .map_err(|e: crate::db::KvStoreError| e.to_string())?;
+        if stored.map(|t| t.root()) != Some(block.header.block_root) {
+            return Err("stored merkle tree does not match the block".into());
+        }
+        Ok(())
+    }
+
+    fn state_diff(
+        &self,
+        rebuilt: &KvStoreChain<RamKvStore>,
+    ) -> Result<Option<String>, BlockchainError> {
+        for namespace in STATE_NAMESPACES {
+            let stored = self.database.pairs(StringKey::new(namespace))?;
+            let expected = rebuilt.database.pairs(StringKey::new(namespace))?;
+            let mut stored = stored.into_iter();
+            let mut expected = expected.into_iter();
+            loop {
+                match (stored.next(), expected.next()) {
+                    (None, None) => break,
+                    (Some((k, v)), Some((ek, ev))) if k == ek && v == ev => {}
+                    (Some((k, _)), Some((ek, _))) => {
+                        let key = std::cmp::min(k.as_str(), ek.as_str());
+                        return Ok(Some(format!("state differs at {}", key)));
+                    }
+                    (Some((k, _)), None) | (None, Some((k, _))) => {
+                        return Ok(Some(format!("state differs at {}", k.as_str())));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    pub(super) fn verify_blocks(
+        &self,
+        level: VerifyLevel,
+    ) -> Result<VerifyReport, BlockchainError> {
+        let height = self.get_height()?;
+        // Only the headers of the pruned blocks are left to check
+        if level != VerifyLevel::Headers && self.pruned_height()? > 0 {
+            return Err(BlockchainError::BlockPruned);
+        }
+        let mut report = VerifyReport {
+            level,
+            verified: 0,
+            inconsistency: None,
+        };
+        let genesis = genesis::get_genesis_block();
+        let mut prev = match self.get_block(0) {
+            Ok(block) if block.header.hash() == genesis.header.hash() => block,
+            Ok(_) => {
+                report.inconsistency = Some((0, "genesis block differs".into()));
+                return Ok(report);
+            }
+            Err(e) => {
+                report.inconsistency = Some((0, e.to_string()));
+                return Ok(report);
+            }
+        };
+        report.verified = 1;
+
+        let mut rebuilt = if level == VerifyLevel::Full {
+            Some(KvStoreChain::open(RamKvStore::new(), self.indexed)?)
+        } else {
+            None
+        };
+
+        for index in 1..height {
+            let result = self
+                .get_block(index)
+                .map_err(|e| e.to_string())
+                .and_then(|block| {
+                    self.verify_header(index, &block, &prev)?;
+                    if level != VerifyLevel::Headers {
+                        self.verify_body(index, &block, &prev)?;
+                    }
+                    if let Some(rebuilt) = rebuilt.as_mut() {
+                        // The PoW is already checked above
+                        rebuilt
+                            .apply_block(&block, true)
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Ok(block)
+                });
+            match result {
+                Ok(block) => {
+                    prev = block;
+                    report.verified += 1;
+                }
+                Err(reason) => {
+                    report.inconsistency = Some((index, reason));
+                    return Ok(report);
+                }
+            }
+        }
+
+        if let Some(rebuilt) = rebuilt {
+            if let Some(reason) = self.state_diff(&rebuilt)? {
+                report.verified -= 1;
+                report.inconsistency = Some((height - 1, reason));
+            }
+        }
+        Ok(report)
+    }
+}
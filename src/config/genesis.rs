@@ -1,7 +1,37 @@
 use crate::core::{Address, Block, Signature, Transaction, TransactionData};
 
+#[cfg(feature = "pos")]
+use {crate::consensus::pos::Authority, crate::crypto::VRFPublicKey, std::num::NonZeroU64};
+
+// VRF public keys and weights of the validators the network starts with
+#[cfg(feature = "pos")]
+const GENESIS_AUTHORITIES: &[(&str, u64)] = &[
+    (
+        "aec5f616d7ee5d49630533666c413ff731fdc27d442d3101f6e77fd71d741c78",
+        1,
+    ),
+    (
+        "322d6d9a142ce736a735518c3c2e30c4c10fe92e3caa57aa8b859c093ef0984e",
+        1,
+    ),
+];
+
+#[cfg(feature = "pos")]
+pub const GENESIS_RANDOMNESS: [u8; 32] = *b"BAZUKA GENESIS EPOCH RANDOMNESS!";
+
+#[cfg(feature = "pos")]
+pub fn get_genesis_authorities() -> Vec<Authority<VRFPublicKey>> {
+    GENESIS_AUTHORITIES
+        .iter()
+        .map(|(public_key, weight)| Authority {
+            weight: NonZeroU64::new(*weight).unwrap(),
+            public_key: VRFPublicKey::from_bytes(&hex::decode(public_key).unwrap()).unwrap(),
+        })
+        .collect()
+}
+
 pub fn get_genesis_block() -> Block {
-    Block {
+    let mut blk = Block {
         header: Default::default(),
         body: vec![Transaction {
             src: Address::Treasury,
@@ -15,5 +45,7 @@ pub fn get_genesis_block() -> Block {
             fee: 0,
             sig: Signature::Unsigned,
         }],
-    }
+    };
+    blk.header.tx_count = blk.body.len() as u64;
+    blk
 }
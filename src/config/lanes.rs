@@ -0,0 +1,4 @@
+// Update transactions of the known aggregators (MPN and other contract
+// operators, see `--aggregator`) are given this slice of every drafted block, so
+// rollups stay live even when fees spike
+pub const AGGREGATOR_LANE_SHARE: (u64, u64) = (1, 4); // Of the block weight
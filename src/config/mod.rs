@@ -1,20 +1,174 @@
+pub mod checkpoint;
 pub mod genesis;
+pub mod lanes;
 
 #[cfg(feature = "node")]
 pub mod punish;
 
 pub const SYMBOL: &str = "ZIK";
+
+// Announced to peers, so that upgrade adoption can be measured
+pub const NODE_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const USER_AGENT: &str = concat!("bazuka/", env!("CARGO_PKG_VERSION"));
 pub const TOTAL_SUPPLY: u64 = 10000000000_000000000u64; // 10 Billion ZIK
 
+// Block subsidy, halves every REWARD_HALVING_INTERVAL blocks
+pub const INITIAL_BLOCK_REWARD: u64 = 100_000000000u64; // 100 ZIK
+pub const REWARD_HALVING_INTERVAL: u64 = 2102400; // Blocks (~4 years)
+
+// Block rewards may only be spent once they are this deep in the chain, so
+// that a reorg can't invalidate the transactions spending them
+pub const COINBASE_MATURITY: u64 = 100; // Blocks
+
+// Unstaked funds stay locked for a while, so that validators can still be
+// held accountable for the blocks they have recently proposed
+pub const UNBONDING_PERIOD: u64 = 20160; // Blocks (~2 weeks)
+
+// Part of the stake burnt when a validator is caught sealing two different
+// blocks at the same height. The rest is unbonded.
+pub const DOUBLE_SIGN_SLASH: (u64, u64) = (1, 2);
+
 // Delta means: block size + state size changes
 pub const MAX_DELTA_SIZE: usize = 1024 * 1024 * 1024; // Bytes
 
+// Blocks whose entropy is mixed into the randomness beacon. A longer window
+// doesn't make the value less biasable, the last block decides anyway, but it
+// makes a single block hash (Or VRF output) less of a dependency.
+pub const RANDOMNESS_WINDOW: u64 = 16; // Blocks
+
+// Defaults of the parameters changeable through governance, and the bounds
+// they may be changed within
+pub const MIN_RELAY_FEE: u64 = 0;
+pub const MIN_RELAY_FEE_BOUNDS: (u64, u64) = (0, 1_000000000); // 1 ZIK
+pub const MAX_BLOCK_WEIGHT: u64 = 4 * 1024 * 1024;
+pub const MAX_BLOCK_WEIGHT_BOUNDS: (u64, u64) = (256 * 1024, 64 * 1024 * 1024);
+
+// Proposals are tallied at the end of every governance epoch, once they have
+// been open for voting for a whole epoch
+pub const GOVERNANCE_EPOCH_LENGTH: u64 = 10080; // Blocks (~1 week)
+
+// Share of the total stake needed for approving a proposal
+pub const GOVERNANCE_APPROVAL: (u64, u64) = (2, 3);
+
+// Serialized responses of blocks and header ranges kept in memory, for
+// explorer-like clients asking for the same data over and over
+pub const RESPONSE_CACHE_SIZE: usize = 256; // Responses
+
+// Mempool size limit (Cheapest transactions are evicted) and how long a
+// transaction may wait in it
+pub const MEMPOOL_MAX_SIZE: usize = 10000; // Transactions
+pub const MEMPOOL_TX_TTL: u32 = 3 * 3600; // Seconds
+
+// Transactions with nonces further than this ahead of their account's nonce
+// are not accepted into the mempool
+pub const MEMPOOL_NONCE_HORIZON: u32 = 64;
+
+// Transactions of a sender waiting for missing nonces before them
+pub const MEMPOOL_MAX_FUTURE_PER_SENDER: usize = 16;
+
+// Creating a contract costs this much per byte of its verifier keys, on top
+// of the transaction fee
+pub const CONTRACT_CREATION_FEE_PER_BYTE: u64 = 1_000000; // 0.001 ZIK
+
 // Every n blocks, recalculate difficulty
 pub const BLOCK_TIME: usize = 60; // Seconds
 pub const DIFFICULTY_CALC_INTERVAL: usize = 128; // Blocks
 
+// Measured timespan of a retarget window is clamped to at most this many
+// times shorter/longer than expected, i.e. difficulty changes at most 4x at once
+pub const RETARGET_CLAMP_FACTOR: u32 = 4;
+
+// Warn when the average block time is this many times off from BLOCK_TIME
+pub const BLOCK_TIME_DRIFT_FACTOR: f64 = 2.0;
+
+// Values of these key namespaces are stored zstd-compressed on disk. The
+// dictionary is trained once, when the database is created
+pub const COMPRESSED_NAMESPACES: &[&str] = &["block"];
+pub const COMPRESSION_LEVEL: i32 = 3;
+pub const COMPRESSION_DICTIONARY_SIZE: usize = 16 * 1024; // Bytes
+
+// The memory-mapped store rewrites its file once the overwritten data exceeds
+// both this and the half of the file
+pub const MMAP_COMPACTION_THRESHOLD: usize = 64 * 1024 * 1024; // Bytes
+
 pub const MAX_BLOCK_FETCH: usize = 16; // Blocks
 
+// Blocks of losing branches are kept until they are this deep behind the tip,
+// in case the branch overtakes later. The store is capped, the rest is dropped.
+pub const FORK_STORE_DEPTH: usize = 128; // Blocks
+pub const FORK_STORE_MAX_BLOCKS: usize = 1024;
+
+// Pruned nodes drop the bodies and the rollback records of the blocks this deep
+// behind the tip, way beyond any reorg the fork store is able to follow
+pub const PRUNE_DEPTH: usize = 1024; // Blocks
+
+// Block bodies are synced in windows of MAX_BLOCK_FETCH blocks, at most this
+// many windows are downloaded (In parallel) per sync round
+pub const MAX_SYNC_WINDOWS: usize = 16;
+
+// Block bodies are requested from this many peers at the same time, a request
+// taking longer than the timeout is retried on another peer
+pub const MAX_CONCURRENT_FETCHES: usize = 4;
+pub const BLOCK_FETCH_TIMEOUT: u64 = 10; // Seconds
+
+// New tips are pushed to the peers, polling them for new blocks is only a
+// fallback (Unless the node is catching up with the network)
+pub const ANNOUNCE_TIMEOUT: u64 = 2; // Seconds
+                                     // Announcements of a peer coming sooner than this after its previous one are
+                                     // dropped
+pub const ANNOUNCE_MIN_INTERVAL: u32 = 5; // Seconds
+pub const SYNC_POLL_INTERVAL: u32 = 30; // Seconds
+
+// Number of invalid block hashes remembered (And persisted)
+pub const INVALID_BLOCK_CACHE_SIZE: usize = 10000;
+
+// A peer that stalls or serves invalid blocks isn't used as a sync source of
+// the same chain tip for this long
+pub const SYNC_BLACKLIST_TTL: u32 = 600; // Seconds
+
+// Heights reported by peers longer than this ago are ignored when
+// estimating the height of the network
+pub const NETWORK_HEIGHT_WINDOW: u32 = 60; // Seconds
+
+// Anchor peers are retried with exponential backoff, starting from
+// ANCHOR_RETRY_BASE and capped at ANCHOR_RETRY_MAX
+pub const MAX_ANCHOR_PEERS: usize = 4;
+pub const ANCHOR_RETRY_BASE: u32 = 5; // Seconds
+pub const ANCHOR_RETRY_MAX: u32 = 300; // Seconds
+
+// Unknown peers are added only after answering a challenge sent to their
+// claimed address, in this long
+pub const HANDSHAKE_TIMEOUT: u64 = 5; // Seconds
+
+// Known peers are saved to the database every PEER_PERSIST_INTERVAL, peers
+// that haven't been seen for PEER_MAX_AGE are forgotten
+pub const PEER_PERSIST_INTERVAL: u32 = 60; // Seconds
+pub const PEER_MAX_AGE: u32 = 7 * 24 * 3600; // Seconds
+
+// GET /peers returns at most PEER_EXCHANGE_LIMIT random peers, leaving out the
+// ones learned in the last PEER_EXCHANGE_MIN_AGE, to make topology inference
+// and crawling more expensive
+pub const PEER_EXCHANGE_LIMIT: usize = 32;
+pub const PEER_EXCHANGE_MIN_AGE: u32 = 600; // Seconds
+
+// Concurrently processed and queued requests per priority class, requests
+// beyond the queue limit are answered with 503 Service Unavailable
+pub const MAX_ACTIVE_CONSENSUS_REQUESTS: usize = 32;
+pub const MAX_QUEUED_CONSENSUS_REQUESTS: usize = 256;
+pub const MAX_ACTIVE_QUERY_REQUESTS: usize = 16;
+pub const MAX_QUEUED_QUERY_REQUESTS: usize = 64;
+
+// Node is considered synced when it's at most this far behind the network
+pub const SYNC_MARGIN: usize = 2; // Blocks
+
+// Reorgs kept in the persistent journal, the oldest are dropped first
+pub const REORG_JOURNAL_SIZE: usize = 1000;
+
+// Succinct chain proofs: number of randomly sampled headers and
+// the length of the fully linked suffix
+pub const CHAIN_PROOF_SAMPLES: usize = 32;
+pub const CHAIN_PROOF_SUFFIX: usize = 16; // Blocks
+
 // 0 63 -> BAZUKA BASE KEY
 // 64 2111 -> hash(blk#0)
 // 2112 4159 -> hash(blk#2048)
@@ -28,5 +182,27 @@ pub const POW_KEY_CHANGE_INTERVAL: usize = 2048; // Blocks
 // timestamp of 10 previous blocks
 pub const MEDIAN_TIMESTAMP_COUNT: usize = 10;
 
+// Slots are counted since the unix epoch. Blocks claiming a slot that starts
+// more than MAX_SLOT_DRIFT in the future are rejected.
+#[cfg(feature = "pos")]
+pub const SLOT_DURATION: u32 = 6; // Seconds
+#[cfg(feature = "pos")]
+pub const MAX_SLOT_DRIFT: u32 = 15; // Seconds
+#[cfg(feature = "pos")]
+pub const EPOCH_DURATION: u64 = 600; // Slots
+
+// The validator set is snapshotted from the stakes at the end of every
+// validator epoch, and elects the proposers of the next one
+#[cfg(feature = "pos")]
+pub const VALIDATOR_EPOCH_LENGTH: u64 = 600; // Blocks
+
+// Chance of a slot being claimable by at least one validator through its VRF
+// output, the rest of the slots go round-robin
+#[cfg(feature = "pos")]
+pub const PRIMARY_SLOT_PROBABILITY: (u64, u64) = (1, 4);
+
+// Public key of the network alert authority, alerts are ignored when empty
+pub const ALERT_PUBLIC_KEY: &str = "";
+
 // Our Zero-Knowledge RAM will have 2^32 memory cells
 pub const LOG_ZK_RAM_SIZE: usize = 32;
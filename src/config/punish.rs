@@ -1,3 +1,16 @@
 pub const NO_RESPONSE_PUNISH: u32 = 5;
 pub const INVALID_DATA_PUNISH: u32 = 10;
 pub const MAX_PUNISH: u32 = 15;
+
+// Misbehavior scores, a peer is banned for BAN_DURATION once its score
+// reaches BAN_THRESHOLD
+pub const INVALID_BLOCK_SCORE: u32 = 50;
+pub const BAD_SIGNATURE_SCORE: u32 = 50;
+pub const MALFORMED_RESPONSE_SCORE: u32 = 20;
+pub const PROTOCOL_VIOLATION_SCORE: u32 = 10;
+pub const BAN_THRESHOLD: u32 = 100;
+pub const BAN_DURATION: u32 = 24 * 3600; // Seconds
+
+// Misbehaving addresses of inbound clients are tracked apart from the peers,
+// up to this many of them (Banned ones are kept first)
+pub const MAX_TRACKED_IPS: usize = 4096;
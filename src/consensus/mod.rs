@@ -1,21 +1,81 @@
 #[cfg(feature = "pos")]
 pub mod pos;
 
+#[cfg(feature = "sim")]
+pub mod simulate;
+
 #[cfg(feature = "pow")]
 pub mod pow {
     use rust_randomx::{Context, Hasher, Output};
+    use std::cell::RefCell;
     use std::sync::{Arc, Mutex};
 
+    // The key changes every POW_KEY_CHANGE_INTERVAL blocks, headers around the
+    // change are verified (In parallel) against both keys
+    const CONTEXT_CACHE_SIZE: usize = 2;
+
+    // Contexts are expensive to initialize, they are shared by the hashers of
+    // all threads. The most recently used one comes last.
+    struct ContextCache(Mutex<Vec<Arc<Context>>>);
+
+    impl ContextCache {
+        fn new() -> Self {
+            Self(Mutex::new(Vec::with_capacity(CONTEXT_CACHE_SIZE)))
+        }
+        fn get(&self, key: &[u8]) -> Arc<Context> {
+            let mut contexts = self.0.lock().unwrap();
+            let ctx = match contexts.iter().position(|ctx| ctx.key() == key) {
+                Some(i) => contexts.remove(i),
+                None => {
+                    tracing::info!("Initializing RandomX context");
+                    if contexts.len() >= CONTEXT_CACHE_SIZE {
+                        contexts.remove(0);
+                    }
+                    Arc::new(Context::new(key, false))
+                }
+            };
+            contexts.push(ctx.clone());
+            ctx
+        }
+    }
+
     lazy_static! {
-        pub static ref HASHER: Arc<Mutex<Option<Hasher>>> = Arc::new(Mutex::new(None));
+        static ref CONTEXTS: ContextCache = ContextCache::new();
     }
 
+    thread_local! {
+        static HASHER: RefCell<Option<Hasher>> = const { RefCell::new(None) };
+    }
+
+    // Every thread hashes with its own VM, so that headers can be verified in
+    // parallel
     pub fn hash(key: &[u8], input: &[u8]) -> Output {
-        let mut hasher = HASHER.lock().unwrap();
-        if hasher.is_none() || hasher.as_ref().unwrap().context().key() != key {
-            println!("Initializing RandomX hasher...");
-            *hasher = Some(Hasher::new(Arc::new(Context::new(key, false))));
+        HASHER.with(|hasher| {
+            let mut hasher = hasher.borrow_mut();
+            if hasher.is_none() || hasher.as_ref().unwrap().context().key() != key {
+                *hasher = Some(Hasher::new(CONTEXTS.get(key)));
+            }
+            hasher.as_ref().unwrap().hash(input)
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_context_cache() {
+            let cache = ContextCache::new();
+            let a = cache.get(b"a");
+            let b = cache.get(b"b");
+            assert!(Arc::ptr_eq(&a, &cache.get(b"a")));
+            assert!(Arc::ptr_eq(&b, &cache.get(b"b")));
+
+            // The least recently used one makes room
+            cache.get(b"a");
+            cache.get(b"c");
+            assert!(Arc::ptr_eq(&a, &cache.get(b"a")));
+            assert!(!Arc::ptr_eq(&b, &cache.get(b"b")));
         }
-        hasher.as_ref().unwrap().hash(input)
     }
 }
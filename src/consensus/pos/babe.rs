@@ -9,6 +9,7 @@ use num_traits::{One, Zero};
 use schnorrkel::vrf::{VRFInOut, VRFOutput, VRFProof};
 
 use super::digest::{PreDigest, PrimaryPreDigest, SecondaryPlainPreDigest, SecondaryVRFPreDigest};
+use super::epoch::AllowSlot;
 use super::epoch::Epoch;
 use super::slots::{proposing_remaining_duration, Slot, SlotLenienceType, SlotProportion, Ticker};
 use super::{ChainSelector, CreateSlotAuxProvider, EpochBuilder};
@@ -108,11 +109,40 @@ pub struct Authority<T: AsRef<[u8]>> {
     pub public_key: T,
 }
 
+// Claims the slot with our key, if we are an authority of the epoch and the
+// slot is ours, and seals the header. Returns whether the header got sealed.
+pub fn propose(
+    header: &mut Header,
+    slot: Slot,
+    epoch: &Epoch<VRFPublicKey>,
+    pair: &VRFPair,
+) -> bool {
+    let public_key = pair.to_public();
+    let index = match epoch
+        .authorities
+        .iter()
+        .position(|author| author.public_key.as_ref() == public_key.as_ref())
+    {
+        Some(index) => index,
+        None => return false,
+    };
+    let pairs = HashMap::from([(index, pair)]);
+    match claim(slot, epoch, epoch.c, &pairs) {
+        Some(pre_digest) => {
+            header.digests.push(Digest::PreDigest(pre_digest));
+            let seal = pair.seal(&header.pre_seal_hash());
+            header.digests.push(Digest::Seal(seal));
+            true
+        }
+        None => false,
+    }
+}
+
 fn claim<P: PublicKey>(
     slot: Slot,
     epoch: &Epoch<P>,
     c: (u64, u64),
-    pairs: &HashMap<usize, VRFPair>,
+    pairs: &HashMap<usize, &VRFPair>,
 ) -> Option<PreDigest> {
     claim_primary_slot(slot, epoch, c, pairs).or_else(|| claim_secondary_slot(slot, epoch, pairs))
 }
@@ -121,7 +151,7 @@ fn claim_primary_slot<P: PublicKey>(
     slot: Slot,
     epoch: &Epoch<P>,
     c: (u64, u64),
-    pairs: &HashMap<usize, VRFPair>,
+    pairs: &HashMap<usize, &VRFPair>,
 ) -> Option<PreDigest> {
     let Epoch {
         authorities,
@@ -183,7 +213,7 @@ fn claim_primary_slot<P: PublicKey>(
 fn claim_secondary_slot<P: PublicKey>(
     slot: Slot,
     epoch: &Epoch<P>,
-    pairs: &HashMap<usize, VRFPair>,
+    pairs: &HashMap<usize, &VRFPair>,
 ) -> Option<PreDigest> {
     let Epoch {
         authorities,
@@ -194,8 +224,7 @@ fn claim_secondary_slot<P: PublicKey>(
     if authorities.is_empty() {
         return None;
     }
-    // @TODO: this is a fake round robin
-    let r = (slot.0 / authorities.len() as u64) as usize;
+    let r = secondary_slot_author(slot, authorities.len());
     let transcript = make_vrf_transcript(*index, slot, randomness);
     pairs.get(&r).map(|pair| {
         let signature = pair.sign(transcript.clone());
@@ -208,6 +237,96 @@ fn claim_secondary_slot<P: PublicKey>(
     })
 }
 
+// Secondary slots are assigned round-robin, so that no slot stays empty
+fn secondary_slot_author(slot: Slot, authorities: usize) -> usize {
+    (slot.0 % authorities as u64) as usize
+}
+
+// Checks that the header was proposed in a slot after its parent's, no later
+// than `max_slot`, by an authority of the epoch entitled to that slot. Returns
+// the weight of the proposer, which is what the block adds to the chain score.
+pub fn verify_header(
+    header: &Header,
+    parent: &Header,
+    epoch: &Epoch<VRFPublicKey>,
+    max_slot: Slot,
+) -> Result<u64> {
+    let pre_digest = find_pre_digest(header)?;
+    let slot = pre_digest.slot();
+    if slot <= find_pre_digest(parent)?.slot() {
+        return Err(Error::SlotNotIncreasing(slot));
+    }
+    if slot > max_slot {
+        return Err(Error::SlotInFuture(slot));
+    }
+
+    let authority_index = match &pre_digest {
+        PreDigest::Primary(p) => p.authority_index,
+        PreDigest::SecondaryPlain(p) => p.authority_index,
+        PreDigest::SecondaryVRF(p) => p.authority_index,
+    };
+    let authority = epoch
+        .authorities
+        .get(authority_index as usize)
+        .ok_or(Error::UnknownAuthority(authority_index))?;
+
+    let transcript = make_vrf_transcript(epoch.index, slot, &epoch.randomness);
+    match &pre_digest {
+        PreDigest::Primary(primary) => {
+            let output =
+                VRFOutput::from_bytes(&primary.vrf_output).map_err(|_| Error::InvalidVrfProof)?;
+            let proof =
+                VRFProof::from_bytes(&primary.vrf_proof).map_err(|_| Error::InvalidVrfProof)?;
+            let (inout, _) = authority
+                .public_key
+                .0
+                .vrf_verify(to_transcript(transcript), &output, &proof)
+                .map_err(|_| Error::InvalidVrfProof)?;
+            let weights = epoch
+                .authorities
+                .iter()
+                .map(|author| author.weight.get())
+                .collect::<Vec<u64>>();
+            let threshold =
+                calculate_primary_threshold(epoch.c, authority_index as usize, &weights);
+            if !check_primary_threshold(&inout, threshold) {
+                return Err(Error::PrimaryThresholdUnmet);
+            }
+        }
+        PreDigest::SecondaryPlain(_) | PreDigest::SecondaryVRF(_) => {
+            let allowed = match pre_digest {
+                PreDigest::SecondaryPlain(_) => AllowSlot::PrimaryAndSecondaryPlain,
+                _ => AllowSlot::PrimaryAndSecondaryVFR,
+            };
+            if epoch.allow_slots != allowed {
+                return Err(Error::SecondarySlotNotAllowed);
+            }
+            if secondary_slot_author(slot, epoch.authorities.len()) != authority_index as usize {
+                return Err(Error::WrongSecondaryAuthor(slot));
+            }
+            if let PreDigest::SecondaryVRF(secondary) = &pre_digest {
+                VRFPair::verify(
+                    &authority.public_key,
+                    transcript,
+                    secondary.vrf_output,
+                    secondary.vrf_proof,
+                )
+                .map_err(|_| Error::InvalidVrfProof)?;
+            }
+        }
+    }
+
+    let seal = header.seal().ok_or(Error::NoSeal)?;
+    if !authority
+        .public_key
+        .verify_seal(&header.pre_seal_hash(), seal)
+    {
+        return Err(Error::InvalidSeal);
+    }
+
+    Ok(authority.weight.get())
+}
+
 fn check_primary_threshold(inout: &VRFInOut, threshold: BigUint) -> bool {
     BigUint::from_bytes_le(&inout.make_bytes::<[u8; 16]>(b"bazuka-baba")) < threshold
 }
@@ -245,3 +364,59 @@ fn make_vrf_transcript(epoch_num: u64, slot: Slot, randomness: &[u8]) -> VRFTran
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn epoch(pair: &VRFPair) -> Epoch<VRFPublicKey> {
+        Epoch {
+            index: 0,
+            start_slot_number: 0.into(),
+            duration: 100,
+            authorities: vec![Authority {
+                weight: NonZeroU64::new(7).unwrap(),
+                public_key: pair.to_public(),
+            }],
+            randomness: [0u8; 32],
+            c: (1, 1),
+            allow_slots: AllowSlot::PrimaryAndSecondaryVFR,
+        }
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let pair = VRFPair::generate(b"12345678901234567890123456789012").unwrap();
+        let epoch = epoch(&pair);
+        let pairs = HashMap::from([(0, &pair)]);
+
+        let parent = Header::default();
+        let mut header = Header::default();
+        header.number = 1;
+        header.digests.push(Digest::PreDigest(
+            claim(10.into(), &epoch, epoch.c, &pairs).unwrap(),
+        ));
+        assert!(matches!(
+            verify_header(&header, &parent, &epoch, 20.into()),
+            Err(Error::NoSeal)
+        ));
+
+        let seal = pair.seal(&header.pre_seal_hash());
+        header.digests.push(Digest::Seal(seal));
+        assert_eq!(
+            verify_header(&header, &parent, &epoch, 20.into()).unwrap(),
+            7
+        );
+        assert!(matches!(
+            verify_header(&header, &parent, &epoch, 9.into()),
+            Err(Error::SlotInFuture(_))
+        ));
+
+        let mut forged = header.clone();
+        forged.tx_count = 1;
+        assert!(matches!(
+            verify_header(&forged, &parent, &epoch, 20.into()),
+            Err(Error::InvalidSeal)
+        ));
+    }
+}
@@ -1,9 +1,16 @@
 use super::babe::Authority;
 use super::slots::Slot;
+use crate::config;
 use crate::crypto::PublicKey;
 
 const RANDOMNESS_LEN: usize = 32;
 
+/// Validator epoch of a block. Unlike `Epoch`, which is counted in slots, the
+/// validator set changes every `VALIDATOR_EPOCH_LENGTH` blocks
+pub fn validator_epoch(number: u64) -> u64 {
+    number / config::VALIDATOR_EPOCH_LENGTH
+}
+
 /// Epoch Information
 pub struct Epoch<P: PublicKey> {
     pub index: u64,
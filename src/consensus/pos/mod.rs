@@ -1,7 +1,9 @@
 use crate::core::Header;
 use crate::crypto::PublicKey;
-use epoch::Epoch;
-use slots::Slot;
+
+pub use babe::{find_pre_digest, propose, verify_header, Authority};
+pub use epoch::{validator_epoch, AllowSlot, Epoch};
+pub use slots::Slot;
 
 mod babe;
 mod epoch;
@@ -23,6 +25,24 @@ pub enum Error {
     EarlierThanBestFinalized,
     #[error("block had been imported already")]
     BlockHadBeenImported,
+    #[error("slot {0} is not after the slot of the parent block")]
+    SlotNotIncreasing(Slot),
+    #[error("slot {0} has not started yet")]
+    SlotInFuture(Slot),
+    #[error("there is no authority with index {0}")]
+    UnknownAuthority(u32),
+    #[error("vrf proof of the slot claim is invalid")]
+    InvalidVrfProof,
+    #[error("vrf output doesn't meet the primary slot threshold")]
+    PrimaryThresholdUnmet,
+    #[error("secondary slot {0} is claimed by the wrong authority")]
+    WrongSecondaryAuthor(Slot),
+    #[error("secondary slots of this kind are not allowed")]
+    SecondarySlotNotAllowed,
+    #[error("header is not sealed")]
+    NoSeal,
+    #[error("seal signature is invalid")]
+    InvalidSeal,
 }
 
 #[async_trait::async_trait]
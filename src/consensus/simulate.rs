@@ -0,0 +1,112 @@
+use crate::blockchain::next_target;
+use crate::config;
+use rand::Rng;
+
+// Models hashrate swings against the retarget algorithm, so that choices for
+// DIFFICULTY_CALC_INTERVAL/BLOCK_TIME can be validated before launch.
+
+// Expected number of hashes needed for meeting a compact difficulty target
+// (Leading zero bytes in the highest byte, the rest is the postfix).
+pub fn expected_hashes(target: u32) -> f64 {
+    let zeros = (target >> 24) as i32;
+    let postfix = (target & 0x00ffffff) as f64;
+    256f64.powi(zeros) * (0x1000000 as f64 / (postfix + 1.0))
+}
+
+#[derive(Debug, Clone)]
+pub struct HashratePhase {
+    pub blocks: usize,
+    pub hashrate: f64, // Hashes per second
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockTrace {
+    pub number: usize,
+    pub timestamp: u32,
+    pub target: u32,
+    pub block_time: u32,
+    pub hashrate: f64,
+}
+
+pub fn simulate<R: Rng>(
+    rng: &mut R,
+    initial_target: u32,
+    phases: &[HashratePhase],
+) -> Vec<BlockTrace> {
+    // Genesis block is mined at timestamp 0
+    let mut timestamps = vec![0u32];
+    let mut target = initial_target;
+    let mut traces = Vec::new();
+    for phase in phases.iter() {
+        for _ in 0..phase.blocks {
+            let height = timestamps.len();
+            target = next_target(height, target, |i| Ok::<u32, ()>(timestamps[i])).unwrap();
+            // Solve times are exponentially distributed
+            let mean = expected_hashes(target) / phase.hashrate;
+            let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+            let block_time = (-u.ln() * mean).round() as u32;
+            let timestamp = timestamps[height - 1] + block_time;
+            timestamps.push(timestamp);
+            traces.push(BlockTrace {
+                number: height,
+                timestamp,
+                target,
+                block_time,
+                hashrate: phase.hashrate,
+            });
+        }
+    }
+    traces
+}
+
+pub fn mean_block_time(traces: &[BlockTrace]) -> f64 {
+    if traces.is_empty() {
+        return 0.0;
+    }
+    traces.iter().map(|t| t.block_time as f64).sum::<f64>() / traces.len() as f64
+}
+
+pub fn to_csv(traces: &[BlockTrace]) -> String {
+    let mut csv = "number,timestamp,target,block_time,hashrate\n".to_string();
+    for t in traces.iter() {
+        csv += &format!(
+            "{},{},{:#010x},{},{}\n",
+            t.number, t.timestamp, t.target, t.block_time, t.hashrate
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_converges_after_hashrate_jump() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let target = 0x02ffffff;
+        let stable = expected_hashes(target) / config::BLOCK_TIME as f64;
+        let interval = config::DIFFICULTY_CALC_INTERVAL;
+        let traces = simulate(
+            &mut rng,
+            target,
+            &[
+                HashratePhase {
+                    blocks: interval * 4,
+                    hashrate: stable,
+                },
+                HashratePhase {
+                    blocks: interval * 8,
+                    hashrate: stable * 4.0,
+                },
+            ],
+        );
+        assert_eq!(traces.len(), interval * 12);
+        let before = mean_block_time(&traces[..interval * 4]);
+        let after = mean_block_time(&traces[interval * 8..]);
+        let expected = config::BLOCK_TIME as f64;
+        assert!((before - expected).abs() < expected * 0.25);
+        assert!((after - expected).abs() < expected * 0.25);
+    }
+}
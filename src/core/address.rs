@@ -49,4 +49,71 @@ pub enum Signature<S: SignatureScheme> {
 pub struct Account {
     pub balance: Money,
     pub nonce: u32,
+    // Part of the balance locked by the protocol itself (Vesting, validator
+    // lockups), cleared once it expires
+    pub locked: Option<LockedFunds>,
+}
+
+impl Account {
+    // Locked part of the balance at the given height
+    pub fn locked_at(&self, height: u64) -> Money {
+        self.locked
+            .as_ref()
+            .filter(|lock| !lock.is_unlocked(height))
+            .map(|lock| lock.amount)
+            .unwrap_or(0)
+    }
+
+    // An account holds a single lock, a new one is merged into it when both
+    // expire at the same height. A lock expiring at another height is handed
+    // back, to be kept elsewhere, so that nobody can block (Or extend) the
+    // locks of others by sending them locked funds.
+    pub fn add_lock(&mut self, height: u64, lock: LockedFunds) -> Option<LockedFunds> {
+        if lock.is_unlocked(height) {
+            return None;
+        }
+        match self.locked.as_mut() {
+            Some(curr) if !curr.is_unlocked(height) => {
+                if curr.until != lock.until {
+                    return Some(lock);
+                }
+                curr.amount += lock.amount;
+            }
+            _ => {
+                self.locked = Some(lock);
+            }
+        }
+        None
+    }
+}
+
+// Accounts as stored before they could hold a lock
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct AccountV0 {
+    pub balance: Money,
+    pub nonce: u32,
+}
+
+impl From<AccountV0> for Account {
+    fn from(acc: AccountV0) -> Self {
+        Self {
+            balance: acc.balance,
+            nonce: acc.nonce,
+            locked: None,
+        }
+    }
+}
+
+// Part of a balance that can't be spent before a height, i.e. block rewards
+// that haven't matured, unbonding stake and vesting grants
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct LockedFunds {
+    pub until: u64,
+    pub amount: Money,
+}
+
+impl LockedFunds {
+    pub fn is_unlocked(&self, height: u64) -> bool {
+        height >= self.until
+    }
 }
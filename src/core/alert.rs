@@ -0,0 +1,64 @@
+use super::address::Signature;
+use crate::crypto::SignatureScheme;
+
+// An in-band notice for node operators (Urgent upgrades, vulnerabilities, etc.)
+// signed by the network alert key and gossiped between peers.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct Alert<S: SignatureScheme> {
+    pub id: u32,
+    pub expires_at: u32,
+    pub message: String,
+    pub sig: Signature<S>,
+}
+
+impl<S: SignatureScheme> Alert<S> {
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"BAZUKA ALERT".to_vec();
+        bytes.extend(self.id.to_le_bytes());
+        bytes.extend(self.expires_at.to_le_bytes());
+        bytes.extend(self.message.as_bytes());
+        bytes
+    }
+    pub fn verify_signature(&self, pk: &S::Pub) -> bool {
+        match &self.sig {
+            Signature::Unsigned => false,
+            Signature::Signed(sig) => S::verify(pk, &self.signing_bytes(), sig),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{Alert, Signature};
+    use crate::crypto::{EdDSA, SignatureScheme};
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_alert_signature() {
+        let authority = Wallet::new(b"ALERT".to_vec());
+        let (alert_key, _) = EdDSA::generate_keys(b"ALERT");
+        let (other_key, _) = EdDSA::generate_keys(b"OTHER");
+        let mut alert = Alert {
+            id: 1,
+            expires_at: 1000,
+            message: "Upgrade to the latest version".into(),
+            sig: Signature::Unsigned,
+        };
+        assert!(!alert.verify_signature(&alert_key));
+
+        authority.sign_alert(&mut alert);
+        assert!(alert.verify_signature(&alert_key));
+        assert!(!alert.verify_signature(&other_key));
+
+        // Every signed field is covered
+        let mut tampered = alert.clone();
+        tampered.message = "Downgrade to an old version".into();
+        assert!(!tampered.verify_signature(&alert_key));
+        let mut tampered = alert.clone();
+        tampered.expires_at += 1;
+        assert!(!tampered.verify_signature(&alert_key));
+        let mut tampered = alert.clone();
+        tampered.id += 1;
+        assert!(!tampered.verify_signature(&alert_key));
+    }
+}
@@ -1,13 +1,21 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::crypto::merkle::MerkleTree;
 use crate::crypto::SignatureScheme;
 
+use super::address::Address;
 use super::hash::Hash;
 use super::header::Header;
-use super::transaction::Transaction;
+use super::transaction::{Transaction, TransactionData};
+use super::Money;
+
+// Transactions carrying zk-proofs are much more expensive to verify than their
+// size suggests, so they weigh this many times their size
+pub const ZK_WEIGHT_FACTOR: u64 = 4;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(deserialize = "H: Deserialize<'de>, S: DeserializeOwned"))]
 pub struct Block<H: Hash, S: SignatureScheme> {
     pub header: Header<H>,
     pub body: Vec<Transaction<S>>,
@@ -18,3 +26,75 @@ impl<H: Hash, S: SignatureScheme> Block<H, S> {
         MerkleTree::<H>::new(self.body.iter().map(|tx| tx.hash::<H>()).collect())
     }
 }
+
+// Derived data of a block, computed once when the block is applied so that
+// explorers don't need to recompute it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockMetadata {
+    pub size: u64,
+    pub weight: u64,
+    pub tx_count: u64,
+    pub fees: Money,
+    pub coinbase_value: Money,
+}
+
+// What a transaction adds to the weight of its block
+pub fn tx_weight<S: SignatureScheme>(tx: &Transaction<S>) -> u64 {
+    let size = bincode::serialized_size(tx).unwrap();
+    match &tx.data {
+        TransactionData::DepositWithdraw { .. } | TransactionData::Update { .. } => {
+            size * ZK_WEIGHT_FACTOR
+        }
+        _ => size,
+    }
+}
+
+impl BlockMetadata {
+    pub fn new(block: &super::Block) -> Self {
+        let mut weight = bincode::serialized_size(&block.header).unwrap();
+        let mut fees: Money = 0;
+        let mut coinbase_value: Money = 0;
+        for tx in block.body.iter() {
+            weight += tx_weight(tx);
+            match (&tx.src, &tx.data) {
+                (Address::Treasury, TransactionData::RegularSend { amount, .. }) => {
+                    coinbase_value += amount;
+                }
+                _ => {
+                    fees += tx.fee;
+                }
+            }
+        }
+        Self {
+            size: bincode::serialized_size(block).unwrap(),
+            weight,
+            tx_count: block.body.len() as u64,
+            fees,
+            coinbase_value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::genesis;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_block_metadata() {
+        let mut block = genesis::get_genesis_block();
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        block
+            .body
+            .push(wallet.create_transaction(Address::Treasury, 10, 3));
+        let meta = BlockMetadata::new(&block);
+        assert_eq!(meta.tx_count, 2);
+        assert_eq!(meta.fees, 3);
+        assert_eq!(meta.coinbase_value, 123);
+        assert_eq!(meta.size, bincode::serialized_size(&block).unwrap());
+        // Nothing needs zk verification, only the length prefix of the body
+        // is left out
+        assert_eq!(meta.weight, meta.size - 8);
+    }
+}
@@ -0,0 +1,102 @@
+use super::hash::Hash;
+use crate::crypto::SignatureScheme;
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct CheckpointSignature<S: SignatureScheme> {
+    pub authority: S::Pub,
+    pub sig: S::Sig,
+}
+
+// A checkpoint beacon, signed by a configured set of authorities. Nodes refuse
+// to reorg past the latest checkpoint signed by at least `threshold` of them.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Checkpoint<H: Hash, S: SignatureScheme> {
+    pub number: u64,
+    pub hash: H::Output,
+    pub signatures: Vec<CheckpointSignature<S>>,
+}
+
+impl<H: Hash, S: SignatureScheme> Checkpoint<H, S> {
+    pub fn new(number: u64, hash: H::Output) -> Self {
+        Self {
+            number,
+            hash,
+            signatures: Vec::new(),
+        }
+    }
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"BAZUKA CHECKPOINT".to_vec();
+        bytes.extend(self.number.to_le_bytes());
+        bytes.extend(self.hash.as_ref());
+        bytes
+    }
+    pub fn verify(&self, authorities: &[S::Pub], threshold: usize) -> bool {
+        let msg = self.signing_bytes();
+        let mut signers: Vec<&S::Pub> = Vec::new();
+        for s in self.signatures.iter() {
+            if authorities.contains(&s.authority)
+                && !signers.contains(&&s.authority)
+                && S::verify(&s.authority, &msg, &s.sig)
+            {
+                signers.push(&s.authority);
+            }
+        }
+        threshold > 0 && signers.len() >= threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hash;
+    use crate::core::{Checkpoint, Hasher};
+    use crate::wallet::Wallet;
+
+    fn signed(number: u64, wallets: &[&Wallet]) -> Checkpoint {
+        let mut checkpoint = Checkpoint::new(number, Default::default());
+        for w in wallets {
+            w.sign_checkpoint(&mut checkpoint);
+        }
+        checkpoint
+    }
+
+    #[test]
+    fn test_checkpoint_verify() {
+        let wallets: Vec<Wallet> = ["A", "B", "C", "D"]
+            .iter()
+            .map(|s| Wallet::new(s.as_bytes().to_vec()))
+            .collect();
+        let pub_key = |w: &Wallet| signed(0, &[w]).signatures[0].authority.clone();
+        let authorities: Vec<_> = wallets[..3].iter().map(pub_key).collect();
+
+        let checkpoint = signed(10, &[&wallets[0], &wallets[1]]);
+        assert!(checkpoint.verify(&authorities, 1));
+        assert!(checkpoint.verify(&authorities, 2));
+        assert!(!checkpoint.verify(&authorities, 3));
+
+        // Nothing is ever trusted with a zero threshold
+        assert!(!checkpoint.verify(&authorities, 0));
+
+        // The same authority signing twice is counted once
+        let twice = signed(10, &[&wallets[0], &wallets[0]]);
+        assert!(!twice.verify(&authorities, 2));
+
+        // Signatures of non-authorities are ignored
+        let outsider = signed(10, &[&wallets[0], &wallets[3]]);
+        assert!(!outsider.verify(&authorities, 2));
+
+        // Signatures don't carry over to another number or hash
+        let mut moved = checkpoint.clone();
+        moved.number = 11;
+        assert!(!moved.verify(&authorities, 1));
+        let mut rehashed = checkpoint.clone();
+        rehashed.hash = Hasher::hash(b"another block");
+        assert!(!rehashed.verify(&authorities, 1));
+
+        // A signature taken from another checkpoint is rejected
+        let mut forged = signed(10, &[&wallets[0]]);
+        forged
+            .signatures
+            .push(signed(12, &[&wallets[1]]).signatures[0].clone());
+        assert!(!forged.verify(&authorities, 2));
+    }
+}
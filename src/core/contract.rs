@@ -1,9 +1,56 @@
 use super::address::{Address, Signature};
-use super::Money;
+use super::hash::Hash;
+use super::transaction::Transaction;
+use super::{Hasher, Money};
 use crate::crypto::SignatureScheme;
+use crate::zk::{ZkStateModel, ZkVerifierKey};
 
+// Contracts are identified by the hash of the transaction that created them
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
-pub struct ContractId {}
+pub struct ContractId(<Hasher as Hash>::Output);
+
+impl ContractId {
+    pub fn new<S: SignatureScheme>(creation_tx: &Transaction<S>) -> Self {
+        Self(creation_tx.hash::<Hasher>())
+    }
+}
+
+impl std::fmt::Display for ContractId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseContractIdError {
+    #[error("contract id invalid")]
+    Invalid,
+}
+
+impl std::str::FromStr for ContractId {
+    type Err = ParseContractIdError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut id = <Hasher as Hash>::Output::default();
+        hex::decode_to_slice(s, &mut id).map_err(|_| ParseContractIdError::Invalid)?;
+        Ok(Self(id))
+    }
+}
+
+// The immutable part of a contract, as given in its creation transaction
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct Contract {
+    pub deposit_withdraw_circuit: ZkVerifierKey,
+    pub update_circuits: Vec<ZkVerifierKey>,
+    pub state_model: ZkStateModel,
+}
+
+impl Contract {
+    // Total size of the verifier keys in bytes
+    pub fn circuits_size(&self) -> usize {
+        self.deposit_withdraw_circuit.size()
+            + self.update_circuits.iter().map(|c| c.size()).sum::<usize>()
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
 pub enum PaymentDirection {
@@ -13,11 +60,28 @@ pub enum PaymentDirection {
 
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
 pub struct ContractPayment<S: SignatureScheme> {
-    initiator: Address<S>,
-    contract_id: ContractId, // Makes sure the payment can only run on this contract.
-    nonce: usize,            // Makes sure a contract payment cannot be replayed on this contract.
-    amount: Money,
-    fee: Money,
-    direction: PaymentDirection,
-    sig: Signature<S>,
+    pub initiator: Address<S>,
+    pub contract_id: ContractId, // Makes sure the payment can only run on this contract.
+    pub nonce: usize, // Makes sure a contract payment cannot be replayed on this contract.
+    pub amount: Money,
+    pub fee: Money, // Paid to the executor of the DepositWithdraw transaction
+    pub direction: PaymentDirection,
+    pub sig: Signature<S>,
+}
+
+impl<S: SignatureScheme> ContractPayment<S> {
+    pub fn verify_signature(&self) -> bool {
+        match &self.initiator {
+            Address::<S>::Treasury => false,
+            Address::<S>::PublicKey(pk) => match &self.sig {
+                Signature::Unsigned => false,
+                Signature::Signed(sig) => {
+                    let mut unsigned = self.clone();
+                    unsigned.sig = Signature::Unsigned;
+                    let bytes = bincode::serialize(&unsigned).unwrap();
+                    S::verify(&pk, &bytes, &sig)
+                }
+            },
+        }
+    }
 }
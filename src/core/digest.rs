@@ -43,6 +43,8 @@ pub enum Digest {
     PreDigest(PreDigest),
     /// runtime to consensus
     Consensus(BabeConsensusLog),
+    /// signature of the proposer over the rest of the header, always the last item
+    Seal(Vec<u8>),
 }
 
 #[cfg(test)]
@@ -0,0 +1,71 @@
+use super::hash::Hash;
+use super::transaction::Transaction;
+use super::{Hasher, Money};
+use crate::config;
+use crate::crypto::SignatureScheme;
+
+// Non-critical parameters the validators can change by voting, without a
+// hard fork
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum GovernanceParam {
+    // Transactions paying less are not relayed (Policy, not consensus)
+    MinRelayFee,
+    // See `BlockMetadata::weight`
+    MaxBlockWeight,
+}
+
+impl GovernanceParam {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GovernanceParam::MinRelayFee => "min_relay_fee",
+            GovernanceParam::MaxBlockWeight => "max_block_weight",
+        }
+    }
+    // Value before any proposal on the parameter gets approved
+    pub fn default_value(&self) -> u64 {
+        match self {
+            GovernanceParam::MinRelayFee => config::MIN_RELAY_FEE,
+            GovernanceParam::MaxBlockWeight => config::MAX_BLOCK_WEIGHT,
+        }
+    }
+    // Even an approved proposal can't take a parameter out of its bounds, e.g.
+    // shrink the blocks so much that they can't even hold the rewards
+    pub fn is_valid(&self, value: u64) -> bool {
+        let (min, max) = match self {
+            GovernanceParam::MinRelayFee => config::MIN_RELAY_FEE_BOUNDS,
+            GovernanceParam::MaxBlockWeight => config::MAX_BLOCK_WEIGHT_BOUNDS,
+        };
+        value >= min && value <= max
+    }
+}
+
+// Proposals are identified by the hash of the transaction that made them
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct ProposalId(<Hasher as Hash>::Output);
+
+impl ProposalId {
+    pub fn new<S: SignatureScheme>(proposal_tx: &Transaction<S>) -> Self {
+        Self(proposal_tx.hash::<Hasher>())
+    }
+}
+
+impl std::fmt::Display for ProposalId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct Proposal {
+    pub param: GovernanceParam,
+    pub value: u64,
+    // Number of the block the proposal got in
+    pub created: u64,
+}
+
+// Sum of the stakes of the approving validators, which is a fraction of the
+// total stake
+pub fn is_approved(approving: Money, total: Money) -> bool {
+    let (num, den) = config::GOVERNANCE_APPROVAL;
+    total > 0 && approving as u128 * den as u128 > total as u128 * num as u128
+}
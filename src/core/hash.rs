@@ -127,11 +127,78 @@ pub enum HasherError {
     Mismatch,
 }
 
+// Every kind of hashed (Or signed) structure gets its own domain tag, which is
+// prepended to the data before hashing. This way, a hash (Or signature) of one
+// kind of structure can never be passed off as another one.
+pub mod domains {
+    use super::Hash;
+
+    pub const TRANSACTION: &[u8] = b"BAZUKA TRANSACTION";
+    pub const HEADER: &[u8] = b"BAZUKA HEADER";
+    pub const MESSAGE: &[u8] = b"BAZUKA SIGNED MESSAGE";
+    pub const PEER_HANDSHAKE: &[u8] = b"BAZUKA PEER HANDSHAKE";
+    pub const RELAY: &[u8] = b"BAZUKA RELAY";
+    pub const HD_DERIVATION: &[u8] = b"BAZUKA HD DERIVATION";
+    pub const RANDOMNESS: &[u8] = b"BAZUKA RANDOMNESS";
+
+    // The tag is length-prefixed, so that no (tag, data) pair can collide with
+    // another one
+    pub fn hash_with_domain<H: Hash>(domain: &[u8], data: &[u8]) -> H::Output {
+        let mut bytes = Vec::with_capacity(1 + domain.len() + data.len());
+        bytes.push(domain.len() as u8);
+        bytes.extend(domain);
+        bytes.extend(data);
+        H::hash(&bytes)
+    }
+
+    pub fn hash_tx<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(TRANSACTION, data)
+    }
+    pub fn hash_header<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(HEADER, data)
+    }
+    pub fn hash_message<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(MESSAGE, data)
+    }
+    pub fn hash_peer_handshake<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(PEER_HANDSHAKE, data)
+    }
+    pub fn hash_relay<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(RELAY, data)
+    }
+    pub fn hash_hd_derivation<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(HD_DERIVATION, data)
+    }
+    pub fn hash_randomness<H: Hash>(data: &[u8]) -> H::Output {
+        hash_with_domain::<H>(RANDOMNESS, data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::core::hash::domains;
     use crate::core::hash::Hash;
     use crate::core::hash::Sha3Hasher;
 
+    #[test]
+    fn test_domains_are_separated() {
+        let data = b"123123";
+        let hashes = [
+            Sha3Hasher::hash(data),
+            domains::hash_tx::<Sha3Hasher>(data),
+            domains::hash_header::<Sha3Hasher>(data),
+            domains::hash_message::<Sha3Hasher>(data),
+            domains::hash_peer_handshake::<Sha3Hasher>(data),
+            domains::hash_relay::<Sha3Hasher>(data),
+        ];
+        for i in 0..hashes.len() {
+            for j in i + 1..hashes.len() {
+                assert_ne!(hashes[i], hashes[j]);
+            }
+        }
+        assert_eq!(hashes[1], domains::hash_tx::<Sha3Hasher>(data));
+    }
+
     #[test]
     fn test_sha3_works() {
         let output1 = Sha3Hasher::hash(b"123123");
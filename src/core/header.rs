@@ -2,9 +2,12 @@
 use super::digest::{Digest, Digests};
 
 #[cfg(feature = "pow")]
-use rust_randomx::{Difficulty, Output};
+use {
+    num_bigint::BigUint,
+    rust_randomx::{Difficulty, Output},
+};
 
-use super::hash::Hash;
+use super::hash::{domains, Hash};
 
 #[cfg(feature = "pow")]
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -38,6 +41,8 @@ pub struct Header<H: Hash> {
     pub state_root: H::Output,
     /// the merkle root of current block
     pub block_root: H::Output,
+    /// number of transactions in the chain, up to and including this block
+    pub tx_count: u64,
 
     /// aux data for Proof-of-Stake consensus
     #[cfg(feature = "pos")]
@@ -55,6 +60,7 @@ impl<H: Hash> Default for Header<H> {
             number: 0,
             state_root: H::Output::default(),
             block_root: H::Output::default(),
+            tx_count: 0,
 
             #[cfg(feature = "pos")]
             digests: Default::default(),
@@ -65,9 +71,27 @@ impl<H: Hash> Default for Header<H> {
     }
 }
 
+// The hash commits to every field of the header
+impl<H: Hash> PartialEq for Header<H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash() == other.hash()
+    }
+}
+
+// Expands a compact difficulty target (Number of leading zero bytes in the highest
+// byte, followed by a 3-byte postfix) to the 256-bit number a hash should not exceed.
+#[cfg(feature = "pow")]
+fn expand_target(target: u32) -> BigUint {
+    let zeros = std::cmp::min(target >> 24, 29);
+    let postfix = target & 0x00ffffff;
+    ((BigUint::from(postfix) + BigUint::from(1u32)) << (8 * (29 - zeros))) - BigUint::from(1u32)
+}
+
 impl<H: Hash> Header<H> {
     pub fn hash(&self) -> H::Output {
-        H::hash(&bincode::serialize(&self).expect("convert header to bincode format"))
+        domains::hash_header::<H>(
+            &bincode::serialize(&self).expect("convert header to bincode format"),
+        )
     }
 
     #[cfg(feature = "pow")]
@@ -76,15 +100,19 @@ impl<H: Hash> Header<H> {
         crate::consensus::pow::hash(key, &bin)
     }
 
+    // Expected number of hashes needed for generating this block, derived from
+    // the target so that a lucky hash doesn't count as more work:
+    // floor(2^256 / (target + 1))
     #[cfg(feature = "pow")]
-    fn leading_zeros(&self, key: &[u8]) -> u8 {
-        self.pow_hash(key).leading_zeros() as u8
-    }
-
-    // Approximate number of hashes run in order to generate this block
-    #[cfg(feature = "pow")]
-    pub fn power(&self, key: &[u8]) -> u64 {
-        1u64 << self.leading_zeros(key)
+    pub fn power(&self) -> u64 {
+        let work = (BigUint::from(1u32) << 256u32)
+            / (expand_target(self.proof_of_work.target) + BigUint::from(1u32));
+        let digits = work.to_u64_digits();
+        match digits.len() {
+            0 => 0,
+            1 => digits[0],
+            _ => u64::MAX,
+        }
     }
 
     #[cfg(feature = "pow")]
@@ -97,4 +125,40 @@ impl<H: Hash> Header<H> {
     pub fn logs(&self) -> &[Digest] {
         self.digests.logs()
     }
+
+    /// hash of the header as it was before being sealed, which is what the seal signs
+    #[cfg(feature = "pos")]
+    pub fn pre_seal_hash(&self) -> H::Output {
+        let mut unsealed = self.clone();
+        if let Some(Digest::Seal(_)) = unsealed.logs().last() {
+            unsealed.digests.pop();
+        }
+        unsealed.hash()
+    }
+
+    #[cfg(feature = "pos")]
+    pub fn seal(&self) -> Option<&[u8]> {
+        match self.logs().last() {
+            Some(Digest::Seal(seal)) => Some(seal),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "pow"))]
+mod tests {
+    use crate::core::Header;
+
+    #[test]
+    fn test_power_from_target() {
+        let mut h = Header::default();
+        h.proof_of_work.target = 0x02ffffff;
+        assert_eq!(h.power(), 0x10000);
+        h.proof_of_work.target = 0x027fffff;
+        assert_eq!(h.power(), 0x20000);
+        h.proof_of_work.target = 0x03ffffff;
+        assert_eq!(h.power(), 0x1000000);
+        h.proof_of_work.target = 0x1d000000;
+        assert_eq!(h.power(), u64::MAX);
+    }
 }
@@ -0,0 +1,53 @@
+use super::address::{Address, Signature};
+use super::hash::domains;
+use super::Hasher;
+use crate::crypto::SignatureScheme;
+
+// Messages are signed through their domain-separated hash, so that a signature
+// over a message can never be replayed as a signature over a transaction (Or
+// anything else) and vice versa
+pub fn message_signing_bytes(msg: &[u8]) -> Vec<u8> {
+    domains::hash_message::<Hasher>(msg).to_vec()
+}
+
+// Proves that the owner of the address has signed the message
+pub fn verify_message<S: SignatureScheme>(
+    addr: &Address<S>,
+    msg: &[u8],
+    sig: &Signature<S>,
+) -> bool {
+    match (addr, sig) {
+        (Address::PublicKey(pk), Signature::Signed(sig)) => {
+            S::verify(pk, &message_signing_bytes(msg), sig)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Transaction;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_message_signature() {
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        let addr = wallet.get_address();
+        let sig = wallet.sign_message(b"I own this address");
+        assert!(verify_message(&addr, b"I own this address", &sig));
+        assert!(!verify_message(&addr, b"I own that address", &sig));
+        assert!(!verify_message(
+            &Address::Treasury,
+            b"I own this address",
+            &sig
+        ));
+
+        // A transaction signature is not a valid message signature
+        let tx: Transaction = wallet.create_transaction(Address::Treasury, 1, 1);
+        let mut unsigned = tx.clone();
+        unsigned.sig = Signature::Unsigned;
+        let bytes = bincode::serialize(&unsigned).unwrap();
+        assert!(!verify_message(&addr, &bytes, &tx.sig));
+    }
+}
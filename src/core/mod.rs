@@ -1,8 +1,16 @@
 mod address;
+mod alert;
 mod blocks;
+mod checkpoint;
 mod contract;
+mod governance;
 mod header;
+mod message;
 mod transaction;
+mod validator;
+
+#[cfg(test)]
+mod wire;
 
 #[cfg(feature = "pos")]
 pub mod digest;
@@ -14,16 +22,36 @@ use std::fmt::Debug;
 
 use crate::crypto;
 
+pub use blocks::tx_weight;
+pub use governance::{is_approved, GovernanceParam, Proposal, ProposalId};
+pub use message::{message_signing_bytes, verify_message};
+pub use validator::VRF_PUBLIC_KEY_LENGTH;
+
 pub type Money = u64;
 pub type Signer = crypto::EdDSA;
 pub type Hasher = hash::Sha3Hasher;
 pub type Address = address::Address<Signer>;
 pub type Account = address::Account;
+pub type AccountV0 = address::AccountV0;
+pub type LockedFunds = address::LockedFunds;
+pub type Alert = alert::Alert<Signer>;
 pub type Signature = address::Signature<Signer>;
 pub type Transaction = transaction::Transaction<Signer>;
+pub type TransactionId = <Hasher as hash::Hash>::Output;
 pub type TransactionData = transaction::TransactionData<Signer>;
+pub type TransactionLocation = transaction::TransactionLocation;
+pub type TxReceipt = transaction::TxReceipt;
+pub type Contract = contract::Contract;
+pub type Validator = validator::Validator;
+pub type Unbonding = validator::Unbonding;
+pub type ContractId = contract::ContractId;
+pub type PaymentDirection = contract::PaymentDirection;
+pub type ContractPayment = contract::ContractPayment<Signer>;
 pub type Header = header::Header<Hasher>;
 pub type Block = blocks::Block<Hasher, Signer>;
+pub type BlockMetadata = blocks::BlockMetadata;
+pub type Checkpoint = checkpoint::Checkpoint<Hasher, Signer>;
+pub type CheckpointSignature = checkpoint::CheckpointSignature<Signer>;
 
 #[cfg(feature = "pow")]
 pub type ProofOfWork = header::ProofOfWork;
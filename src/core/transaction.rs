@@ -1,20 +1,32 @@
 use super::address::{Address, Signature};
 use super::contract::{ContractId, ContractPayment};
-use super::hash::Hash;
-use super::Money;
+use super::governance::{GovernanceParam, ProposalId};
+use super::hash::{domains, Hash};
+use super::{Header, Money};
 use crate::crypto::SignatureScheme;
 use crate::zk::{ZkProof, ZkScalar, ZkStateData, ZkStateModel, ZkVerifierKey};
 
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 // A transaction could be as simple as sending some funds, or as complicated as
 // creating a smart-contract.
+//
+// On binary encodings, the data is written as a (tag, length-prefixed payload)
+// pair instead of a plain bincode enum, so that nodes running older versions can
+// still decode (And relay) transactions with variants they don't know about.
+// Human-readable encodings (JSON) keep the usual enum representation.
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+#[serde(remote = "Self")]
 pub enum TransactionData<S: SignatureScheme> {
     RegularSend {
         dst: Address<S>,
         amount: Money,
     },
+    // Locks `amount` as stake of the sender, who then proposes blocks with the
+    // given VRF key. Registering again adds to the stake and replaces the key.
     RegisterValidator {
-        vrf_stuff: u8,
+        vrf_public_key: Vec<u8>,
         amount: Money,
     },
 
@@ -40,9 +52,145 @@ pub enum TransactionData<S: SignatureScheme> {
         next_state: ZkScalar,
         proof: ZkProof,
     },
+
+    // Adds to the stake of a registered validator
+    Stake {
+        amount: Money,
+    },
+    // Takes back some stake, which stays locked for the unbonding period
+    Unstake {
+        amount: Money,
+    },
+    // Two different headers sealed by the same validator for the same height.
+    // Anyone can report them, part of the offender's stake gets burnt.
+    SlashEvidence {
+        offender: Address<S>,
+        first: Header,
+        second: Header,
+    },
+    // Proposes a new value for a governance parameter, the proposer (Who has
+    // to be a validator) approves it right away
+    Propose {
+        param: GovernanceParam,
+        value: u64,
+    },
+    // Stake-weighted vote of a validator on an open proposal, a later vote
+    // overrides the earlier one
+    Vote {
+        proposal: ProposalId,
+        approve: bool,
+    },
+    // Pays several destinations at once (e.g. a batch of withdrawals of an
+    // exchange) for a single fee and nonce. Either all of them get paid, or
+    // none of them.
+    RegularSendMany {
+        outputs: Vec<(Address<S>, Money)>,
+    },
+    // Sends money that the destination can't spend before the `until` height,
+    // e.g. vesting grants. Sending to oneself locks one's own funds.
+    LockedSend {
+        dst: Address<S>,
+        amount: Money,
+        until: u64,
+    },
+
+    // A variant introduced by a newer version of the protocol. Kept as is, so
+    // that it can be re-encoded byte-by-byte. MUST remain the last variant!
+    Unknown {
+        tag: u32,
+        payload: Vec<u8>,
+    },
+}
+
+// Number of variants this version understands (Everything before `Unknown`)
+const KNOWN_TRANSACTION_TAGS: u32 = 12;
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "", deserialize = "S: DeserializeOwned"))]
+struct TaggedTransactionData<S: SignatureScheme>(
+    #[serde(with = "TransactionData")] TransactionData<S>,
+);
+
+impl<S: SignatureScheme> TransactionData<S> {
+    pub fn tag(&self) -> u32 {
+        match self {
+            TransactionData::Unknown { tag, .. } => *tag,
+            _ => {
+                let bytes = bincode::serialize(&TaggedTransactionData(self.clone())).unwrap();
+                u32::from_le_bytes(bytes[..4].try_into().unwrap())
+            }
+        }
+    }
+    pub fn is_known(&self) -> bool {
+        !matches!(self, TransactionData::Unknown { .. })
+    }
+}
+
+impl<S: SignatureScheme> Serialize for TransactionData<S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        if serializer.is_human_readable() {
+            return TransactionData::serialize(self, serializer);
+        }
+        match self {
+            TransactionData::Unknown { tag, payload } => {
+                (tag, serde_bytes::Bytes::new(payload)).serialize(serializer)
+            }
+            _ => {
+                let bytes = bincode::serialize(&TaggedTransactionData(self.clone()))
+                    .map_err(serde::ser::Error::custom)?;
+                let tag = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+                (tag, serde_bytes::Bytes::new(&bytes[4..])).serialize(serializer)
+            }
+        }
+    }
 }
 
+impl<'de, S: SignatureScheme + DeserializeOwned> Deserialize<'de> for TransactionData<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let data = TransactionData::deserialize(deserializer)?;
+            // Known variants must be spelled out, not smuggled in as payloads
+            if let TransactionData::Unknown { tag, .. } = &data {
+                if *tag < KNOWN_TRANSACTION_TAGS {
+                    return Err(serde::de::Error::custom(
+                        "known transaction type as unknown",
+                    ));
+                }
+            }
+            return Ok(data);
+        }
+        let (tag, payload): (u32, serde_bytes::ByteBuf) = Deserialize::deserialize(deserializer)?;
+        let payload = payload.into_vec();
+        if tag >= KNOWN_TRANSACTION_TAGS {
+            return Ok(TransactionData::Unknown { tag, payload });
+        }
+        let mut bytes = tag.to_le_bytes().to_vec();
+        bytes.extend(payload);
+        let tagged: TaggedTransactionData<S> =
+            bincode::deserialize(&bytes).map_err(serde::de::Error::custom)?;
+        Ok(tagged.0)
+    }
+}
+
+// Where a transaction got confirmed: block number and position in its body
 #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct TransactionLocation {
+    pub block: u64,
+    pub index: usize,
+}
+
+// Outcome of a confirmed transaction
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct TxReceipt {
+    // Paid to the block producer
+    pub fee: Money,
+    // Everything the Treasury collected, contract creation fees included
+    pub collected: Money,
+    pub created_contract: Option<ContractId>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+#[serde(bound(deserialize = "S: DeserializeOwned"))]
 pub struct Transaction<S: SignatureScheme> {
     pub src: Address<S>,
     pub nonce: u32,
@@ -56,7 +204,14 @@ impl<S: SignatureScheme> Transaction<S> {
         format!("{}_{}", self.src, self.nonce)
     }
     pub fn hash<H: Hash>(&self) -> H::Output {
-        H::hash(&bincode::serialize(self).unwrap())
+        domains::hash_tx::<H>(&bincode::serialize(self).unwrap())
+    }
+    // Canonical transaction id, computed over the unsigned payload so that it
+    // doesn't change when the transaction is re-signed.
+    pub fn txid<H: Hash>(&self) -> H::Output {
+        let mut unsigned = self.clone();
+        unsigned.sig = Signature::Unsigned;
+        domains::hash_tx::<H>(&bincode::serialize(&unsigned).unwrap())
     }
     pub fn verify_signature(&self) -> bool {
         match &self.src {
@@ -84,3 +239,61 @@ impl<S: SignatureScheme> std::hash::Hash for Transaction<S> {
         state.finish();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::core::hash::Sha3Hasher;
+    use crate::core::{Address, Signature, Transaction, TransactionData};
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_txid_ignores_signature() {
+        let mut tx = Wallet::new(b"ABC".to_vec()).create_transaction(Address::Treasury, 100, 1);
+        let txid = tx.txid::<Sha3Hasher>();
+        let hash = tx.hash::<Sha3Hasher>();
+        tx.sig = Signature::Unsigned;
+        assert_eq!(tx.txid::<Sha3Hasher>(), txid);
+        assert_ne!(tx.hash::<Sha3Hasher>(), hash);
+
+        tx.data = TransactionData::RegularSend {
+            dst: Address::Treasury,
+            amount: 101,
+        };
+        assert_ne!(tx.txid::<Sha3Hasher>(), txid);
+    }
+
+    #[test]
+    fn test_unknown_transaction_data() {
+        let tx = Wallet::new(b"ABC".to_vec()).create_transaction(Address::Treasury, 100, 1);
+        let bytes = bincode::serialize(&tx).unwrap();
+        let decoded: Transaction = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, tx);
+        assert_eq!(decoded.data.tag(), 0);
+
+        // A transaction type from the future survives a decode/encode round-trip
+        let mut future = tx.clone();
+        future.data = TransactionData::Unknown {
+            tag: 1000,
+            payload: vec![1, 2, 3, 4],
+        };
+        let bytes = bincode::serialize(&future).unwrap();
+        let decoded: Transaction = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, future);
+        assert!(!decoded.data.is_known());
+        assert_eq!(bincode::serialize(&decoded).unwrap(), bytes);
+
+        // Known tags with garbage payloads are rejected
+        let mut garbage = tx;
+        garbage.data = TransactionData::Unknown {
+            tag: 0,
+            payload: vec![1, 2, 3],
+        };
+        let bytes = bincode::serialize(&garbage).unwrap();
+        assert!(bincode::deserialize::<Transaction>(&bytes).is_err());
+        let json = serde_json::to_string(&garbage).unwrap();
+        assert!(serde_json::from_str::<Transaction>(&json).is_err());
+
+        let json = serde_json::to_string(&future).unwrap();
+        assert_eq!(serde_json::from_str::<Transaction>(&json).unwrap(), future);
+    }
+}
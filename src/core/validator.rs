@@ -0,0 +1,20 @@
+use super::{LockedFunds, Money};
+
+// Validators register sr25519 VRF public keys
+pub const VRF_PUBLIC_KEY_LENGTH: usize = 32;
+
+// An account taking part in the block proposer election (PoS), its chance of
+// being elected in a slot grows with its stake
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct Validator {
+    pub vrf_public_key: Vec<u8>,
+    pub stake: Money,
+}
+
+// Stake that has left a validator is still slashable until it unbonds. The key
+// is kept for checking evidence against validators that have left since.
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug, Clone)]
+pub struct Unbonding {
+    pub vrf_public_key: Vec<u8>,
+    pub locks: Vec<LockedFunds>,
+}
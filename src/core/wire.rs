@@ -0,0 +1,413 @@
+// Golden vectors of the consensus encoding of core types. If one of these tests
+// fails, a change has altered what goes on the wire (Or into the database), which
+// is a hard fork unless done deliberately. Update the vectors only then.
+
+use super::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+fn check<T: Serialize + DeserializeOwned>(value: &T, golden: &str) {
+    let bytes = bincode::serialize(value).unwrap();
+    assert_eq!(hex::encode(&bytes), golden, "encoding has changed");
+    let decoded: T = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(bincode::serialize(&decoded).unwrap(), bytes);
+}
+
+fn sample_tx() -> Transaction {
+    Transaction {
+        src: Address::Treasury,
+        nonce: 1,
+        data: TransactionData::RegularSend {
+            dst: Address::Treasury,
+            amount: 100,
+        },
+        fee: 2,
+        sig: Signature::Unsigned,
+    }
+}
+
+fn sample_header() -> Header {
+    Header {
+        parent_hash: [1u8; 32],
+        number: 2,
+        state_root: [3u8; 32],
+        block_root: [4u8; 32],
+        tx_count: 8,
+        #[cfg(feature = "pos")]
+        digests: Default::default(),
+        #[cfg(feature = "pow")]
+        proof_of_work: ProofOfWork {
+            timestamp: 5,
+            target: 6,
+            nonce: 7,
+        },
+    }
+}
+
+fn sample_payment() -> ContractPayment {
+    ContractPayment {
+        initiator: Address::Treasury,
+        contract_id: "0505050505050505050505050505050505050505050505050505050505050505"
+            .parse()
+            .unwrap(),
+        nonce: 1,
+        amount: 100,
+        fee: 2,
+        direction: PaymentDirection::Withdraw,
+        sig: Signature::Unsigned,
+    }
+}
+
+// Values of types which can only be built through their encoding
+fn decode<T: DeserializeOwned>(golden: &str) -> T {
+    bincode::deserialize(&hex::decode(golden).unwrap()).unwrap()
+}
+
+// Field elements, keys and signatures are encoded by the underlying zk/crypto
+// libraries. Vectors containing them embed their encoding as is.
+fn encoded<T: Serialize>(value: &T) -> String {
+    hex::encode(bincode::serialize(value).unwrap())
+}
+
+fn scalar() -> crate::zk::ZkScalar {
+    decode(&encoded(&zeekit::Fr::zero()))
+}
+
+// A transaction variant is encoded as its tag followed by its length-prefixed
+// fields, so that unknown variants can be skipped
+fn variant(tag: u32, fields: &str) -> String {
+    format!(
+        "{}{}{}",
+        encoded(&tag),
+        encoded(&(fields.len() as u64 / 2)),
+        fields
+    )
+}
+
+// `sample_tx` with the given encoded data
+fn sample_tx_with(data: &str) -> String {
+    format!("0000000001000000{}020000000000000000000000", data)
+}
+
+#[test]
+fn test_account_wire() {
+    check(
+        &Account {
+            balance: 1000,
+            nonce: 3,
+            locked: None,
+        },
+        "e8030000000000000300000000",
+    );
+    check(
+        &Account {
+            balance: 1000,
+            nonce: 3,
+            locked: Some(LockedFunds {
+                until: 10,
+                amount: 500,
+            }),
+        },
+        "e80300000000000003000000010a00000000000000f401000000000000",
+    );
+}
+
+#[test]
+fn test_address_wire() {
+    check(&Address::Treasury, "00000000");
+    let addr = crate::wallet::Wallet::new(b"ABC".to_vec()).get_address();
+    let pk = match &addr {
+        Address::PublicKey(pk) => encoded(pk),
+        Address::Treasury => unreachable!(),
+    };
+    check(&addr, &format!("01000000{}", pk));
+}
+
+#[test]
+fn test_transaction_wire() {
+    check(
+        &sample_tx(),
+        "0000000001000000000000000c00000000000000000000006400000000000000020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_locked_send_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::LockedSend {
+        dst: Address::Treasury,
+        amount: 100,
+        until: 20,
+    };
+    check(
+        &tx,
+        "00000000010000000b00000014000000000000000000000064000000000000001400000000000000020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_unknown_transaction_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::Unknown {
+        tag: 1000,
+        payload: vec![0xaa, 0xbb],
+    };
+    check(
+        &tx,
+        "0000000001000000e80300000200000000000000aabb020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_signed_transaction_wire() {
+    let mut tx = sample_tx();
+    tx.sig = crate::wallet::Wallet::new(b"ABC".to_vec()).sign_message(b"");
+    let sig = match &tx.sig {
+        Signature::Signed(sig) => encoded(sig),
+        Signature::Unsigned => unreachable!(),
+    };
+    check(
+        &tx,
+        &format!(
+            "0000000001000000000000000c00000000000000000000006400000000000000020000000000000001000000{}",
+            sig
+        ),
+    );
+}
+
+#[test]
+fn test_register_validator_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::RegisterValidator {
+        vrf_public_key: vec![0xaa, 0xbb],
+        amount: 100,
+    };
+    check(
+        &tx,
+        "00000000010000000100000012000000000000000200000000000000aabb6400000000000000020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_create_contract_wire() {
+    let vk: crate::zk::ZkVerifierKey = decode("0200000000000000aabb");
+    let mut tx = sample_tx();
+    tx.data = TransactionData::CreateContract {
+        deposit_withdraw_circuit: vk.clone(),
+        update_circuits: vec![vk],
+        initial_state: decode("0000000000000000"),
+        state_model: decode("0400000003"),
+    };
+    check(
+        &tx,
+        "00000000010000000200000029000000000000000200000000000000aabb01000000000000000200000000000000aabb00000000000000000400000003020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_deposit_withdraw_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::DepositWithdraw {
+        contract_id: sample_payment().contract_id,
+        deposit_withdraws: vec![sample_payment()],
+        next_state: scalar(),
+        proof: decode("0200000000000000ccdd"),
+    };
+    let fields = format!(
+        "{}{}{}{}",
+        "0505050505050505050505050505050505050505050505050505050505050505",
+        "01000000000000000000000005050505050505050505050505050505050505050505050505050505050505050100000000000000640000000000000002000000000000000100000000000000",
+        encoded(&scalar()),
+        "0200000000000000ccdd"
+    );
+    check(&tx, &sample_tx_with(&variant(3, &fields)));
+}
+
+#[test]
+fn test_update_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::Update {
+        contract_id: sample_payment().contract_id,
+        circuit_index: 3,
+        next_state: scalar(),
+        proof: decode("0200000000000000ccdd"),
+    };
+    let fields = format!(
+        "{}{}{}",
+        "050505050505050505050505050505050505050505050505050505050505050503000000",
+        encoded(&scalar()),
+        "0200000000000000ccdd"
+    );
+    check(&tx, &sample_tx_with(&variant(4, &fields)));
+}
+
+#[test]
+fn test_stake_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::Stake { amount: 100 };
+    check(
+        &tx,
+        "00000000010000000500000008000000000000006400000000000000020000000000000000000000",
+    );
+    tx.data = TransactionData::Unstake { amount: 100 };
+    check(
+        &tx,
+        "00000000010000000600000008000000000000006400000000000000020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_slash_evidence_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::SlashEvidence {
+        offender: Address::Treasury,
+        first: sample_header(),
+        second: sample_header(),
+    };
+    let header = encoded(&sample_header());
+    let fields = format!("00000000{}{}", header, header);
+    check(&tx, &sample_tx_with(&variant(7, &fields)));
+}
+
+#[test]
+fn test_governance_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::Propose {
+        param: GovernanceParam::MaxBlockWeight,
+        value: 1000,
+    };
+    check(
+        &tx,
+        "0000000001000000080000000c0000000000000001000000e803000000000000020000000000000000000000",
+    );
+    tx.data = TransactionData::Vote {
+        proposal: decode("0707070707070707070707070707070707070707070707070707070707070707"),
+        approve: true,
+    };
+    check(
+        &tx,
+        "0000000001000000090000002100000000000000070707070707070707070707070707070707070707070707070707070707070701020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_regular_send_many_wire() {
+    let mut tx = sample_tx();
+    tx.data = TransactionData::RegularSendMany {
+        outputs: vec![(Address::Treasury, 100), (Address::Treasury, 200)],
+    };
+    check(
+        &tx,
+        "00000000010000000a0000002000000000000000020000000000000000000000640000000000000000000000c800000000000000020000000000000000000000",
+    );
+}
+
+#[test]
+fn test_alert_wire() {
+    check(
+        &Alert {
+            id: 1,
+            expires_at: 2,
+            message: "upgrade".into(),
+            sig: Signature::Unsigned,
+        },
+        "010000000200000007000000000000007570677261646500000000",
+    );
+}
+
+#[test]
+fn test_checkpoint_wire() {
+    check(&Checkpoint::new(10, [9u8; 32]), "0a0000000000000009090909090909090909090909090909090909090909090909090909090909090000000000000000");
+}
+
+#[test]
+fn test_transaction_location_wire() {
+    check(
+        &TransactionLocation { block: 2, index: 3 },
+        "02000000000000000300000000000000",
+    );
+}
+
+#[cfg(feature = "pow")]
+#[test]
+fn test_header_wire() {
+    check(&sample_header(), "0101010101010101010101010101010101010101010101010101010101010101020000000000000003030303030303030303030303030303030303030303030303030303030303030404040404040404040404040404040404040404040404040404040404040404080000000000000005000000060000000700000000000000");
+}
+
+#[cfg(feature = "pow")]
+#[test]
+fn test_block_wire() {
+    check(
+        &Block {
+            header: sample_header(),
+            body: vec![sample_tx()],
+        },
+        "010101010101010101010101010101010101010101010101010101010101010102000000000000000303030303030303030303030303030303030303030303030303030303030303040404040404040404040404040404040404040404040404040404040404040408000000000000000500000006000000070000000000000001000000000000000000000001000000000000000c00000000000000000000006400000000000000020000000000000000000000",
+    );
+}
+
+// Node messages are plain wrappers around these types, e.g. `GetHeadersResponse`
+// is encoded exactly as a `Vec<Header>`.
+#[cfg(feature = "pow")]
+#[test]
+fn test_headers_wire() {
+    check(&vec![sample_header(), sample_header()], "020000000000000001010101010101010101010101010101010101010101010101010101010101010200000000000000030303030303030303030303030303030303030303030303030303030303030304040404040404040404040404040404040404040404040404040404040404040800000000000000050000000600000007000000000000000101010101010101010101010101010101010101010101010101010101010101020000000000000003030303030303030303030303030303030303030303030303030303030303030404040404040404040404040404040404040404040404040404040404040404080000000000000005000000060000000700000000000000");
+}
+
+#[cfg(feature = "pos")]
+#[test]
+fn test_header_wire() {
+    use crate::consensus::pos::digest::{
+        BabeConsensusLog, PreDigest, PrimaryPreDigest, SecondaryPlainPreDigest,
+        SecondaryVRFPreDigest,
+    };
+    use crate::consensus::pos::Slot;
+    use crate::core::digest::Digest;
+    let mut header = sample_header();
+    let fields = "01010101010101010101010101010101010101010101010101010101010101010200000000000000030303030303030303030303030303030303030303030303030303030303030304040404040404040404040404040404040404040404040404040404040404040800000000000000";
+    check(&header, &format!("{}0000000000000000", fields));
+    header
+        .digests
+        .push(Digest::PreDigest(PreDigest::Primary(PrimaryPreDigest {
+            authority_index: 1,
+            slot: Slot(2),
+            vrf_output: [3u8; 32],
+            vrf_proof: [4u8; 64],
+        })));
+    header
+        .digests
+        .push(Digest::PreDigest(PreDigest::SecondaryPlain(
+            SecondaryPlainPreDigest {
+                authority_index: 5,
+                slot: Slot(6),
+            },
+        )));
+    header
+        .digests
+        .push(Digest::PreDigest(PreDigest::SecondaryVRF(
+            SecondaryVRFPreDigest::new(7, Slot(8), [9u8; 32], [10u8; 64]),
+        )));
+    header
+        .digests
+        .push(Digest::Consensus(BabeConsensusLog::NextEpochData));
+    header.digests.push(Digest::Seal(vec![0xaa, 0xbb]));
+    let digests = [
+        "0500000000000000",
+        // Pre-digests: variant, authority index, slot, VRF output and proof
+        &format!(
+            "{}{}{}",
+            "0000000000000000010000000200000000000000",
+            "03".repeat(32),
+            "04".repeat(64)
+        ),
+        "0000000001000000050000000600000000000000",
+        &format!(
+            "{}{}{}",
+            "0000000002000000070000000800000000000000",
+            "09".repeat(32),
+            "0a".repeat(64)
+        ),
+        "0100000000000000",
+        "020000000200000000000000aabb",
+    ];
+    check(&header, &format!("{}{}", fields, digests.concat()));
+}
@@ -63,6 +63,29 @@ pub struct PrivateKey(eddsa::PrivateKey);
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Signature(eddsa::Signature);
 
+// Order of the prime-order subgroup of the curve (Little-endian)
+const SUBGROUP_ORDER: [u8; 32] = [
+    0xb7, 0x2c, 0xf7, 0xd6, 0x5e, 0x0e, 0x97, 0xd0, 0x82, 0x10, 0xc8, 0xcc, 0x93, 0x20, 0x68, 0xa6,
+    0x00, 0x3b, 0x34, 0x01, 0x01, 0x3b, 0x67, 0x06, 0xa9, 0xaf, 0x33, 0x65, 0xea, 0xb4, 0x7d, 0x0e,
+];
+
+// The scalar part of a signature should be strictly less than the subgroup order,
+// otherwise anyone could malleate a valid signature `s` into `s + ORDER`.
+fn is_canonical_scalar(repr: &[u8]) -> bool {
+    for (a, b) in repr.iter().rev().zip(SUBGROUP_ORDER.iter().rev()) {
+        if a != b {
+            return a < b;
+        }
+    }
+    false
+}
+
+impl Signature {
+    pub fn is_canonical(&self) -> bool {
+        is_canonical_scalar(self.0.s.to_repr().as_ref())
+    }
+}
+
 fn mimc_u8(inp: &[u8]) -> Fr {
     mimc::mimc(inp.iter().map(|u| Fr::from(*u as u64)).collect())
 }
@@ -82,6 +105,9 @@ impl SignatureScheme for EdDSA {
         Signature(eddsa::sign(&sk.0, hash))
     }
     fn verify(pk: &EdDSAPublicKey, message: &[u8], sig: &Signature) -> bool {
+        if !sig.is_canonical() {
+            return false;
+        }
         let hash = mimc::mimc(message.iter().map(|u| Fr::from(*u as u64)).collect());
         eddsa::verify(&pk.0, hash, &sig.0)
     }
@@ -111,4 +137,14 @@ mod tests {
         assert!(EdDSA::verify(&pk, msg, &sig));
         assert!(!EdDSA::verify(&pk, fake_msg, &sig));
     }
+
+    #[test]
+    fn test_canonical_scalar() {
+        let mut below = SUBGROUP_ORDER;
+        below[0] -= 1;
+        assert!(is_canonical_scalar(&[0u8; 32]));
+        assert!(is_canonical_scalar(&below));
+        assert!(!is_canonical_scalar(&SUBGROUP_ORDER));
+        assert!(!is_canonical_scalar(&[0xffu8; 32]));
+    }
 }
@@ -6,6 +6,10 @@ use schnorrkel::{ExpansionMode, MiniSecretKey, SecretKey, SignatureResult};
 
 use crate::crypto::{Error, VRFTranscript, VRFTranscriptData, VerifiableRandomFunction};
 
+// Signing context of block seals
+const SEAL_CONTEXT: &[u8] = b"bazuka-seal";
+
+#[derive(Clone)]
 pub struct VRFPublicKey(pub schnorrkel::keys::PublicKey);
 
 impl AsRef<[u8]> for VRFPublicKey {
@@ -21,6 +25,19 @@ impl Display for VRFPublicKey {
 }
 
 impl VRFPublicKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(VRFPublicKey(
+            schnorrkel::keys::PublicKey::from_bytes(bytes)
+                .map_err(|_| Error::InvalidLength("public key".to_string()))?,
+        ))
+    }
+
+    pub fn verify_seal(&self, msg: &[u8], seal: &[u8]) -> bool {
+        schnorrkel::Signature::from_bytes(seal)
+            .and_then(|sig| self.0.verify_simple(SEAL_CONTEXT, msg, &sig))
+            .is_ok()
+    }
+
     pub fn vrf_verify(
         &self,
         transcript: VRFTranscript,
@@ -48,6 +65,11 @@ impl VRFPair {
     pub fn to_public(&self) -> VRFPublicKey {
         VRFPublicKey(self.0.public)
     }
+
+    // The same key that claims a slot signs the block proposed in it
+    pub fn seal(&self, msg: &[u8]) -> Vec<u8> {
+        self.0.sign_simple(SEAL_CONTEXT, msg).to_bytes().to_vec()
+    }
 }
 
 pub fn to_transcript(t: VRFTranscript) -> merlin::Transcript {
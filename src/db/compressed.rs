@@ -0,0 +1,119 @@
+use super::*;
+use std::collections::HashSet;
+use std::io::Read;
+
+// Every zstd frame starts with these bytes, values without them were written
+// before compression was enabled and are returned as they are
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const DICTIONARY_KEY: &str = "compression_dictionary";
+
+// Transparently compresses the values of the given key namespaces. The
+// dictionary is kept in the underlying store and is never replaced, since the
+// values compressed with it can't be read without it
+pub struct CompressedKvStore<K: KvStore> {
+    store: K,
+    namespaces: HashSet<String>,
+    level: i32,
+    dictionary: Option<Vec<u8>>,
+}
+
+impl<K: KvStore> CompressedKvStore<K> {
+    pub fn new(store: K, namespaces: &[&str], level: i32) -> Result<Self, KvStoreError> {
+        let dictionary = store.get(DICTIONARY_KEY.into())?.map(|b| b.0);
+        Ok(Self {
+            store,
+            namespaces: namespaces.iter().map(|ns| ns.to_string()).collect(),
+            level,
+            dictionary,
+        })
+    }
+    pub fn into_inner(self) -> K {
+        self.store
+    }
+    pub fn has_dictionary(&self) -> bool {
+        self.dictionary.is_some()
+    }
+    // Returns false if the store already has a dictionary
+    pub fn train_dictionary(
+        &mut self,
+        samples: &[Vec<u8>],
+        max_size: usize,
+    ) -> Result<bool, KvStoreError> {
+        if self.has_dictionary() {
+            return Ok(false);
+        }
+        let dictionary = zstd::dict::from_samples(samples, max_size)?;
+        self.store.update(&vec![WriteOp::Put(
+            DICTIONARY_KEY.into(),
+            Blob(dictionary.clone()),
+        )])?;
+        self.dictionary = Some(dictionary);
+        Ok(true)
+    }
+    fn is_compressed(&self, k: &StringKey) -> bool {
+        let namespace = k.0.split('_').next().unwrap_or(&k.0);
+        self.namespaces.contains(namespace)
+    }
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>, KvStoreError> {
+        Ok(match &self.dictionary {
+            Some(dict) => {
+                zstd::bulk::Compressor::with_dictionary(self.level, dict)?.compress(data)?
+            }
+            None => zstd::bulk::compress(data, self.level)?,
+        })
+    }
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, KvStoreError> {
+        Ok(match &self.dictionary {
+            Some(dict) => {
+                let mut decoded = Vec::new();
+                zstd::stream::read::Decoder::with_dictionary(data, dict)?
+                    .read_to_end(&mut decoded)?;
+                decoded
+            }
+            None => zstd::stream::decode_all(data)?,
+        })
+    }
+}
+
+impl<K: KvStore> KvStore for CompressedKvStore<K> {
+    fn get(&self, k: StringKey) -> Result<Option<Blob>, KvStoreError> {
+        let compressed = self.is_compressed(&k);
+        Ok(match self.store.get(k)? {
+            Some(b) if compressed && b.0.starts_with(&ZSTD_MAGIC) => {
+                Some(Blob(self.decompress(&b.0)?))
+            }
+            v => v,
+        })
+    }
+    fn update(&mut self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError> {
+        let mut compressed_ops = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            compressed_ops.push(match op {
+                WriteOp::Put(k, v) if self.is_compressed(k) => {
+                    WriteOp::Put(k.clone(), Blob(self.compress(&v.0)?))
+                }
+                op => op.clone(),
+            });
+        }
+        self.store.update(&compressed_ops)
+    }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        let mut pairs = self.store.pairs(prefix)?;
+        for (k, v) in pairs.iter_mut() {
+            if self.is_compressed(k) && v.0.starts_with(&ZSTD_MAGIC) {
+                *v = Blob(self.decompress(&v.0)?);
+            }
+        }
+        Ok(pairs)
+    }
+    // Sizes are reported as stored, i.e. compressed
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        self.store.stats()
+    }
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        self.store.compact()
+    }
+    fn recover(&mut self) -> Result<(), KvStoreError> {
+        self.store.recover()
+    }
+}
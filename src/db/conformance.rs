@@ -0,0 +1,232 @@
+// Backend-agnostic tests of the KvStore contract. Every backend should pass
+// all of them, so that they stay interchangeable.
+
+use super::*;
+
+fn key(s: &str) -> StringKey {
+    s.into()
+}
+
+fn blob(n: u64) -> Blob {
+    n.into()
+}
+
+fn value<K: KvStore>(store: &K, k: &str) -> Option<u64> {
+    store.get(key(k)).unwrap().map(|b| b.try_into().unwrap())
+}
+
+fn read_your_writes<K: KvStore>(store: &mut K) {
+    assert_eq!(value(store, "a"), None);
+    store
+        .update(&vec![WriteOp::Put(key("a"), blob(1))])
+        .unwrap();
+    assert_eq!(value(store, "a"), Some(1));
+    store.update(&vec![WriteOp::Remove(key("a"))]).unwrap();
+    assert_eq!(value(store, "a"), None);
+    // Removing a missing key is not an error
+    store.update(&vec![WriteOp::Remove(key("a"))]).unwrap();
+}
+
+fn overwrite<K: KvStore>(store: &mut K) {
+    store
+        .update(&vec![WriteOp::Put(key("b"), blob(1))])
+        .unwrap();
+    store
+        .update(&vec![WriteOp::Put(key("b"), blob(2))])
+        .unwrap();
+    assert_eq!(value(store, "b"), Some(2));
+}
+
+// All ops of a batch are applied, in order
+fn batch<K: KvStore>(store: &mut K) {
+    store
+        .update(&vec![
+            WriteOp::Put(key("c"), blob(1)),
+            WriteOp::Put(key("d"), blob(2)),
+            WriteOp::Put(key("c"), blob(3)),
+            WriteOp::Put(key("e"), blob(4)),
+            WriteOp::Remove(key("e")),
+        ])
+        .unwrap();
+    assert_eq!(value(store, "c"), Some(3));
+    assert_eq!(value(store, "d"), Some(2));
+    assert_eq!(value(store, "e"), None);
+}
+
+// Applying the rollback of a batch brings back the exact previous state
+fn rollback<K: KvStore>(store: &mut K) {
+    store
+        .update(&vec![
+            WriteOp::Put(key("f"), blob(1)),
+            WriteOp::Put(key("g"), blob(2)),
+        ])
+        .unwrap();
+    let ops = vec![
+        WriteOp::Put(key("f"), blob(10)),
+        WriteOp::Remove(key("g")),
+        WriteOp::Put(key("h"), blob(30)),
+    ];
+    let rollback = store.rollback_of(&ops).unwrap();
+    store.update(&ops).unwrap();
+    assert_eq!(value(store, "f"), Some(10));
+    assert_eq!(value(store, "g"), None);
+    assert_eq!(value(store, "h"), Some(30));
+    store.update(&rollback).unwrap();
+    assert_eq!(value(store, "f"), Some(1));
+    assert_eq!(value(store, "g"), Some(2));
+    assert_eq!(value(store, "h"), None);
+}
+
+fn large_values<K: KvStore>(store: &mut K) {
+    let large = Blob((0..4 * 1024 * 1024).map(|i| i as u8).collect());
+    store
+        .update(&vec![WriteOp::Put(key("large"), large.clone())])
+        .unwrap();
+    assert_eq!(store.get(key("large")).unwrap().unwrap().0, large.0);
+}
+
+fn prefix_pairs<K: KvStore>(store: &mut K) {
+    store
+        .update(&vec![
+            WriteOp::Put(key("p_2"), blob(2)),
+            WriteOp::Put(key("p_1"), blob(1)),
+            WriteOp::Put(key("p_3"), blob(3)),
+            WriteOp::Put(key("q_1"), blob(4)),
+            WriteOp::Remove(key("p_3")),
+        ])
+        .unwrap();
+    let pairs = store
+        .pairs(key("p_"))
+        .unwrap()
+        .into_iter()
+        .map(|(k, v)| (k.0, v.try_into().unwrap()))
+        .collect::<Vec<(String, u64)>>();
+    assert_eq!(pairs, vec![("p_1".into(), 1), ("p_2".into(), 2)]);
+    assert!(store.pairs(key("r_")).unwrap().is_empty());
+}
+
+fn conformance<K: KvStore>(mut store: K) {
+    read_your_writes(&mut store);
+    overwrite(&mut store);
+    batch(&mut store);
+    rollback(&mut store);
+    large_values(&mut store);
+    prefix_pairs(&mut store);
+}
+
+#[test]
+fn test_ram_conformance() {
+    conformance(RamKvStore::new());
+}
+
+#[test]
+fn test_lru_cache_conformance() {
+    conformance(LruCacheKvStore::new(RamKvStore::new(), 2));
+}
+
+#[test]
+fn test_ram_mirror_conformance() {
+    let base = RamKvStore::new();
+    conformance(RamMirrorKvStore::new(&base));
+}
+
+// Pending changes of the mirror shadow the pairs of the underlying store
+#[test]
+fn test_ram_mirror_pairs() {
+    let mut base = RamKvStore::new();
+    base.update(&vec![
+        WriteOp::Put(key("p_1"), blob(1)),
+        WriteOp::Put(key("p_2"), blob(2)),
+    ])
+    .unwrap();
+    let mut mirror = RamMirrorKvStore::new(&base);
+    mirror
+        .update(&vec![
+            WriteOp::Remove(key("p_1")),
+            WriteOp::Put(key("p_2"), blob(20)),
+            WriteOp::Put(key("p_3"), blob(3)),
+        ])
+        .unwrap();
+    let pairs = mirror
+        .pairs(key("p_"))
+        .unwrap()
+        .into_iter()
+        .map(|(k, v)| (k.0, v.try_into().unwrap()))
+        .collect::<Vec<(String, u64)>>();
+    assert_eq!(pairs, vec![("p_2".into(), 20), ("p_3".into(), 3)]);
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_leveldb_conformance() {
+    let path = std::env::temp_dir().join(format!("bazuka-kvstore-{}", rand::random::<u64>()));
+    conformance(LevelDbKvStore::new(&path));
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_compressed_conformance() {
+    let namespaces = ["a", "b", "c", "d", "e", "f", "g", "h", "large", "p"];
+    conformance(CompressedKvStore::new(RamKvStore::new(), &namespaces, 3).unwrap());
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_compressed_dictionary() {
+    let mut base = RamKvStore::new();
+    // Written before compression was enabled
+    base.update(&vec![WriteOp::Put(key("block_0000000000"), blob(1))])
+        .unwrap();
+    let mut store = CompressedKvStore::new(base, &["block"], 3).unwrap();
+    let samples: Vec<Vec<u8>> = (0..100u64).map(|i| blob(i).0).collect();
+    assert!(store.train_dictionary(&samples, 1024).unwrap());
+    assert!(!store.train_dictionary(&samples, 1024).unwrap());
+    store
+        .update(&vec![
+            WriteOp::Put(key("block_0000000001"), blob(2)),
+            WriteOp::Put(key("height"), blob(3)),
+        ])
+        .unwrap();
+    assert_eq!(value(&store, "block_0000000000"), Some(1));
+    assert_eq!(value(&store, "block_0000000001"), Some(2));
+    assert_eq!(value(&store, "height"), Some(3));
+
+    // The dictionary is loaded back when the store is reopened
+    let reopened = CompressedKvStore::new(store.into_inner(), &["block"], 3).unwrap();
+    assert!(reopened.has_dictionary());
+    assert_eq!(value(&reopened, "block_0000000001"), Some(2));
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_journaled_conformance() {
+    let path = std::env::temp_dir().join(format!("bazuka-journal-{}", rand::random::<u64>()));
+    conformance(JournaledKvStore::new(RamKvStore::new(), &path));
+    assert!(!path.exists());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_conformance() {
+    let path = std::env::temp_dir().join(format!("bazuka-mmap-{}", rand::random::<u64>()));
+    conformance(MmapKvStore::new(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stats_namespaces() {
+    let mut store = RamKvStore::new();
+    store
+        .update(&vec![
+            WriteOp::Put(key("block_0000000001"), blob(1)),
+            WriteOp::Put(key("block_0000000002"), blob(2)),
+            WriteOp::Put(key("height"), blob(3)),
+        ])
+        .unwrap();
+    let stats = store.stats().unwrap();
+    assert_eq!(stats.namespaces["block"].keys, 2);
+    assert_eq!(stats.namespaces["block"].bytes, 2 * (16 + 8));
+    assert_eq!(stats.namespaces["height"].keys, 1);
+    assert_eq!(stats.total_bytes(), 2 * (16 + 8) + 6 + 8);
+}
@@ -1,7 +1,9 @@
 use super::*;
 use leveldb::batch::Batch;
+use leveldb::compaction::Compaction;
 use leveldb::database::batch::Writebatch;
 use leveldb::database::Database;
+use leveldb::iterator::{Iterable, LevelDBIterator};
 use leveldb::kv::KV;
 use leveldb::options::{Options, ReadOptions, WriteOptions};
 use std::fs;
@@ -39,4 +41,25 @@ impl KvStore for LevelDbKvStore {
             Err(_) => Err(KvStoreError::Failure),
         }
     }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        Ok(self
+            .0
+            .iter(ReadOptions::new())
+            .from(&prefix)
+            .take_while(|(k, _)| k.0.starts_with(&prefix.0))
+            .map(|(k, v)| (k, Blob(v)))
+            .collect())
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        let mut stats = DbStats::default();
+        for (k, v) in self.0.iter(ReadOptions::new()) {
+            stats.add(&k.0, v.len());
+        }
+        Ok(stats)
+    }
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        // Keys are ASCII, so this range covers all of them
+        self.0.compact(&StringKey::new(""), &StringKey::new("\x7f"));
+        Ok(())
+    }
 }
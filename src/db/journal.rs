@@ -0,0 +1,125 @@
+use super::*;
+use crate::core::hash::Hash;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Makes multi-key updates atomic across crashes. The ops of an update are
+// first written (And synced) to a journal file, and the journal is removed
+// once the underlying store has applied them. An update interrupted by a
+// crash is applied again by `recover()`, which is harmless since the ops only
+// contain final values. A torn journal means the crash happened before the
+// store was touched, so it's simply discarded.
+//
+// Journal format: payload length (u64 LE) | hash of payload | payload
+pub struct JournaledKvStore<K: KvStore> {
+    store: K,
+    path: PathBuf,
+}
+
+impl<K: KvStore> JournaledKvStore<K> {
+    pub fn new(store: K, path: &Path) -> Self {
+        Self {
+            store,
+            path: path.to_path_buf(),
+        }
+    }
+    fn write_journal(&self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError> {
+        let payload = bincode::serialize(ops)?;
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(&(payload.len() as u64).to_le_bytes())?;
+        file.write_all(Hasher::hash(&payload).as_ref())?;
+        file.write_all(&payload)?;
+        file.sync_all()?;
+        Ok(())
+    }
+    fn read_journal(&self) -> Result<Option<Vec<WriteOp>>, KvStoreError> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let header = 8 + Hasher::LENGTH;
+        if data.len() < header {
+            return Ok(None);
+        }
+        let len = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+        let payload = &data[header..];
+        if payload.len() != len || Hasher::hash(payload).as_ref() != &data[8..header] {
+            return Ok(None);
+        }
+        Ok(Some(bincode::deserialize(payload)?))
+    }
+}
+
+impl<K: KvStore> KvStore for JournaledKvStore<K> {
+    fn get(&self, k: StringKey) -> Result<Option<Blob>, KvStoreError> {
+        self.store.get(k)
+    }
+    fn update(&mut self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError> {
+        self.write_journal(ops)?;
+        self.store.update(ops)?;
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        self.store.pairs(prefix)
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        self.store.stats()
+    }
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        self.store.compact()
+    }
+    fn recover(&mut self) -> Result<(), KvStoreError> {
+        self.store.recover()?;
+        if let Some(ops) = self.read_journal()? {
+            tracing::warn!(ops = ops.len(), "Replaying interrupted database writes");
+            self.store.update(&ops)?;
+        }
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_path() -> PathBuf {
+        std::env::temp_dir().join(format!("bazuka-journal-{}", rand::random::<u64>()))
+    }
+
+    #[test]
+    fn test_journal_recovery() {
+        let path = journal_path();
+        let ops = vec![
+            WriteOp::Put("block_0000000001".into(), 1u64.into()),
+            WriteOp::Put("height".into(), 2u64.into()),
+        ];
+
+        // Crashed after journaling, before the store was updated
+        let mut store = JournaledKvStore::new(RamKvStore::new(), &path);
+        store.write_journal(&ops).unwrap();
+        store.recover().unwrap();
+        assert!(!path.exists());
+        let height: u64 = store
+            .get("height".into())
+            .unwrap()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(height, 2);
+
+        // Crashed while journaling, the update is dropped
+        let mut store = JournaledKvStore::new(RamKvStore::new(), &path);
+        store.write_journal(&ops).unwrap();
+        let data = fs::read(&path).unwrap();
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        store.recover().unwrap();
+        assert!(!path.exists());
+        assert!(store.get("height".into()).unwrap().is_none());
+    }
+}
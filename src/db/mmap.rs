@@ -0,0 +1,346 @@
+use super::*;
+use crate::config;
+use crate::core::hash::Hash;
+use memmap2::Mmap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const OP_REMOVE: u8 = 0;
+const OP_PUT: u8 = 1;
+const HEADER_SIZE: usize = 8 + Hasher::LENGTH;
+// Compaction writes the live pairs in batches of about this size
+const COMPACTION_BATCH_SIZE: usize = 4 * 1024 * 1024;
+
+// Append-only log of update batches, read through a memory map. Only the
+// positions of the values are kept in memory, the values themselves are read
+// from the map. Overwritten values stay in the file until it's compacted, which
+// happens on its own once they take more space than the live ones.
+//
+// Batch format: payload length (u64 LE) | hash of payload | payload
+// Payload: (op (u8) | key length (u32 LE) | key | [value length (u32 LE) | value])*
+//
+// A batch is either entirely valid or, if it was torn by a crash, dropped along
+// with everything after it when the file is opened. So updates are atomic.
+pub struct MmapKvStore {
+    path: PathBuf,
+    file: File,
+    map: Option<Mmap>,
+    // Offsets and lengths of the values in the file
+    index: HashMap<String, (usize, usize)>,
+    size: usize,
+    garbage: usize,
+}
+
+impl MmapKvStore {
+    pub fn new(path: &Path) -> Result<Self, KvStoreError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut store = Self {
+            path: path.to_path_buf(),
+            file,
+            map: None,
+            index: HashMap::new(),
+            size: 0,
+            garbage: 0,
+        };
+        store.remap()?;
+        store.load()?;
+        Ok(store)
+    }
+
+    fn remap(&mut self) -> Result<(), KvStoreError> {
+        self.map = if self.file.metadata()?.len() > 0 {
+            Some(unsafe { Mmap::map(&self.file)? })
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    fn data(&self) -> &[u8] {
+        self.map.as_ref().map(|m| &m[..]).unwrap_or(&[])
+    }
+
+    // Rebuilds the index by replaying the batches, the file is cut at the
+    // first invalid one
+    fn load(&mut self) -> Result<(), KvStoreError> {
+        let mut pos = 0;
+        let mut ops = Vec::new();
+        {
+            let data = self.data();
+            while let Some((payload, next)) = Self::batch_at(data, pos) {
+                Self::decode(payload, pos + HEADER_SIZE, &mut ops)?;
+                pos = next;
+            }
+        }
+        if pos < self.data().len() {
+            tracing::warn!(
+                bytes = self.data().len() - pos,
+                "Dropping torn writes at the end of the database"
+            );
+            self.map = None;
+            self.file.set_len(pos as u64)?;
+            self.file.sync_all()?;
+            self.remap()?;
+        }
+        self.size = pos;
+        for (key, value) in ops {
+            self.apply(key, value);
+        }
+        Ok(())
+    }
+
+    fn batch_at(data: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+        let header = data.get(pos..pos + HEADER_SIZE)?;
+        let len = u64::from_le_bytes(header[..8].try_into().unwrap()) as usize;
+        let payload = data.get(pos + HEADER_SIZE..pos + HEADER_SIZE + len)?;
+        if Hasher::hash(payload).as_ref() != &header[8..] {
+            return None;
+        }
+        Some((payload, pos + HEADER_SIZE + len))
+    }
+
+    // Key and value position of each op of a batch, None for removals
+    fn decode(
+        payload: &[u8],
+        base: usize,
+        ops: &mut Vec<(String, Option<(usize, usize)>)>,
+    ) -> Result<(), KvStoreError> {
+        let read_u32 = |pos: usize| -> Result<usize, KvStoreError> {
+            Ok(u32::from_le_bytes(
+                payload
+                    .get(pos..pos + 4)
+                    .ok_or(KvStoreError::Failure)?
+                    .try_into()
+                    .unwrap(),
+            ) as usize)
+        };
+        let mut pos = 0;
+        while pos < payload.len() {
+            let op = payload[pos];
+            let key_len = read_u32(pos + 1)?;
+            let key = payload
+                .get(pos + 5..pos + 5 + key_len)
+                .ok_or(KvStoreError::Failure)?;
+            let key = String::from_utf8(key.to_vec()).map_err(|_| KvStoreError::Failure)?;
+            pos += 5 + key_len;
+            match op {
+                OP_REMOVE => ops.push((key, None)),
+                OP_PUT => {
+                    let value_len = read_u32(pos)?;
+                    if pos + 4 + value_len > payload.len() {
+                        return Err(KvStoreError::Failure);
+                    }
+                    ops.push((key, Some((base + pos + 4, value_len))));
+                    pos += 4 + value_len;
+                }
+                _ => {
+                    return Err(KvStoreError::Failure);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_op(payload: &mut Vec<u8>, key: &str, value: Option<&[u8]>) {
+        payload.push(if value.is_some() { OP_PUT } else { OP_REMOVE });
+        payload.extend((key.len() as u32).to_le_bytes());
+        payload.extend(key.as_bytes());
+        if let Some(value) = value {
+            payload.extend((value.len() as u32).to_le_bytes());
+            payload.extend(value);
+        }
+    }
+
+    fn encode(ops: &[WriteOp]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for op in ops {
+            let value = match op {
+                WriteOp::Remove(_) => None,
+                WriteOp::Put(_, value) => Some(&value.0[..]),
+            };
+            Self::encode_op(&mut payload, op.key().as_str(), value);
+        }
+        payload
+    }
+
+    fn write_batch<W: Write>(out: &mut W, payload: &[u8]) -> Result<usize, KvStoreError> {
+        out.write_all(&(payload.len() as u64).to_le_bytes())?;
+        out.write_all(Hasher::hash(payload).as_ref())?;
+        out.write_all(payload)?;
+        Ok(HEADER_SIZE + payload.len())
+    }
+
+    fn apply(&mut self, key: String, value: Option<(usize, usize)>) {
+        let previous = match value {
+            Some(value) => self.index.insert(key.clone(), value),
+            None => self.index.remove(&key),
+        };
+        if let Some((_, len)) = previous {
+            self.garbage += key.len() + len;
+        }
+        // The removal records themselves are garbage too
+        if value.is_none() {
+            self.garbage += key.len();
+        }
+    }
+
+    fn append(&mut self, ops: &[WriteOp]) -> Result<(), KvStoreError> {
+        let payload = Self::encode(ops);
+        let mut batch = Vec::with_capacity(HEADER_SIZE + payload.len());
+        Self::write_batch(&mut batch, &payload)?;
+        if let Err(e) = self
+            .file
+            .write_all(&batch)
+            .and_then(|_| self.file.sync_data())
+        {
+            // A partially written batch is cut off, or the next ones would be
+            // appended after it and dropped on reload
+            self.file.set_len(self.size as u64)?;
+            return Err(e.into());
+        }
+
+        let mut decoded = Vec::new();
+        Self::decode(&payload, self.size + HEADER_SIZE, &mut decoded)?;
+        self.size += batch.len();
+        self.remap()?;
+        for (key, value) in decoded {
+            self.apply(key, value);
+        }
+        Ok(())
+    }
+
+    fn value(&self, (offset, len): (usize, usize)) -> Blob {
+        Blob(self.data()[offset..offset + len].to_vec())
+    }
+
+    // Writes the live pairs into a new file, batch by batch, straight from the map
+    fn write_compacted(&self, path: &Path) -> Result<(), KvStoreError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let mut payload = Vec::new();
+        for (key, &(offset, len)) in self.index.iter() {
+            Self::encode_op(&mut payload, key, Some(&self.data()[offset..offset + len]));
+            if payload.len() >= COMPACTION_BATCH_SIZE {
+                Self::write_batch(&mut out, &payload)?;
+                payload.clear();
+            }
+        }
+        if !payload.is_empty() {
+            Self::write_batch(&mut out, &payload)?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    }
+
+    pub fn garbage(&self) -> usize {
+        self.garbage
+    }
+}
+
+impl KvStore for MmapKvStore {
+    fn get(&self, k: StringKey) -> Result<Option<Blob>, KvStoreError> {
+        Ok(self.index.get(k.as_str()).map(|pos| self.value(*pos)))
+    }
+    fn update(&mut self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        self.append(ops)?;
+        if self.garbage >= config::MMAP_COMPACTION_THRESHOLD && self.garbage * 2 > self.size {
+            // The update itself is already persisted, compaction is retried
+            // on the next one
+            if let Err(e) = self.compact() {
+                tracing::warn!(error = %e, "Could not compact the database");
+            }
+        }
+        Ok(())
+    }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        let mut pairs = self
+            .index
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix.as_str()))
+            .map(|(k, pos)| (StringKey::new(k), self.value(*pos)))
+            .collect::<Vec<_>>();
+        pairs.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        Ok(pairs)
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        let mut stats = DbStats::default();
+        for (k, (_, len)) in self.index.iter() {
+            stats.add(k, *len);
+        }
+        Ok(stats)
+    }
+    // Rewrites the live pairs into a fresh file, which then atomically replaces
+    // the old one. The old file stays mapped until then, so the store is still
+    // usable if any of it fails.
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        let tmp = self.path.with_extension("compact");
+        self.write_compacted(&tmp)?;
+        let mut compacted = MmapKvStore::new(&tmp)?;
+        fs::rename(&tmp, &self.path)?;
+        compacted.path = self.path.clone();
+        *self = compacted;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mmap_reopen() {
+        let path = std::env::temp_dir().join(format!("bazuka-mmap-{}", rand::random::<u64>()));
+        let mut store = MmapKvStore::new(&path).unwrap();
+        store
+            .update(&vec![
+                WriteOp::Put("a".into(), 1u64.into()),
+                WriteOp::Put("b".into(), 2u64.into()),
+            ])
+            .unwrap();
+        store
+            .update(&vec![
+                WriteOp::Put("a".into(), 3u64.into()),
+                WriteOp::Remove("b".into()),
+            ])
+            .unwrap();
+        assert!(store.garbage() > 0);
+        drop(store);
+
+        // A batch torn by a crash is dropped as a whole
+        let valid = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&100u64.to_le_bytes()).unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+        drop(file);
+
+        let value = |store: &MmapKvStore, k: &str| -> Option<u64> {
+            store.get(k.into()).unwrap().map(|b| b.try_into().unwrap())
+        };
+        let mut store = MmapKvStore::new(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), valid);
+        assert_eq!(value(&store, "a"), Some(3));
+        assert_eq!(value(&store, "b"), None);
+
+        store.compact().unwrap();
+        assert_eq!(store.garbage(), 0);
+        assert!(fs::metadata(&path).unwrap().len() < valid);
+        assert!(!path.with_extension("compact").exists());
+        // Later updates go to the compacted file
+        store
+            .update(&vec![WriteOp::Put("c".into(), 4u64.into())])
+            .unwrap();
+        drop(store);
+        let store = MmapKvStore::new(&path).unwrap();
+        assert_eq!(value(&store, "a"), Some(3));
+        assert_eq!(value(&store, "c"), Some(4));
+        assert_eq!(store.pairs("".into()).unwrap().len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
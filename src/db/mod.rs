@@ -1,4 +1,8 @@
-use crate::core::{Account, Block, Hasher};
+use crate::blockchain::ReorgRecord;
+use crate::core::{
+    Account, AccountV0, Block, BlockMetadata, Checkpoint, Contract, Hasher, LockedFunds, Proposal,
+    TransactionLocation, TxReceipt, Unbonding, Validator,
+};
 use crate::crypto::merkle::MerkleTree;
 use crate::zk::{ZkCompressedState, ZkStateData, ZkStateModel, ZkVerifierKey};
 use db_key::Key;
@@ -13,18 +17,23 @@ pub enum KvStoreError {
     Failure,
     #[error("kvstore data corrupted: {0}")]
     Corrupted(#[from] bincode::Error),
+    #[error("kvstore io failure: {0}")]
+    Io(#[from] std::io::Error),
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StringKey(String);
 
 impl StringKey {
     pub fn new(s: &str) -> StringKey {
         StringKey(s.to_string())
     }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Blob(Vec<u8>);
 
 macro_rules! gen_try_into {
@@ -53,11 +62,25 @@ macro_rules! gen_from {
 }
 
 gen_try_into!(
+    bool,
     u32,
     u64,
     usize,
+    Vec<u8>,
     Account,
+    AccountV0,
+    Vec<LockedFunds>,
     Block,
+    BlockMetadata,
+    Checkpoint,
+    ReorgRecord,
+    Contract,
+    Validator,
+    Vec<Validator>,
+    Unbonding,
+    Proposal,
+    TransactionLocation,
+    TxReceipt,
     Vec<WriteOp>,
     MerkleTree<Hasher>,
     ZkVerifierKey,
@@ -66,11 +89,25 @@ gen_try_into!(
     ZkCompressedState
 );
 gen_from!(
+    bool,
     u32,
     u64,
     usize,
+    Vec<u8>,
     Account,
+    AccountV0,
+    Vec<LockedFunds>,
     &Block,
+    BlockMetadata,
+    Checkpoint,
+    ReorgRecord,
+    Contract,
+    Validator,
+    Vec<Validator>,
+    Unbonding,
+    Proposal,
+    TransactionLocation,
+    TxReceipt,
     Vec<WriteOp>,
     MerkleTree<Hasher>,
     ZkVerifierKey,
@@ -106,9 +143,53 @@ pub enum WriteOp {
     Put(StringKey, Blob),
 }
 
+impl WriteOp {
+    pub fn key(&self) -> &StringKey {
+        match self {
+            WriteOp::Remove(k) | WriteOp::Put(k, _) => k,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NamespaceStats {
+    pub keys: usize,
+    pub bytes: usize,
+}
+
+// Approximate storage usage, keys are grouped by their namespace (The part of
+// the key before the first `_`, e.g. `block` or `account`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DbStats {
+    pub namespaces: HashMap<String, NamespaceStats>,
+}
+
+impl DbStats {
+    pub fn add(&mut self, key: &str, value_size: usize) {
+        let namespace = key.split('_').next().unwrap_or(key);
+        let stats = self.namespaces.entry(namespace.into()).or_default();
+        stats.keys += 1;
+        stats.bytes += key.len() + value_size;
+    }
+    pub fn total_bytes(&self) -> usize {
+        self.namespaces.values().map(|s| s.bytes).sum()
+    }
+}
+
 pub trait KvStore {
     fn get(&self, k: StringKey) -> Result<Option<Blob>, KvStoreError>;
     fn update(&mut self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError>;
+    // All of the key-value pairs whose keys start with the prefix, sorted by key
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError>;
+    fn stats(&self) -> Result<DbStats, KvStoreError>;
+    // Reclaims the space of the removed data, on backends that need it
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        Ok(())
+    }
+    // Finishes the updates interrupted by a crash, on backends that need it
+    fn recover(&mut self) -> Result<(), KvStoreError> {
+        Ok(())
+    }
     fn rollback_of(&self, ops: &Vec<WriteOp>) -> Result<Vec<WriteOp>, KvStoreError> {
         let mut rollback = Vec::new();
         for op in ops.iter() {
@@ -161,6 +242,19 @@ impl<K: KvStore> KvStore for LruCacheKvStore<K> {
         }
         self.store.update(ops)
     }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        self.store.pairs(prefix)
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        self.store.stats()
+    }
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        self.store.compact()
+    }
+    fn recover(&mut self) -> Result<(), KvStoreError> {
+        self.cache.clear();
+        self.store.recover()
+    }
 }
 
 pub struct RamMirrorKvStore<'a, K: KvStore> {
@@ -202,12 +296,53 @@ impl<'a, K: KvStore> KvStore for RamMirrorKvStore<'a, K> {
         }
         Ok(())
     }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        let mut pairs = self
+            .store
+            .pairs(prefix.clone())?
+            .into_iter()
+            .map(|(k, v)| (k.0, Some(v)))
+            .collect::<HashMap<_, _>>();
+        for (k, v) in self.overwrite.iter() {
+            if k.starts_with(&prefix.0) {
+                pairs.insert(k.clone(), v.clone());
+            }
+        }
+        let mut pairs = pairs
+            .into_iter()
+            .filter_map(|(k, v)| v.map(|v| (StringKey(k), v)))
+            .collect::<Vec<_>>();
+        pairs.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        Ok(pairs)
+    }
+    // Usage of the underlying store, the pending changes are not included
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        self.store.stats()
+    }
 }
 
 mod ram;
 pub use ram::*;
 
+#[cfg(test)]
+mod conformance;
+
 #[cfg(feature = "node")]
 mod disk;
 #[cfg(feature = "node")]
 pub use disk::*;
+
+#[cfg(feature = "node")]
+mod journal;
+#[cfg(feature = "node")]
+pub use journal::*;
+
+#[cfg(feature = "node")]
+mod compressed;
+#[cfg(feature = "node")]
+pub use compressed::*;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use mmap::*;
@@ -21,4 +21,21 @@ impl KvStore for RamKvStore {
         }
         Ok(())
     }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        let mut pairs = self
+            .0
+            .iter()
+            .filter(|(k, _)| k.starts_with(&prefix.0))
+            .map(|(k, v)| (StringKey(k.clone()), v.clone()))
+            .collect::<Vec<_>>();
+        pairs.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        Ok(pairs)
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        let mut stats = DbStats::default();
+        for (k, v) in self.0.iter() {
+            stats.add(k, v.0.len());
+        }
+        Ok(stats)
+    }
 }
@@ -13,3 +13,6 @@ pub mod zk;
 
 #[cfg(feature = "node")]
 pub mod node;
+
+#[cfg(feature = "node")]
+pub mod tools;
@@ -3,20 +3,25 @@ extern crate lazy_static;
 
 #[cfg(feature = "node")]
 use {
-    bazuka::blockchain::KvStoreChain,
-    bazuka::db::{LevelDbKvStore, LruCacheKvStore},
-    bazuka::node::{Node, NodeError, PeerAddress},
+    bazuka::blockchain::{Blockchain, KvStoreChain, PayoutShare, PayoutSplit, VerifyLevel},
+    bazuka::config,
+    bazuka::core::Address,
+    bazuka::db::{
+        Blob, CompressedKvStore, DbStats, JournaledKvStore, KvStore, KvStoreError, LevelDbKvStore,
+        LruCacheKvStore, MmapKvStore, StringKey, WriteOp,
+    },
+    bazuka::node::{AnchorPeers, Node, NodeError, NodeRole, PeerAddress, RelayConfig},
     bazuka::wallet::Wallet,
     std::path::{Path, PathBuf},
     structopt::StructOpt,
+    tracing_subscriber::EnvFilter,
 };
 
 #[cfg(not(feature = "node"))]
 use {
     bazuka::blockchain::Blockchain,
-    bazuka::blockchain::KvStoreChain,
+    bazuka::blockchain::{KvStoreChain, PayoutSplit},
     bazuka::core::Address,
-    bazuka::core::{Signature, Transaction, TransactionData},
     bazuka::db::RamKvStore,
     bazuka::wallet::Wallet,
 };
@@ -31,57 +36,241 @@ struct NodeOptions {
     port: Option<u16>,
     #[structopt(long, parse(from_os_str))]
     db: Option<PathBuf>,
+    // Keep the database in a memory-mapped file instead of LevelDB
+    #[structopt(long)]
+    mmap_db: bool,
     #[structopt(long)]
     bootstrap: Vec<String>,
+    #[structopt(long)]
+    anchor: Vec<String>,
+    #[structopt(long, parse(from_os_str))]
+    anchors_file: Option<PathBuf>,
+    // Split block rewards, e.g. --payout 0x...:90 --payout 0x...:10
+    #[structopt(long)]
+    payout: Vec<PayoutShare>,
+    // Key (In hex) shared between a block producing node and its API nodes
+    #[structopt(long)]
+    relay_key: Option<String>,
+    // API nodes the produced blocks are forwarded to
+    #[structopt(long)]
+    relay_to: Vec<String>,
+    // Token the chain-modifying admin endpoints (Rewind, invalidate) expect in
+    // the x-bazuka-admin-token header, they are disabled without it
+    #[structopt(long)]
+    admin_token: Option<String>,
+    // archival, pruned, miner, api-only or relay
+    #[structopt(long)]
+    role: Option<NodeRole>,
+    // Contract operators whose Update transactions get a reserved slice of the
+    // produced blocks
+    #[structopt(long)]
+    aggregator: Vec<Address>,
+    // Log filter directives (e.g. "info,bazuka::node=debug"), RUST_LOG is
+    // used when not given
+    #[structopt(long)]
+    log_filter: Option<String>,
+    // Log JSON lines, for log aggregation
+    #[structopt(long)]
+    log_json: bool,
+    // Checks the database (headers, merkle or full) and exits instead of
+    // running the node
+    #[structopt(long)]
+    verify_chain: Option<VerifyLevel>,
+}
+
+#[cfg(feature = "node")]
+fn parse_peer(s: &str) -> PeerAddress {
+    let mut parts = s.splitn(2, ":");
+    let host = parts.next().unwrap();
+    let port = parts.next().unwrap();
+    PeerAddress(host.parse().unwrap(), port.parse().unwrap())
 }
 
 lazy_static! {
     static ref WALLET: Wallet = Wallet::new(b"random seed".to_vec());
 }
 
+// Storage backend picked by the node options
+#[cfg(feature = "node")]
+enum NodeKvStore {
+    LevelDb(LevelDbKvStore),
+    Mmap(MmapKvStore),
+}
+
+#[cfg(feature = "node")]
+impl KvStore for NodeKvStore {
+    fn get(&self, k: StringKey) -> Result<Option<Blob>, KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.get(k),
+            NodeKvStore::Mmap(db) => db.get(k),
+        }
+    }
+    fn update(&mut self, ops: &Vec<WriteOp>) -> Result<(), KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.update(ops),
+            NodeKvStore::Mmap(db) => db.update(ops),
+        }
+    }
+    fn pairs(&self, prefix: StringKey) -> Result<Vec<(StringKey, Blob)>, KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.pairs(prefix),
+            NodeKvStore::Mmap(db) => db.pairs(prefix),
+        }
+    }
+    fn stats(&self) -> Result<DbStats, KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.stats(),
+            NodeKvStore::Mmap(db) => db.stats(),
+        }
+    }
+    fn compact(&mut self) -> Result<(), KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.compact(),
+            NodeKvStore::Mmap(db) => db.compact(),
+        }
+    }
+    fn recover(&mut self) -> Result<(), KvStoreError> {
+        match self {
+            NodeKvStore::LevelDb(db) => db.recover(),
+            NodeKvStore::Mmap(db) => db.recover(),
+        }
+    }
+}
+
+// Dictionary of a fresh database is trained on typical (Regular-send)
+// transactions, since they make up most of the block bodies
+#[cfg(feature = "node")]
+fn open_db(path: &Path, mmap: bool) -> JournaledKvStore<CompressedKvStore<NodeKvStore>> {
+    let store = if mmap {
+        NodeKvStore::Mmap(MmapKvStore::new(&path.with_extension("mmap")).unwrap())
+    } else {
+        NodeKvStore::LevelDb(LevelDbKvStore::new(path))
+    };
+    let mut db = CompressedKvStore::new(
+        store,
+        config::COMPRESSED_NAMESPACES,
+        config::COMPRESSION_LEVEL,
+    )
+    .unwrap();
+    if !db.has_dictionary() {
+        let samples = (0..1024u64)
+            .map(|i| {
+                let mut src = Wallet::new(i.to_le_bytes().to_vec());
+                let dst = Wallet::new((i + 1).to_le_bytes().to_vec()).get_address();
+                let tx = src.create_transaction(dst, (i + 1) * 1_000000, i % 10 + 1);
+                bincode::serialize(&tx).unwrap()
+            })
+            .collect::<Vec<_>>();
+        db.train_dictionary(&samples, config::COMPRESSION_DICTIONARY_SIZE)
+            .unwrap();
+    }
+    JournaledKvStore::new(db, &path.with_extension("journal"))
+}
+
+#[cfg(feature = "node")]
+type NodeChain = KvStoreChain<LruCacheKvStore<JournaledKvStore<CompressedKvStore<NodeKvStore>>>>;
+
+#[cfg(feature = "node")]
+fn open_chain(opts: &NodeOptions) -> NodeChain {
+    let role = opts.role.unwrap_or_default();
+    let chain = KvStoreChain::open(
+        LruCacheKvStore::new(
+            open_db(
+                &opts
+                    .db
+                    .clone()
+                    .unwrap_or(home::home_dir().unwrap().join(Path::new(".bazuka"))),
+                opts.mmap_db,
+            ),
+            64,
+        ),
+        role.keeps_indexes(),
+    )
+    .unwrap();
+    let chain = chain.with_aggregators(opts.aggregator.clone());
+    if role.prunes() {
+        chain.with_pruning(config::PRUNE_DEPTH)
+    } else {
+        chain
+    }
+}
+
 #[cfg(feature = "node")]
 lazy_static! {
     static ref OPTS: NodeOptions = NodeOptions::from_args();
-    static ref NODE: Node<KvStoreChain<LruCacheKvStore<LevelDbKvStore>>> = {
+    static ref NODE: Node<NodeChain> = {
         let opts = OPTS.clone();
+        let role = opts.role.unwrap_or_default();
         Node::new(
             PeerAddress(
                 opts.host
+                    .clone()
                     .unwrap_or("127.0.0.1".to_string())
                     .parse()
                     .unwrap(),
                 opts.port.unwrap_or(3030),
             ),
-            opts.bootstrap
-                .clone()
-                .into_iter()
-                .map(|b| {
-                    let mut parts = b.splitn(2, ":");
-                    let host = parts.next().unwrap();
-                    let port = parts.next().unwrap();
-                    PeerAddress(host.parse().unwrap(), port.parse().unwrap())
-                })
-                .collect(),
-            KvStoreChain::new(LruCacheKvStore::new(
-                LevelDbKvStore::new(
-                    &opts
-                        .db
-                        .unwrap_or(home::home_dir().unwrap().join(Path::new(".bazuka"))),
-                ),
-                64,
-            ))
-            .unwrap(),
+            opts.bootstrap.iter().map(|b| parse_peer(b)).collect(),
+            open_chain(&opts),
             Some(WALLET.clone()),
+            {
+                let mut anchors = AnchorPeers::load(
+                    opts.anchors_file.unwrap_or(
+                        home::home_dir()
+                            .unwrap()
+                            .join(Path::new(".bazuka-anchors.json")),
+                    ),
+                )
+                .unwrap();
+                for a in opts.anchor.iter() {
+                    anchors.add(parse_peer(a));
+                }
+                anchors.save().unwrap();
+                anchors
+            },
+            if opts.payout.is_empty() {
+                None
+            } else {
+                Some(PayoutSplit::new(opts.payout.clone()).unwrap())
+            },
+            opts.relay_key.map(|key| RelayConfig {
+                key: hex::decode(key).unwrap(),
+                targets: opts.relay_to.iter().map(|t| parse_peer(t)).collect(),
+            }),
+            opts.admin_token,
+            role,
         )
     };
 }
 
+#[cfg(feature = "node")]
+fn init_logging(opts: &NodeOptions) {
+    let filter = match &opts.log_filter {
+        Some(directives) => EnvFilter::new(directives),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if opts.log_json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
+
 #[cfg(feature = "node")]
 #[tokio::main]
 async fn main() -> Result<(), NodeError> {
-    println!(
-        "Public Ip: {:?}",
-        bazuka::node::upnp::get_public_ip().await.ok()
+    init_logging(&OPTS);
+
+    if let Some(level) = OPTS.verify_chain {
+        let report = open_chain(&OPTS).verify_chain(level)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        std::process::exit(if report.inconsistency.is_some() { 1 } else { 0 });
+    }
+
+    tracing::info!(
+        public_ip = ?bazuka::node::upnp::get_public_ip().await.ok(),
+        "Starting node"
     );
 
     NODE.run().await?;
@@ -103,19 +292,12 @@ fn main() {
     chain
         .draft_block(
             0,
-            &vec![Transaction {
-                src: Address::Treasury,
-                data: TransactionData::RegularSend {
-                    dst: "0x215d9af3a1bfa2a87929b6e8265e95c61c36f91493f3dbd702215255f68742552"
-                        .parse()
-                        .unwrap(),
-                    amount: 123,
-                },
-                nonce: 1,
-                fee: 0,
-                sig: Signature::Unsigned,
-            }],
-            &WALLET,
+            &vec![],
+            &PayoutSplit::single(
+                "0x215d9af3a1bfa2a87929b6e8265e95c61c36f91493f3dbd702215255f68742552"
+                    .parse()
+                    .unwrap(),
+            ),
         )
         .unwrap();
 
@@ -125,6 +307,9 @@ fn main() {
         chain.get_account(WALLET.get_address()).unwrap()
     );
 
-    let tx = WALLET.create_transaction(Address::Treasury, 123, 0);
+    let mut wallet = WALLET.clone();
+    wallet.update_account(&chain.get_account(wallet.get_address()).unwrap());
+    wallet.update_locked(chain.get_locked_balance(wallet.get_address()).unwrap());
+    let tx = wallet.create_transaction(Address::Treasury, 123, 0);
     println!("Verify tx signature: {}", tx.verify_signature());
 }
@@ -0,0 +1,156 @@
+use super::{NodeError, PeerAddress, Timestamp};
+use crate::config;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct AnchorPeer {
+    pub failures: u32,
+    pub retry_at: Timestamp,
+}
+
+// A small set of outbound peers the node always tries to stay connected to,
+// (With exponential backoff) so that it can't get completely isolated when the
+// gossip-discovered peers churn away. Anchors are persisted across restarts.
+#[derive(Default)]
+pub struct AnchorPeers {
+    path: Option<PathBuf>,
+    peers: HashMap<PeerAddress, AnchorPeer>,
+}
+
+impl AnchorPeers {
+    pub fn load(path: PathBuf) -> Result<Self, NodeError> {
+        let addrs: Vec<PeerAddress> = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)?
+        } else {
+            Vec::new()
+        };
+        let mut anchors = Self {
+            path: Some(path),
+            peers: HashMap::new(),
+        };
+        for addr in addrs {
+            anchors.add(addr);
+        }
+        Ok(anchors)
+    }
+    pub fn save(&self) -> Result<(), NodeError> {
+        if let Some(path) = &self.path {
+            let addrs = self.peers.keys().cloned().collect::<Vec<_>>();
+            std::fs::write(path, serde_json::to_vec(&addrs)?)?;
+        }
+        Ok(())
+    }
+    pub fn is_full(&self) -> bool {
+        self.peers.len() >= config::MAX_ANCHOR_PEERS
+    }
+    pub fn contains(&self, addr: &PeerAddress) -> bool {
+        self.peers.contains_key(addr)
+    }
+    pub fn add(&mut self, addr: PeerAddress) -> bool {
+        if self.is_full() || self.contains(&addr) {
+            return false;
+        }
+        self.peers.insert(
+            addr,
+            AnchorPeer {
+                failures: 0,
+                retry_at: 0,
+            },
+        );
+        true
+    }
+    pub fn addresses(&self) -> Vec<PeerAddress> {
+        self.peers.keys().cloned().collect()
+    }
+    // Anchors whose backoff period has passed
+    pub fn due(&self, now: Timestamp) -> Vec<PeerAddress> {
+        self.peers
+            .iter()
+            .filter(|(_, a)| a.retry_at <= now)
+            .map(|(addr, _)| addr.clone())
+            .collect()
+    }
+    pub fn mark_success(&mut self, addr: &PeerAddress, now: Timestamp) {
+        if let Some(anchor) = self.peers.get_mut(addr) {
+            anchor.failures = 0;
+            anchor.retry_at = now + config::ANCHOR_RETRY_BASE;
+        }
+    }
+    pub fn mark_failure(&mut self, addr: &PeerAddress, now: Timestamp) {
+        if let Some(anchor) = self.peers.get_mut(addr) {
+            anchor.failures += 1;
+            let backoff = config::ANCHOR_RETRY_BASE << std::cmp::min(anchor.failures, 16);
+            anchor.retry_at = now + std::cmp::min(backoff, config::ANCHOR_RETRY_MAX);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> PeerAddress {
+        PeerAddress("127.0.0.1".parse().unwrap(), port)
+    }
+
+    #[test]
+    fn test_anchor_backoff() {
+        let mut anchors = AnchorPeers::default();
+        assert!(anchors.add(peer(1)));
+        assert!(!anchors.add(peer(1)));
+        assert_eq!(anchors.due(0), vec![peer(1)]);
+
+        anchors.mark_failure(&peer(1), 100);
+        assert!(anchors.due(100).is_empty());
+        assert_eq!(
+            anchors.due(100 + 2 * config::ANCHOR_RETRY_BASE),
+            vec![peer(1)]
+        );
+        anchors.mark_failure(&peer(1), 100);
+        assert!(anchors.due(100 + 2 * config::ANCHOR_RETRY_BASE).is_empty());
+        assert_eq!(
+            anchors.due(100 + 4 * config::ANCHOR_RETRY_BASE),
+            vec![peer(1)]
+        );
+
+        // The backoff never exceeds the maximum, however many the failures
+        for _ in 0..100 {
+            anchors.mark_failure(&peer(1), 100);
+        }
+        assert_eq!(anchors.due(100 + config::ANCHOR_RETRY_MAX), vec![peer(1)]);
+
+        // A success resets the backoff
+        anchors.mark_success(&peer(1), 100);
+        anchors.mark_failure(&peer(1), 100);
+        assert_eq!(
+            anchors.due(100 + 2 * config::ANCHOR_RETRY_BASE),
+            vec![peer(1)]
+        );
+    }
+
+    #[test]
+    fn test_anchor_limit_and_persistence() {
+        let path = std::env::temp_dir().join(format!("bazuka-anchors-{}", rand::random::<u64>()));
+        let mut anchors = AnchorPeers::load(path.clone()).unwrap();
+        for port in 0..config::MAX_ANCHOR_PEERS as u16 {
+            assert!(anchors.add(peer(port)));
+        }
+        assert!(anchors.is_full());
+        assert!(!anchors.add(peer(1000)));
+        anchors.save().unwrap();
+
+        let mut loaded = AnchorPeers::load(path.clone()).unwrap();
+        let mut addrs = loaded.addresses();
+        addrs.sort_by_key(|a| a.1);
+        assert_eq!(addrs.len(), config::MAX_ANCHOR_PEERS);
+        assert!(addrs.iter().enumerate().all(|(i, a)| *a == peer(i as u16)));
+        assert!(!loaded.contains(&peer(1000)));
+        // Loaded anchors are tried right away
+        assert_eq!(loaded.due(0).len(), config::MAX_ANCHOR_PEERS);
+        loaded.mark_failure(&peer(0), 0);
+        assert_eq!(loaded.due(0).len(), config::MAX_ANCHOR_PEERS - 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
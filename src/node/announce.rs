@@ -0,0 +1,227 @@
+use super::api::messages::{
+    GetBlocksRequest, GetBlocksResponse, PostAnnounceRequest, PostAnnounceResponse,
+};
+use super::{http, NodeContext, NodeError, PeerAddress};
+use crate::blockchain::Blockchain;
+use crate::config::{punish, ANNOUNCE_TIMEOUT};
+use crate::core::Header;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+const EVENT_QUEUE_SIZE: usize = 64;
+
+pub enum AnnounceEvent {
+    // A new tip has been accepted by the chain actor
+    Tip(Header),
+    // A peer has announced its new tip
+    Received { from: PeerAddress, header: Header },
+}
+
+// Announcements are sent and handled by their own actor, so that neither the
+// chain actor nor the announcing peers have to wait for the network.
+#[derive(Clone)]
+pub struct AnnounceHandle {
+    sender: mpsc::Sender<AnnounceEvent>,
+}
+
+impl AnnounceHandle {
+    pub fn new() -> (Self, mpsc::Receiver<AnnounceEvent>) {
+        let (sender, receiver) = mpsc::channel(EVENT_QUEUE_SIZE);
+        (Self { sender }, receiver)
+    }
+    // Announcements are best-effort, they are dropped when the queue is full
+    // and the peers will catch up by polling
+    pub fn send(&self, event: AnnounceEvent) {
+        let _ = self.sender.try_send(event);
+    }
+}
+
+async fn announce_tip<B: Blockchain>(
+    address: PeerAddress,
+    context: &NodeContext<B>,
+    header: Header,
+) {
+    let peers = context.active_peers().await.into_keys().collect::<Vec<_>>();
+    let req = PostAnnounceRequest { address, header };
+    http::group_request(&peers, |peer| {
+        timeout(
+            Duration::from_secs(ANNOUNCE_TIMEOUT),
+            http::bincode_post::<PostAnnounceRequest, PostAnnounceResponse>(
+                format!("{}/bincode/announce", peer).to_string(),
+                req.clone(),
+            ),
+        )
+    })
+    .await;
+}
+
+// Pulls the body of an announced block that directly extends our chain. Blocks
+// further ahead are left to the poller, claiming them costs nothing.
+async fn handle_announcement<B: Blockchain>(
+    context: &NodeContext<B>,
+    from: PeerAddress,
+    header: Header,
+) -> Result<(), NodeError> {
+    let hash = header.hash();
+    if context.known_invalid(std::iter::once(hash)).await.is_some() {
+        context.misbehave(from, punish::INVALID_BLOCK_SCORE).await;
+        return Ok(());
+    }
+    let height = context.blockchain.read().await.get_height()?;
+    let number = header.number as usize;
+    if number != height {
+        return Ok(());
+    }
+    let will_extend = context
+        .blockchain
+        .read()
+        .await
+        .will_extend(height, &vec![header.clone()]);
+    match will_extend {
+        Ok(true) => {
+            context.add_verified_header(&header).await;
+        }
+        Ok(false) => {
+            return Ok(());
+        }
+        Err(e) => {
+            if e.is_invalid_block() {
+                if e.is_context_free() {
+                    context.mark_invalid(hash, e.to_string()).await;
+                }
+                context.misbehave(from, punish::INVALID_BLOCK_SCORE).await;
+            }
+            return Err(e.into());
+        }
+    }
+    let resp = timeout(
+        Duration::from_secs(ANNOUNCE_TIMEOUT),
+        http::bincode_get::<GetBlocksRequest, GetBlocksResponse>(
+            format!("{}/bincode/blocks", from).to_string(),
+            GetBlocksRequest {
+                since: number,
+                until: Some(number + 1),
+            },
+        ),
+    )
+    .await;
+    match resp {
+        Ok(Ok(resp)) if resp.blocks.len() == 1 && resp.blocks[0].header.hash() == hash => {
+            match context.chain.extend_from(from, height, resp.blocks).await {
+                Err(NodeError::BlockchainError(e)) if e.is_invalid_block() => {
+                    if e.is_context_free() {
+                        context.mark_invalid(hash, e.to_string()).await;
+                    }
+                    context.misbehave(from, punish::INVALID_BLOCK_SCORE).await;
+                }
+                res => res?,
+            }
+        }
+        _ => {
+            // Let the poller find another source
+            context.blocks_polled_at.store(0, Ordering::Relaxed);
+        }
+    }
+    Ok(())
+}
+
+pub async fn announcer<B: Blockchain>(
+    address: PeerAddress,
+    context: Arc<NodeContext<B>>,
+    mut events: mpsc::Receiver<AnnounceEvent>,
+) -> Result<(), NodeError> {
+    while let Some(event) = events.recv().await {
+        match event {
+            AnnounceEvent::Tip(header) => {
+                announce_tip(address, &context, header).await;
+            }
+            AnnounceEvent::Received { from, header } => {
+                tracing::debug!(peer = %from, height = header.number, "Block announced");
+                if let Err(e) = handle_announcement(&context, from, header).await {
+                    tracing::warn!(peer = %from, error = %e, "Could not handle block announcement");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::api::{self, messages::PostAnnounceRequest};
+    use super::super::tests::test_node;
+    use super::super::PeerStats;
+    use super::*;
+    use crate::config::genesis;
+
+    #[tokio::test]
+    async fn test_announcement_sources() {
+        let node = test_node();
+        let mut events = node.announce_events.lock().unwrap().take().unwrap();
+        let peer = PeerAddress("10.0.0.1".parse().unwrap(), 3030);
+        let announce = |client: &str| {
+            api::post_announce(
+                Arc::clone(&node.context),
+                client.parse().unwrap(),
+                PostAnnounceRequest {
+                    address: peer,
+                    header: genesis::get_genesis_block().header,
+                },
+            )
+        };
+
+        // Only known peers are listened to
+        announce("10.0.0.1").await.unwrap();
+        assert!(events.try_recv().is_err());
+        node.context.peers.write().await.insert(
+            peer,
+            PeerStats {
+                punished_until: 0,
+                info: None,
+                last_seen: 0,
+                misbehavior: 0,
+                banned_until: 0,
+                added: 0,
+                announced_at: 0,
+            },
+        );
+
+        // Another host can't make us fetch from the peer
+        announce("10.0.0.2").await.unwrap();
+        assert!(events.try_recv().is_err());
+        assert!(node
+            .context
+            .ip_stats
+            .read()
+            .await
+            .contains_key(&"10.0.0.2".parse().unwrap()));
+
+        announce("10.0.0.1").await.unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(AnnounceEvent::Received { from, .. }) if from == peer
+        ));
+
+        // Too soon after the previous one
+        announce("10.0.0.1").await.unwrap();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_announced_future_block() {
+        let context = test_node().context;
+        context.blocks_polled_at.store(1234, Ordering::Relaxed);
+        let mut header = genesis::get_genesis_block().header;
+        header.number = 100;
+        handle_announcement(
+            &context,
+            PeerAddress("10.0.0.1".parse().unwrap(), 3030),
+            header,
+        )
+        .await
+        .unwrap();
+        assert_eq!(context.blocks_polled_at.load(Ordering::Relaxed), 1234);
+    }
+}
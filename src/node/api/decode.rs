@@ -0,0 +1,107 @@
+use super::messages::{
+    DecodeError, PostDecodeBlockResponse, PostDecodeRequest, PostDecodeTxResponse,
+};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::{Block, Hasher, Transaction};
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use std::sync::Arc;
+
+// Keeps track of how far the decoder got
+struct CountingReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = std::cmp::min(buf.len(), self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Decodes with the same options as the wire format. On failure, the offset is
+// where the decoder stopped, i.e. right after the offending bytes.
+fn decode<T: DeserializeOwned>(raw: &[u8]) -> Result<T, DecodeError> {
+    let mut reader = CountingReader { data: raw, pos: 0 };
+    let value: T = bincode::deserialize_from(&mut reader).map_err(|e| DecodeError {
+        message: e.to_string(),
+        offset: reader.pos,
+    })?;
+    if reader.pos < raw.len() {
+        return Err(DecodeError {
+            message: format!("{} trailing bytes", raw.len() - reader.pos),
+            offset: reader.pos,
+        });
+    }
+    Ok(value)
+}
+
+pub async fn post_decode_tx<B: Blockchain>(
+    _context: Arc<NodeContext<B>>,
+    req: PostDecodeRequest,
+) -> Result<PostDecodeTxResponse, NodeError> {
+    Ok(match decode::<Transaction>(&hex::decode(req.raw)?) {
+        Ok(tx) => PostDecodeTxResponse {
+            txid: Some(hex::encode(tx.txid::<Hasher>())),
+            signed_hash: Some(hex::encode(tx.hash::<Hasher>())),
+            tx: Some(tx),
+            error: None,
+        },
+        Err(e) => PostDecodeTxResponse {
+            txid: None,
+            signed_hash: None,
+            tx: None,
+            error: Some(e),
+        },
+    })
+}
+
+pub async fn post_decode_block<B: Blockchain>(
+    _context: Arc<NodeContext<B>>,
+    req: PostDecodeRequest,
+) -> Result<PostDecodeBlockResponse, NodeError> {
+    Ok(match decode::<Block>(&hex::decode(req.raw)?) {
+        Ok(block) => PostDecodeBlockResponse {
+            hash: Some(hex::encode(block.header.hash())),
+            block: Some(block),
+            error: None,
+        },
+        Err(e) => PostDecodeBlockResponse {
+            hash: None,
+            block: None,
+            error: Some(e),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Address;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_decode() {
+        let tx = Wallet::new(b"ABC".to_vec()).create_transaction(Address::Treasury, 10, 1);
+        let raw = bincode::serialize(&tx).unwrap();
+        let decoded: Transaction = decode(&raw).unwrap();
+        assert_eq!(decoded.hash::<Hasher>(), tx.hash::<Hasher>());
+
+        let err = decode::<Transaction>(&raw[..raw.len() - 1]).unwrap_err();
+        assert_eq!(err.offset, raw.len() - 1);
+
+        let mut trailing = raw.clone();
+        trailing.push(0);
+        let err = decode::<Transaction>(&trailing).unwrap_err();
+        assert_eq!(err.offset, raw.len());
+        assert_eq!(err.message, "1 trailing bytes");
+
+        // Not a valid address variant
+        let err = decode::<Transaction>(&[9, 0, 0, 0]).unwrap_err();
+        assert_eq!(err.offset, 4);
+    }
+}
@@ -0,0 +1,29 @@
+use super::messages::{GetAccountRequest, GetAccountResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::Address;
+use std::sync::Arc;
+
+pub async fn get_account<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetAccountRequest,
+) -> Result<GetAccountResponse, NodeError> {
+    let addr: Address = req.addr.parse().map_err(|_| NodeError::InvalidAddress)?;
+    let (account, locked) = {
+        let chain = context.blockchain.read().await;
+        (
+            chain.get_account(addr.clone())?,
+            chain.get_locked_balance(addr.clone())?,
+        )
+    };
+    let pending = if req.pending {
+        Some(context.mempool.read().await.transactions_of(&addr))
+    } else {
+        None
+    };
+    Ok(GetAccountResponse {
+        account,
+        locked,
+        pending,
+    })
+}
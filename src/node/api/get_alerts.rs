@@ -0,0 +1,13 @@
+use super::messages::{GetAlertsRequest, GetAlertsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_alerts<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetAlertsRequest,
+) -> Result<GetAlertsResponse, NodeError> {
+    Ok(GetAlertsResponse {
+        alerts: context.active_alerts().await,
+    })
+}
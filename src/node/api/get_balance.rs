@@ -0,0 +1,27 @@
+use super::messages::{GetBalanceRequest, GetBalanceResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::Address;
+use std::sync::Arc;
+
+pub async fn get_balance<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetBalanceRequest,
+) -> Result<GetBalanceResponse, NodeError> {
+    let addr: Address = req.addr.parse().map_err(|_| NodeError::InvalidAddress)?;
+    let (amount, locked) = {
+        let chain = context.blockchain.read().await;
+        (
+            chain.get_account(addr.clone())?.balance,
+            chain.get_locked_balance(addr.clone())?,
+        )
+    };
+    let (incoming, outgoing) = context.mempool.read().await.pending_amounts(&addr);
+    Ok(GetBalanceResponse {
+        amount,
+        locked,
+        incoming,
+        outgoing,
+        projected: amount.saturating_sub(outgoing).saturating_add(incoming),
+    })
+}
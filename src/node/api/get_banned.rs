@@ -0,0 +1,21 @@
+use super::messages::{BannedPeer, GetBannedRequest, GetBannedResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_banned<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetBannedRequest,
+) -> Result<GetBannedResponse, NodeError> {
+    Ok(GetBannedResponse {
+        banned: context
+            .banned_peers()
+            .await
+            .into_iter()
+            .map(|(peer, stats)| BannedPeer {
+                peer,
+                banned_until: stats.banned_until,
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,19 @@
+use super::messages::{GetBlockRequest, GetBlockResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::{Blockchain, BlockchainError};
+use std::sync::Arc;
+
+pub async fn get_block<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetBlockRequest,
+) -> Result<GetBlockResponse, NodeError> {
+    let chain = context.blockchain.read().await;
+    let block = chain
+        .get_blocks(req.number, Some(req.number + 1))?
+        .pop()
+        .ok_or(BlockchainError::BlockNotFound)?;
+    Ok(GetBlockResponse {
+        block,
+        metadata: chain.get_block_metadata(req.number)?,
+    })
+}
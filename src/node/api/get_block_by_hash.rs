@@ -0,0 +1,19 @@
+use super::messages::{GetBlockByHashRequest, GetBlockResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::{Blockchain, BlockchainError};
+use std::sync::Arc;
+
+pub async fn get_block_by_hash<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetBlockByHashRequest,
+) -> Result<GetBlockResponse, NodeError> {
+    let hash = hex::decode(req.hash)?;
+    let chain = context.blockchain.read().await;
+    let block = chain
+        .get_block_by_hash(&hash)?
+        .ok_or(BlockchainError::BlockNotFound)?;
+    Ok(GetBlockResponse {
+        metadata: chain.get_block_metadata(block.header.number as usize)?,
+        block,
+    })
+}
@@ -0,0 +1,46 @@
+use super::messages::{
+    CoinbaseConstraints, GetBlockTemplateRequest, GetBlockTemplateResponse, TemplateTransaction,
+};
+use super::{NodeContext, NodeError};
+use crate::blockchain::{block_reward, Blockchain};
+use crate::core::{Address, Hasher, Money};
+use std::sync::Arc;
+
+// Structured version of the miner puzzle, so that external mining software can
+// pick transactions and assemble its own block instead of mining an opaque blob.
+pub async fn get_block_template<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetBlockTemplateRequest,
+) -> Result<GetBlockTemplateResponse, NodeError> {
+    let wallet = context.wallet.clone().ok_or(NodeError::NoWalletError)?;
+    let (block, _) = context.get_puzzle(wallet).await?;
+    let chain = context.blockchain.read().await;
+    let treasury_nonce = chain.get_account(Address::Treasury)?.nonce;
+    let transactions = block
+        .body
+        .iter()
+        .filter(|tx| tx.src != Address::Treasury)
+        .map(|tx| TemplateTransaction {
+            hash: hex::encode(tx.txid::<Hasher>()),
+            fee: tx.fee,
+            tx: tx.clone(),
+        })
+        .collect::<Vec<_>>();
+    let subsidy = block_reward(block.header.number);
+    let fees = transactions.iter().map(|t| t.fee).sum::<Money>();
+    Ok(GetBlockTemplateResponse {
+        height: block.header.number,
+        parent_hash: hex::encode(block.header.parent_hash),
+        parent_tx_count: block.header.tx_count - block.body.len() as u64,
+        timestamp: block.header.proof_of_work.timestamp,
+        target: block.header.proof_of_work.target,
+        key: hex::encode(chain.pow_key(block.header.number as usize)?),
+        coinbase: CoinbaseConstraints {
+            treasury_nonce,
+            subsidy,
+            fees,
+            max_reward: subsidy.saturating_add(fees),
+        },
+        transactions,
+    })
+}
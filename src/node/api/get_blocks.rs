@@ -3,16 +3,16 @@ use super::{NodeContext, NodeError};
 use crate::blockchain::Blockchain;
 use crate::config::MAX_BLOCK_FETCH;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 pub async fn get_blocks<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: GetBlocksRequest,
 ) -> Result<GetBlocksResponse, NodeError> {
-    let context = context.read().await;
-    let height = context.blockchain.get_height()?;
+    let chain = context.blockchain.read().await;
+    let height = chain.get_height()?;
     let until = std::cmp::min(height, req.since + MAX_BLOCK_FETCH);
+    let until = req.until.map(|u| std::cmp::min(u, until)).unwrap_or(until);
     Ok(GetBlocksResponse {
-        blocks: context.blockchain.get_blocks(req.since, Some(until))?,
+        blocks: chain.get_blocks(req.since, Some(until))?,
     })
 }
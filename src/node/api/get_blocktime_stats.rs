@@ -0,0 +1,13 @@
+use super::messages::{GetBlockTimeStatsRequest, GetBlockTimeStatsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_blocktime_stats<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetBlockTimeStatsRequest,
+) -> Result<GetBlockTimeStatsResponse, NodeError> {
+    Ok(GetBlockTimeStatsResponse {
+        stats: context.block_time_stats().await?,
+    })
+}
@@ -0,0 +1,13 @@
+use super::messages::{GetChainProofRequest, GetChainProofResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_chain_proof<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetChainProofRequest,
+) -> Result<GetChainProofResponse, NodeError> {
+    Ok(GetChainProofResponse {
+        proof: context.blockchain.read().await.generate_chain_proof()?,
+    })
+}
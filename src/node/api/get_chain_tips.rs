@@ -0,0 +1,14 @@
+use super::messages::{GetChainTipsRequest, GetChainTipsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_chain_tips<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetChainTipsRequest,
+) -> Result<GetChainTipsResponse, NodeError> {
+    let chain = context.blockchain.read().await;
+    Ok(GetChainTipsResponse {
+        tips: chain.get_chain_tips()?,
+    })
+}
@@ -0,0 +1,13 @@
+use super::messages::{GetCheckpointRequest, GetCheckpointResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_checkpoint<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetCheckpointRequest,
+) -> Result<GetCheckpointResponse, NodeError> {
+    Ok(GetCheckpointResponse {
+        checkpoint: context.blockchain.read().await.get_checkpoint()?,
+    })
+}
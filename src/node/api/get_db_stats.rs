@@ -0,0 +1,15 @@
+use super::messages::{GetDbStatsRequest, GetDbStatsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_db_stats<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetDbStatsRequest,
+) -> Result<GetDbStatsResponse, NodeError> {
+    let stats = context.blockchain.read().await.db_stats()?;
+    Ok(GetDbStatsResponse {
+        total_bytes: stats.total_bytes(),
+        stats,
+    })
+}
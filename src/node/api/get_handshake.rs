@@ -0,0 +1,19 @@
+use super::messages::{GetHandshakeRequest, GetHandshakeResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::hash::domains;
+use crate::core::Hasher;
+use std::sync::Arc;
+
+pub fn handshake_proof(nonce: &[u8]) -> String {
+    hex::encode(domains::hash_peer_handshake::<Hasher>(nonce))
+}
+
+pub async fn get_handshake<B: Blockchain>(
+    _context: Arc<NodeContext<B>>,
+    req: GetHandshakeRequest,
+) -> Result<GetHandshakeResponse, NodeError> {
+    Ok(GetHandshakeResponse {
+        proof: handshake_proof(&hex::decode(req.nonce)?),
+    })
+}
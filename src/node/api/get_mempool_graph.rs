@@ -0,0 +1,46 @@
+use super::messages::{
+    GetMempoolGraphRequest, GetMempoolGraphResponse, MempoolGraphChain, MempoolGraphNode,
+};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_mempool_graph<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetMempoolGraphRequest,
+) -> Result<GetMempoolGraphResponse, NodeError> {
+    let blockchain = context.blockchain.read().await;
+    let mempool = context.mempool.read().await;
+    let mut chains = Vec::new();
+    for chain in mempool.chains() {
+        let sender = chain[0].1.src.clone();
+        let account_nonce = blockchain.get_account(sender.clone())?.nonce;
+        let (next_nonce, gaps) = mempool.next_nonce(&sender, account_nonce);
+        let mut prev: Option<(u32, String)> = None;
+        let mut transactions = Vec::new();
+        for (id, tx, stats) in chain {
+            let txid = hex::encode(id);
+            let size = bincode::serialized_size(tx).unwrap_or(1).max(1);
+            transactions.push(MempoolGraphNode {
+                txid: txid.clone(),
+                nonce: tx.nonce,
+                fee: tx.fee,
+                fee_per_byte: tx.fee / size,
+                first_seen: stats.first_seen,
+                depends_on: prev
+                    .filter(|(nonce, _)| nonce + 1 == tx.nonce)
+                    .map(|(_, txid)| txid),
+                // Stale ones (Below the account nonce) are pruned on the next block
+                executable: tx.nonce > account_nonce && tx.nonce < next_nonce,
+            });
+            prev = Some((tx.nonce, txid));
+        }
+        chains.push(MempoolGraphChain {
+            sender: sender.to_string(),
+            account_nonce,
+            gaps,
+            transactions,
+        });
+    }
+    Ok(GetMempoolGraphResponse { chains })
+}
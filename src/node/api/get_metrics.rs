@@ -0,0 +1,13 @@
+use super::messages::{GetMetricsRequest, GetMetricsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_metrics<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetMetricsRequest,
+) -> Result<GetMetricsResponse, NodeError> {
+    Ok(GetMetricsResponse {
+        endpoints: context.metrics.snapshot(),
+    })
+}
@@ -0,0 +1,13 @@
+use super::messages::{GetMinerStatsRequest, GetMinerStatsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_miner_stats<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: GetMinerStatsRequest,
+) -> Result<GetMinerStatsResponse, NodeError> {
+    Ok(GetMinerStatsResponse {
+        miners: context.miner.read().await.miner_stats.clone(),
+    })
+}
@@ -0,0 +1,28 @@
+use super::messages::{GetNextNonceRequest, GetNextNonceResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::Address;
+use std::sync::Arc;
+
+pub async fn get_next_nonce<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetNextNonceRequest,
+) -> Result<GetNextNonceResponse, NodeError> {
+    let addr: Address = req.addr.parse().map_err(|_| NodeError::InvalidAddress)?;
+    let account_nonce = context
+        .blockchain
+        .read()
+        .await
+        .get_account(addr.clone())?
+        .nonce;
+    let (next_nonce, gaps) = context
+        .mempool
+        .read()
+        .await
+        .next_nonce(&addr, account_nonce);
+    Ok(GetNextNonceResponse {
+        account_nonce,
+        next_nonce,
+        gaps,
+    })
+}
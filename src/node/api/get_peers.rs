@@ -1,15 +1,75 @@
 use super::messages::{GetPeersRequest, GetPeersResponse};
-use super::{NodeContext, NodeError};
+use super::{NodeContext, NodeError, PeerAddress, PeerStats, Timestamp};
 use crate::blockchain::Blockchain;
+use crate::config;
+use crate::utils;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+
+// A random sample of the peers known for long enough. Freshly added peers are
+// held back, so that a crawler can't learn about the connections a node has
+// just made.
+fn exchangeable_peers<R: Rng>(
+    peers: HashMap<PeerAddress, PeerStats>,
+    now: Timestamp,
+    rng: &mut R,
+) -> HashMap<PeerAddress, PeerStats> {
+    peers
+        .into_iter()
+        .filter(|(_, stats)| now.saturating_sub(stats.added) >= config::PEER_EXCHANGE_MIN_AGE)
+        .choose_multiple(rng, config::PEER_EXCHANGE_LIMIT)
+        .into_iter()
+        .collect()
+}
 
 pub async fn get_peers<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     _req: GetPeersRequest,
 ) -> Result<GetPeersResponse, NodeError> {
-    let context = context.read().await;
+    // The order of the response is already random, HashMaps are serialized
+    // in the order of their randomly seeded hashes
     Ok(GetPeersResponse {
-        peers: context.active_peers(),
+        peers: exchangeable_peers(
+            context.active_peers().await,
+            utils::local_timestamp(),
+            &mut rand::thread_rng(),
+        ),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exchangeable_peers() {
+        let now = 100000;
+        let peers = (0..100u16)
+            .map(|i| {
+                (
+                    PeerAddress("127.0.0.1".parse().unwrap(), i),
+                    PeerStats {
+                        punished_until: 0,
+                        info: None,
+                        last_seen: 0,
+                        misbehavior: 0,
+                        banned_until: 0,
+                        // Every other peer was just added
+                        added: if i % 2 == 0 { 0 } else { now - 10 },
+                        announced_at: 0,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let mut rng = rand::thread_rng();
+        let exchanged = exchangeable_peers(peers.clone(), now, &mut rng);
+        assert_eq!(exchanged.len(), config::PEER_EXCHANGE_LIMIT);
+        assert!(exchanged.keys().all(|addr| addr.1 % 2 == 0));
+
+        let few = peers.into_iter().take(3).collect::<HashMap<_, _>>();
+        let old = few.values().filter(|stats| stats.added == 0).count();
+        assert_eq!(exchangeable_peers(few, now, &mut rng).len(), old);
+    }
+}
@@ -0,0 +1,15 @@
+use super::messages::{GetReorgsRequest, GetReorgsResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn get_reorgs<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetReorgsRequest,
+) -> Result<GetReorgsResponse, NodeError> {
+    let mut reorgs = context.blockchain.read().await.get_reorgs()?;
+    if let Some(limit) = req.limit {
+        reorgs.drain(..reorgs.len().saturating_sub(limit));
+    }
+    Ok(GetReorgsResponse { reorgs })
+}
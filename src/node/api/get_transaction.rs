@@ -0,0 +1,29 @@
+use super::messages::{GetTransactionRequest, GetTransactionResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::core::TransactionId;
+use std::sync::Arc;
+
+pub async fn get_transaction<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: GetTransactionRequest,
+) -> Result<GetTransactionResponse, NodeError> {
+    let hash: TransactionId = hex::decode(req.hash)?
+        .try_into()
+        .map_err(|_| NodeError::InvalidTransactionHash)?;
+    let chain = context.blockchain.read().await;
+    Ok(match chain.get_transaction(hash)? {
+        Some((tx, location)) => GetTransactionResponse {
+            receipt: chain.get_receipt(hash)?,
+            tx: Some(tx),
+            confirmations: chain.get_height()? - location.block as usize,
+            location: Some(location),
+        },
+        None => GetTransactionResponse {
+            tx: None,
+            location: None,
+            receipt: None,
+            confirmations: 0,
+        },
+    })
+}
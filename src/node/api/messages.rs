@@ -1,6 +1,19 @@
-use crate::core::{Address, Block, Header, Money, Transaction};
+use crate::blockchain::ReorgRecord;
+use crate::core::{
+    Account, Alert, Block, BlockMetadata, Checkpoint, Header, Money, Transaction,
+    TransactionLocation, TxReceipt,
+};
+
+#[cfg(feature = "pow")]
+use {
+    super::MinerStats,
+    crate::blockchain::{BlockTimeStats, ChainProof},
+};
 
 use super::{PeerAddress, PeerInfo, PeerStats};
+use crate::blockchain::ChainTip;
+use crate::db::DbStats;
+use crate::node::{EndpointMetrics, RejectReason};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -8,6 +21,10 @@ use std::collections::HashMap;
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PostMinerSolutionRequest {
     pub nonce: String,
+    // Webhook of the registered miner submitting the solution, solutions
+    // without it are rejected
+    #[serde(default)]
+    pub miner: Option<String>,
 }
 
 #[cfg(feature = "pow")]
@@ -28,6 +45,58 @@ pub struct Puzzle {
     pub target: u32,   // Difficulty target
 }
 
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockTemplateRequest {}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TemplateTransaction {
+    pub hash: String, // Transaction id (See `Transaction::txid`) encoded in hex
+    pub fee: Money,
+    pub tx: Transaction,
+}
+
+// Rules the reward transactions of an assembled block should follow. They are
+// unsigned, zero-fee RegularSends from the Treasury with consecutive nonces
+// starting at treasury_nonce + 1, placed before every other transaction.
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CoinbaseConstraints {
+    pub treasury_nonce: u32,
+    pub subsidy: Money,
+    pub fees: Money,
+    pub max_reward: Money,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockTemplateResponse {
+    pub height: u64,
+    pub parent_hash: String, // Encoded in hex
+    pub parent_tx_count: u64,
+    pub timestamp: u32,
+    pub target: u32,
+    pub key: String, // PoW key encoded in hex
+    pub coinbase: CoinbaseConstraints,
+    pub transactions: Vec<TemplateTransaction>,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SubmitBlockRequest {
+    pub block: Block,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SubmitBlockResponse {
+    pub accepted: bool,
+    pub reason: Option<String>,     // Why the block was rejected
+    pub transaction: Option<usize>, // Index of the offending transaction
+    pub txid: Option<String>,       // Id of the offending transaction encoded in hex
+}
+
 #[cfg(feature = "pow")]
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RegisterMinerRequest {
@@ -38,6 +107,36 @@ pub struct RegisterMinerRequest {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RegisterMinerResponse {}
 
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMinerStatsRequest {}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMinerStatsResponse {
+    pub miners: HashMap<String, MinerStats>,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetChainProofRequest {}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetChainProofResponse {
+    pub proof: ChainProof,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockTimeStatsRequest {}
+
+#[cfg(feature = "pow")]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockTimeStatsResponse {
+    pub stats: Option<BlockTimeStats>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PostPeerRequest {
     pub address: PeerAddress,
@@ -51,6 +150,32 @@ pub struct PostPeerResponse {
     pub timestamp: u32,
 }
 
+// Peers prove they are reachable at their claimed address by answering a
+// challenge sent there
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetHandshakeRequest {
+    pub nonce: String, // Hex encoded
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetHandshakeResponse {
+    pub proof: String, // Hex encoded
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WsRequest {
+    #[serde(default)]
+    pub subscribe: Vec<String>,
+    #[serde(default)]
+    pub unsubscribe: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WsResponse {
+    pub subscriptions: Vec<String>,
+    pub error: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GetPeersRequest {}
 
@@ -67,6 +192,99 @@ pub struct PostBlockRequest {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PostBlockResponse {}
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetDbStatsRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetDbStatsResponse {
+    pub stats: DbStats,
+    pub total_bytes: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMetricsRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMetricsResponse {
+    // Keyed by `<method> <path>`
+    pub endpoints: HashMap<String, EndpointMetrics>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostDbCompactRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostDbCompactResponse {}
+
+// A zero duration lifts the ban
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostBanRequest {
+    pub peer: PeerAddress,
+    pub duration: u32, // Seconds
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostBanResponse {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostRewindRequest {
+    pub height: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostRewindResponse {
+    pub rolled_back: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostInvalidateRequest {
+    pub hash: String, // Block hash encoded in hex
+}
+
+// Nothing is rolled back if the block isn't part of our chain
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostInvalidateResponse {
+    pub rolled_back: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBannedRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BannedPeer {
+    pub peer: PeerAddress,
+    pub banned_until: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBannedResponse {
+    pub banned: Vec<BannedPeer>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetTransactionRequest {
+    pub hash: String, // Transaction id (See `Transaction::txid`) encoded in hex
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetTransactionResponse {
+    // None if the transaction is not confirmed (yet)
+    pub tx: Option<Transaction>,
+    pub location: Option<TransactionLocation>,
+    pub receipt: Option<TxReceipt>,
+    pub confirmations: usize,
+}
+
+// `address` is where the announcer serves the body of the block
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostAnnounceRequest {
+    pub address: PeerAddress,
+    pub header: Header,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostAnnounceResponse {}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GetBlocksRequest {
     pub since: usize,
@@ -89,14 +307,157 @@ pub struct GetHeadersResponse {
     pub headers: Vec<Header>,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetCheckpointRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetCheckpointResponse {
+    pub checkpoint: Option<Checkpoint>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetReorgsRequest {
+    // Only the most recent ones
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetReorgsResponse {
+    // Oldest first
+    pub reorgs: Vec<ReorgRecord>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetAlertsRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetAlertsResponse {
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostAlertRequest {
+    pub alert: Alert,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostAlertResponse {
+    pub accepted: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostRelayBlockRequest {
+    pub block: Block,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostRelayBlockResponse {
+    pub accepted: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockRequest {
+    pub number: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockByHashRequest {
+    pub hash: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetChainTipsRequest {}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetChainTipsResponse {
+    pub tips: Vec<ChainTip>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetBlockResponse {
+    pub block: Block,
+    pub metadata: BlockMetadata,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetAccountRequest {
+    pub addr: String,
+    // Whether to include the transactions of the account waiting in the mempool
+    #[serde(default)]
+    pub pending: bool,
+}
+
+// Confirmed state of an account, what a wallet needs to sync its nonce and
+// balance with
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetAccountResponse {
+    pub account: Account,
+    pub locked: Money,
+    // In nonce order, only when requested
+    pub pending: Option<Vec<Transaction>>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GetBalanceRequest {
-    pub addr: Address,
+    pub addr: String,
 }
 
+// `amount` is the confirmed balance, `projected` is what it will be once the
+// pending (mempool) transactions of the address are confirmed
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GetBalanceResponse {
     pub amount: Money,
+    // Part of `amount` that is locked, i.e. immature block rewards and
+    // unbonding stake
+    pub locked: Money,
+    pub incoming: Money,
+    pub outgoing: Money,
+    pub projected: Money,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetNextNonceRequest {
+    pub addr: String,
+}
+
+// `gaps` are the (inclusive) ranges of nonces that are missing before some
+// pending transactions of the address, those transactions won't be confirmed
+// until the gaps are filled
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetNextNonceResponse {
+    pub account_nonce: u32,
+    pub next_nonce: u32,
+    pub gaps: Vec<(u32, u32)>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMempoolGraphRequest {}
+
+// A pending transaction, depending on the one with the previous nonce of the
+// same sender (If it's in the mempool). `executable` is false when a missing
+// nonce blocks it from getting into a block.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MempoolGraphNode {
+    pub txid: String,
+    pub nonce: u32,
+    pub fee: Money,
+    pub fee_per_byte: Money,
+    pub first_seen: u32,
+    pub depends_on: Option<String>,
+    pub executable: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MempoolGraphChain {
+    pub sender: String,
+    pub account_nonce: u32,
+    pub gaps: Vec<(u32, u32)>,
+    pub transactions: Vec<MempoolGraphNode>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GetMempoolGraphResponse {
+    pub chains: Vec<MempoolGraphChain>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -105,4 +466,83 @@ pub struct TransactRequest {
 }
 
 #[derive(Deserialize, Serialize, Debug)]
-pub struct TransactResponse {}
+pub struct TransactResponse {
+    pub txid: String, // Transaction id (See `Transaction::txid`) encoded in hex
+    pub accepted: bool,
+    // Set when the transaction is not accepted
+    pub reason: Option<RejectReason>,
+}
+
+// Bincode (The wire format) encoded in hex
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostDecodeRequest {
+    pub raw: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub message: String,
+    pub offset: usize, // Bytes
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostDecodeTxResponse {
+    // What the chain indexes the transaction by, see `Transaction::txid`
+    pub txid: Option<String>,
+    // Hash of the whole transaction, signature included
+    pub signed_hash: Option<String>,
+    pub tx: Option<Transaction>,
+    pub error: Option<DecodeError>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostDecodeBlockResponse {
+    pub hash: Option<String>,
+    pub block: Option<Block>,
+    pub error: Option<DecodeError>,
+}
+
+// JSON-RPC 2.0 envelopes, requests without an id are notifications
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+    pub id: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: serde_json::Value,
+}
+
+impl RpcResponse {
+    pub fn result(id: serde_json::Value, result: serde_json::Value) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+    pub fn error(id: serde_json::Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: "2.0".into(),
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
@@ -1,11 +1,22 @@
-use super::{NodeContext, NodeError, PeerAddress, PeerInfo, PeerStats, TransactionStats};
+use super::{
+    http, relay, AnnounceEvent, BlockHash, NodeContext, NodeError, NodeEvent, PeerAddress,
+    PeerInfo, PeerStats, Timestamp, TOPICS,
+};
 
 pub mod messages;
 
 mod get_peers;
 pub use get_peers::*;
+mod rpc;
+pub use rpc::*;
+mod ws;
+pub use ws::*;
+mod get_handshake;
+pub use get_handshake::*;
 mod post_peer;
 pub use post_peer::*;
+mod post_announce;
+pub use post_announce::*;
 mod post_block;
 pub use post_block::*;
 mod get_blocks;
@@ -14,9 +25,51 @@ mod get_headers;
 pub use get_headers::*;
 mod transact;
 pub use transact::*;
+mod get_db_stats;
+pub use get_db_stats::*;
+mod get_metrics;
+pub use get_metrics::*;
+mod post_db_compact;
+pub use post_db_compact::*;
+mod post_ban;
+pub use post_ban::*;
+mod get_banned;
+pub use get_banned::*;
+mod post_rewind;
+pub use post_rewind::*;
+mod post_invalidate;
+pub use post_invalidate::*;
+mod get_transaction;
+pub use get_transaction::*;
+mod post_relay_block;
+pub use post_relay_block::*;
+mod get_block;
+pub use get_block::*;
+mod get_block_by_hash;
+pub use get_block_by_hash::*;
+mod get_chain_tips;
+pub use get_chain_tips::*;
+mod decode;
+pub use decode::*;
+mod get_account;
+pub use get_account::*;
+mod get_balance;
+pub use get_balance::*;
+mod get_next_nonce;
+pub use get_next_nonce::*;
+mod get_mempool_graph;
+pub use get_mempool_graph::*;
+mod get_checkpoint;
+pub use get_checkpoint::*;
+mod get_reorgs;
+pub use get_reorgs::*;
+mod get_alerts;
+pub use get_alerts::*;
+mod post_alert;
+pub use post_alert::*;
 
 #[cfg(feature = "pow")]
-use super::Miner;
+use super::{Miner, MinerStats, SolutionStatus};
 
 #[cfg(feature = "pow")]
 mod post_miner;
@@ -28,6 +81,31 @@ mod get_miner_puzzle;
 #[cfg(feature = "pow")]
 pub use get_miner_puzzle::*;
 
+#[cfg(feature = "pow")]
+mod get_block_template;
+#[cfg(feature = "pow")]
+pub use get_block_template::*;
+
+#[cfg(feature = "pow")]
+mod submit_block;
+#[cfg(feature = "pow")]
+pub use submit_block::*;
+
+#[cfg(feature = "pow")]
+mod get_miner_stats;
+#[cfg(feature = "pow")]
+pub use get_miner_stats::*;
+
+#[cfg(feature = "pow")]
+mod get_blocktime_stats;
+#[cfg(feature = "pow")]
+pub use get_blocktime_stats::*;
+
+#[cfg(feature = "pow")]
+mod get_chain_proof;
+#[cfg(feature = "pow")]
+pub use get_chain_proof::*;
+
 #[cfg(feature = "pow")]
 mod post_miner_solution;
 #[cfg(feature = "pow")]
@@ -0,0 +1,13 @@
+use super::messages::{PostAlertRequest, PostAlertResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn post_alert<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: PostAlertRequest,
+) -> Result<PostAlertResponse, NodeError> {
+    Ok(PostAlertResponse {
+        accepted: context.add_alert(req.alert).await,
+    })
+}
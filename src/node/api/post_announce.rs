@@ -0,0 +1,26 @@
+use super::messages::{PostAnnounceRequest, PostAnnounceResponse};
+use super::{AnnounceEvent, NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use crate::config::punish;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+pub async fn post_announce<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    client: IpAddr,
+    req: PostAnnounceRequest,
+) -> Result<PostAnnounceResponse, NodeError> {
+    // The body is fetched from the announced address, which must be the one of
+    // the calling peer, so that nodes can't be made to request other hosts
+    if req.address.0 != client {
+        context
+            .misbehave_ip(client, punish::PROTOCOL_VIOLATION_SCORE)
+            .await;
+    } else if context.accept_announcement(req.address).await {
+        context.announcer.send(AnnounceEvent::Received {
+            from: req.address,
+            header: req.header,
+        });
+    }
+    Ok(PostAnnounceResponse {})
+}
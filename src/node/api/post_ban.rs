@@ -0,0 +1,34 @@
+use super::messages::{PostBanRequest, PostBanResponse};
+use super::{NodeContext, NodeError, PeerStats};
+use crate::blockchain::Blockchain;
+use crate::utils;
+use std::sync::Arc;
+
+pub async fn post_ban<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: PostBanRequest,
+) -> Result<PostBanResponse, NodeError> {
+    let mut peers = context.peers.write().await;
+    if req.duration > 0 {
+        // Peers we have never heard of can be banned in advance too
+        peers
+            .entry(req.peer)
+            .or_insert(PeerStats {
+                info: None,
+                punished_until: 0,
+                last_seen: 0,
+                misbehavior: 0,
+                banned_until: 0,
+                added: utils::local_timestamp(),
+                announced_at: 0,
+            })
+            .ban(req.duration);
+    } else {
+        if let Some(stats) = peers.get_mut(&req.peer) {
+            stats.banned_until = 0;
+        }
+        // Including whatever the address did as an inbound client
+        context.ip_stats.write().await.remove(&req.peer.0);
+    }
+    Ok(PostBanResponse {})
+}
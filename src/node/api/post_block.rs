@@ -2,15 +2,25 @@ use super::messages::{PostBlockRequest, PostBlockResponse};
 use super::{NodeContext, NodeError};
 use crate::blockchain::Blockchain;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 pub async fn post_block<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: PostBlockRequest,
 ) -> Result<PostBlockResponse, NodeError> {
-    let mut context = context.write().await;
-    context
-        .blockchain
-        .extend(req.block.header.number as usize, &vec![req.block])?;
+    let hash = req.block.header.hash();
+    if let Some(reason) = context.known_invalid(std::iter::once(hash)).await {
+        return Err(NodeError::KnownInvalidBlock(reason));
+    }
+    match context
+        .chain
+        .extend(req.block.header.number as usize, vec![req.block])
+        .await
+    {
+        Err(NodeError::BlockchainError(e)) if e.is_context_free() => {
+            context.mark_invalid(hash, e.to_string()).await;
+            Err(e.into())
+        }
+        res => res,
+    }?;
     Ok(PostBlockResponse {})
 }
@@ -0,0 +1,12 @@
+use super::messages::{PostDbCompactRequest, PostDbCompactResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn post_db_compact<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    _req: PostDbCompactRequest,
+) -> Result<PostDbCompactResponse, NodeError> {
+    context.chain.compact().await?;
+    Ok(PostDbCompactResponse {})
+}
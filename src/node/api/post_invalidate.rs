@@ -0,0 +1,18 @@
+use super::messages::{PostInvalidateRequest, PostInvalidateResponse};
+use super::{BlockHash, NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn post_invalidate<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: PostInvalidateRequest,
+) -> Result<PostInvalidateResponse, NodeError> {
+    let hash: BlockHash = hex::decode(req.hash)?
+        .try_into()
+        .map_err(|_| NodeError::InvalidBlockHash)?;
+    let rolled_back = context.chain.invalidate(hash).await?;
+    if rolled_back > 0 {
+        *context.best_header.write().await = None;
+    }
+    Ok(PostInvalidateResponse { rolled_back })
+}
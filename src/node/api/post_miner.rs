@@ -2,17 +2,18 @@ use super::messages::{RegisterMinerRequest, RegisterMinerResponse};
 use super::{Miner, NodeContext, NodeError};
 use crate::blockchain::Blockchain;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 pub async fn post_miner<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: RegisterMinerRequest,
 ) -> Result<RegisterMinerResponse, NodeError> {
-    let mut context = context.write().await;
-    println!("Registered miner: {}", req.webhook);
-    context.miner = Some(Miner {
-        webhook: req.webhook,
-        block: None,
-    });
+    tracing::info!(webhook = %req.webhook, "Registered miner");
+    context.miner.write().await.miners.insert(
+        req.webhook.clone(),
+        Miner {
+            webhook: req.webhook,
+        },
+    );
+    context.miner_registered.notify_one();
     Ok(RegisterMinerResponse {})
 }
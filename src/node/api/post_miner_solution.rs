@@ -1,32 +1,58 @@
 use super::messages::{PostMinerSolutionRequest, PostMinerSolutionResponse};
-use super::{NodeContext, NodeError};
+use super::{relay, NodeContext, NodeError, SolutionStatus};
 use crate::blockchain::Blockchain;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 pub async fn post_miner_solution<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: PostMinerSolutionRequest,
 ) -> Result<PostMinerSolutionResponse, NodeError> {
-    let mut context = context.write().await;
+    let now = context.network_timestamp();
+    // Holding the miner lock until the end, so that only the first valid
+    // solution of the template gets submitted
+    let mut miner = context.miner.write().await;
+
+    // Solutions are only accepted from registered miners, for the stats to
+    // stay attributable
+    let miner_id = req.miner.ok_or(NodeError::UnidentifiedMinerError)?;
+    if !miner.miners.contains_key(&miner_id) {
+        return Err(NodeError::NoMinerError);
+    }
 
     let mut nonce_bytes = [0u8; 8];
     nonce_bytes.copy_from_slice(&hex::decode(req.nonce).unwrap());
-    let mut block = context
-        .miner
-        .as_ref()
-        .ok_or(NodeError::NoMinerError)?
-        .block
-        .as_ref()
-        .ok_or(NodeError::NoCurrentlyMiningBlockError)?
-        .clone();
+    let mut block = match miner.mining_template.as_ref() {
+        Some(template) => template.block.clone(),
+        None => {
+            miner.record_solution(miner_id, SolutionStatus::Stale, 0, now);
+            return Err(NodeError::NoCurrentlyMiningBlockError);
+        }
+    };
     block.header.proof_of_work.nonce = u64::from_le_bytes(nonce_bytes);
+
+    let index = block.header.number as usize;
+    if !context.builds_on_tip(&block.header).await? {
+        miner.record_solution(miner_id, SolutionStatus::Stale, 0, now);
+        return Ok(PostMinerSolutionResponse {});
+    }
+
+    let work = block.header.power();
     if context
-        .blockchain
-        .extend(block.header.number as usize, &vec![block])
+        .chain
+        .extend(index, vec![block.clone()])
+        .await
         .is_ok()
     {
-        context.miner.as_mut().unwrap().block = None;
+        // First valid solution wins, the others will be counted as stale
+        miner.mining_template = None;
+        miner.record_solution(miner_id, SolutionStatus::Accepted, work, now);
+        drop(miner);
+        relay::forward_block(&context, block).await;
+    } else if context.builds_on_tip(&block.header).await? {
+        miner.record_solution(miner_id, SolutionStatus::Invalid, 0, now);
+    } else {
+        // The tip moved while the block was being applied
+        miner.record_solution(miner_id, SolutionStatus::Stale, 0, now);
     }
     Ok(PostMinerSolutionResponse {})
 }
@@ -1,26 +1,57 @@
-use super::messages::{PostPeerRequest, PostPeerResponse};
-use super::{NodeContext, NodeError, PeerStats};
+use super::messages::{
+    GetHandshakeRequest, GetHandshakeResponse, PostPeerRequest, PostPeerResponse,
+};
+use super::{handshake_proof, http, NodeContext, NodeError, PeerAddress, PeerStats};
 use crate::blockchain::Blockchain;
+use crate::config::HANDSHAKE_TIMEOUT;
+use crate::utils;
+use rand::RngCore;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::time::{timeout, Duration};
+
+// Calls the claimed address back, so that nobody can fill the peer tables of
+// the network with addresses they don't own
+async fn challenge(address: PeerAddress) -> bool {
+    let mut nonce = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let resp = timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT),
+        http::json_get::<GetHandshakeRequest, GetHandshakeResponse>(
+            format!("{}/handshake", address).to_string(),
+            GetHandshakeRequest {
+                nonce: hex::encode(nonce),
+            },
+        ),
+    )
+    .await;
+    matches!(resp, Ok(Ok(resp)) if resp.proof == handshake_proof(&nonce))
+}
 
 pub async fn post_peer<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: PostPeerRequest,
 ) -> Result<PostPeerResponse, NodeError> {
-    let mut context = context.write().await;
+    let known = context.peers.read().await.contains_key(&req.address);
+    if !known && !challenge(req.address).await {
+        return Err(NodeError::HandshakeFailed);
+    }
     context
         .peers
+        .write()
+        .await
         .entry(req.address)
-        .and_modify(|s| {
-            s.info = Some(req.info.clone());
-        })
         .or_insert(PeerStats {
-            info: Some(req.info),
+            info: None,
             punished_until: 0,
-        });
+            last_seen: 0,
+            misbehavior: 0,
+            banned_until: 0,
+            added: utils::local_timestamp(),
+            announced_at: 0,
+        })
+        .update_info(req.info);
     Ok(PostPeerResponse {
-        info: context.get_info()?,
+        info: context.get_info().await?,
         timestamp: context.network_timestamp(),
     })
 }
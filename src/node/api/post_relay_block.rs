@@ -0,0 +1,15 @@
+use super::messages::{PostRelayBlockRequest, PostRelayBlockResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+// Blocks forwarded by our own block producing node, already authenticated
+pub async fn post_relay_block<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: PostRelayBlockRequest,
+) -> Result<PostRelayBlockResponse, NodeError> {
+    let index = req.block.header.number as usize;
+    Ok(PostRelayBlockResponse {
+        accepted: context.chain.extend(index, vec![req.block]).await.is_ok(),
+    })
+}
@@ -0,0 +1,14 @@
+use super::messages::{PostRewindRequest, PostRewindResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+
+pub async fn post_rewind<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: PostRewindRequest,
+) -> Result<PostRewindResponse, NodeError> {
+    let rolled_back = context.chain.rewind(req.height).await?;
+    // Most probably on the chain we just left
+    *context.best_header.write().await = None;
+    Ok(PostRewindResponse { rolled_back })
+}
@@ -0,0 +1,143 @@
+use super::messages::{RpcError, RpcRequest, RpcResponse};
+use super::{NodeContext, NodeError};
+use crate::blockchain::Blockchain;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Standard JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+// Failures of the node itself (e.g. an invalid address)
+pub const SERVER_ERROR: i32 = -32000;
+
+fn error(code: i32, message: impl ToString) -> RpcError {
+    RpcError {
+        code,
+        message: message.to_string(),
+    }
+}
+
+// Params are given by name, methods without arguments may omit them
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| error(INVALID_PARAMS, e))
+}
+
+fn result<T: Serialize>(resp: Result<T, NodeError>) -> Result<Value, RpcError> {
+    let resp = resp.map_err(|e| error(SERVER_ERROR, e))?;
+    serde_json::to_value(resp).map_err(|e| error(INTERNAL_ERROR, e))
+}
+
+async fn call<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    method: &str,
+    p: Value,
+) -> Result<Value, RpcError> {
+    match method {
+        "get_balance" => result(super::get_balance(context, params(p)?).await),
+        "get_next_nonce" => result(super::get_next_nonce(context, params(p)?).await),
+        "get_block" => result(super::get_block(context, params(p)?).await),
+        "get_block_by_hash" => result(super::get_block_by_hash(context, params(p)?).await),
+        "get_chain_tips" => result(super::get_chain_tips(context, params(p)?).await),
+        "get_reorgs" => result(super::get_reorgs(context, params(p)?).await),
+        "get_blocks" => result(super::get_blocks(context, params(p)?).await),
+        "get_headers" => result(super::get_headers(context, params(p)?).await),
+        "get_transaction" => result(super::get_transaction(context, params(p)?).await),
+        "transact" => result(super::transact(context, params(p)?).await),
+        // Peer addresses can't be JSON object keys
+        "get_peers" => result(
+            super::get_peers(context, params(p)?)
+                .await
+                .map(|resp| resp.peers.into_iter().collect::<Vec<_>>()),
+        ),
+        _ => Err(error(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+// None for notifications, which aren't answered
+async fn handle<B: Blockchain>(context: Arc<NodeContext<B>>, req: Value) -> Option<RpcResponse> {
+    let req = match serde_json::from_value::<RpcRequest>(req) {
+        Ok(req) if req.jsonrpc == "2.0" => req,
+        _ => {
+            return Some(RpcResponse::error(
+                Value::Null,
+                error(INVALID_REQUEST, "invalid request"),
+            ));
+        }
+    };
+    let resp = call(context, &req.method, req.params).await;
+    let id = req.id?;
+    Some(match resp {
+        Ok(result) => RpcResponse::result(id, result),
+        Err(e) => RpcResponse::error(id, e),
+    })
+}
+
+// Returns None when there is nothing to answer (Only notifications)
+pub async fn rpc<B: Blockchain>(context: Arc<NodeContext<B>>, body: &[u8]) -> Option<Value> {
+    let req = match serde_json::from_slice::<Value>(body) {
+        Ok(req) => req,
+        Err(e) => {
+            return serde_json::to_value(RpcResponse::error(Value::Null, error(PARSE_ERROR, e)))
+                .ok();
+        }
+    };
+    match req {
+        Value::Array(batch) => {
+            if batch.is_empty() {
+                return serde_json::to_value(RpcResponse::error(
+                    Value::Null,
+                    error(INVALID_REQUEST, "empty batch"),
+                ))
+                .ok();
+            }
+            let mut resps = Vec::new();
+            for req in batch {
+                if let Some(resp) = handle(Arc::clone(&context), req).await {
+                    resps.push(resp);
+                }
+            }
+            if resps.is_empty() {
+                None
+            } else {
+                serde_json::to_value(resps).ok()
+            }
+        }
+        req => handle(context, req)
+            .await
+            .and_then(|resp| serde_json::to_value(resp).ok()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::messages::{GetBlockRequest, GetPeersRequest};
+    use super::*;
+
+    #[test]
+    fn test_rpc_params() {
+        assert!(params::<GetPeersRequest>(Value::Null).is_ok());
+        assert_eq!(
+            params::<GetBlockRequest>(serde_json::json!({"number": 3}))
+                .unwrap()
+                .number,
+            3
+        );
+        assert_eq!(
+            params::<GetBlockRequest>(Value::Null).unwrap_err().code,
+            INVALID_PARAMS
+        );
+    }
+}
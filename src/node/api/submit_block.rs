@@ -0,0 +1,45 @@
+use super::messages::{SubmitBlockRequest, SubmitBlockResponse};
+use super::{relay, NodeContext, NodeError};
+use crate::blockchain::{Blockchain, BlockchainError};
+use crate::core::Hasher;
+use std::sync::Arc;
+
+// Accepts blocks assembled from a /blocktemplate. Unlike post_block, a rejected
+// block is reported back with the reason, and the offending transaction if any.
+pub async fn submit_block<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    req: SubmitBlockRequest,
+) -> Result<SubmitBlockResponse, NodeError> {
+    let index = req.block.header.number as usize;
+    let result = context.chain.extend(index, vec![req.block.clone()]).await;
+    match result {
+        Ok(()) => {
+            context.miner.write().await.mining_template = None;
+            relay::forward_block(&context, req.block).await;
+            Ok(SubmitBlockResponse {
+                accepted: true,
+                reason: None,
+                transaction: None,
+                txid: None,
+            })
+        }
+        Err(NodeError::BlockchainError(BlockchainError::KvStoreError(e))) => {
+            Err(BlockchainError::KvStoreError(e).into())
+        }
+        Err(NodeError::BlockchainError(e)) => {
+            let transaction = match &e {
+                BlockchainError::InvalidBlockTransaction(i, _) => Some(*i),
+                _ => None,
+            };
+            Ok(SubmitBlockResponse {
+                accepted: false,
+                reason: Some(e.to_string()),
+                transaction,
+                txid: transaction
+                    .and_then(|i| req.block.body.get(i))
+                    .map(|tx| hex::encode(tx.txid::<Hasher>())),
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -1,22 +1,67 @@
 use super::messages::{TransactRequest, TransactResponse};
-use super::{NodeContext, NodeError, TransactionStats};
+use super::{NodeContext, NodeError, NodeEvent};
 use crate::blockchain::Blockchain;
+use crate::core::{GovernanceParam, Hasher};
+use crate::node::mempool::{pre_verify, total_spent};
+use crate::node::RejectReason;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 pub async fn transact<B: Blockchain>(
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
     req: TransactRequest,
 ) -> Result<TransactResponse, NodeError> {
-    let mut context = context.write().await;
     let now = context.network_timestamp();
+    let txid = hex::encode(req.tx.txid::<Hasher>());
+    // Garbage is refused before taking any of the locks
+    if let Err(reason) = pre_verify(&req.tx) {
+        tracing::debug!(tx = %txid, ?reason, "Transaction rejected");
+        return Ok(TransactResponse {
+            txid,
+            accepted: false,
+            reason: Some(reason),
+        });
+    }
     // Prevent spamming mempool
-    if context.blockchain.get_account(req.tx.src.clone())?.balance > 0 {
-        if req.tx.verify_signature() {
-            context
-                .mempool
-                .insert(req.tx, TransactionStats { first_seen: now });
+    let (account, min_relay_fee) = {
+        let chain = context.blockchain.read().await;
+        (
+            chain.get_account(req.tx.src.clone())?,
+            chain.get_param(GovernanceParam::MinRelayFee)?,
+        )
+    };
+    let result = if req.tx.fee < min_relay_fee {
+        Err(RejectReason::FeeTooSmall)
+    } else if account.balance < total_spent(&req.tx) {
+        Err(RejectReason::BalanceInsufficient)
+    } else {
+        let tx = req.tx.clone();
+        context
+            .mempool
+            .write()
+            .await
+            .insert(req.tx, account.nonce, now)
+            .map(|_| tx)
+    };
+    match result {
+        Ok(tx) => {
+            tracing::debug!(tx = %txid, "Transaction added to mempool");
+            let _ = context.events.send(NodeEvent::NewTransaction {
+                hash: txid.clone(),
+                tx,
+            });
+            Ok(TransactResponse {
+                txid,
+                accepted: true,
+                reason: None,
+            })
+        }
+        Err(reason) => {
+            tracing::debug!(tx = %txid, ?reason, "Transaction rejected");
+            Ok(TransactResponse {
+                txid,
+                accepted: false,
+                reason: Some(reason),
+            })
         }
     }
-    Ok(TransactResponse {})
 }
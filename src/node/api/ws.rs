@@ -0,0 +1,108 @@
+use super::messages::{WsRequest, WsResponse};
+use super::{NodeContext, NodeError, NodeEvent, TOPICS};
+use crate::blockchain::Blockchain;
+use futures::{SinkExt, StreamExt};
+use hyper::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE};
+use hyper::upgrade::Upgraded;
+use hyper::{Body, Request, Response, StatusCode};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::{handshake::derive_accept_key, protocol::Role, Message};
+use tokio_tungstenite::WebSocketStream;
+
+fn ws_response(subscriptions: &HashSet<&'static str>, error: Option<String>) -> Message {
+    let mut subscriptions = subscriptions
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>();
+    subscriptions.sort();
+    Message::Text(
+        serde_json::to_string(&WsResponse {
+            subscriptions,
+            error,
+        })
+        .unwrap_or_default(),
+    )
+}
+
+// Clients (Un)subscribe by sending `{"subscribe": ["new_block", ...]}`, each
+// request is answered with the current subscriptions
+async fn serve(ws: WebSocketStream<Upgraded>, mut events: broadcast::Receiver<NodeEvent>) {
+    let (mut sink, mut stream) = ws.split();
+    let mut subscriptions = HashSet::<&'static str>::new();
+    loop {
+        tokio::select! {
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let resp = match serde_json::from_str::<WsRequest>(&text) {
+                        Ok(req) => {
+                            for topic in TOPICS {
+                                if req.subscribe.iter().any(|t| t == topic) {
+                                    subscriptions.insert(topic);
+                                }
+                                if req.unsubscribe.iter().any(|t| t == topic) {
+                                    subscriptions.remove(topic);
+                                }
+                            }
+                            ws_response(&subscriptions, None)
+                        }
+                        Err(e) => ws_response(&subscriptions, Some(e.to_string())),
+                    };
+                    if sink.send(resp).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if subscriptions.contains(event.topic()) {
+                        let text = serde_json::to_string(&event).unwrap_or_default();
+                        if sink.send(Message::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                // A slow client misses some of the events, rather than slowing
+                // down the node
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+pub async fn ws<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, NodeError> {
+    let accept = match req.headers().get(SEC_WEBSOCKET_KEY) {
+        Some(key) => derive_accept_key(key.as_bytes()),
+        None => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+            return Ok(response);
+        }
+    };
+    let events = context.events.subscribe();
+    tokio::spawn(async move {
+        match hyper::upgrade::on(&mut req).await {
+            Ok(upgraded) => {
+                serve(
+                    WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await,
+                    events,
+                )
+                .await
+            }
+            Err(e) => tracing::warn!(error = %e, "WebSocket upgrade failed"),
+        }
+    });
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())?)
+}
@@ -0,0 +1,48 @@
+use super::{PeerAddress, Timestamp};
+use crate::core::hash::Hash;
+use crate::core::Hasher;
+use std::collections::HashMap;
+
+pub type ChainTip = <Hasher as Hash>::Output;
+
+// Peers that failed to serve (Or served invalid) blocks of a chain, they are
+// skipped when syncing towards that chain tip, until the entry expires.
+#[derive(Default)]
+pub struct SyncBlacklist {
+    entries: HashMap<(PeerAddress, ChainTip), Timestamp>,
+}
+
+impl SyncBlacklist {
+    pub fn add(&mut self, peer: PeerAddress, tip: ChainTip, expires_at: Timestamp) {
+        self.entries.insert((peer, tip), expires_at);
+    }
+    pub fn contains(&self, peer: PeerAddress, tip: ChainTip, now: Timestamp) -> bool {
+        self.entries
+            .get(&(peer, tip))
+            .map(|expires_at| now < *expires_at)
+            .unwrap_or(false)
+    }
+    pub fn expire(&mut self, now: Timestamp) {
+        self.entries.retain(|_, expires_at| now < *expires_at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_blacklist() {
+        let peer = PeerAddress("127.0.0.1".parse().unwrap(), 3030);
+        let tip = ChainTip::default();
+        let mut blacklist = SyncBlacklist::default();
+        blacklist.add(peer, tip, 100);
+        assert!(blacklist.contains(peer, tip, 50));
+        // Other chains are still synced from the peer
+        let mut other = ChainTip::default();
+        other[0] = 1;
+        assert!(!blacklist.contains(peer, other, 50));
+        blacklist.expire(100);
+        assert!(!blacklist.contains(peer, tip, 50));
+    }
+}
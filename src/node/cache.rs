@@ -0,0 +1,63 @@
+use lru::LruCache;
+use std::sync::{Arc, Mutex};
+
+// Serialized responses of immutable resources (Blocks, header ranges). An
+// entry is keyed by the ETag of its resource, i.e. by its content, so it never
+// needs to be invalidated.
+pub struct ResponseCache {
+    entries: Mutex<LruCache<String, Arc<Vec<u8>>>>,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+    // Serializes the body only when it's not cached already
+    pub fn get_or_insert<E>(
+        &self,
+        etag: &str,
+        serialize: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Arc<Vec<u8>>, E> {
+        if let Some(body) = self.entries.lock().unwrap().get(etag) {
+            return Ok(Arc::clone(body));
+        }
+        let body = Arc::new(serialize()?);
+        self.entries
+            .lock()
+            .unwrap()
+            .put(etag.to_string(), Arc::clone(&body));
+        Ok(body)
+    }
+}
+
+// Whether an `If-None-Match` header value covers the ETag, weak ones included
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::new(1);
+        let body = cache.get_or_insert::<()>("\"a\"", || Ok(vec![1])).unwrap();
+        assert_eq!(*body, vec![1]);
+        // Not serialized again
+        assert!(cache.get_or_insert("\"a\"", || Err(())).is_ok());
+        cache.get_or_insert::<()>("\"b\"", || Ok(vec![2])).unwrap();
+        assert!(cache.get_or_insert("\"a\"", || Err(())).is_err());
+
+        assert!(etag_matches("\"a\"", "\"a\""));
+        assert!(etag_matches("\"x\", W/\"a\"", "\"a\""));
+        assert!(etag_matches("*", "\"a\""));
+        assert!(!etag_matches("\"b\"", "\"a\""));
+        assert!(!etag_matches("", "\"a\""));
+    }
+}
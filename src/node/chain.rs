@@ -0,0 +1,377 @@
+use super::{
+    AnnounceEvent, BlockHash, NodeContext, NodeError, NodeEvent, PeerAddress, INVALID_RECORDS,
+};
+use crate::blockchain::{
+    BlockExecutionResult, Blockchain, BlockchainError, PayoutSplit, ReorgRecord,
+};
+use crate::config;
+use crate::core::{Block, Checkpoint, Transaction};
+use crate::utils;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(feature = "pos")]
+use crate::blockchain::check_slot_drift;
+
+const COMMAND_QUEUE_SIZE: usize = 64;
+
+type Reply<T> = oneshot::Sender<Result<T, BlockchainError>>;
+
+pub enum ChainCommand {
+    Extend {
+        from: usize,
+        blocks: Vec<Block>,
+        // Where the blocks came from, None if produced or submitted locally
+        peer: Option<PeerAddress>,
+        reply: Reply<()>,
+    },
+    ApplyCheckpoint {
+        checkpoint: Checkpoint,
+        reply: Reply<()>,
+    },
+    DraftBlock {
+        timestamp: u32,
+        txs: Vec<Transaction>,
+        payouts: PayoutSplit,
+        reply: Reply<Block>,
+    },
+    Compact {
+        reply: Reply<()>,
+    },
+    Rewind {
+        height: usize,
+        reply: Reply<usize>,
+    },
+    // Rewinds to just before the block, if it's part of the chain
+    Invalidate {
+        hash: BlockHash,
+        reply: Reply<usize>,
+    },
+    UpdateRecords {
+        namespace: String,
+        records: Vec<(String, Option<Vec<u8>>)>,
+        reply: Reply<()>,
+    },
+}
+
+// Cheap, cloneable interface of the chain actor. Mutations are applied one by
+// one in the order they are sent, the rest of the node only reads the chain.
+#[derive(Clone)]
+pub struct ChainHandle {
+    sender: mpsc::Sender<ChainCommand>,
+}
+
+impl ChainHandle {
+    pub fn new() -> (Self, mpsc::Receiver<ChainCommand>) {
+        let (sender, receiver) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        (Self { sender }, receiver)
+    }
+    async fn request<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> ChainCommand,
+    ) -> Result<T, NodeError> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(command(reply))
+            .await
+            .map_err(|_| NodeError::ChainActorStopped)?;
+        Ok(response.await.map_err(|_| NodeError::ChainActorStopped)??)
+    }
+    pub async fn extend(&self, from: usize, blocks: Vec<Block>) -> Result<(), NodeError> {
+        self.request(|reply| ChainCommand::Extend {
+            from,
+            blocks,
+            peer: None,
+            reply,
+        })
+        .await
+    }
+    // Same as `extend`, for blocks fetched from a peer
+    pub async fn extend_from(
+        &self,
+        peer: PeerAddress,
+        from: usize,
+        blocks: Vec<Block>,
+    ) -> Result<(), NodeError> {
+        self.request(|reply| ChainCommand::Extend {
+            from,
+            blocks,
+            peer: Some(peer),
+            reply,
+        })
+        .await
+    }
+    pub async fn apply_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), NodeError> {
+        self.request(|reply| ChainCommand::ApplyCheckpoint { checkpoint, reply })
+            .await
+    }
+    pub async fn draft_block(
+        &self,
+        timestamp: u32,
+        txs: Vec<Transaction>,
+        payouts: PayoutSplit,
+    ) -> Result<Block, NodeError> {
+        self.request(|reply| ChainCommand::DraftBlock {
+            timestamp,
+            txs,
+            payouts,
+            reply,
+        })
+        .await
+    }
+    pub async fn compact(&self) -> Result<(), NodeError> {
+        self.request(|reply| ChainCommand::Compact { reply }).await
+    }
+    pub async fn rewind(&self, height: usize) -> Result<usize, NodeError> {
+        self.request(|reply| ChainCommand::Rewind { height, reply })
+            .await
+    }
+    pub async fn invalidate(&self, hash: BlockHash) -> Result<usize, NodeError> {
+        self.request(|reply| ChainCommand::Invalidate { hash, reply })
+            .await
+    }
+    pub async fn update_records(
+        &self,
+        namespace: &str,
+        records: Vec<(String, Option<Vec<u8>>)>,
+    ) -> Result<(), NodeError> {
+        self.request(|reply| ChainCommand::UpdateRecords {
+            namespace: namespace.into(),
+            records,
+            reply,
+        })
+        .await
+    }
+}
+
+// Replacing blocks is only allowed when the new branch is heavier, which is
+// checked again here since the chain might have changed since the caller looked.
+fn extend_if_heavier<B: Blockchain>(
+    chain: &mut B,
+    from: usize,
+    blocks: &Vec<Block>,
+) -> Result<Vec<BlockExecutionResult>, BlockchainError> {
+    if from < chain.get_height()? {
+        let headers = blocks.iter().map(|b| b.header.clone()).collect();
+        if !chain.will_extend(from, &headers)? {
+            chain.store_side_blocks(blocks)?;
+            return Err(BlockchainError::WeakerChain);
+        }
+    }
+    chain.extend(from, blocks)
+}
+
+fn tip_hash<B: Blockchain>(chain: &B) -> Result<String, BlockchainError> {
+    let height = chain.get_height()?;
+    let tip = chain.get_headers(height - 1, Some(height))?;
+    Ok(tip
+        .first()
+        .map(|h| hex::encode(h.hash()))
+        .unwrap_or_default())
+}
+
+// A reorg missing from the journal is no reason to fail the chain operation
+fn journal_reorg<B: Blockchain>(
+    chain: &mut B,
+    old_tip: String,
+    fork_height: usize,
+    depth: usize,
+    peer: Option<PeerAddress>,
+) {
+    let result = tip_hash(chain).and_then(|new_tip| {
+        let record = ReorgRecord {
+            old_tip,
+            new_tip,
+            fork_height,
+            depth,
+            timestamp: utils::local_timestamp(),
+            peer: peer.map(|p| format!("{}:{}", p.0, p.1)),
+        };
+        chain.record_reorg(record, config::REORG_JOURNAL_SIZE)
+    });
+    if let Err(e) = result {
+        tracing::error!(error = %e, "Could not record reorg");
+    }
+}
+
+// Blocks building on a side-chain we already have are completed with its
+// blocks, so that the whole branch is weighed against ours. The tip of the used
+// side-chain is returned along.
+fn with_side_branch<B: Blockchain>(
+    chain: &B,
+    from: usize,
+    blocks: Vec<Block>,
+) -> Result<(usize, Vec<Block>, Option<BlockHash>), BlockchainError> {
+    if let Some(first) = blocks.first() {
+        let side_tip = first.header.parent_hash;
+        if let Some((fork_height, mut branch)) = chain.get_side_branch(&side_tip)? {
+            branch.extend(blocks);
+            return Ok((fork_height, branch, Some(side_tip)));
+        }
+    }
+    Ok((from, blocks, None))
+}
+
+fn rewind<B: Blockchain>(
+    context: &NodeContext<B>,
+    chain: &mut B,
+    height: usize,
+) -> Result<usize, BlockchainError> {
+    let old_height = chain.get_height()?;
+    let old_tip = tip_hash(chain)?;
+    let rolled_back = chain.rewind(height)?;
+    if rolled_back > 0 {
+        journal_reorg(chain, old_tip, height, rolled_back, None);
+        let _ = context.events.send(NodeEvent::Reorg {
+            fork_height: height,
+            old_height,
+            new_height: height,
+        });
+    }
+    Ok(rolled_back)
+}
+
+// The chain itself doesn't look at the clock, blocks claiming slots too far
+// ahead of ours are refused here
+#[cfg(feature = "pos")]
+fn check_slots(blocks: &[Block], timestamp: u32) -> Result<(), BlockchainError> {
+    blocks
+        .iter()
+        .try_for_each(|b| check_slot_drift(&b.header, timestamp))
+}
+
+// Nobody listening is not an error
+fn publish_blocks<B: Blockchain>(
+    context: &NodeContext<B>,
+    from: usize,
+    old_height: usize,
+    blocks: &[Block],
+    results: &[BlockExecutionResult],
+) {
+    if from < old_height {
+        let _ = context.events.send(NodeEvent::Reorg {
+            fork_height: from,
+            old_height,
+            new_height: from + blocks.len(),
+        });
+    }
+    for (block, result) in blocks.iter().zip(results.iter()) {
+        let _ = context.events.send(NodeEvent::NewBlock {
+            hash: hex::encode(block.header.hash()),
+            header: block.header.clone(),
+            fees: result.fees,
+            weight: result.weight,
+        });
+    }
+}
+
+pub async fn chain_actor<B: Blockchain>(
+    context: Arc<NodeContext<B>>,
+    mut commands: mpsc::Receiver<ChainCommand>,
+) -> Result<(), NodeError> {
+    while let Some(command) = commands.recv().await {
+        // A dropped reply only means the requester is gone
+        match command {
+            ChainCommand::Extend {
+                from,
+                blocks,
+                peer,
+                reply,
+            } => {
+                #[cfg(feature = "pos")]
+                if let Err(e) = check_slots(&blocks, context.network_timestamp()) {
+                    let _ = reply.send(Err(e));
+                    continue;
+                }
+                let mut chain = context.blockchain.write().await;
+                let old_height = chain.get_height().unwrap_or_default();
+                let (from, blocks, side_tip) = match with_side_branch(&*chain, from, blocks) {
+                    Ok(branch) => branch,
+                    Err(e) => {
+                        let _ = reply.send(Err(e));
+                        continue;
+                    }
+                };
+                // Only looked up when blocks are going to be replaced
+                let old_tip = if from < old_height {
+                    tip_hash(&*chain).unwrap_or_default()
+                } else {
+                    String::new()
+                };
+                let result = match (extend_if_heavier(&mut *chain, from, &blocks), side_tip) {
+                    // The stored blocks came from other peers, so the failure
+                    // is not blamed on this one. The branch is dropped instead.
+                    (Err(e), Some(side_tip)) if e.is_invalid_block() => {
+                        if let Err(e) = chain.drop_side_branch(&side_tip) {
+                            tracing::error!(error = %e, "Could not drop side-chain");
+                        }
+                        Err(BlockchainError::InvalidSideBranch(Box::new(e)))
+                    }
+                    (result, _) => result,
+                };
+                if result.is_ok() && from < old_height {
+                    journal_reorg(&mut *chain, old_tip, from, old_height - from, peer);
+                }
+                if let Ok(results) = &result {
+                    publish_blocks(&context, from, old_height, &blocks, results);
+                    // The blocks are now our tip
+                    if let Some(tip) = blocks.last() {
+                        context
+                            .announcer
+                            .send(AnnounceEvent::Tip(tip.header.clone()));
+                    }
+                }
+                let _ = reply.send(result.map(|_| ()));
+            }
+            ChainCommand::ApplyCheckpoint { checkpoint, reply } => {
+                let mut chain = context.blockchain.write().await;
+                let _ = reply.send(chain.apply_checkpoint(&checkpoint));
+            }
+            ChainCommand::DraftBlock {
+                timestamp,
+                txs,
+                payouts,
+                reply,
+            } => {
+                let chain = context.blockchain.read().await;
+                let _ = reply.send(chain.draft_block(timestamp, &txs, &payouts));
+            }
+            ChainCommand::Rewind { height, reply } => {
+                let mut chain = context.blockchain.write().await;
+                let _ = reply.send(rewind(&context, &mut *chain, height));
+            }
+            ChainCommand::Invalidate { hash, reply } => {
+                let mut chain = context.blockchain.write().await;
+                let result = match chain.get_block_height(&hash) {
+                    Ok(Some(height)) => rewind(&context, &mut *chain, height),
+                    Ok(None) => Ok(0),
+                    Err(e) => Err(e),
+                };
+                // Only once the block is gone, but before the chain is unlocked,
+                // so that it isn't synced again right after the rewind
+                if result.is_ok() {
+                    let records = context
+                        .remember_invalid(hash, "Invalidated by the operator".into())
+                        .await;
+                    if let Err(e) = chain.update_records(INVALID_RECORDS, records) {
+                        tracing::error!(error = %e, "Could not persist invalid block");
+                    }
+                }
+                let _ = reply.send(result);
+            }
+            ChainCommand::Compact { reply } => {
+                let mut chain = context.blockchain.write().await;
+                let _ = reply.send(chain.compact());
+            }
+            ChainCommand::UpdateRecords {
+                namespace,
+                records,
+                reply,
+            } => {
+                let mut chain = context.blockchain.write().await;
+                let _ = reply.send(chain.update_records(&namespace, records));
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,69 +1,414 @@
-use super::{PeerAddress, PeerInfo, PeerStats};
-use crate::blockchain::{Blockchain, BlockchainError};
-use crate::core::Transaction;
+use super::{
+    AnchorPeers, AnnounceHandle, ApiMetrics, BlockHash, ChainHandle, ChainTip, InvalidBlocks,
+    IpStats, Mempool, NodeError, NodeEvent, NodeRole, PeerAddress, PeerInfo, PeerStats,
+    RelayConfig, RequestScheduler, ResponseCache, SyncBlacklist, INVALID_RECORDS,
+};
+use crate::blockchain::{Blockchain, BlockchainError, PayoutSplit};
+use crate::config;
+use crate::core::{Alert, Header};
+use crate::crypto::EdDSAPublicKey;
 use crate::utils;
 use crate::wallet::Wallet;
 use rand::seq::IteratorRandom;
 use rand::RngCore;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use tokio::sync::{broadcast, Notify, RwLock};
 
 #[cfg(feature = "pow")]
-use {super::api::messages::Puzzle, crate::core::Block};
+use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
-pub struct TransactionStats {
-    pub first_seen: u32,
-}
+#[cfg(feature = "pow")]
+use {super::api::messages::Puzzle, crate::blockchain::BlockTimeStats, crate::core::Block};
 
 #[cfg(feature = "pow")]
 pub struct Miner {
-    pub block: Option<Block>,
     pub webhook: String,
 }
 
+// The block currently being mined, shared by all of the registered miners.
+// The first valid solution wins.
+#[cfg(feature = "pow")]
+pub struct MiningTemplate {
+    pub block: Block,
+    pub puzzle: Puzzle,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolutionStatus {
+    Accepted,
+    // Solution for a block that is no longer being mined
+    Stale,
+    Invalid,
+}
+
+#[cfg(feature = "pow")]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MinerStats {
+    pub accepted: u64,
+    pub stale: u64,
+    pub invalid: u64,
+    // Approximate number of hashes behind the accepted solutions
+    pub work: u64,
+    pub first_solution: u32,
+    pub last_solution: u32,
+    // Effective hashes per second, estimated from the accepted work
+    pub hashrate: f64,
+}
+
+#[cfg(feature = "pow")]
+impl MinerStats {
+    pub fn record(&mut self, status: SolutionStatus, work: u64, timestamp: u32) {
+        match status {
+            SolutionStatus::Accepted => {
+                self.accepted += 1;
+                self.work = self.work.saturating_add(work);
+            }
+            SolutionStatus::Stale => {
+                self.stale += 1;
+            }
+            SolutionStatus::Invalid => {
+                self.invalid += 1;
+            }
+        }
+        if self.first_solution == 0 {
+            self.first_solution = timestamp;
+        }
+        self.last_solution = timestamp;
+        // The clock may go backwards (e.g. network time adjustments)
+        let elapsed = self.last_solution.saturating_sub(self.first_solution);
+        if elapsed > 0 {
+            self.hashrate = self.work as f64 / elapsed as f64;
+        }
+    }
+}
+
+// Everything related to the registered miners, locked as a single component
+#[cfg(feature = "pow")]
+#[derive(Default)]
+pub struct MinerState {
+    pub miners: HashMap<String, Miner>,
+    pub mining_template: Option<MiningTemplate>,
+    pub miner_stats: HashMap<String, MinerStats>,
+}
+
+#[cfg(feature = "pow")]
+impl MinerState {
+    pub fn record_solution(
+        &mut self,
+        miner: String,
+        status: SolutionStatus,
+        work: u64,
+        timestamp: u32,
+    ) {
+        self.miner_stats
+            .entry(miner)
+            .or_default()
+            .record(status, work, timestamp);
+    }
+    pub fn is_template_fresh(&self, height: usize) -> bool {
+        match &self.mining_template {
+            Some(t) => t.block.header.number as usize == height,
+            None => false,
+        }
+    }
+}
+
+// Components are locked independently, so that a slow chain update doesn't
+// stall the peer handling. When more than one lock is needed at once, they
+// should be acquired in the order of the fields, to avoid deadlocks.
 pub struct NodeContext<B: Blockchain> {
-    pub blockchain: B,
-    pub wallet: Option<Wallet>,
-    pub mempool: HashMap<Transaction, TransactionStats>,
-    pub peers: HashMap<PeerAddress, PeerStats>,
-    pub timestamp_offset: i32,
     #[cfg(feature = "pow")]
-    pub miner: Option<Miner>,
+    pub miner: RwLock<MinerState>,
+    // Only the chain actor writes, mutations should be requested through `chain`
+    pub blockchain: RwLock<B>,
+    pub chain: ChainHandle,
+    pub announcer: AnnounceHandle,
+    pub mempool: RwLock<Mempool>,
+    pub peers: RwLock<HashMap<PeerAddress, PeerStats>>,
+    pub ip_stats: RwLock<HashMap<IpAddr, IpStats>>,
+    pub alerts: RwLock<HashMap<u32, Alert>>,
+    pub anchors: RwLock<AnchorPeers>,
+    pub sync_blacklist: RwLock<SyncBlacklist>,
+    pub invalid_blocks: RwLock<InvalidBlocks>,
+    // Tip of the longest chain whose headers we have verified, unlike the
+    // heights peers claim to have
+    pub best_header: RwLock<Option<Header>>,
+    pub events: broadcast::Sender<NodeEvent>,
+    // Lets the miner notifier send the current puzzle to a newly registered
+    // miner without waiting for the next block
+    #[cfg(feature = "pow")]
+    pub miner_registered: Notify,
+    pub timestamp_offset: AtomicI32,
+    pub peers_persisted_at: AtomicU32,
+    pub blocks_polled_at: AtomicU32,
+    pub wallet: Option<Wallet>,
+    // Where block rewards go, the wallet address if not set
+    pub payouts: Option<PayoutSplit>,
+    pub relay: Option<RelayConfig>,
+    // Required by the admin endpoints that modify the chain, which are
+    // disabled when not set
+    pub admin_token: Option<String>,
+    pub scheduler: RequestScheduler,
+    pub metrics: ApiMetrics,
+    pub response_cache: ResponseCache,
+    pub role: NodeRole,
 }
 
 impl<B: Blockchain> NodeContext<B> {
     pub fn network_timestamp(&self) -> u32 {
-        (utils::local_timestamp() as i32 + self.timestamp_offset) as u32
+        (utils::local_timestamp() as i32 + self.timestamp_offset.load(Ordering::Relaxed)) as u32
+    }
+    pub fn set_timestamp_offset(&self, offset: i32) {
+        self.timestamp_offset.store(offset, Ordering::Relaxed);
     }
-    pub fn punish(&mut self, bad_peer: PeerAddress, secs: u32) {
+    // Compared in constant time, not to leak the token
+    pub fn is_admin(&self, token: &str) -> bool {
+        match &self.admin_token {
+            Some(expected) if !expected.is_empty() => {
+                expected.len() == token.len()
+                    && expected
+                        .bytes()
+                        .zip(token.bytes())
+                        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                        == 0
+            }
+            _ => false,
+        }
+    }
+    pub async fn punish(&self, bad_peer: PeerAddress, secs: u32) {
         self.peers
+            .write()
+            .await
             .entry(bad_peer.clone())
             .and_modify(|stats| stats.punish(secs));
     }
-    pub fn get_info(&self) -> Result<PeerInfo, BlockchainError> {
+    pub async fn misbehave(&self, bad_peer: PeerAddress, score: u32) {
+        if let Some(stats) = self.peers.write().await.get_mut(&bad_peer) {
+            if stats.misbehave(score) {
+                tracing::warn!(peer = %bad_peer, "Banned peer for misbehaving");
+            }
+        }
+    }
+    // Whether an announcement of a known peer is taken, at most one per
+    // ANNOUNCE_MIN_INTERVAL
+    pub async fn accept_announcement(&self, from: PeerAddress) -> bool {
+        let now = utils::local_timestamp();
+        match self.peers.write().await.get_mut(&from) {
+            Some(stats)
+                if !stats.is_banned()
+                    && now.saturating_sub(stats.announced_at) >= config::ANNOUNCE_MIN_INTERVAL =>
+            {
+                stats.announced_at = now;
+                true
+            }
+            _ => false,
+        }
+    }
+    // Inbound connections don't reveal the listening port of the peer, so the
+    // address gets scored on its own, not any of the peers behind it
+    pub async fn misbehave_ip(&self, ip: IpAddr, score: u32) {
+        let mut ip_stats = self.ip_stats.write().await;
+        if !ip_stats.contains_key(&ip) && ip_stats.len() >= config::punish::MAX_TRACKED_IPS {
+            ip_stats.retain(|_, stats| stats.is_banned());
+            if ip_stats.len() >= config::punish::MAX_TRACKED_IPS {
+                return;
+            }
+        }
+        if ip_stats.entry(ip).or_default().misbehave(score) {
+            tracing::warn!(%ip, "Banned address for misbehaving");
+        }
+    }
+    pub async fn is_banned(&self, ip: IpAddr) -> bool {
+        let peer_banned = self
+            .peers
+            .read()
+            .await
+            .iter()
+            .any(|(addr, stats)| addr.0 == ip && stats.is_banned());
+        peer_banned
+            || self
+                .ip_stats
+                .read()
+                .await
+                .get(&ip)
+                .map(|stats| stats.is_banned())
+                .unwrap_or(false)
+    }
+    pub async fn banned_peers(&self) -> Vec<(PeerAddress, PeerStats)> {
+        self.peers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, stats)| stats.is_banned())
+            .map(|(addr, stats)| (*addr, stats.clone()))
+            .collect()
+    }
+    pub async fn blacklist_sync_source(&self, peer: PeerAddress, tip: ChainTip) {
+        let now = utils::local_timestamp();
+        let mut blacklist = self.sync_blacklist.write().await;
+        blacklist.expire(now);
+        blacklist.add(peer, tip, now + config::SYNC_BLACKLIST_TTL);
+    }
+    pub async fn is_blacklisted_source(&self, peer: PeerAddress, tip: ChainTip) -> bool {
+        self.sync_blacklist
+            .read()
+            .await
+            .contains(peer, tip, utils::local_timestamp())
+    }
+    // The reason, if any of the blocks is known to be invalid
+    pub async fn known_invalid(&self, hashes: impl Iterator<Item = BlockHash>) -> Option<String> {
+        let invalid = self.invalid_blocks.read().await;
+        for hash in hashes {
+            if let Some(reason) = invalid.get(&hash) {
+                return Some(reason.clone());
+            }
+        }
+        None
+    }
+    pub async fn mark_invalid(&self, hash: BlockHash, reason: String) {
+        let records = self.remember_invalid(hash, reason).await;
+        if let Err(e) = self.chain.update_records(INVALID_RECORDS, records).await {
+            tracing::error!(error = %e, "Could not persist invalid block");
+        }
+    }
+    // Caches the block as invalid, returns the records persisting it. For the
+    // chain actor, which can't wait for itself through `mark_invalid`.
+    pub async fn remember_invalid(
+        &self,
+        hash: BlockHash,
+        reason: String,
+    ) -> Vec<(String, Option<Vec<u8>>)> {
+        tracing::warn!(block = %hex::encode(hash), reason = %reason, "Invalid block");
+        let evicted = self
+            .invalid_blocks
+            .write()
+            .await
+            .insert(hash, reason.clone());
+        let mut best = self.best_header.write().await;
+        if best.as_ref().map(|h| h.hash()) == Some(hash) {
+            *best = None;
+        }
+        let mut records = vec![(hex::encode(hash), Some(reason.into_bytes()))];
+        records.extend(evicted.into_iter().map(|h| (hex::encode(h), None)));
+        records
+    }
+    // Headers should pass `will_extend` before being reported here
+    pub async fn add_verified_header(&self, header: &Header) {
+        let mut best = self.best_header.write().await;
+        if best
+            .as_ref()
+            .map(|h| h.number < header.number)
+            .unwrap_or(true)
+        {
+            *best = Some(header.clone());
+        }
+    }
+    // Height of the longest chain known to be valid, ours or a peer's
+    pub async fn best_known_height(&self) -> Result<usize, BlockchainError> {
+        let height = self.blockchain.read().await.get_height()?;
+        Ok(match self.best_header.read().await.as_ref() {
+            Some(h) => std::cmp::max(height, h.number as usize + 1),
+            None => height,
+        })
+    }
+    pub async fn get_info(&self) -> Result<PeerInfo, BlockchainError> {
+        let chain = self.blockchain.read().await;
         Ok(PeerInfo {
-            height: self.blockchain.get_height()?,
+            height: chain.get_height()?,
             #[cfg(feature = "pow")]
-            power: self.blockchain.get_power()?,
+            power: chain.get_power()?,
+            version: config::NODE_VERSION.into(),
+            user_agent: config::USER_AGENT.into(),
         })
     }
-    pub fn random_peers<R: RngCore>(
+    // Number of active peers running each version, for measuring upgrade
+    // adoption before activating consensus changes.
+    pub async fn peer_versions(&self) -> HashMap<String, usize> {
+        let mut versions = HashMap::new();
+        for stats in self.active_peers().await.values() {
+            if let Some(inf) = &stats.info {
+                let version = if inf.version.is_empty() {
+                    "unknown".into()
+                } else {
+                    inf.version.clone()
+                };
+                *versions.entry(version).or_insert(0) += 1;
+            }
+        }
+        versions
+    }
+    // Robust estimation of the network height based on recent peer reports,
+    // a single peer can't fool us by claiming a huge height. Only good for
+    // display, nothing proves the reports.
+    pub async fn network_height(&self) -> Option<usize> {
+        let now = utils::local_timestamp();
+        let heights = self
+            .active_peers()
+            .await
+            .values()
+            .filter(|s| s.last_seen + config::NETWORK_HEIGHT_WINDOW >= now)
+            .filter_map(|s| s.info.as_ref().map(|inf| inf.height))
+            .collect::<Vec<_>>();
+        utils::robust_median(&heights)
+    }
+    pub async fn is_syncing(&self) -> Result<bool, BlockchainError> {
+        let height = self.blockchain.read().await.get_height()?;
+        Ok(height + config::SYNC_MARGIN < self.best_known_height().await?)
+    }
+    pub async fn add_alert(&self, alert: Alert) -> bool {
+        match config::ALERT_PUBLIC_KEY.parse() {
+            Ok(alert_key) => self.add_alert_signed_by(alert, &alert_key).await,
+            Err(_) => false,
+        }
+    }
+    pub(crate) async fn add_alert_signed_by(
+        &self,
+        alert: Alert,
+        alert_key: &EdDSAPublicKey,
+    ) -> bool {
+        let mut alerts = self.alerts.write().await;
+        if alert.expires_at < self.network_timestamp()
+            || alerts.contains_key(&alert.id)
+            || !alert.verify_signature(alert_key)
+        {
+            return false;
+        }
+        tracing::warn!(id = alert.id, message = %alert.message, "Network alert");
+        alerts.insert(alert.id, alert);
+        true
+    }
+    pub async fn active_alerts(&self) -> Vec<Alert> {
+        let now = self.network_timestamp();
+        self.alerts
+            .read()
+            .await
+            .values()
+            .filter(|a| a.expires_at >= now)
+            .cloned()
+            .collect()
+    }
+    pub async fn random_peers<R: RngCore>(
         &self,
         rng: &mut R,
         count: usize,
     ) -> HashMap<PeerAddress, PeerStats> {
         self.active_peers()
-            .clone()
+            .await
             .into_iter()
             .choose_multiple(rng, count)
             .into_iter()
             .collect()
     }
-    pub fn active_peers(&self) -> HashMap<PeerAddress, PeerStats> {
+    pub async fn active_peers(&self) -> HashMap<PeerAddress, PeerStats> {
         self.peers
+            .read()
+            .await
             .iter()
             .filter_map(|(k, v)| {
-                if !v.is_punished() {
+                if !v.is_punished() && !v.is_banned() {
                     Some((k.clone(), v.clone()))
                 } else {
                     None
@@ -73,12 +418,58 @@ impl<B: Blockchain> NodeContext<B> {
     }
 
     #[cfg(feature = "pow")]
-    pub fn get_puzzle(&self, wallet: Wallet) -> Result<(Block, Puzzle), BlockchainError> {
-        let txs = self.mempool.keys().cloned().collect();
+    pub async fn block_time_stats(&self) -> Result<Option<BlockTimeStats>, BlockchainError> {
+        let chain = self.blockchain.read().await;
+        let height = chain.get_height()?;
+        // Genesis block timestamp is not a real solve time
+        let since = std::cmp::max(1, height.saturating_sub(config::DIFFICULTY_CALC_INTERVAL));
+        Ok(BlockTimeStats::from_headers(
+            &chain.get_headers(since, None)?,
+        ))
+    }
+
+    // Returns the puzzle of the current mining template, a new template is
+    // drafted when there is none or when the chain has moved on.
+    #[cfg(feature = "pow")]
+    pub async fn current_puzzle(&self, wallet: Wallet) -> Result<Puzzle, NodeError> {
+        let mut miner = self.miner.write().await;
+        if !miner.is_template_fresh(self.blockchain.read().await.get_height()?) {
+            let (block, puzzle) = self.get_puzzle(wallet).await?;
+            miner.mining_template = Some(MiningTemplate { block, puzzle });
+        }
+        Ok(miner.mining_template.as_ref().unwrap().puzzle.clone())
+    }
+
+    // Whether the header extends the current tip. Solutions for anything else
+    // are stale, even when the height matches (E.g. after a reorg).
+    #[cfg(feature = "pow")]
+    pub async fn builds_on_tip(&self, header: &Header) -> Result<bool, BlockchainError> {
+        let chain = self.blockchain.read().await;
+        let height = chain.get_height()?;
+        Ok(header.number as usize == height
+            && chain
+                .get_headers(height - 1, Some(height))?
+                .last()
+                .map(|tip| tip.hash())
+                == Some(header.parent_hash))
+    }
+
+    #[cfg(feature = "pow")]
+    pub async fn get_puzzle(&self, wallet: Wallet) -> Result<(Block, Puzzle), NodeError> {
+        let txs = self.mempool.read().await.transactions();
         let ts = self.network_timestamp();
-        let block = self.blockchain.draft_block(ts, &txs, &wallet)?;
+        let payouts = self
+            .payouts
+            .clone()
+            .unwrap_or_else(|| PayoutSplit::single(wallet.get_address()));
+        let block = self.chain.draft_block(ts, txs, payouts).await?;
+        let key = self
+            .blockchain
+            .read()
+            .await
+            .pow_key(block.header.number as usize)?;
         let puzzle = Puzzle {
-            key: hex::encode(self.blockchain.pow_key(block.header.number as usize)?),
+            key: hex::encode(key),
             blob: hex::encode(bincode::serialize(&block.header).unwrap()),
             offset: 112,
             size: 8,
@@ -15,14 +15,32 @@ pub enum NodeError {
     QueryStringError(#[from] serde_qs::Error),
     #[error("bincode error happened: {0}")]
     BincodeError(#[from] bincode::Error),
+    #[error("io error happened: {0}")]
+    IoError(#[from] std::io::Error),
     #[error("utf8 error happened: {0}")]
     Utf8Error(#[from] std::str::Utf8Error),
     #[error("addr parse error happened: {0}")]
     AddrParseError(#[from] std::net::AddrParseError),
+    #[error("hex error happened: {0}")]
+    HexError(#[from] hex::FromHexError),
+    #[error("invalid transaction hash")]
+    InvalidTransactionHash,
+    #[error("invalid block hash")]
+    InvalidBlockHash,
+    #[error("invalid address")]
+    InvalidAddress,
+    #[error("peer failed the handshake challenge")]
+    HandshakeFailed,
+    #[error("block is known to be invalid: {0}")]
+    KnownInvalidBlock(String),
+    #[error("chain actor has stopped")]
+    ChainActorStopped,
     #[error("no wallet available")]
     NoWalletError,
-    #[error("no miner is registered")]
+    #[error("miner is not registered")]
     NoMinerError,
+    #[error("solution doesn't specify the miner who found it")]
+    UnidentifiedMinerError,
     #[error("no block is currently being mined")]
     NoCurrentlyMiningBlockError,
 }
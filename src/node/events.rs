@@ -0,0 +1,37 @@
+use crate::core::{Header, Money, Transaction};
+use serde_derive::Serialize;
+
+// Pushed to the WebSocket subscribers, the tag is also the name of the topic
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NodeEvent {
+    NewBlock {
+        hash: String,
+        header: Header,
+        fees: Money,
+        weight: u64,
+    },
+    NewTransaction {
+        hash: String,
+        tx: Transaction,
+    },
+    // Blocks above `fork_height` have been replaced, `new_block` events of the
+    // new branch follow
+    Reorg {
+        fork_height: usize,
+        old_height: usize,
+        new_height: usize,
+    },
+}
+
+pub const TOPICS: &[&str] = &["new_block", "new_transaction", "reorg"];
+
+impl NodeEvent {
+    pub fn topic(&self) -> &'static str {
+        match self {
+            NodeEvent::NewBlock { .. } => "new_block",
+            NodeEvent::NewTransaction { .. } => "new_transaction",
+            NodeEvent::Reorg { .. } => "reorg",
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+use crate::config::{BLOCK_FETCH_TIMEOUT, MAX_BLOCK_FETCH, MAX_CONCURRENT_FETCHES};
+use crate::core::{Block, Header};
+use futures::stream::{self, StreamExt};
+use tokio::time::timeout;
+
+// Downloads the bodies of already validated headers. The range is split into
+// chunks of MAX_BLOCK_FETCH blocks, which are requested from different peers at
+// the same time (At most MAX_CONCURRENT_FETCHES at once). A chunk that fails,
+// times out or doesn't match its headers is re-requested from the next peer.
+// Returns the blocks of the chunks that could be fetched, up to the first one
+// that couldn't. Peers failing a chunk are blacklisted as sources of `tip`.
+pub async fn fetch_blocks<B: Blockchain>(
+    context: &Arc<NodeContext<B>>,
+    preferred: &[PeerAddress],
+    tip: ChainTip,
+    since: usize,
+    headers: &[Header],
+) -> Vec<Block> {
+    // Peers known to be on the same branch are asked first
+    let mut peers = preferred.to_vec();
+    for peer in context.active_peers().await.into_keys() {
+        if !peers.contains(&peer) && !context.is_blacklisted_source(peer, tip).await {
+            peers.push(peer);
+        }
+    }
+    let chunks = stream::iter(headers.chunks(MAX_BLOCK_FETCH).enumerate())
+        .map(|(i, chunk)| fetch_chunk(context, &peers, tip, i, since + i * MAX_BLOCK_FETCH, chunk))
+        .buffered(MAX_CONCURRENT_FETCHES)
+        .collect::<Vec<_>>()
+        .await;
+    chunks
+        .into_iter()
+        .take_while(|chunk| chunk.is_some())
+        .flatten()
+        .flatten()
+        .collect()
+}
+
+async fn fetch_chunk<B: Blockchain>(
+    context: &Arc<NodeContext<B>>,
+    peers: &[PeerAddress],
+    tip: ChainTip,
+    index: usize,
+    since: usize,
+    headers: &[Header],
+) -> Option<Vec<Block>> {
+    // Chunks start from different peers, so that the load is spread
+    for i in 0..peers.len() {
+        let peer = peers[(index + i) % peers.len()];
+        // Might have failed another chunk in the meantime
+        if context.is_blacklisted_source(peer, tip).await {
+            continue;
+        }
+        let resp = timeout(
+            Duration::from_secs(BLOCK_FETCH_TIMEOUT),
+            http::bincode_get::<GetBlocksRequest, GetBlocksResponse>(
+                format!("{}/bincode/blocks", peer).to_string(),
+                GetBlocksRequest {
+                    since,
+                    until: Some(since + headers.len()),
+                },
+            ),
+        )
+        .await;
+        match resp {
+            Ok(Ok(resp)) => {
+                let matches = resp.blocks.len() == headers.len()
+                    && resp
+                        .blocks
+                        .iter()
+                        .zip(headers.iter())
+                        .all(|(b, h)| b.header.hash() == h.hash());
+                if matches {
+                    return Some(resp.blocks);
+                }
+                context
+                    .misbehave(peer, punish::MALFORMED_RESPONSE_SCORE)
+                    .await;
+            }
+            Ok(Err(NodeError::BincodeError(_))) => {
+                context.punish(peer, punish::NO_RESPONSE_PUNISH).await;
+                context
+                    .misbehave(peer, punish::MALFORMED_RESPONSE_SCORE)
+                    .await;
+            }
+            _ => {
+                context.punish(peer, punish::NO_RESPONSE_PUNISH).await;
+            }
+        }
+        context.blacklist_sync_source(peer, tip).await;
+    }
+    None
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+pub async fn keep_anchors<B: Blockchain>(
+    address: PeerAddress,
+    context: &Arc<NodeContext<B>>,
+) -> Result<(), NodeError> {
+    let now = utils::local_timestamp();
+    let timestamp = context.network_timestamp();
+    let info = context.get_info().await?;
+    let anchors = context.anchors.read().await.due(now);
+
+    let anchor_responses: Vec<(PeerAddress, Result<PostPeerResponse, NodeError>)> =
+        http::group_request(&anchors, |peer| {
+            http::json_post::<PostPeerRequest, PostPeerResponse>(
+                format!("{}/peers", peer).to_string(),
+                PostPeerRequest {
+                    address: address.clone(),
+                    timestamp,
+                    info: info.clone(),
+                },
+            )
+        })
+        .await;
+
+    {
+        let mut peers = context.peers.write().await;
+        let mut anchors = context.anchors.write().await;
+        for (peer, resp) in anchor_responses.into_iter() {
+            match resp {
+                Ok(resp) => {
+                    anchors.mark_success(&peer, now);
+                    // Bring the anchor back if it has been forgotten
+                    peers
+                        .entry(peer)
+                        .or_insert(PeerStats {
+                            punished_until: 0,
+                            info: None,
+                            last_seen: 0,
+                            misbehavior: 0,
+                            banned_until: 0,
+                            added: now,
+                            announced_at: 0,
+                        })
+                        .update_info(resp.info);
+                }
+                Err(_) => {
+                    anchors.mark_failure(&peer, now);
+                }
+            }
+        }
+
+        // Promote peers that have recently answered us to anchors
+        if !anchors.is_full() {
+            let candidates = peers
+                .iter()
+                .filter(|(_, stats)| !stats.is_punished() && stats.info.is_some())
+                .map(|(addr, _)| addr.clone())
+                .collect::<Vec<_>>();
+            let mut changed = false;
+            for addr in candidates {
+                changed |= anchors.add(addr);
+            }
+            if changed {
+                anchors.save()?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,24 +1,38 @@
 use super::*;
 
-pub async fn log_info<B: Blockchain>(
-    context: &Arc<RwLock<NodeContext<B>>>,
-) -> Result<(), NodeError> {
-    let ctx = context.read().await;
-    let mut inf = Vec::new();
-    inf.extend([
-        (
-            "Height".to_string(),
-            ctx.blockchain.get_height()?.to_string(),
-        ),
-        ("Timestamp".to_string(), ctx.network_timestamp().to_string()),
-        (
-            "Active peers".to_string(),
-            ctx.active_peers().len().to_string(),
-        ),
-    ]);
+pub async fn log_info<B: Blockchain>(context: &Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    // Lock guards are not kept in temporaries, as they would live until the end of
+    // the whole statement
+    let height = context.blockchain.read().await.get_height()?;
+    let mempool_size = context.mempool.read().await.len();
     #[cfg(feature = "pow")]
-    inf.push(("Power".to_string(), ctx.blockchain.get_power()?.to_string()));
-    println!("Lub dub! {:?}", inf);
+    let power = Some(context.blockchain.read().await.get_power()?);
+    #[cfg(not(feature = "pow"))]
+    let power: Option<u64> = None;
+    tracing::info!(
+        height,
+        best_known_height = context.best_known_height().await?,
+        network_height = ?context.network_height().await,
+        syncing = context.is_syncing().await?,
+        timestamp = context.network_timestamp(),
+        active_peers = context.active_peers().await.len(),
+        mempool = mempool_size,
+        alerts = context.active_alerts().await.len(),
+        peer_versions = ?context.peer_versions().await,
+        power = ?power,
+        "Lub dub!"
+    );
+
+    #[cfg(feature = "pow")]
+    if let Some(stats) = context.block_time_stats().await? {
+        if stats.is_drifting() {
+            tracing::warn!(
+                mean = stats.mean,
+                expected = crate::config::BLOCK_TIME,
+                "Average block time is drifting"
+            );
+        }
+    }
 
     Ok(())
 }
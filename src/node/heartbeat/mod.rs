@@ -1,57 +1,74 @@
+mod fetcher;
+mod keep_anchors;
 mod log_info;
-#[cfg(feature = "pow")]
-mod send_mining_puzzle;
+mod persist_peers;
+mod refresh_mempool;
+mod sync_alerts;
 mod sync_blocks;
+mod sync_checkpoint;
 mod sync_clock;
 
 use super::api::messages::*;
-use super::{http, NodeContext, NodeError, PeerAddress};
+use super::{
+    http, peer_record_id, ChainTip, NodeContext, NodeError, PeerAddress, PeerStats, PEER_RECORDS,
+};
 use crate::blockchain::Blockchain;
 use crate::config::punish;
 use crate::utils;
 use std::sync::Arc;
-use tokio::sync::{RwLock, RwLockWriteGuard};
 use tokio::time::{sleep, Duration};
 
 const NUM_PEERS: usize = 8;
 
 pub async fn heartbeat<B: Blockchain>(
     address: PeerAddress,
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
 ) -> Result<(), NodeError> {
     log_info::log_info(&context).await?;
+    keep_anchors::keep_anchors(address.clone(), &context).await?;
     sync_clock::sync_clock(address, &context).await?;
+    sync_checkpoint::sync_checkpoint(&context).await?;
+    sync_alerts::sync_alerts(&context).await?;
     sync_blocks::sync_blocks(&context).await?;
-    #[cfg(feature = "pow")]
-    send_mining_puzzle::send_mining_puzzle(&context).await?;
+    refresh_mempool::refresh_mempool(&context).await?;
+    persist_peers::persist_peers(&context).await?;
     Ok(())
 }
 
 pub async fn heartbeater<B: Blockchain>(
     address: PeerAddress,
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
 ) -> Result<(), NodeError> {
     loop {
         if let Err(e) = heartbeat(address.clone(), Arc::clone(&context)).await {
-            println!("Error happened: {}", e);
+            tracing::error!(error = %e, "Heartbeat failed");
         }
         sleep(Duration::from_millis(1000)).await;
     }
 }
 
-async fn punish_non_responding<B: Blockchain, R: Clone, E>(
-    ctx: &mut RwLockWriteGuard<'_, NodeContext<B>>,
-    resps: &Vec<(PeerAddress, Result<R, E>)>,
+async fn punish_non_responding<B: Blockchain, R: Clone>(
+    ctx: &NodeContext<B>,
+    resps: &Vec<(PeerAddress, Result<R, NodeError>)>,
 ) -> Vec<(PeerAddress, R)> {
+    let mut peers = ctx.peers.write().await;
     resps
         .iter()
-        .filter_map(|(peer, resp)| {
-            if let Ok(resp) = resp {
-                ctx.punish(peer.clone(), punish::NO_RESPONSE_PUNISH);
+        .filter_map(|(peer, resp)| match resp {
+            Ok(resp) => {
+                peers
+                    .entry(peer.clone())
+                    .and_modify(|stats| stats.punish(punish::NO_RESPONSE_PUNISH));
                 Some((peer.clone(), resp.clone()))
-            } else {
+            }
+            // Responded, but with garbage
+            Err(NodeError::JsonError(_)) | Err(NodeError::BincodeError(_)) => {
+                peers.entry(peer.clone()).and_modify(|stats| {
+                    stats.misbehave(punish::MALFORMED_RESPONSE_SCORE);
+                });
                 None
             }
+            Err(_) => None,
         })
         .collect()
 }
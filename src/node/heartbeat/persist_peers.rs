@@ -0,0 +1,30 @@
+use super::*;
+use crate::config::{PEER_MAX_AGE, PEER_PERSIST_INTERVAL};
+use std::sync::atomic::Ordering;
+
+// Saves the peers that have responded at some point, so that the node can
+// rejoin the network after a restart without relying on the bootstrap nodes
+pub async fn persist_peers<B: Blockchain>(context: &Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    let now = utils::local_timestamp();
+    if now < context.peers_persisted_at.load(Ordering::Relaxed) + PEER_PERSIST_INTERVAL {
+        return Ok(());
+    }
+    context.peers_persisted_at.store(now, Ordering::Relaxed);
+
+    let peers = context.peers.read().await.clone();
+    let mut records = Vec::new();
+    for (addr, stats) in peers.into_iter() {
+        if stats.last_seen == 0 {
+            continue;
+        }
+        records.push((
+            peer_record_id(&addr),
+            if now.saturating_sub(stats.last_seen) > PEER_MAX_AGE {
+                None
+            } else {
+                Some(serde_json::to_vec(&(addr, stats))?)
+            },
+        ));
+    }
+    context.chain.update_records(PEER_RECORDS, records).await
+}
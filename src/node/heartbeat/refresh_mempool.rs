@@ -0,0 +1,16 @@
+use super::*;
+
+pub async fn refresh_mempool<B: Blockchain>(
+    context: &Arc<NodeContext<B>>,
+) -> Result<(), NodeError> {
+    let now = context.network_timestamp();
+    let chain = context.blockchain.read().await;
+    let mut mempool = context.mempool.write().await;
+    mempool.expire(now);
+    // Drop the transactions that have been included in blocks
+    for sender in mempool.senders() {
+        let nonce = chain.get_account(sender.clone())?.nonce;
+        mempool.prune(&sender, nonce);
+    }
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use super::*;
+
+pub async fn sync_alerts<B: Blockchain>(context: &Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    let peer_addresses = context
+        .random_peers(&mut rand::thread_rng(), NUM_PEERS)
+        .await
+        .keys()
+        .cloned()
+        .collect::<Vec<PeerAddress>>();
+
+    let alert_responses: Vec<(PeerAddress, Result<GetAlertsResponse, NodeError>)> =
+        http::group_request(&peer_addresses, |peer| {
+            http::json_get::<GetAlertsRequest, GetAlertsResponse>(
+                format!("{}/alerts", peer).to_string(),
+                GetAlertsRequest {},
+            )
+        })
+        .await;
+
+    {
+        let resps = punish_non_responding(context, &alert_responses).await;
+        for (_, resp) in resps.into_iter() {
+            for alert in resp.alerts.into_iter() {
+                context.add_alert(alert).await;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,16 +1,28 @@
 use super::*;
+use crate::blockchain::BlockchainError;
+use crate::config::{MAX_BLOCK_FETCH, MAX_SYNC_WINDOWS, SYNC_POLL_INTERVAL};
+use std::sync::atomic::Ordering;
 
-pub async fn sync_blocks<B: Blockchain>(
-    context: &Arc<RwLock<NodeContext<B>>>,
-) -> Result<(), NodeError> {
-    let ctx = context.read().await;
-    let height = ctx.blockchain.get_height()?;
-    let peer_addresses = ctx
+// Header-first sync: the header chain of the peer with the longest valid chain
+// is validated as a whole, then the bodies are downloaded by the fetcher and
+// applied in order.
+pub async fn sync_blocks<B: Blockchain>(context: &Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    // Synced nodes get new blocks through announcements, polling is only
+    // needed once in a while, in case some were missed
+    let now = utils::local_timestamp();
+    let polled_at = context.blocks_polled_at.load(Ordering::Relaxed);
+    if now < polled_at + SYNC_POLL_INTERVAL && !context.is_syncing().await? {
+        return Ok(());
+    }
+    context.blocks_polled_at.store(now, Ordering::Relaxed);
+
+    let height = context.blockchain.read().await.get_height()?;
+    let peer_addresses = context
         .random_peers(&mut rand::thread_rng(), NUM_PEERS)
+        .await
         .keys()
         .cloned()
         .collect::<Vec<PeerAddress>>();
-    drop(ctx);
 
     let header_responses: Vec<(PeerAddress, Result<GetHeadersResponse, NodeError>)> =
         http::group_request(&peer_addresses, |peer| {
@@ -24,33 +36,97 @@ pub async fn sync_blocks<B: Blockchain>(
         })
         .await;
 
-    {
-        let mut ctx = context.write().await;
-        let resps = punish_non_responding(&mut ctx, &header_responses).await;
-        for (peer, resp) in resps.iter() {
-            if !resp.headers.is_empty() {
-                if ctx
-                    .blockchain
-                    .will_extend(height, &resp.headers)
-                    .unwrap_or(false)
-                {
-                    println!("{} has a longer chain!", peer);
-                    let resp = http::bincode_get::<GetBlocksRequest, GetBlocksResponse>(
-                        format!("{}/bincode/blocks", peer).to_string(),
-                        GetBlocksRequest {
-                            since: height,
-                            until: None,
-                        },
-                    )
-                    .await?;
-                    if ctx.blockchain.extend(height, &resp.blocks).is_err() {
-                        ctx.punish(*peer, punish::INVALID_DATA_PUNISH);
+    let resps = punish_non_responding(context, &header_responses).await;
+    let mut candidates = Vec::new();
+    for (peer, resp) in resps.into_iter() {
+        if let Some(last) = resp.headers.last() {
+            // Sources that have already failed us on this chain are skipped,
+            // so that the next best peer advertising it gets its turn
+            let tip = last.hash();
+            if context.is_blacklisted_source(peer, tip).await {
+                continue;
+            }
+            if context
+                .known_invalid(resp.headers.iter().map(|h| h.hash()))
+                .await
+                .is_some()
+            {
+                context.punish(peer, punish::INVALID_DATA_PUNISH).await;
+                context.misbehave(peer, punish::INVALID_BLOCK_SCORE).await;
+                continue;
+            }
+            let will_extend = context
+                .blockchain
+                .read()
+                .await
+                .will_extend(height, &resp.headers);
+            match will_extend {
+                Ok(true) => {
+                    context.add_verified_header(last).await;
+                    candidates.push((peer, tip, resp.headers));
+                }
+                Ok(false) | Err(_) => {
+                    if let Err(e) = will_extend {
+                        if e.is_context_free() {
+                            context.mark_invalid(tip, e.to_string()).await;
+                        }
                     }
-                } else {
-                    ctx.punish(*peer, punish::INVALID_DATA_PUNISH);
+                    context.punish(peer, punish::INVALID_DATA_PUNISH).await;
+                    context
+                        .misbehave(peer, punish::PROTOCOL_VIOLATION_SCORE)
+                        .await;
+                }
+            }
+        }
+    }
+    candidates.sort_by_key(|(_, _, headers)| std::cmp::Reverse(headers.len()));
+    let (best, tip, headers) = match candidates.first() {
+        Some(best) => best.clone(),
+        None => {
+            return Ok(());
+        }
+    };
+    tracing::info!(peer = %best, height, headers = headers.len(), "Peer has a longer chain");
+
+    // Peers on the same branch are able to serve the bodies too, the ones
+    // advertising the very same chain first
+    let fork_point = headers[0].hash();
+    let mut sources = candidates
+        .iter()
+        .filter(|(_, _, h)| h[0].hash() == fork_point)
+        .map(|(peer, t, _)| (*peer, *t != tip))
+        .collect::<Vec<_>>();
+    sources.sort_by_key(|(_, other_tip)| *other_tip);
+    let sources = sources
+        .into_iter()
+        .map(|(peer, _)| peer)
+        .collect::<Vec<_>>();
+
+    let headers = &headers[..std::cmp::min(headers.len(), MAX_SYNC_WINDOWS * MAX_BLOCK_FETCH)];
+    let blocks = fetcher::fetch_blocks(context, &sources, tip, height, headers).await;
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    match context.chain.extend_from(best, height, blocks).await {
+        // Our chain might have grown in the meantime
+        Ok(()) | Err(NodeError::BlockchainError(BlockchainError::WeakerChain)) => {}
+        Err(NodeError::BlockchainError(e)) if e.is_invalid_block() => {
+            if e.is_context_free() {
+                context.mark_invalid(tip, e.to_string()).await;
+            }
+            context.punish(best, punish::INVALID_DATA_PUNISH).await;
+            context.misbehave(best, punish::INVALID_BLOCK_SCORE).await;
+            // Everyone advertising the chain is serving the same invalid blocks
+            for (peer, t, _) in candidates.iter() {
+                if *t == tip {
+                    context.blacklist_sync_source(*peer, tip).await;
                 }
             }
         }
+        Err(_) => {
+            context.punish(best, punish::INVALID_DATA_PUNISH).await;
+        }
     }
 
     Ok(())
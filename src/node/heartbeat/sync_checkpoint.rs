@@ -0,0 +1,43 @@
+use super::*;
+use crate::blockchain::BlockchainError;
+use crate::config::checkpoint::CHECKPOINT_THRESHOLD;
+
+pub async fn sync_checkpoint<B: Blockchain>(
+    context: &Arc<NodeContext<B>>,
+) -> Result<(), NodeError> {
+    if CHECKPOINT_THRESHOLD == 0 {
+        return Ok(());
+    }
+
+    let peer_addresses = context
+        .random_peers(&mut rand::thread_rng(), NUM_PEERS)
+        .await
+        .keys()
+        .cloned()
+        .collect::<Vec<PeerAddress>>();
+
+    let checkpoint_responses: Vec<(PeerAddress, Result<GetCheckpointResponse, NodeError>)> =
+        http::group_request(&peer_addresses, |peer| {
+            http::json_get::<GetCheckpointRequest, GetCheckpointResponse>(
+                format!("{}/checkpoint", peer).to_string(),
+                GetCheckpointRequest {},
+            )
+        })
+        .await;
+
+    {
+        let resps = punish_non_responding(context, &checkpoint_responses).await;
+        for (peer, resp) in resps.iter() {
+            if let Some(checkpoint) = &resp.checkpoint {
+                if let Err(NodeError::BlockchainError(BlockchainError::InvalidCheckpoint)) =
+                    context.chain.apply_checkpoint(checkpoint.clone()).await
+                {
+                    context.punish(*peer, punish::INVALID_DATA_PUNISH).await;
+                    context.misbehave(*peer, punish::BAD_SIGNATURE_SCORE).await;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
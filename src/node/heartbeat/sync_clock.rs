@@ -2,17 +2,16 @@ use super::*;
 
 pub async fn sync_clock<B: Blockchain>(
     address: PeerAddress,
-    context: &Arc<RwLock<NodeContext<B>>>,
+    context: &Arc<NodeContext<B>>,
 ) -> Result<(), NodeError> {
-    let ctx = context.read().await;
-    let timestamp = ctx.network_timestamp();
-    let info = ctx.get_info()?;
-    let peer_addresses = ctx
+    let timestamp = context.network_timestamp();
+    let info = context.get_info().await?;
+    let peer_addresses = context
         .random_peers(&mut rand::thread_rng(), NUM_PEERS)
+        .await
         .keys()
         .cloned()
         .collect::<Vec<PeerAddress>>();
-    drop(ctx);
 
     let peer_responses: Vec<(PeerAddress, Result<PostPeerResponse, NodeError>)> =
         http::group_request(&peer_addresses, |peer| {
@@ -28,16 +27,21 @@ pub async fn sync_clock<B: Blockchain>(
         .await;
 
     {
-        let mut ctx = context.write().await;
-        let timestamps = punish_non_responding(&mut ctx, &peer_responses)
-            .await
+        let resps = punish_non_responding(context, &peer_responses).await;
+        let mut peers = context.peers.write().await;
+        for (peer, resp) in resps.iter() {
+            if let Some(stats) = peers.get_mut(peer) {
+                stats.update_info(resp.info.clone());
+            }
+        }
+        let timestamps = resps
             .into_iter()
             .map(|(_, r)| r.timestamp)
             .collect::<Vec<_>>();
         if timestamps.len() > 0 {
             // Set timestamp_offset according to median timestamp of the network
             let median_timestamp = utils::median(&timestamps);
-            ctx.timestamp_offset = median_timestamp as i32 - utils::local_timestamp() as i32;
+            context.set_timestamp_offset(median_timestamp as i32 - utils::local_timestamp() as i32);
         }
     }
 
@@ -0,0 +1,57 @@
+use crate::core::hash::Hash;
+use crate::core::Hasher;
+use std::collections::{HashMap, VecDeque};
+
+pub type BlockHash = <Hasher as Hash>::Output;
+
+// Hashes of the blocks (Or the tips of the header chains) that failed
+// validation, along with the reason. Gossip of the same objects is dropped
+// right away instead of being validated again. The oldest entries are
+// forgotten once there are more than `capacity`.
+pub struct InvalidBlocks {
+    capacity: usize,
+    reasons: HashMap<BlockHash, String>,
+    order: VecDeque<BlockHash>,
+}
+
+impl InvalidBlocks {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            reasons: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+    pub fn get(&self, hash: &BlockHash) -> Option<&String> {
+        self.reasons.get(hash)
+    }
+    // Returns the evicted entries
+    pub fn insert(&mut self, hash: BlockHash, reason: String) -> Vec<BlockHash> {
+        if self.reasons.insert(hash, reason).is_none() {
+            self.order.push_back(hash);
+        }
+        let mut evicted = Vec::new();
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.reasons.remove(&old);
+                evicted.push(old);
+            }
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_blocks_eviction() {
+        let mut invalid = InvalidBlocks::new(2);
+        assert!(invalid.insert([1; 32], "a".into()).is_empty());
+        assert!(invalid.insert([2; 32], "b".into()).is_empty());
+        assert_eq!(invalid.insert([3; 32], "c".into()), vec![[1; 32]]);
+        assert!(invalid.get(&[1; 32]).is_none());
+        assert_eq!(invalid.get(&[3; 32]).unwrap(), "c");
+    }
+}
@@ -0,0 +1,577 @@
+use crate::config;
+use crate::core::{tx_weight, Address, Hasher, Money, Transaction, TransactionData, TransactionId};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// Why a transaction was not let into the mempool, reported back to the client
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    InvalidSignature,
+    NonceTooLow,
+    NonceTooHigh,
+    BalanceInsufficient,
+    FeeTooSmall,
+    AlreadyKnown,
+    // Another transaction with the same nonce pays at least as much
+    ReplacementUnderpriced,
+    // The sender already has too many transactions waiting for missing nonces
+    TooManyFutureTransactions,
+    MempoolFull,
+    // Can't ever be applied, whatever the state of the chain
+    Malformed,
+}
+
+// Recipients of the money moved by a transaction
+fn outputs(tx: &Transaction) -> Vec<(&Address, Money)> {
+    match &tx.data {
+        TransactionData::RegularSend { dst, amount }
+        | TransactionData::LockedSend { dst, amount, .. } => vec![(dst, *amount)],
+        TransactionData::RegularSendMany { outputs } => {
+            outputs.iter().map(|(dst, amount)| (dst, *amount)).collect()
+        }
+        _ => vec![],
+    }
+}
+
+// Everything the sender of a transaction pays, fee included
+pub fn total_spent(tx: &Transaction) -> Money {
+    outputs(tx)
+        .into_iter()
+        .fold(tx.fee, |total, (_, amount)| total.saturating_add(amount))
+}
+
+// Checks that don't need the chain, cheap enough to run on everything that is
+// submitted. The signature is checked last, being the most expensive.
+pub fn pre_verify(tx: &Transaction) -> Result<(), RejectReason> {
+    // Only the block producer may spend from the Treasury
+    if tx.src == Address::Treasury {
+        return Err(RejectReason::Malformed);
+    }
+    // Accounts start at nonce 0, their first transaction has nonce 1
+    if tx.nonce == 0 {
+        return Err(RejectReason::NonceTooLow);
+    }
+    // Transaction types of newer versions can't be validated by this one
+    if !tx.data.is_known() {
+        return Err(RejectReason::Malformed);
+    }
+    // Contract state transitions can't be proven without the `zk` feature
+    if !cfg!(feature = "zk")
+        && matches!(
+            tx.data,
+            TransactionData::DepositWithdraw { .. } | TransactionData::Update { .. }
+        )
+    {
+        return Err(RejectReason::Malformed);
+    }
+    if let TransactionData::RegularSendMany { outputs } = &tx.data {
+        let total = outputs
+            .iter()
+            .try_fold(0 as Money, |total, (_, amount)| total.checked_add(*amount));
+        if outputs.is_empty() || total.is_none() {
+            return Err(RejectReason::Malformed);
+        }
+    }
+    if tx_weight(tx) > config::MAX_BLOCK_WEIGHT_BOUNDS.1 {
+        return Err(RejectReason::Malformed);
+    }
+    if !tx.verify_signature() {
+        return Err(RejectReason::InvalidSignature);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionStats {
+    pub first_seen: u32,
+}
+
+// Transactions waiting to get into a block. Deduplicated by transaction id, and
+// tracked as per-sender nonce chains, so that a sender can't occupy a nonce twice
+// and eviction never leaves a gap in the middle of a chain. Transactions that
+// can't be applied yet, because of missing nonces before them, wait in a
+// separate (Per-sender limited) queue until the gap is filled.
+pub struct Mempool {
+    txs: HashMap<TransactionId, (Transaction, TransactionStats)>,
+    chains: HashMap<String, BTreeMap<u32, TransactionId>>,
+    future: BTreeMap<(String, u32), TransactionId>,
+    max_size: usize,
+    ttl: u32,
+    nonce_horizon: u32,
+    max_future_per_sender: usize,
+}
+
+impl Mempool {
+    pub fn new(
+        max_size: usize,
+        ttl: u32,
+        nonce_horizon: u32,
+        max_future_per_sender: usize,
+    ) -> Self {
+        Self {
+            txs: HashMap::new(),
+            chains: HashMap::new(),
+            future: BTreeMap::new(),
+            max_size,
+            ttl,
+            nonce_horizon,
+            max_future_per_sender,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+    fn future_of<'a>(
+        &'a self,
+        sender: &str,
+    ) -> impl DoubleEndedIterator<Item = (u32, &'a TransactionId)> + 'a {
+        self.future
+            .range((sender.to_string(), 0)..=(sender.to_string(), u32::MAX))
+            .map(|((_, nonce), id)| (*nonce, id))
+    }
+    // Nonce the chain of a sender continues with
+    fn chain_next(&self, sender: &str, account_nonce: u32) -> u32 {
+        self.chains
+            .get(sender)
+            .and_then(|chain| chain.keys().next_back())
+            .map(|last| std::cmp::max(last + 1, account_nonce + 1))
+            .unwrap_or(account_nonce + 1)
+    }
+    // Moves the queued transactions continuing the chain of a sender into it
+    fn promote(&mut self, sender: &str, account_nonce: u32) {
+        let mut next = self.chain_next(sender, account_nonce);
+        while let Some(id) = self.future.remove(&(sender.to_string(), next)) {
+            self.chains
+                .entry(sender.to_string())
+                .or_default()
+                .insert(next, id);
+            next += 1;
+        }
+    }
+    // Only the transactions with nonces within the horizon after the nonce of
+    // the sender's account are accepted. A transaction occupying an already
+    // taken nonce replaces the old one if it pays a strictly higher fee.
+    pub fn insert(
+        &mut self,
+        tx: Transaction,
+        account_nonce: u32,
+        now: u32,
+    ) -> Result<(), RejectReason> {
+        if tx.nonce <= account_nonce {
+            return Err(RejectReason::NonceTooLow);
+        }
+        if tx.nonce - account_nonce > self.nonce_horizon {
+            return Err(RejectReason::NonceTooHigh);
+        }
+        let id = tx.txid::<Hasher>();
+        if self.txs.contains_key(&id) {
+            return Err(RejectReason::AlreadyKnown);
+        }
+        let sender = tx.src.to_string();
+        let replaced = self
+            .chains
+            .get(&sender)
+            .and_then(|chain| chain.get(&tx.nonce))
+            .or_else(|| self.future.get(&(sender.clone(), tx.nonce)))
+            .cloned();
+        if let Some(old) = replaced {
+            if self.txs[&old].0.fee >= tx.fee {
+                return Err(RejectReason::ReplacementUnderpriced);
+            }
+            self.remove(&old);
+        }
+        if tx.nonce > self.chain_next(&sender, account_nonce)
+            && self.future_of(&sender).count() >= self.max_future_per_sender
+        {
+            return Err(RejectReason::TooManyFutureTransactions);
+        }
+        if self.txs.len() >= self.max_size {
+            // Make room by evicting the cheapest transaction, if the new one pays more
+            match self.cheapest_tail() {
+                Some((cheapest, fee)) if fee < tx.fee => {
+                    self.remove(&cheapest);
+                }
+                _ => {
+                    return Err(RejectReason::MempoolFull);
+                }
+            }
+        }
+        // Checked again, as eviction may have cut the chain short
+        if tx.nonce <= self.chain_next(&sender, account_nonce) {
+            self.chains
+                .entry(sender.clone())
+                .or_default()
+                .insert(tx.nonce, id);
+        } else {
+            self.future.insert((sender.clone(), tx.nonce), id);
+        }
+        self.txs
+            .insert(id, (tx, TransactionStats { first_seen: now }));
+        self.promote(&sender, account_nonce);
+        Ok(())
+    }
+    // Removing a transaction from the middle of a chain sends the ones after it
+    // back to the queue, since they can't be applied anymore. The queue of the
+    // sender is kept within its limit by dropping the highest nonces.
+    pub fn remove(&mut self, id: &TransactionId) -> Option<Transaction> {
+        let (tx, _) = self.txs.remove(id)?;
+        let sender = tx.src.to_string();
+        if self.future.remove(&(sender.clone(), tx.nonce)).is_some() {
+            return Some(tx);
+        }
+        if let Some(chain) = self.chains.get_mut(&sender) {
+            let is_head = chain.keys().next() == Some(&tx.nonce);
+            chain.remove(&tx.nonce);
+            if !is_head {
+                for (nonce, id) in chain.split_off(&tx.nonce) {
+                    self.future.insert((sender.clone(), nonce), id);
+                }
+            }
+            if chain.is_empty() {
+                self.chains.remove(&sender);
+            }
+        }
+        let excess = self
+            .future_of(&sender)
+            .rev()
+            .take(
+                self.future_of(&sender)
+                    .count()
+                    .saturating_sub(self.max_future_per_sender),
+            )
+            .map(|(nonce, _)| nonce)
+            .collect::<Vec<_>>();
+        for nonce in excess {
+            if let Some(id) = self.future.remove(&(sender.clone(), nonce)) {
+                self.txs.remove(&id);
+            }
+        }
+        Some(tx)
+    }
+    // Lowest paying transaction among the queued ones and the last transactions
+    // of the chains
+    fn cheapest_tail(&self) -> Option<(TransactionId, Money)> {
+        self.chains
+            .values()
+            .filter_map(|chain| chain.values().next_back())
+            .chain(self.future.values())
+            .map(|id| (*id, self.txs[id].0.fee))
+            .min_by_key(|(_, fee)| *fee)
+    }
+    // Removes the transactions that have been waiting longer than the TTL
+    pub fn expire(&mut self, now: u32) -> usize {
+        let expired = self
+            .txs
+            .iter()
+            .filter(|(_, (_, stats))| stats.first_seen + self.ttl < now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in expired.iter() {
+            self.remove(id);
+        }
+        expired.len()
+    }
+    pub fn senders(&self) -> Vec<Address> {
+        let mut senders = HashMap::new();
+        for id in self
+            .chains
+            .values()
+            .filter_map(|chain| chain.values().next())
+            .chain(self.future.values())
+        {
+            let src = &self.txs[id].0.src;
+            senders.insert(src.to_string(), src.clone());
+        }
+        senders.into_values().collect()
+    }
+    // Removes the transactions of a sender that can no longer be applied, given
+    // the nonce of its account. Queued transactions the account has caught up
+    // with are promoted.
+    pub fn prune(&mut self, sender: &Address, account_nonce: u32) {
+        let sender = sender.to_string();
+        let stale = self
+            .chains
+            .get(&sender)
+            .into_iter()
+            .flat_map(|chain| chain.range(..=account_nonce).map(|(_, id)| *id))
+            .chain(
+                self.future_of(&sender)
+                    .filter(|(nonce, _)| *nonce <= account_nonce)
+                    .map(|(_, id)| *id),
+            )
+            .collect::<Vec<_>>();
+        for id in stale.iter() {
+            self.remove(id);
+        }
+        self.promote(&sender, account_nonce);
+    }
+    // Total amount the pending transactions move into and out of an address,
+    // as (incoming, outgoing). Fees are counted as outgoing
+    pub fn pending_amounts(&self, addr: &Address) -> (Money, Money) {
+        let mut incoming: Money = 0;
+        let mut outgoing: Money = 0;
+        for (tx, _) in self.txs.values() {
+            if tx.src == *addr {
+                outgoing = outgoing.saturating_add(tx.fee);
+            }
+            for (dst, amount) in outputs(tx) {
+                if tx.src == *addr {
+                    outgoing = outgoing.saturating_add(amount);
+                }
+                if dst == addr {
+                    incoming = incoming.saturating_add(amount);
+                }
+            }
+        }
+        (incoming, outgoing)
+    }
+    // Pending nonces of a sender, whether executable or queued
+    fn nonces(&self, sender: &str) -> BTreeMap<u32, TransactionId> {
+        let mut nonces = self.chains.get(sender).cloned().unwrap_or_default();
+        nonces.extend(self.future_of(sender).map(|(nonce, id)| (nonce, *id)));
+        nonces
+    }
+    // Nonce the next transaction of a sender should use, given the nonce of its
+    // account. Also returns the (inclusive) ranges of nonces missing before its
+    // later pending transactions, which can't get into a block until filled
+    pub fn next_nonce(&self, sender: &Address, account_nonce: u32) -> (u32, Vec<(u32, u32)>) {
+        let mut next = account_nonce + 1;
+        let mut gaps = Vec::new();
+        let mut expected = next;
+        for &nonce in self
+            .nonces(&sender.to_string())
+            .range(next..)
+            .map(|(n, _)| n)
+        {
+            if nonce > expected {
+                gaps.push((expected, nonce - 1));
+            }
+            if gaps.is_empty() {
+                next = nonce + 1;
+            }
+            expected = nonce + 1;
+        }
+        (next, gaps)
+    }
+    // Nonce chains of the senders, queued transactions included, along with
+    // the ids and stats
+    pub fn chains(&self) -> Vec<Vec<(TransactionId, &Transaction, &TransactionStats)>> {
+        self.senders()
+            .into_iter()
+            .map(|sender| {
+                self.nonces(&sender.to_string())
+                    .values()
+                    .map(|id| {
+                        let (tx, stats) = &self.txs[id];
+                        (*id, tx, stats)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    // Transactions of a single sender, queued ones included, in nonce order
+    pub fn transactions_of(&self, sender: &Address) -> Vec<Transaction> {
+        self.nonces(&sender.to_string())
+            .values()
+            .map(|id| self.txs[id].0.clone())
+            .collect()
+    }
+    // Executable transactions of each sender, in nonce order
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.chains
+            .values()
+            .flat_map(|chain| chain.values())
+            .map(|id| self.txs[id].0.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_pre_verify() {
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        let tx = wallet.create_transaction(Address::Treasury, 10, 1);
+        assert_eq!(pre_verify(&tx), Ok(()));
+
+        let mut treasury = tx.clone();
+        treasury.src = Address::Treasury;
+        assert_eq!(pre_verify(&treasury), Err(RejectReason::Malformed));
+
+        let mut zero_nonce = tx.clone();
+        zero_nonce.nonce = 0;
+        assert_eq!(pre_verify(&zero_nonce), Err(RejectReason::NonceTooLow));
+
+        let mut unknown = tx.clone();
+        unknown.data = TransactionData::Unknown {
+            tag: 1000,
+            payload: vec![],
+        };
+        assert_eq!(pre_verify(&unknown), Err(RejectReason::Malformed));
+
+        let state = crate::zk::ZkState::new(
+            serde_json::from_str(r#"{"leaf_size": 1, "tree_depth": 8}"#).unwrap(),
+            serde_json::from_str("{}").unwrap(),
+        )
+        .compress();
+        let deposit = wallet.deposit_withdraw(
+            "0505050505050505050505050505050505050505050505050505050505050505"
+                .parse()
+                .unwrap(),
+            vec![],
+            state.state_hash().clone(),
+            serde_json::from_str("[1, 2, 3]").unwrap(),
+            1,
+        );
+        let expected = if cfg!(feature = "zk") {
+            Ok(())
+        } else {
+            Err(RejectReason::Malformed)
+        };
+        assert_eq!(pre_verify(&deposit), expected);
+        let update = wallet.update_contract(
+            "0505050505050505050505050505050505050505050505050505050505050505"
+                .parse()
+                .unwrap(),
+            0,
+            state.state_hash().clone(),
+            serde_json::from_str("[1, 2, 3]").unwrap(),
+            1,
+        );
+        assert_eq!(pre_verify(&update), expected);
+
+        let empty = wallet.create_batch_transaction(vec![], 1);
+        assert_eq!(pre_verify(&empty), Err(RejectReason::Malformed));
+        let overflow = wallet.create_batch_transaction(
+            vec![(Address::Treasury, Money::MAX), (Address::Treasury, 1)],
+            1,
+        );
+        assert_eq!(pre_verify(&overflow), Err(RejectReason::Malformed));
+    }
+
+    #[test]
+    fn test_mempool() {
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        let mut mempool = Mempool::new(2, 10, 3, 1);
+        let tx1 = wallet.create_transaction(Address::Treasury, 10, 1);
+        let tx2 = wallet.create_transaction(Address::Treasury, 10, 2);
+        let tx3 = wallet.create_transaction(Address::Treasury, 10, 3);
+        assert_eq!(mempool.insert(tx1.clone(), 0, 0), Ok(()));
+        assert_eq!(
+            mempool.insert(tx1.clone(), 0, 0),
+            Err(RejectReason::AlreadyKnown)
+        );
+        assert_eq!(mempool.insert(tx2.clone(), 0, 5), Ok(()));
+
+        // Full, the tail of the chain (tx2) is evicted in favor of a higher fee
+        assert_eq!(mempool.insert(tx3.clone(), 0, 5), Ok(()));
+        assert_eq!(total_spent(&tx3), 13);
+        // tx3 can't be applied without tx2
+        assert_eq!(mempool.transactions(), vec![tx1.clone()]);
+        assert_eq!(mempool.pending_amounts(&tx1.src), (0, 24));
+        assert_eq!(mempool.pending_amounts(&Address::Treasury), (20, 0));
+
+        assert_eq!(mempool.next_nonce(&tx1.src, 0), (2, vec![(2, 2)]));
+        assert_eq!(mempool.next_nonce(&Address::Treasury, 7), (8, vec![]));
+        assert_eq!(
+            mempool.transactions_of(&tx1.src),
+            vec![tx1.clone(), tx3.clone()]
+        );
+        assert!(mempool.transactions_of(&Address::Treasury).is_empty());
+        let chains = mempool.chains();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(
+            chains[0].iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+            vec![tx1.txid::<Hasher>(), tx3.txid::<Hasher>()]
+        );
+
+        mempool.prune(&tx1.src, 1);
+        assert!(mempool.transactions().is_empty());
+        assert_eq!(mempool.transactions_of(&tx1.src), vec![tx3]);
+
+        assert_eq!(mempool.expire(16), 1);
+        assert_eq!(mempool.len(), 0);
+    }
+
+    #[test]
+    fn test_mempool_nonce_horizon() {
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        let mut mempool = Mempool::new(10, 10, 2, 1);
+        let txs = (0..3)
+            .map(|_| wallet.create_transaction(Address::Treasury, 10, 1))
+            .collect::<Vec<_>>();
+        // Already applied
+        assert_eq!(
+            mempool.insert(txs[0].clone(), 1, 0),
+            Err(RejectReason::NonceTooLow)
+        );
+        assert_eq!(mempool.insert(txs[1].clone(), 1, 0), Ok(()));
+        assert_eq!(mempool.insert(txs[2].clone(), 1, 0), Ok(()));
+        let far = wallet.create_transaction(Address::Treasury, 10, 1);
+        assert_eq!(mempool.insert(far, 1, 0), Err(RejectReason::NonceTooHigh));
+
+        // Replace-by-fee, only with a strictly higher fee
+        let mut replacement = txs[1].clone();
+        replacement.data = TransactionData::RegularSend {
+            dst: Address::Treasury,
+            amount: 5,
+        };
+        assert_eq!(
+            mempool.insert(replacement.clone(), 1, 0),
+            Err(RejectReason::ReplacementUnderpriced)
+        );
+        replacement.fee = 2;
+        assert_eq!(mempool.insert(replacement.clone(), 1, 0), Ok(()));
+        assert_eq!(mempool.transactions(), vec![replacement, txs[2].clone()]);
+    }
+
+    #[test]
+    fn test_future_queue() {
+        let mut wallet = Wallet::new(b"ABC".to_vec());
+        let mut mempool = Mempool::new(10, 10, 10, 2);
+        let txs = (0..6)
+            .map(|_| wallet.create_transaction(Address::Treasury, 10, 1))
+            .collect::<Vec<_>>();
+        // Nonces 3 and 4 wait for 1 and 2, the queue of the sender is full
+        assert_eq!(mempool.insert(txs[2].clone(), 0, 0), Ok(()));
+        assert_eq!(mempool.insert(txs[3].clone(), 0, 0), Ok(()));
+        assert_eq!(
+            mempool.insert(txs[4].clone(), 0, 0),
+            Err(RejectReason::TooManyFutureTransactions)
+        );
+        assert!(mempool.transactions().is_empty());
+        assert_eq!(mempool.next_nonce(&txs[0].src, 0), (1, vec![(1, 2)]));
+
+        assert_eq!(mempool.insert(txs[0].clone(), 0, 0), Ok(()));
+        assert_eq!(mempool.transactions(), vec![txs[0].clone()]);
+        // Filling the gap promotes the queued ones
+        assert_eq!(mempool.insert(txs[1].clone(), 0, 0), Ok(()));
+        assert_eq!(mempool.transactions(), txs[..4].to_vec());
+        assert_eq!(mempool.insert(txs[4].clone(), 0, 0), Ok(()));
+        assert_eq!(mempool.insert(txs[5].clone(), 0, 0), Ok(()));
+
+        // Removing from the middle queues the rest again, as much as the limit
+        // of the sender allows
+        mempool.remove(&txs[2].txid::<Hasher>());
+        assert_eq!(mempool.transactions(), txs[..2].to_vec());
+        assert_eq!(
+            mempool.transactions_of(&txs[0].src),
+            vec![
+                txs[0].clone(),
+                txs[1].clone(),
+                txs[3].clone(),
+                txs[4].clone()
+            ]
+        );
+        assert_eq!(mempool.len(), 4);
+
+        // The account catching up (e.g. by a block from another node) promotes
+        // them as well
+        mempool.prune(&txs[0].src, 3);
+        assert_eq!(mempool.transactions(), txs[3..5].to_vec());
+        assert_eq!(mempool.len(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+use super::NodeError;
+use hyper::{Body, Response, StatusCode};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Upper bounds of the latency buckets, in milliseconds. Slower requests are
+// counted in an extra bucket.
+pub const LATENCY_BUCKETS: &[u64] = &[1, 5, 10, 50, 100, 500, 1000, 5000];
+
+// Requests not reaching any route (Unknown paths, or refused before routing)
+// are counted together, so that they can't blow up the table
+const OTHER_ENDPOINT: &str = "other";
+
+// Marks the responses of the known routes
+#[derive(Debug, Clone, Copy)]
+pub struct Routed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestOutcome {
+    Ok,
+    // Malformed request, query string or body
+    BadRequest,
+    // Refused before being processed (Banned client, overloaded node)
+    Rejected,
+    NotFound,
+    // The handler failed
+    Failed,
+}
+
+impl RequestOutcome {
+    pub fn of(result: &Result<Response<Body>, NodeError>) -> Self {
+        match result {
+            Ok(resp) => match resp.status() {
+                StatusCode::NOT_FOUND => RequestOutcome::NotFound,
+                StatusCode::FORBIDDEN | StatusCode::SERVICE_UNAVAILABLE => RequestOutcome::Rejected,
+                status if status.is_client_error() => RequestOutcome::BadRequest,
+                status if status.is_server_error() => RequestOutcome::Failed,
+                _ => RequestOutcome::Ok,
+            },
+            Err(NodeError::JsonError(_))
+            | Err(NodeError::QueryStringError(_))
+            | Err(NodeError::BincodeError(_))
+            | Err(NodeError::HexError(_))
+            | Err(NodeError::Utf8Error(_))
+            | Err(NodeError::InvalidAddress)
+            | Err(NodeError::InvalidTransactionHash) => RequestOutcome::BadRequest,
+            Err(_) => RequestOutcome::Failed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EndpointMetrics {
+    pub requests: u64,
+    pub ok: u64,
+    pub bad_request: u64,
+    pub rejected: u64,
+    pub not_found: u64,
+    pub failed: u64,
+    // Requests per bucket of `LATENCY_BUCKETS`, plus the slower ones
+    pub latency_buckets: Vec<u64>,
+    pub total_latency_ms: u64,
+}
+
+impl EndpointMetrics {
+    pub fn record(&mut self, outcome: RequestOutcome, latency: Duration) {
+        self.requests += 1;
+        *match outcome {
+            RequestOutcome::Ok => &mut self.ok,
+            RequestOutcome::BadRequest => &mut self.bad_request,
+            RequestOutcome::Rejected => &mut self.rejected,
+            RequestOutcome::NotFound => &mut self.not_found,
+            RequestOutcome::Failed => &mut self.failed,
+        } += 1;
+
+        let millis = latency.as_millis() as u64;
+        if self.latency_buckets.is_empty() {
+            self.latency_buckets = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| millis <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket] += 1;
+        self.total_latency_ms += millis;
+    }
+}
+
+// Counters of the API requests, per `<method> <path>` of the route. Never
+// locked across an await point.
+#[derive(Default)]
+pub struct ApiMetrics {
+    endpoints: Mutex<HashMap<String, EndpointMetrics>>,
+}
+
+impl ApiMetrics {
+    pub fn record(&self, route: Option<String>, outcome: RequestOutcome, latency: Duration) {
+        self.endpoints
+            .lock()
+            .unwrap()
+            .entry(route.unwrap_or_else(|| OTHER_ENDPOINT.into()))
+            .or_default()
+            .record(outcome, latency);
+    }
+    pub fn snapshot(&self) -> HashMap<String, EndpointMetrics> {
+        self.endpoints.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_metrics() {
+        let metrics = ApiMetrics::default();
+        let ok = Ok(Response::new(Body::empty()));
+        let bad: Result<Response<Body>, NodeError> = Err(NodeError::InvalidAddress);
+        metrics.record(
+            Some("GET /block".into()),
+            RequestOutcome::of(&ok),
+            Duration::from_millis(3),
+        );
+        metrics.record(
+            Some("GET /block".into()),
+            RequestOutcome::of(&bad),
+            Duration::from_secs(10),
+        );
+        metrics.record(None, RequestOutcome::NotFound, Duration::from_millis(0));
+        metrics.record(None, RequestOutcome::Rejected, Duration::from_millis(0));
+
+        let snapshot = metrics.snapshot();
+        let block = &snapshot["GET /block"];
+        assert_eq!((block.requests, block.ok, block.bad_request), (2, 1, 1));
+        assert_eq!(block.latency_buckets[1], 1);
+        assert_eq!(block.latency_buckets[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(block.total_latency_ms, 10003);
+        assert_eq!(snapshot[OTHER_ENDPOINT].not_found, 1);
+        assert_eq!(snapshot[OTHER_ENDPOINT].rejected, 1);
+        assert_eq!(snapshot.len(), 2);
+    }
+}
@@ -0,0 +1,66 @@
+use super::api::messages::Puzzle;
+use super::{http, NodeContext, NodeError, NodeEvent};
+use crate::blockchain::Blockchain;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+// Templates are only invalidated when the tip changes, the miners get the new
+// puzzle right away instead of on the next heartbeat
+pub async fn miner_notifier<B: Blockchain>(context: Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    if !context.role.serves_miners() {
+        return Ok(());
+    }
+    let mut events = context.events.subscribe();
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(NodeEvent::NewBlock { .. }) | Ok(NodeEvent::Reorg { .. }) => {
+                    context.miner.write().await.mining_template = None;
+                }
+                Ok(_) => continue,
+                // Some events were missed, one of them might have moved the tip
+                Err(RecvError::Lagged(_)) => {
+                    context.miner.write().await.mining_template = None;
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            _ = context.miner_registered.notified() => {}
+        }
+        if let Err(e) = send_mining_puzzle(&context).await {
+            tracing::error!(error = %e, "Sending the mining puzzle failed");
+        }
+    }
+}
+
+async fn send_mining_puzzle<B: Blockchain>(context: &Arc<NodeContext<B>>) -> Result<(), NodeError> {
+    // Mining on top of a stale tip is a waste while catching up with the network
+    let no_miners = context.miner.read().await.miners.is_empty();
+    if no_miners || context.is_syncing().await? {
+        return Ok(());
+    }
+    if let Some(w) = context.wallet.clone() {
+        let puzzle = context.current_puzzle(w).await?;
+        let webhooks = context
+            .miner
+            .read()
+            .await
+            .miners
+            .values()
+            .map(|m| m.webhook.clone())
+            .collect::<Vec<_>>();
+
+        // All of the miners work on the same template
+        let resps = futures::future::join_all(
+            webhooks
+                .iter()
+                .map(|webhook| http::json_post::<Puzzle, String>(webhook.clone(), puzzle.clone())),
+        )
+        .await;
+        for (webhook, resp) in webhooks.iter().zip(resps.into_iter()) {
+            if let Err(e) = resp {
+                tracing::warn!(webhook = %webhook, error = %e, "Miner is not responding");
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,30 +1,60 @@
-mod api;
+mod anchors;
+mod announce;
+pub(crate) mod api;
+mod blacklist;
+mod cache;
+mod chain;
 mod context;
 mod errors;
+mod events;
 mod heartbeat;
-mod http;
+pub(crate) mod http;
+mod invalid;
+mod mempool;
+mod metrics;
+#[cfg(feature = "pow")]
+mod miner;
+mod priority;
+mod relay;
+mod role;
 pub mod upnp;
-use context::{NodeContext, TransactionStats};
+pub use anchors::AnchorPeers;
+use announce::{AnnounceEvent, AnnounceHandle};
+use blacklist::{ChainTip, SyncBlacklist};
+use cache::{etag_matches, ResponseCache};
+use chain::{ChainCommand, ChainHandle};
+use context::NodeContext;
 pub use errors::NodeError;
+pub use events::{NodeEvent, TOPICS};
+use invalid::{BlockHash, InvalidBlocks};
+use mempool::Mempool;
+pub use mempool::RejectReason;
+use metrics::ApiMetrics;
+pub use metrics::EndpointMetrics;
+use priority::{Priority, RequestScheduler};
+pub use relay::RelayConfig;
+pub use role::NodeRole;
 
 #[cfg(feature = "pow")]
-use context::Miner;
+use context::{Miner, MinerStats, SolutionStatus};
 
-use crate::blockchain::Blockchain;
+use crate::blockchain::{Blockchain, PayoutSplit};
 use crate::utils;
 use crate::wallet::Wallet;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicI32, AtomicU32};
 use std::sync::Arc;
 
+use crate::config;
 use crate::config::punish;
 
 use serde_derive::{Deserialize, Serialize};
 
 use hyper::server::conn::AddrStream;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, Notify, RwLock};
 use tokio::try_join;
 
 pub type Timestamp = u32;
@@ -43,18 +73,56 @@ pub struct PeerInfo {
     pub height: usize,
     #[cfg(feature = "pow")]
     pub power: u64,
+    // Empty when the peer runs a version that doesn't announce these
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub user_agent: String,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct PeerStats {
     pub punished_until: Timestamp,
     pub info: Option<PeerInfo>,
+    pub last_seen: Timestamp,
+    // Accumulated misbehavior, reset once the peer gets banned
+    #[serde(default)]
+    pub misbehavior: u32,
+    #[serde(default)]
+    pub banned_until: Timestamp,
+    // When the peer became known, 0 for bootstrap peers
+    #[serde(default)]
+    pub added: Timestamp,
+    // Last accepted block announcement, they are rate limited
+    #[serde(default)]
+    pub announced_at: Timestamp,
 }
 
 impl PeerStats {
+    pub fn is_banned(&self) -> bool {
+        utils::local_timestamp() < self.banned_until
+    }
+    pub fn ban(&mut self, secs: u32) {
+        self.banned_until = utils::local_timestamp() + secs;
+        self.misbehavior = 0;
+    }
+    // Returns true if the peer got banned
+    pub fn misbehave(&mut self, score: u32) -> bool {
+        self.misbehavior += score;
+        if self.misbehavior >= punish::BAN_THRESHOLD {
+            self.ban(punish::BAN_DURATION);
+            true
+        } else {
+            false
+        }
+    }
     pub fn is_punished(&self) -> bool {
         utils::local_timestamp() < self.punished_until
     }
+    pub fn update_info(&mut self, info: PeerInfo) {
+        self.info = Some(info);
+        self.last_seen = utils::local_timestamp();
+    }
     pub fn punish(&mut self, secs: u32) {
         let now = utils::local_timestamp();
         self.punished_until = std::cmp::min(
@@ -64,22 +132,195 @@ impl PeerStats {
     }
 }
 
+// Misbehavior of an inbound client, whose listening port (Hence which of the
+// peers it is) is unknown
+#[derive(Clone, Debug, Default)]
+pub struct IpStats {
+    pub misbehavior: u32,
+    pub banned_until: Timestamp,
+}
+
+impl IpStats {
+    pub fn is_banned(&self) -> bool {
+        utils::local_timestamp() < self.banned_until
+    }
+    // Returns true if the address got banned
+    pub fn misbehave(&mut self, score: u32) -> bool {
+        self.misbehavior += score;
+        if self.misbehavior >= punish::BAN_THRESHOLD {
+            self.banned_until = utils::local_timestamp() + punish::BAN_DURATION;
+            self.misbehavior = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Known peers are persisted as `peer_<ip>_<port>` records, JSON encoded so
+// that records saved before a field got added still load, with its default
+const PEER_RECORDS: &str = "peer";
+
+fn peer_record_id(addr: &PeerAddress) -> String {
+    format!("{}_{}", addr.0, addr.1)
+}
+
+// Peers saved by a previous run, except the ones not seen for too long
+fn load_peers<B: Blockchain>(blockchain: &B) -> HashMap<PeerAddress, PeerStats> {
+    let now = utils::local_timestamp();
+    blockchain
+        .get_records(PEER_RECORDS)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(_, v)| serde_json::from_slice::<(PeerAddress, PeerStats)>(&v).ok())
+        .filter(|(_, stats)| now.saturating_sub(stats.last_seen) <= config::PEER_MAX_AGE)
+        .collect()
+}
+
+// Events not yet received by the slowest subscriber
+const EVENT_BUFFER_SIZE: usize = 256;
+
+pub const ADMIN_TOKEN_HEADER: &str = "x-bazuka-admin-token";
+
+// Blocks known to be invalid are persisted as `invalid_<hash>` records
+const INVALID_RECORDS: &str = "invalid";
+
+fn load_invalid_blocks<B: Blockchain>(blockchain: &B) -> InvalidBlocks {
+    let mut invalid = InvalidBlocks::new(config::INVALID_BLOCK_CACHE_SIZE);
+    for (id, reason) in blockchain.get_records(INVALID_RECORDS).unwrap_or_default() {
+        let hash = hex::decode(&id)
+            .ok()
+            .and_then(|h| BlockHash::try_from(h.as_slice()).ok());
+        if let Some(hash) = hash {
+            invalid.insert(hash, String::from_utf8_lossy(&reason).into());
+        }
+    }
+    invalid
+}
+
 pub struct Node<B: Blockchain> {
     address: PeerAddress,
-    context: Arc<RwLock<NodeContext<B>>>,
+    context: Arc<NodeContext<B>>,
+    chain_commands: std::sync::Mutex<Option<mpsc::Receiver<ChainCommand>>>,
+    announce_events: std::sync::Mutex<Option<mpsc::Receiver<AnnounceEvent>>>,
+}
+
+// Conditional requests of a resource the client already has are answered
+// with an empty body
+fn respond_cached<B: Blockchain, E: Into<NodeError>>(
+    context: &NodeContext<B>,
+    response: &mut Response<Body>,
+    if_none_match: &str,
+    etag: String,
+    serialize: impl FnOnce() -> Result<Vec<u8>, E>,
+) -> Result<(), NodeError> {
+    response.headers_mut().insert(
+        hyper::header::ETAG,
+        hyper::header::HeaderValue::from_str(&etag).expect("etags are ascii"),
+    );
+    if etag_matches(if_none_match, &etag) {
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+    } else {
+        let body = context
+            .response_cache
+            .get_or_insert(&etag, serialize)
+            .map_err(|e| e.into())?;
+        *response.body_mut() = Body::from(body.as_ref().clone());
+    }
+    Ok(())
+}
+
+// Every request is counted, including the refused ones. Errors can only come
+// from the routes.
+async fn metered_node_service<B: Blockchain>(
+    client: SocketAddr,
+    context: Arc<NodeContext<B>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, NodeError> {
+    let endpoint = format!("{} {}", req.method(), req.uri().path());
+    let started = std::time::Instant::now();
+    let result = node_service(client, Arc::clone(&context), req).await;
+    let routed = match &result {
+        Ok(resp) => resp.extensions().get::<metrics::Routed>().is_some(),
+        Err(_) => true,
+    };
+    context.metrics.record(
+        Some(endpoint).filter(|_| routed),
+        metrics::RequestOutcome::of(&result),
+        started.elapsed(),
+    );
+    result
 }
 
 async fn node_service<B: Blockchain>(
-    _client: SocketAddr,
-    context: Arc<RwLock<NodeContext<B>>>,
+    client: SocketAddr,
+    context: Arc<NodeContext<B>>,
     req: Request<Body>,
 ) -> Result<Response<Body>, NodeError> {
     let mut response = Response::new(Body::empty());
     let method = req.method().clone();
     let path = req.uri().path().to_string();
     let qs = req.uri().query().unwrap_or("").to_string();
+    let relay_auth = req
+        .headers()
+        .get(relay::RELAY_AUTH_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let admin_token = req
+        .headers()
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let if_none_match = req
+        .headers()
+        .get(hyper::header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+
+    // Admin endpoints are only served to local clients
+    if path.starts_with("/admin/") && !client.ip().is_loopback() {
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        return Ok(response);
+    }
+
+    if !context.role.serves(&path) {
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+
+    // Banned peers are refused, the admin endpoints stay reachable so that
+    // the operator can lift a ban
+    if !path.starts_with("/admin/") && context.is_banned(client.ip()).await {
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        return Ok(response);
+    }
+
+    // Held until the response is ready
+    let _permit = match context.scheduler.admit(Priority::of(&method, &path)).await {
+        Some(permit) => permit,
+        None => {
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            response.headers_mut().insert(
+                hyper::header::RETRY_AFTER,
+                hyper::header::HeaderValue::from(1),
+            );
+            return Ok(response);
+        }
+    };
+
+    // Upgraded to a WebSocket, needs the whole request
+    if method == Method::GET && path == "/ws" {
+        let mut response = api::ws(Arc::clone(&context), req).await?;
+        response.extensions_mut().insert(metrics::Routed);
+        return Ok(response);
+    }
     let body = req.into_body();
 
+    response.extensions_mut().insert(metrics::Routed);
+
     match (method, &path[..]) {
         // Miner will call this to fetch new PoW work.
         #[cfg(feature = "pow")]
@@ -113,6 +354,206 @@ async fn node_service<B: Blockchain>(
             )?);
         }
 
+        // Succinct proof of the chain's work, for light clients and bridges.
+        #[cfg(feature = "pow")]
+        (Method::GET, "/proof/chain") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_chain_proof(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+
+        #[cfg(feature = "pow")]
+        (Method::GET, "/blocktemplate") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_block_template(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+
+        #[cfg(feature = "pow")]
+        (Method::POST, "/submitblock") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::submit_block(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+
+        #[cfg(feature = "pow")]
+        (Method::GET, "/miner/stats") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_miner_stats(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+
+        #[cfg(feature = "pow")]
+        (Method::GET, "/stats/blocktime") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_blocktime_stats(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+
+        (Method::GET, "/admin/metrics") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_metrics(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/admin/db/stats") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_db_stats(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::POST, "/admin/db/compact") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_db_compact(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/admin/ban") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_ban(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/admin/rewind") if context.is_admin(&admin_token) => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_rewind(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/admin/invalidate") if context.is_admin(&admin_token) => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_invalidate(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/admin/rewind") | (Method::POST, "/admin/invalidate") => {
+            *response.status_mut() = StatusCode::FORBIDDEN;
+        }
+        (Method::GET, "/admin/banned") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_banned(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/transaction") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_transaction(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::POST, "/relay/block") => {
+            let body = hyper::body::to_bytes(body).await?;
+            match &context.relay {
+                Some(relay) if relay.verify(&body, &relay_auth) => {
+                    *response.body_mut() = Body::from(bincode::serialize(
+                        &api::post_relay_block(Arc::clone(&context), bincode::deserialize(&body)?)
+                            .await?,
+                    )?);
+                }
+                _ => {
+                    context
+                        .misbehave_ip(client.ip(), punish::BAD_SIGNATURE_SCORE)
+                        .await;
+                    *response.status_mut() = StatusCode::FORBIDDEN;
+                }
+            }
+        }
+        // The block at a height may change, but not the one behind a hash
+        (Method::GET, "/block") => {
+            let resp = api::get_block(Arc::clone(&context), serde_qs::from_str(&qs)?).await?;
+            let etag = format!("\"block-{}\"", hex::encode(resp.block.header.hash()));
+            respond_cached(&context, &mut response, &if_none_match, etag, || {
+                serde_json::to_vec(&resp)
+            })?;
+        }
+        (Method::GET, "/block/hash") => {
+            let resp =
+                api::get_block_by_hash(Arc::clone(&context), serde_qs::from_str(&qs)?).await?;
+            let etag = format!("\"block-{}\"", hex::encode(resp.block.header.hash()));
+            respond_cached(&context, &mut response, &if_none_match, etag, || {
+                serde_json::to_vec(&resp)
+            })?;
+        }
+        (Method::GET, "/chain/tips") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_chain_tips(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/account") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_account(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/account/balance") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_balance(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/account/next_nonce") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_next_nonce(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/mempool/graph") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_mempool_graph(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/checkpoint") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_checkpoint(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/reorgs") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_reorgs(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::GET, "/alerts") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_alerts(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
+        (Method::POST, "/alerts") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_alert(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/rpc") => {
+            match api::rpc(Arc::clone(&context), &hyper::body::to_bytes(body).await?).await {
+                Some(resp) => {
+                    response.headers_mut().insert(
+                        hyper::header::CONTENT_TYPE,
+                        hyper::header::HeaderValue::from_static("application/json"),
+                    );
+                    *response.body_mut() = Body::from(serde_json::to_vec(&resp)?);
+                }
+                None => {
+                    *response.status_mut() = StatusCode::NO_CONTENT;
+                }
+            }
+        }
+        (Method::GET, "/handshake") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::get_handshake(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+            )?);
+        }
         (Method::GET, "/peers") => {
             *response.body_mut() = Body::from(serde_json::to_vec(
                 &api::get_peers(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
@@ -127,6 +568,24 @@ async fn node_service<B: Blockchain>(
                 .await?,
             )?);
         }
+        (Method::POST, "/decode/tx") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_decode_tx(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
+        (Method::POST, "/decode/block") => {
+            *response.body_mut() = Body::from(serde_json::to_vec(
+                &api::post_decode_block(
+                    Arc::clone(&context),
+                    serde_json::from_slice(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
+            )?);
+        }
         (Method::POST, "/bincode/transact") => {
             *response.body_mut() = Body::from(serde_json::to_vec(
                 &api::transact(
@@ -136,26 +595,63 @@ async fn node_service<B: Blockchain>(
                 .await?,
             )?);
         }
-        (Method::GET, "/bincode/headers") => {
+        (Method::POST, "/bincode/announce") => {
             *response.body_mut() = Body::from(bincode::serialize(
-                &api::get_headers(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
+                &api::post_announce(
+                    Arc::clone(&context),
+                    client.ip(),
+                    bincode::deserialize(&hyper::body::to_bytes(body).await?)?,
+                )
+                .await?,
             )?);
         }
+        // Headers are chained, the last one commits to the whole range
+        (Method::GET, "/bincode/headers") => {
+            let req: api::messages::GetHeadersRequest = serde_qs::from_str(&qs)?;
+            let since = req.since;
+            let resp = api::get_headers(Arc::clone(&context), req).await?;
+            match resp.headers.last() {
+                Some(last) => {
+                    let etag = format!("\"headers-{}-{}\"", since, hex::encode(last.hash()));
+                    respond_cached(&context, &mut response, &if_none_match, etag, || {
+                        bincode::serialize(&resp)
+                    })?;
+                }
+                None => {
+                    *response.body_mut() = Body::from(bincode::serialize(&resp)?);
+                }
+            }
+        }
         (Method::GET, "/bincode/blocks") => {
             *response.body_mut() = Body::from(bincode::serialize(
                 &api::get_blocks(Arc::clone(&context), serde_qs::from_str(&qs)?).await?,
             )?);
         }
         (Method::POST, "/bincode/blocks") => {
-            *response.body_mut() = Body::from(bincode::serialize(
-                &api::post_block(
-                    Arc::clone(&context),
-                    bincode::deserialize(&hyper::body::to_bytes(body).await?)?,
-                )
-                .await?,
-            )?);
+            let req = match bincode::deserialize(&hyper::body::to_bytes(body).await?) {
+                Ok(req) => req,
+                Err(e) => {
+                    context
+                        .misbehave_ip(client.ip(), punish::PROTOCOL_VIOLATION_SCORE)
+                        .await;
+                    return Err(e.into());
+                }
+            };
+            let resp = api::post_block(Arc::clone(&context), req).await;
+            let invalid = match &resp {
+                Err(NodeError::BlockchainError(e)) => e.is_invalid_block(),
+                Err(NodeError::KnownInvalidBlock(_)) => true,
+                _ => false,
+            };
+            if invalid {
+                context
+                    .misbehave_ip(client.ip(), punish::INVALID_BLOCK_SCORE)
+                    .await;
+            }
+            *response.body_mut() = Body::from(bincode::serialize(&resp?)?);
         }
         _ => {
+            response.extensions_mut().remove::<metrics::Routed>();
             *response.status_mut() = StatusCode::NOT_FOUND;
         }
     };
@@ -169,29 +665,74 @@ impl<B: Blockchain + std::marker::Sync + std::marker::Send> Node<B> {
         bootstrap: Vec<PeerAddress>,
         blockchain: B,
         wallet: Option<Wallet>,
+        anchors: AnchorPeers,
+        payouts: Option<PayoutSplit>,
+        relay: Option<RelayConfig>,
+        admin_token: Option<String>,
+        role: NodeRole,
     ) -> Node<B> {
+        let mut peers: HashMap<PeerAddress, PeerStats> = bootstrap
+            .into_iter()
+            .chain(anchors.addresses().into_iter())
+            .map(|addr| {
+                (
+                    addr,
+                    PeerStats {
+                        punished_until: 0,
+                        info: None,
+                        last_seen: 0,
+                        misbehavior: 0,
+                        banned_until: 0,
+                        added: 0,
+                        announced_at: 0,
+                    },
+                )
+            })
+            .collect();
+        for (addr, stats) in load_peers(&blockchain) {
+            peers.entry(addr).or_insert(stats);
+        }
+        let invalid_blocks = load_invalid_blocks(&blockchain);
+        let (chain, chain_commands) = ChainHandle::new();
+        let (announcer, announce_events) = AnnounceHandle::new();
         Node {
             address,
-            context: Arc::new(RwLock::new(NodeContext {
-                blockchain,
-                wallet,
-                mempool: HashMap::new(),
-                peers: bootstrap
-                    .into_iter()
-                    .map(|addr| {
-                        (
-                            addr,
-                            PeerStats {
-                                punished_until: 0,
-                                info: None,
-                            },
-                        )
-                    })
-                    .collect(),
-                timestamp_offset: 0,
+            chain_commands: std::sync::Mutex::new(Some(chain_commands)),
+            announce_events: std::sync::Mutex::new(Some(announce_events)),
+            context: Arc::new(NodeContext {
+                #[cfg(feature = "pow")]
+                miner: RwLock::new(Default::default()),
+                blockchain: RwLock::new(blockchain),
+                chain,
+                announcer,
+                mempool: RwLock::new(Mempool::new(
+                    config::MEMPOOL_MAX_SIZE,
+                    config::MEMPOOL_TX_TTL,
+                    config::MEMPOOL_NONCE_HORIZON,
+                    config::MEMPOOL_MAX_FUTURE_PER_SENDER,
+                )),
+                peers: RwLock::new(peers),
+                ip_stats: RwLock::new(HashMap::new()),
+                alerts: RwLock::new(HashMap::new()),
+                anchors: RwLock::new(anchors),
+                events: broadcast::channel(EVENT_BUFFER_SIZE).0,
                 #[cfg(feature = "pow")]
-                miner: None,
-            })),
+                miner_registered: Notify::new(),
+                sync_blacklist: RwLock::new(SyncBlacklist::default()),
+                invalid_blocks: RwLock::new(invalid_blocks),
+                best_header: RwLock::new(None),
+                timestamp_offset: AtomicI32::new(0),
+                peers_persisted_at: AtomicU32::new(0),
+                blocks_polled_at: AtomicU32::new(0),
+                wallet,
+                payouts,
+                relay,
+                admin_token,
+                scheduler: RequestScheduler::new(),
+                metrics: Default::default(),
+                response_cache: ResponseCache::new(config::RESPONSE_CACHE_SIZE),
+                role,
+            }),
         }
     }
 
@@ -205,7 +746,7 @@ impl<B: Blockchain + std::marker::Sync + std::marker::Send> Node<B> {
                 Ok::<_, NodeError>(service_fn(move |req: Request<Body>| {
                     let node_context = Arc::clone(&node_context);
                     let client = client.clone();
-                    async move { node_service(client, node_context, req).await }
+                    async move { metered_node_service(client, node_context, req).await }
                 }))
             }
         });
@@ -218,9 +759,381 @@ impl<B: Blockchain + std::marker::Sync + std::marker::Send> Node<B> {
         let server_future = self.server();
         let heartbeat_future =
             heartbeat::heartbeater(self.address.clone(), Arc::clone(&self.context));
+        let chain_commands = self
+            .chain_commands
+            .lock()
+            .unwrap()
+            .take()
+            .expect("node is already running");
+        let chain_future = chain::chain_actor(Arc::clone(&self.context), chain_commands);
+        let announce_events = self
+            .announce_events
+            .lock()
+            .unwrap()
+            .take()
+            .expect("node is already running");
+        let announce_future = announce::announcer(
+            self.address.clone(),
+            Arc::clone(&self.context),
+            announce_events,
+        );
+        #[cfg(feature = "pow")]
+        let miner_future = miner::miner_notifier(Arc::clone(&self.context));
+        #[cfg(not(feature = "pow"))]
+        let miner_future = futures::future::ok::<(), NodeError>(());
 
-        try_join!(server_future, heartbeat_future)?;
+        try_join!(
+            server_future,
+            heartbeat_future,
+            chain_future,
+            announce_future,
+            miner_future
+        )?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::KvStoreChain;
+    use crate::core::{Alert, Signature};
+    use crate::crypto::{EdDSA, SignatureScheme};
+    use crate::db::RamKvStore;
+
+    pub(super) fn test_node() -> Node<KvStoreChain<RamKvStore>> {
+        Node::new(
+            PeerAddress("127.0.0.1".parse().unwrap(), 3030),
+            vec![],
+            KvStoreChain::new(RamKvStore::new()).unwrap(),
+            None,
+            AnchorPeers::default(),
+            None,
+            None,
+            None,
+            NodeRole::default(),
+        )
+    }
+
+    fn test_context() -> Arc<NodeContext<KvStoreChain<RamKvStore>>> {
+        test_node().context
+    }
+
+    #[tokio::test]
+    async fn test_metered_routes() {
+        let context = test_context();
+        let client: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        for path in [
+            "/chain/tips",
+            "/transaction?hash=zz",
+            "/nonexistent",
+            "/admin/banned",
+        ] {
+            let req = Request::get(path).body(Body::empty()).unwrap();
+            let _ = metered_node_service(client, Arc::clone(&context), req).await;
+        }
+        let metrics = context.metrics.snapshot();
+        assert_eq!(metrics["GET /chain/tips"].ok, 1);
+        assert_eq!(metrics["GET /transaction"].bad_request, 1);
+        // Unknown, or refused before routing (Admin endpoints of remote clients)
+        assert_eq!(metrics["other"].requests, 2);
+        assert_eq!(metrics.len(), 3);
+    }
+
+    #[cfg(feature = "pow")]
+    #[tokio::test]
+    async fn test_invalid_block_cache() {
+        use crate::blockchain::BlockchainError;
+        use crate::core::Block;
+        let node = test_node();
+        let context = Arc::clone(&node.context);
+        let commands = node.chain_commands.lock().unwrap().take().unwrap();
+        tokio::spawn(chain::chain_actor(Arc::clone(&context), commands));
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        let block = context
+            .blockchain
+            .read()
+            .await
+            .draft_block(utils::local_timestamp(), &vec![], &payouts)
+            .unwrap();
+        let post = |block: Block| {
+            api::post_block(
+                Arc::clone(&context),
+                api::messages::PostBlockRequest { block },
+            )
+        };
+
+        // Blocks of another branch are only invalid where they were tried
+        let mut fork = block.clone();
+        fork.header.parent_hash = [1u8; 32];
+        let hash = fork.header.hash();
+        assert!(matches!(
+            post(fork).await,
+            Err(NodeError::BlockchainError(
+                BlockchainError::InvalidParentHash
+            ))
+        ));
+        assert!(context.known_invalid(std::iter::once(hash)).await.is_none());
+
+        // A body not matching its header is invalid on any chain
+        let mut forged = block;
+        forged.header.block_root = [1u8; 32];
+        let hash = forged.header.hash();
+        assert!(post(forged).await.is_err());
+        assert!(context.known_invalid(std::iter::once(hash)).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate() {
+        use crate::blockchain::BlockchainError;
+        let node = test_node();
+        let context = Arc::clone(&node.context);
+        let commands = node.chain_commands.lock().unwrap().take().unwrap();
+        tokio::spawn(chain::chain_actor(Arc::clone(&context), commands));
+        let invalidate = |hash: [u8; 32]| {
+            api::post_invalidate(
+                Arc::clone(&context),
+                api::messages::PostInvalidateRequest {
+                    hash: hex::encode(hash),
+                },
+            )
+        };
+        let known_invalid = |hash| context.known_invalid(std::iter::once(hash));
+
+        // A failed rewind leaves the block alone
+        let genesis = context
+            .blockchain
+            .read()
+            .await
+            .get_headers(0, Some(1))
+            .unwrap()[0]
+            .hash();
+        assert!(matches!(
+            invalidate(genesis).await,
+            Err(NodeError::BlockchainError(
+                BlockchainError::InvalidRewindHeight(0)
+            ))
+        ));
+        assert!(known_invalid(genesis).await.is_none());
+
+        // Drafted blocks need a validator's seal to be valid under PoS
+        if cfg!(feature = "pos") {
+            return;
+        }
+        let payouts = PayoutSplit::single(Wallet::new(b"MINER".to_vec()).get_address());
+        let block = context
+            .blockchain
+            .read()
+            .await
+            .draft_block(utils::local_timestamp(), &vec![], &payouts)
+            .unwrap();
+        let hash = block.header.hash();
+        api::post_block(
+            Arc::clone(&context),
+            api::messages::PostBlockRequest { block },
+        )
+        .await
+        .unwrap();
+        let resp = invalidate(hash).await.unwrap();
+        assert_eq!(resp.rolled_back, 1);
+        assert!(known_invalid(hash).await.is_some());
+        let records = context
+            .blockchain
+            .read()
+            .await
+            .get_records(INVALID_RECORDS)
+            .unwrap();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_misbehavior_ban() {
+        let mut stats = PeerStats {
+            punished_until: 0,
+            info: None,
+            last_seen: 0,
+            misbehavior: 0,
+            banned_until: 0,
+            added: 0,
+            announced_at: 0,
+        };
+        assert!(!stats.misbehave(punish::INVALID_BLOCK_SCORE));
+        assert!(!stats.is_banned());
+        assert!(stats.misbehave(punish::INVALID_BLOCK_SCORE));
+        assert!(stats.is_banned());
+        // Score starts over after the ban
+        assert_eq!(stats.misbehavior, 0);
+    }
+
+    #[test]
+    fn test_load_peers() {
+        let mut chain = KvStoreChain::new(RamKvStore::new()).unwrap();
+        let now = utils::local_timestamp();
+        let addr = PeerAddress("127.0.0.1".parse().unwrap(), 3030);
+        let stale = PeerAddress("127.0.0.2".parse().unwrap(), 3030);
+        // Saved before the misbehavior and age of peers were tracked
+        let old_record = |addr: &PeerAddress, last_seen: u32| {
+            format!(
+                r#"[{}, {{"punished_until": 0, "info": null, "last_seen": {}}}]"#,
+                serde_json::to_string(addr).unwrap(),
+                last_seen
+            )
+            .into_bytes()
+        };
+        chain
+            .update_records(
+                PEER_RECORDS,
+                vec![
+                    (peer_record_id(&addr), Some(old_record(&addr, now))),
+                    (
+                        peer_record_id(&stale),
+                        Some(old_record(&stale, now - config::PEER_MAX_AGE - 1)),
+                    ),
+                ],
+            )
+            .unwrap();
+        let peers = load_peers(&chain);
+        assert_eq!(peers.len(), 1);
+        let stats = &peers[&addr];
+        assert_eq!(stats.last_seen, now);
+        assert_eq!(
+            (stats.misbehavior, stats.banned_until, stats.added),
+            (0, 0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_misbehave_ip() {
+        let context = test_context();
+        let ip: IpAddr = "127.0.0.2".parse().unwrap();
+        let peers = [PeerAddress(ip, 3030), PeerAddress(ip, 3031)];
+        for peer in peers.iter() {
+            context.peers.write().await.insert(
+                *peer,
+                PeerStats {
+                    punished_until: 0,
+                    info: None,
+                    last_seen: 0,
+                    misbehavior: 0,
+                    banned_until: 0,
+                    added: 0,
+                    announced_at: 0,
+                },
+            );
+        }
+        context.misbehave_ip(ip, punish::BAN_THRESHOLD - 1).await;
+        assert!(!context.is_banned(ip).await);
+        context.misbehave_ip(ip, 1).await;
+        assert!(context.is_banned(ip).await);
+        assert!(!context.is_banned("127.0.0.3".parse().unwrap()).await);
+
+        // The scores of the peers behind the address are kept apart
+        for peer in peers.iter() {
+            let stats = context.peers.read().await[peer].clone();
+            assert_eq!(stats.misbehavior, 0);
+            assert!(!stats.is_banned());
+        }
+        context.misbehave(peers[0], punish::BAN_THRESHOLD - 1).await;
+        assert!(!context.peers.read().await[&peers[0]].is_banned());
+    }
+
+    #[cfg(feature = "pow")]
+    #[test]
+    fn test_miner_stats() {
+        let mut stats = MinerStats::default();
+        stats.record(SolutionStatus::Accepted, 1000, 100);
+        stats.record(SolutionStatus::Stale, 0, 105);
+        stats.record(SolutionStatus::Accepted, 1000, 110);
+        assert_eq!((stats.accepted, stats.stale, stats.invalid), (2, 1, 0));
+        assert_eq!(stats.hashrate, 200.0);
+
+        // A solution timestamped before the first one doesn't underflow
+        stats.record(SolutionStatus::Invalid, 0, 90);
+        assert_eq!(stats.invalid, 1);
+        assert_eq!(stats.hashrate, 200.0);
+        stats.record(SolutionStatus::Accepted, u64::MAX, 120);
+        assert_eq!(stats.work, u64::MAX);
+    }
+
+    #[cfg(feature = "pow")]
+    #[tokio::test]
+    async fn test_unidentified_miner_solution() {
+        let context = test_context();
+        api::post_miner(
+            Arc::clone(&context),
+            api::messages::RegisterMinerRequest {
+                webhook: "http://miner".into(),
+            },
+        )
+        .await
+        .unwrap();
+        let solution = |miner: Option<&str>| api::messages::PostMinerSolutionRequest {
+            nonce: "0000000000000000".into(),
+            miner: miner.map(String::from),
+        };
+        assert!(matches!(
+            api::post_miner_solution(Arc::clone(&context), solution(None)).await,
+            Err(NodeError::UnidentifiedMinerError)
+        ));
+        assert!(matches!(
+            api::post_miner_solution(Arc::clone(&context), solution(Some("http://other"))).await,
+            Err(NodeError::NoMinerError)
+        ));
+        // Nothing is recorded for solutions nobody can be blamed for
+        assert!(context.miner.read().await.miner_stats.is_empty());
+        assert!(matches!(
+            api::post_miner_solution(Arc::clone(&context), solution(Some("http://miner"))).await,
+            Err(NodeError::NoCurrentlyMiningBlockError)
+        ));
+        assert_eq!(
+            context.miner.read().await.miner_stats["http://miner"].stale,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_alert() {
+        let context = test_context();
+        let authority = Wallet::new(b"ALERT".to_vec());
+        let (alert_key, _) = EdDSA::generate_keys(b"ALERT");
+        let now = context.network_timestamp();
+        let alert = |id: u32, expires_at: u32| {
+            let mut alert = Alert {
+                id,
+                expires_at,
+                message: "Upgrade to the latest version".into(),
+                sig: Signature::Unsigned,
+            };
+            authority.sign_alert(&mut alert);
+            alert
+        };
+
+        // Alerts are ignored when no alert key is configured
+        assert!(!context.add_alert(alert(1, now + 100)).await);
+
+        assert!(
+            context
+                .add_alert_signed_by(alert(1, now + 100), &alert_key)
+                .await
+        );
+        // The same id can't be reused to override an alert
+        assert!(
+            !context
+                .add_alert_signed_by(alert(1, now + 200), &alert_key)
+                .await
+        );
+        assert!(
+            !context
+                .add_alert_signed_by(alert(2, now - 1), &alert_key)
+                .await
+        );
+        let mut unsigned = alert(3, now + 100);
+        unsigned.sig = Signature::Unsigned;
+        assert!(!context.add_alert_signed_by(unsigned, &alert_key).await);
+
+        let active = context.active_alerts().await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].expires_at, now + 100);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::config;
+use hyper::Method;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    // Blocks, headers and everything else keeping the node in consensus
+    Consensus,
+    // Explorer and wallet queries
+    Query,
+}
+
+impl Priority {
+    pub fn of(method: &Method, path: &str) -> Self {
+        match (method, path) {
+            (_, "/bincode/blocks")
+            | (_, "/bincode/announce")
+            | (_, "/handshake")
+            | (_, "/bincode/headers")
+            | (_, "/relay/block")
+            | (_, "/submitblock")
+            | (&Method::POST, "/peers") => Priority::Consensus,
+            _ if path.starts_with("/miner") => Priority::Consensus,
+            _ => Priority::Query,
+        }
+    }
+}
+
+// A bounded number of requests are processed at once, at most `max_queued`
+// more may wait for their turn. Anything beyond that is shed right away.
+struct Lane {
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+// A place in the queue of a lane, given up when dropped. Requests are
+// cancelled by dropping their future, which must not leave them counted.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Lane {
+    fn new(max_active: usize, max_queued: usize) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(max_active)),
+            queued: AtomicUsize::new(0),
+            max_queued,
+        }
+    }
+    fn waiting(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = Arc::clone(&self.slots).try_acquire_owned() {
+            return Some(permit);
+        }
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        let _place = Queued(&self.queued);
+        if queued >= self.max_queued {
+            return None;
+        }
+        Arc::clone(&self.slots).acquire_owned().await.ok()
+    }
+}
+
+// Keeps consensus traffic flowing under query storms: the two classes have
+// separate lanes, and queries are shed while consensus requests are waiting.
+pub struct RequestScheduler {
+    consensus: Lane,
+    query: Lane,
+}
+
+impl RequestScheduler {
+    pub fn new() -> Self {
+        Self {
+            consensus: Lane::new(
+                config::MAX_ACTIVE_CONSENSUS_REQUESTS,
+                config::MAX_QUEUED_CONSENSUS_REQUESTS,
+            ),
+            query: Lane::new(
+                config::MAX_ACTIVE_QUERY_REQUESTS,
+                config::MAX_QUEUED_QUERY_REQUESTS,
+            ),
+        }
+    }
+
+    // None means the request should be rejected with a shed-load response
+    pub async fn admit(&self, priority: Priority) -> Option<OwnedSemaphorePermit> {
+        match priority {
+            Priority::Consensus => self.consensus.acquire().await,
+            Priority::Query => {
+                if self.consensus.waiting() > 0 {
+                    return None;
+                }
+                self.query.acquire().await
+            }
+        }
+    }
+}
+
+impl Default for RequestScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_priority() {
+        assert_eq!(
+            Priority::of(&Method::POST, "/bincode/blocks"),
+            Priority::Consensus
+        );
+        assert_eq!(
+            Priority::of(&Method::GET, "/miner/puzzle"),
+            Priority::Consensus
+        );
+        assert_eq!(Priority::of(&Method::GET, "/peers"), Priority::Query);
+        assert_eq!(Priority::of(&Method::GET, "/block"), Priority::Query);
+    }
+
+    #[tokio::test]
+    async fn test_lane_sheds_load() {
+        let lane = Lane::new(1, 1);
+        let first = lane.acquire().await.unwrap();
+        let waiter = {
+            let lane = &lane;
+            async move { lane.acquire().await.is_some() }
+        };
+        tokio::pin!(waiter);
+        // Polling once puts the waiter in the queue
+        assert!(futures::poll!(&mut waiter).is_pending());
+        assert_eq!(lane.waiting(), 1);
+        assert!(lane.acquire().await.is_none());
+        drop(first);
+        assert!(waiter.await);
+        assert_eq!(lane.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_lane_cancelled_waiter() {
+        let lane = Lane::new(1, 1);
+        let first = lane.acquire().await.unwrap();
+        {
+            let waiter = lane.acquire();
+            tokio::pin!(waiter);
+            assert!(futures::poll!(&mut waiter).is_pending());
+            assert_eq!(lane.waiting(), 1);
+            // The client went away while waiting
+        }
+        assert_eq!(lane.waiting(), 0);
+        drop(first);
+        // The queue has room again
+        let _second = lane.acquire().await.unwrap();
+        let waiter = lane.acquire();
+        tokio::pin!(waiter);
+        assert!(futures::poll!(&mut waiter).is_pending());
+        assert_eq!(lane.waiting(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+use super::api::messages::{PostRelayBlockRequest, PostRelayBlockResponse};
+use super::{http, NodeContext, NodeError, PeerAddress};
+use crate::blockchain::Blockchain;
+use crate::core::hash::domains;
+use crate::core::{Block, Hasher};
+use hyper::{Body, Client, Method, Request};
+
+pub const RELAY_AUTH_HEADER: &str = "x-bazuka-relay-auth";
+
+// Hot/cold split: a block producing node (Holding the wallet, not reachable
+// from the outside) forwards the blocks it produces to the public-facing API
+// nodes (`targets`). Both sides share the key, the forwarded blocks are
+// authenticated with a keyed hash of the request body.
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    pub key: Vec<u8>,
+    pub targets: Vec<PeerAddress>,
+}
+
+impl RelayConfig {
+    pub fn auth_tag(&self, body: &[u8]) -> String {
+        let mut data = (self.key.len() as u64).to_le_bytes().to_vec();
+        data.extend(&self.key);
+        data.extend(body);
+        hex::encode(domains::hash_relay::<Hasher>(&data))
+    }
+    // Compared in constant time, not to leak the expected tag
+    pub fn verify(&self, body: &[u8], tag: &str) -> bool {
+        let expected = self.auth_tag(body);
+        !self.key.is_empty()
+            && expected.len() == tag.len()
+            && expected
+                .bytes()
+                .zip(tag.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+async fn post_block(
+    relay: &RelayConfig,
+    target: PeerAddress,
+    body: Vec<u8>,
+) -> Result<PostRelayBlockResponse, NodeError> {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{}/relay/block", target))
+        .header("content-type", "application/octet-stream")
+        .header(RELAY_AUTH_HEADER, relay.auth_tag(&body))
+        .body(Body::from(body))?;
+    let body = Client::new().request(req).await?.into_body();
+    Ok(bincode::deserialize(&hyper::body::to_bytes(body).await?)?)
+}
+
+// Forwards a block produced by this node to the API nodes, if any
+pub async fn forward_block<B: Blockchain>(context: &NodeContext<B>, block: Block) {
+    let relay = match &context.relay {
+        Some(relay) if !relay.targets.is_empty() => relay,
+        _ => {
+            return;
+        }
+    };
+    let body = match bincode::serialize(&PostRelayBlockRequest { block }) {
+        Ok(body) => body,
+        Err(_) => {
+            return;
+        }
+    };
+    let resps = http::group_request(&relay.targets, |target| {
+        post_block(relay, target, body.clone())
+    })
+    .await;
+    for (target, resp) in resps {
+        match resp {
+            Ok(resp) if resp.accepted => {}
+            Ok(_) => tracing::warn!(peer = %target, "Forwarded block was rejected"),
+            Err(e) => tracing::warn!(peer = %target, error = %e, "Could not forward block"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_auth() {
+        let relay = RelayConfig {
+            key: b"secret".to_vec(),
+            targets: vec![],
+        };
+        let tag = relay.auth_tag(b"block");
+        assert!(relay.verify(b"block", &tag));
+        assert!(!relay.verify(b"other block", &tag));
+
+        let other = RelayConfig {
+            key: b"other secret".to_vec(),
+            targets: vec![],
+        };
+        assert!(!other.verify(b"block", &tag));
+
+        // Without a key, nothing is accepted
+        let keyless = RelayConfig {
+            key: vec![],
+            targets: vec![],
+        };
+        assert!(!keyless.verify(b"block", &keyless.auth_tag(b"block")));
+    }
+}
@@ -0,0 +1,102 @@
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+
+// What the node is run for, subsystems the role doesn't need are disabled so
+// that the resource usage matches the actual role of the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeRole {
+    // Everything enabled
+    Archival,
+    // Only follows and validates the chain, without the old block bodies
+    Pruned,
+    // Produces blocks, without the query indexes
+    Miner,
+    // Serves explorers and wallets, doesn't produce blocks
+    ApiOnly,
+    // Only passes blocks and transactions around
+    Relay,
+}
+
+#[derive(Error, Debug)]
+#[error("unknown node role: {0}")]
+pub struct UnknownRole(String);
+
+impl FromStr for NodeRole {
+    type Err = UnknownRole;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "archival" => Ok(NodeRole::Archival),
+            "pruned" => Ok(NodeRole::Pruned),
+            "miner" => Ok(NodeRole::Miner),
+            "api-only" => Ok(NodeRole::ApiOnly),
+            "relay" => Ok(NodeRole::Relay),
+            _ => Err(UnknownRole(s.into())),
+        }
+    }
+}
+
+// Role-specific builds pick their role by default
+impl Default for NodeRole {
+    fn default() -> Self {
+        if cfg!(feature = "relay") {
+            NodeRole::Relay
+        } else if cfg!(feature = "api-only") {
+            NodeRole::ApiOnly
+        } else if cfg!(feature = "miner") {
+            NodeRole::Miner
+        } else if cfg!(feature = "pruned") {
+            NodeRole::Pruned
+        } else {
+            NodeRole::Archival
+        }
+    }
+}
+
+impl NodeRole {
+    // Transaction index and block metadata
+    pub fn keeps_indexes(&self) -> bool {
+        matches!(self, NodeRole::Archival | NodeRole::ApiOnly)
+    }
+    // Bodies deeper than `config::PRUNE_DEPTH` blocks
+    pub fn prunes(&self) -> bool {
+        matches!(self, NodeRole::Pruned)
+    }
+    pub fn serves_miners(&self) -> bool {
+        matches!(self, NodeRole::Archival | NodeRole::Miner)
+    }
+    pub fn serves_admin(&self) -> bool {
+        !matches!(self, NodeRole::Relay)
+    }
+    pub fn serves(&self, path: &str) -> bool {
+        if path.starts_with("/miner") || path == "/blocktemplate" || path == "/submitblock" {
+            self.serves_miners()
+        } else if path.starts_with("/admin/") {
+            self.serves_admin()
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_endpoints() {
+        let archival: NodeRole = "archival".parse().unwrap();
+        assert!(archival.serves("/miner/puzzle"));
+        assert!(archival.serves("/admin/db/stats"));
+
+        let api: NodeRole = "api-only".parse().unwrap();
+        assert!(!api.serves("/submitblock"));
+        assert!(api.serves("/transaction"));
+
+        let relay: NodeRole = "relay".parse().unwrap();
+        assert!(!relay.serves("/admin/banned"));
+        assert!(relay.serves("/bincode/blocks"));
+
+        assert!("full".parse::<NodeRole>().is_err());
+    }
+}
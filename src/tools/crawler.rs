@@ -0,0 +1,252 @@
+use crate::node::api::messages::{GetPeersRequest, GetPeersResponse};
+use crate::node::{http, NodeError, PeerAddress, PeerStats, Timestamp};
+use crate::utils;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct CrawlerConfig {
+    // Stop after visiting this many nodes
+    pub max_nodes: usize,
+    // Nodes queried at the same time
+    pub concurrency: usize,
+    pub timeout: Duration,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        Self {
+            max_nodes: 1000,
+            concurrency: 32,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct CrawledNode {
+    pub address: PeerAddress,
+    // None if never queried, crawling may stop before reaching every node
+    pub reachable: Option<bool>,
+    pub latency_ms: Option<u64>,
+    // Height and version are what the neighbours of the node last heard
+    // from it, a node doesn't report its own
+    pub height: Option<usize>,
+    pub version: Option<String>,
+    pub user_agent: Option<String>,
+    pub peers: Vec<PeerAddress>,
+}
+
+impl CrawledNode {
+    fn new(address: PeerAddress) -> Self {
+        Self {
+            address,
+            reachable: None,
+            latency_ms: None,
+            height: None,
+            version: None,
+            user_agent: None,
+            peers: Vec::new(),
+        }
+    }
+}
+
+// What the crawler saw of the network, nodes are sorted by address
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NetworkSnapshot {
+    pub timestamp: Timestamp,
+    pub nodes: Vec<CrawledNode>,
+}
+
+impl NetworkSnapshot {
+    pub fn reachable(&self) -> impl Iterator<Item = &CrawledNode> {
+        self.nodes.iter().filter(|n| n.reachable == Some(true))
+    }
+    pub fn versions(&self) -> HashMap<String, usize> {
+        let mut versions = HashMap::new();
+        for node in self.nodes.iter() {
+            let version = node.version.clone().unwrap_or_else(|| "unknown".into());
+            *versions.entry(version).or_insert(0) += 1;
+        }
+        versions
+    }
+    pub fn write_json<W: std::io::Write>(&self, writer: W) -> Result<(), NodeError> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+// Book-keeping of a crawl, separate from the networking part
+#[derive(Default)]
+struct CrawlState {
+    nodes: BTreeMap<PeerAddress, CrawledNode>,
+    queue: VecDeque<PeerAddress>,
+    queried: HashSet<PeerAddress>,
+}
+
+impl CrawlState {
+    fn discover(&mut self, addr: PeerAddress) -> &mut CrawledNode {
+        if !self.nodes.contains_key(&addr) {
+            self.queue.push_back(addr);
+        }
+        self.nodes
+            .entry(addr)
+            .or_insert_with(|| CrawledNode::new(addr))
+    }
+    fn next_batch(&mut self, size: usize, max_nodes: usize) -> Vec<PeerAddress> {
+        let mut batch = Vec::new();
+        while batch.len() < size && self.queried.len() < max_nodes {
+            match self.queue.pop_front() {
+                Some(addr) => {
+                    if self.queried.insert(addr) {
+                        batch.push(addr);
+                    }
+                }
+                None => break,
+            }
+        }
+        batch
+    }
+    fn visited(
+        &mut self,
+        addr: PeerAddress,
+        latency: Duration,
+        peers: HashMap<PeerAddress, PeerStats>,
+    ) {
+        let node = self.discover(addr);
+        node.reachable = Some(true);
+        node.latency_ms = Some(latency.as_millis() as u64);
+        node.peers = peers.keys().cloned().collect();
+        node.peers.sort();
+        // Discovered in address order, so that crawls are reproducible
+        let mut peers = peers.into_iter().collect::<Vec<_>>();
+        peers.sort_by_key(|(peer, _)| *peer);
+        for (peer, stats) in peers {
+            let node = self.discover(peer);
+            if let Some(info) = stats.info {
+                // Prefer the most advanced report
+                if node.height.map(|h| h < info.height).unwrap_or(true) {
+                    node.height = Some(info.height);
+                    node.version = Some(info.version).filter(|v| !v.is_empty());
+                    node.user_agent = Some(info.user_agent).filter(|u| !u.is_empty());
+                }
+            }
+        }
+    }
+    fn unreachable(&mut self, addr: PeerAddress) {
+        self.discover(addr).reachable = Some(false);
+    }
+    fn snapshot(self, timestamp: Timestamp) -> NetworkSnapshot {
+        NetworkSnapshot {
+            timestamp,
+            nodes: self.nodes.into_values().collect(),
+        }
+    }
+}
+
+async fn probe(
+    addr: PeerAddress,
+    timeout: Duration,
+) -> Result<(Duration, HashMap<PeerAddress, PeerStats>), NodeError> {
+    let start = Instant::now();
+    let resp: GetPeersResponse = tokio::time::timeout(
+        timeout,
+        http::json_get(format!("{}/peers", addr), GetPeersRequest {}),
+    )
+    .await
+    .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    Ok((start.elapsed(), resp.peers))
+}
+
+// Walks the peer graph breadth-first, starting from the seeds
+pub async fn crawl(seeds: Vec<PeerAddress>, config: &CrawlerConfig) -> NetworkSnapshot {
+    let mut state = CrawlState::default();
+    for seed in seeds {
+        state.discover(seed);
+    }
+    loop {
+        let batch = state.next_batch(config.concurrency, config.max_nodes);
+        if batch.is_empty() {
+            break;
+        }
+        let results = join_all(batch.iter().map(|addr| probe(*addr, config.timeout))).await;
+        for (addr, result) in batch.into_iter().zip(results) {
+            match result {
+                Ok((latency, peers)) => state.visited(addr, latency, peers),
+                Err(e) => {
+                    tracing::debug!(peer = %addr, error = %e, "Node unreachable");
+                    state.unreachable(addr);
+                }
+            }
+        }
+    }
+    state.snapshot(utils::local_timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::PeerInfo;
+
+    fn addr(port: u16) -> PeerAddress {
+        PeerAddress("127.0.0.1".parse().unwrap(), port)
+    }
+
+    fn stats(height: Option<usize>) -> PeerStats {
+        PeerStats {
+            punished_until: 0,
+            info: height.map(|height| PeerInfo {
+                height,
+                #[cfg(feature = "pow")]
+                power: 0,
+                version: "0.1.0".into(),
+                user_agent: String::new(),
+            }),
+            last_seen: 0,
+            misbehavior: 0,
+            banned_until: 0,
+            added: 0,
+            announced_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_crawl_state() {
+        let mut state = CrawlState::default();
+        state.discover(addr(1));
+        assert_eq!(state.next_batch(10, 10), vec![addr(1)]);
+        state.visited(
+            addr(1),
+            Duration::from_millis(20),
+            [(addr(2), stats(Some(5))), (addr(3), stats(None))]
+                .into_iter()
+                .collect(),
+        );
+        // Only one more node may be queried
+        assert_eq!(state.next_batch(10, 2), vec![addr(2)]);
+        state.visited(
+            addr(2),
+            Duration::from_millis(30),
+            [(addr(1), stats(Some(7))), (addr(3), stats(Some(4)))]
+                .into_iter()
+                .collect(),
+        );
+        assert!(state.next_batch(10, 2).is_empty());
+        state.unreachable(addr(3));
+
+        let snapshot = state.snapshot(0);
+        assert_eq!(
+            snapshot.nodes.iter().map(|n| n.address).collect::<Vec<_>>(),
+            vec![addr(1), addr(2), addr(3)]
+        );
+        assert_eq!(snapshot.reachable().count(), 2);
+        assert_eq!(snapshot.nodes[0].height, Some(7));
+        assert_eq!(snapshot.nodes[0].latency_ms, Some(20));
+        assert_eq!(snapshot.nodes[0].peers, vec![addr(2), addr(3)]);
+        assert_eq!(snapshot.nodes[2].reachable, Some(false));
+        assert_eq!(snapshot.nodes[2].height, Some(4));
+        assert_eq!(snapshot.versions()["0.1.0"], 3);
+    }
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "crawler")]
+pub mod crawler;
+#[cfg(feature = "seeder")]
+pub mod seeder;
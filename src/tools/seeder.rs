@@ -0,0 +1,312 @@
+use super::crawler::{crawl, CrawlerConfig, NetworkSnapshot};
+use crate::node::{NodeError, PeerAddress};
+use rand::seq::SliceRandom;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+
+#[derive(Debug, Clone)]
+pub struct SeederConfig {
+    // Name the seeder is authoritative for, e.g. "seed.bazuka.network"
+    pub domain: String,
+    pub listen: SocketAddr,
+    pub seeds: Vec<PeerAddress>,
+    // DNS can't carry ports, only the nodes listening on this one are served
+    pub port: u16,
+    pub crawl_interval: Duration,
+    pub crawler: CrawlerConfig,
+    // Nodes further than this behind the highest known height are left out
+    pub max_height_lag: usize,
+    pub max_records: usize,
+    pub ttl: u32,
+}
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NXDOMAIN: u16 = 3;
+const RCODE_NOTIMP: u16 = 4;
+
+// Responses over UDP are limited to 512 bytes (RFC 1035), as many records as
+// fit are sent
+const MAX_UDP_SIZE: usize = 512;
+
+// Addresses of the nodes worth handing out, the ones that answered the last
+// crawl and are not lagging behind the rest of the network
+pub fn healthy_nodes(snapshot: &NetworkSnapshot, port: u16, max_height_lag: usize) -> Vec<IpAddr> {
+    let best = snapshot
+        .reachable()
+        .filter_map(|n| n.height)
+        .max()
+        .unwrap_or(0);
+    snapshot
+        .reachable()
+        .filter(|n| n.address.1 == port)
+        .filter(|n| n.height.unwrap_or(0) + max_height_lag >= best)
+        .map(|n| n.address.0)
+        .collect()
+}
+
+// Random sample of the nodes of the family asked by the query
+fn sample_nodes(nodes: &[IpAddr], qtype: u16, max_records: usize) -> Vec<IpAddr> {
+    let matching = nodes
+        .iter()
+        .filter(|ip| match qtype {
+            TYPE_A => ip.is_ipv4(),
+            TYPE_AAAA => ip.is_ipv6(),
+            _ => false,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    matching
+        .choose_multiple(&mut rand::thread_rng(), max_records)
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Query {
+    id: u16,
+    flags: u16,
+    name: String,
+    qtype: u16,
+    qclass: u16,
+    // Raw question section, echoed back in the response
+    question: Vec<u8>,
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(pos)?, *buf.get(pos + 1)?]))
+}
+
+// Only single-question queries without name compression are understood, which
+// is what resolvers send
+fn parse_query(buf: &[u8]) -> Option<Query> {
+    let id = read_u16(buf, 0)?;
+    let flags = read_u16(buf, 2)?;
+    if flags & 0x8000 != 0 || read_u16(buf, 4)? != 1 {
+        return None;
+    }
+    let mut pos = 12;
+    let mut labels = Vec::new();
+    loop {
+        let len = *buf.get(pos)? as usize;
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        if len > 63 {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(buf.get(pos..pos + len)?).to_lowercase());
+        pos += len;
+    }
+    let qtype = read_u16(buf, pos)?;
+    let qclass = read_u16(buf, pos + 2)?;
+    Some(Query {
+        id,
+        flags,
+        name: labels.join("."),
+        qtype,
+        qclass,
+        question: buf[12..pos + 4].to_vec(),
+    })
+}
+
+fn build_response(query: &Query, domain: &str, ips: &[IpAddr], ttl: u32) -> Vec<u8> {
+    let opcode = (query.flags >> 11) & 0xf;
+    let rcode = if opcode != 0 {
+        RCODE_NOTIMP
+    } else if !query
+        .name
+        .eq_ignore_ascii_case(domain.trim_end_matches('.'))
+    {
+        RCODE_NXDOMAIN
+    } else {
+        0
+    };
+    let mut answers = if rcode == 0 && query.qclass == CLASS_IN {
+        ips.iter()
+            .filter_map(|ip| match (ip, query.qtype) {
+                (IpAddr::V4(ip), TYPE_A) => Some((TYPE_A, ip.octets().to_vec())),
+                (IpAddr::V6(ip), TYPE_AAAA) => Some((TYPE_AAAA, ip.octets().to_vec())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+    // Name pointer, type, class, ttl and length precede the data of a record
+    let mut size = 12 + query.question.len();
+    let fitting = answers
+        .iter()
+        .take_while(|(_, rdata)| {
+            size += 12 + rdata.len();
+            size <= MAX_UDP_SIZE
+        })
+        .count();
+    answers.truncate(fitting);
+    // Response, authoritative, recursion-desired copied from the query
+    let flags = 0x8000 | 0x0400 | (query.flags & 0x7900) | rcode;
+    let mut resp = Vec::new();
+    resp.extend(query.id.to_be_bytes());
+    resp.extend(flags.to_be_bytes());
+    resp.extend(1u16.to_be_bytes());
+    resp.extend((answers.len() as u16).to_be_bytes());
+    resp.extend(0u16.to_be_bytes());
+    resp.extend(0u16.to_be_bytes());
+    resp.extend(&query.question);
+    for (rtype, rdata) in answers {
+        // Pointer to the name in the question
+        resp.extend(0xc00cu16.to_be_bytes());
+        resp.extend(rtype.to_be_bytes());
+        resp.extend(CLASS_IN.to_be_bytes());
+        resp.extend(ttl.to_be_bytes());
+        resp.extend((rdata.len() as u16).to_be_bytes());
+        resp.extend(rdata);
+    }
+    resp
+}
+
+// Crawls the network periodically and answers A/AAAA queries for the domain
+// with a random sample of the healthy nodes
+pub async fn run(config: SeederConfig) -> Result<(), NodeError> {
+    let nodes = Arc::new(RwLock::new(Vec::<IpAddr>::new()));
+    let crawler = {
+        let nodes = Arc::clone(&nodes);
+        let config = config.clone();
+        async move {
+            loop {
+                let snapshot = crawl(config.seeds.clone(), &config.crawler).await;
+                let healthy = healthy_nodes(&snapshot, config.port, config.max_height_lag);
+                tracing::info!(
+                    crawled = snapshot.nodes.len(),
+                    healthy = healthy.len(),
+                    "Seeder crawl finished"
+                );
+                *nodes.write().await = healthy;
+                tokio::time::sleep(config.crawl_interval).await;
+            }
+        }
+    };
+    tokio::spawn(crawler);
+
+    let socket = UdpSocket::bind(config.listen).await?;
+    let mut buf = [0u8; 512];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        let query = match parse_query(&buf[..len]) {
+            Some(query) => query,
+            None => {
+                continue;
+            }
+        };
+        let ips = sample_nodes(&nodes.read().await, query.qtype, config.max_records);
+        let resp = build_response(&query, &config.domain, &ips, config.ttl);
+        if let Err(e) = socket.send_to(&resp, from).await {
+            tracing::debug!(error = %e, "Could not answer DNS query");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::crawler::CrawledNode;
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut buf = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            buf.push(label.len() as u8);
+            buf.extend(label.as_bytes());
+        }
+        buf.push(0);
+        buf.extend(qtype.to_be_bytes());
+        buf.extend(CLASS_IN.to_be_bytes());
+        buf
+    }
+
+    fn node(ip: &str, port: u16, reachable: bool, height: usize) -> CrawledNode {
+        CrawledNode {
+            address: PeerAddress(ip.parse().unwrap(), port),
+            reachable: Some(reachable),
+            latency_ms: None,
+            height: Some(height),
+            version: None,
+            user_agent: None,
+            peers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_healthy_nodes() {
+        let snapshot = NetworkSnapshot {
+            timestamp: 0,
+            nodes: vec![
+                node("1.1.1.1", 3030, true, 100),
+                node("2.2.2.2", 3030, true, 95),
+                node("3.3.3.3", 3030, true, 10),
+                node("4.4.4.4", 3030, false, 100),
+                node("5.5.5.5", 4040, true, 100),
+            ],
+        };
+        assert_eq!(
+            healthy_nodes(&snapshot, 3030, 10),
+            vec![
+                "1.1.1.1".parse::<IpAddr>().unwrap(),
+                "2.2.2.2".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dns_response() {
+        let ips: Vec<IpAddr> = vec!["1.2.3.4".parse().unwrap(), "::1".parse().unwrap()];
+
+        let q = parse_query(&query("Seed.Example.com", TYPE_A)).unwrap();
+        assert_eq!(q.name, "seed.example.com");
+        let resp = build_response(&q, "seed.example.com.", &ips, 60);
+        assert_eq!(&resp[..2], &[0x12, 0x34]);
+        // No error, a single A record at the end
+        assert_eq!(read_u16(&resp, 2).unwrap() & 0xf, 0);
+        assert_eq!(read_u16(&resp, 6), Some(1));
+        assert_eq!(&resp[resp.len() - 6..], &[0, 4, 1, 2, 3, 4]);
+
+        let q = parse_query(&query("seed.example.com", TYPE_AAAA)).unwrap();
+        let resp = build_response(&q, "seed.example.com", &ips, 60);
+        assert_eq!(read_u16(&resp, 6), Some(1));
+        assert_eq!(resp[resp.len() - 1], 1);
+
+        let q = parse_query(&query("other.example.com", TYPE_A)).unwrap();
+        let resp = build_response(&q, "seed.example.com", &ips, 60);
+        assert_eq!(read_u16(&resp, 2).unwrap() & 0xf, RCODE_NXDOMAIN);
+        assert_eq!(read_u16(&resp, 6), Some(0));
+
+        assert!(parse_query(&[0x12, 0x34]).is_none());
+    }
+
+    #[test]
+    fn test_dns_response_size() {
+        let v4: Vec<IpAddr> = (0..100u8).map(|i| [10, 0, 0, i].into()).collect();
+        let v6: Vec<IpAddr> = (0..100u16)
+            .map(|i| [0xfd00, 0, 0, 0, 0, 0, 0, i].into())
+            .collect();
+        let nodes = v6.iter().chain(v4.iter()).cloned().collect::<Vec<_>>();
+
+        // The sample is taken among the nodes of the asked family only
+        let sample = sample_nodes(&nodes, TYPE_A, 50);
+        assert_eq!(sample.len(), 50);
+        assert!(sample.iter().all(|ip| ip.is_ipv4()));
+        assert!(sample_nodes(&nodes, 16, 50).is_empty());
+
+        for (qtype, records) in [(TYPE_A, 29), (TYPE_AAAA, 17)] {
+            let q = parse_query(&query("seed.example.com", qtype)).unwrap();
+            let resp = build_response(&q, "seed.example.com", &nodes, 60);
+            assert!(resp.len() <= MAX_UDP_SIZE);
+            assert_eq!(read_u16(&resp, 6), Some(records));
+        }
+    }
+}
@@ -14,3 +14,38 @@ pub fn local_timestamp() -> u32 {
 pub fn median<T: Clone>(inps: &Vec<T>) -> T {
     inps[inps.len() / 2].clone()
 }
+
+// Median of the values after rejecting outliers which are further than
+// three median-absolute-deviations away from the median.
+pub fn robust_median(inps: &Vec<usize>) -> Option<usize> {
+    if inps.is_empty() {
+        return None;
+    }
+    let mut sorted = inps.clone();
+    sorted.sort_unstable();
+    let med = median(&sorted);
+    let mut deviations = sorted
+        .iter()
+        .map(|v| if *v > med { v - med } else { med - v })
+        .collect::<Vec<_>>();
+    deviations.sort_unstable();
+    let mad = median(&deviations);
+    let inliers = sorted
+        .into_iter()
+        .filter(|v| (if *v > med { v - med } else { med - v }) <= 3 * mad)
+        .collect::<Vec<_>>();
+    Some(median(&inliers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robust_median() {
+        assert_eq!(robust_median(&vec![]), None);
+        assert_eq!(robust_median(&vec![10]), Some(10));
+        assert_eq!(robust_median(&vec![100, 101, 99, 100, 1000000]), Some(100));
+        assert_eq!(robust_median(&vec![5, 7, 6]), Some(6));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::core::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressBookEntry {
+    pub address: Address,
+    pub label: String,
+    pub note: Option<String>,
+}
+
+// Known addresses of the wallet owner, keyed by their (unique) labels.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressBook(BTreeMap<String, AddressBookEntry>);
+
+impl AddressBook {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+    // Adds a new entry, replacing any entry with the same label
+    pub fn add(&mut self, address: Address, label: String, note: Option<String>) {
+        self.0.insert(
+            label.clone(),
+            AddressBookEntry {
+                address,
+                label,
+                note,
+            },
+        );
+    }
+    pub fn remove(&mut self, label: &str) -> Option<AddressBookEntry> {
+        self.0.remove(label)
+    }
+    pub fn get(&self, label: &str) -> Option<&AddressBookEntry> {
+        self.0.get(label)
+    }
+    pub fn find(&self, address: &Address) -> Option<&AddressBookEntry> {
+        self.0.values().find(|e| e.address == *address)
+    }
+    pub fn entries(&self) -> impl Iterator<Item = &AddressBookEntry> {
+        self.0.values()
+    }
+}
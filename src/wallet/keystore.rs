@@ -0,0 +1,203 @@
+use super::{AddressBook, Wallet};
+use crate::core::{Money, Transaction};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use thiserror::Error;
+
+// Bumped whenever the layout of the file changes
+const WALLET_FILE_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum WalletFileError {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("wallet file corrupted: {0}")]
+    Corrupted(#[from] bincode::Error),
+    #[error("wallet file version {0} not supported")]
+    UnsupportedVersion(u8),
+    #[error("key derivation failed: {0}")]
+    KeyDerivationFailed(String),
+    // AEAD can't tell these two apart
+    #[error("wrong password or tampered wallet file")]
+    Decryption,
+}
+
+// Argon2id costs, stored along with the file so that they can be raised
+// without breaking the existing wallets
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WalletFile {
+    version: u8,
+    kdf: KdfParams,
+    salt: [u8; SALT_LENGTH],
+    nonce: [u8; NONCE_LENGTH],
+    ciphertext: Vec<u8>,
+}
+
+// What gets encrypted. Derived wallets aren't stored, they are re-derived
+// from the seed and the used indices.
+#[derive(Serialize, Deserialize)]
+struct WalletState {
+    seed: Vec<u8>,
+    nonce: u32,
+    balance: Money,
+    locked: Money,
+    pending: Vec<Transaction>,
+    address_book: AddressBook,
+    used_indices: BTreeSet<u32>,
+}
+
+fn derive_key(password: &str, salt: &[u8], kdf: KdfParams) -> Result<[u8; 32], WalletFileError> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| WalletFileError::KeyDerivationFailed(e.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| WalletFileError::KeyDerivationFailed(e.to_string()))?;
+    Ok(key)
+}
+
+impl Wallet {
+    // Encrypts the wallet with a key derived from the password. A fresh salt
+    // and nonce are used on every save.
+    pub fn save<P: AsRef<Path>>(&self, path: P, password: &str) -> Result<(), WalletFileError> {
+        self.save_with(path, password, KdfParams::default())
+    }
+
+    fn save_with<P: AsRef<Path>>(
+        &self,
+        path: P,
+        password: &str,
+        kdf: KdfParams,
+    ) -> Result<(), WalletFileError> {
+        let state = WalletState {
+            seed: self.seed.clone(),
+            nonce: self.nonce,
+            balance: self.balance,
+            locked: self.locked,
+            pending: self.pending.clone(),
+            address_book: self.address_book.clone(),
+            used_indices: self.used_indices.clone(),
+        };
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let key = derive_key(password, &salt, kdf)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                bincode::serialize(&state)?.as_ref(),
+            )
+            .expect("wallet state is small enough to encrypt");
+        let file = WalletFile {
+            version: WALLET_FILE_VERSION,
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        };
+        // Written aside and renamed, so that a crash never leaves a
+        // half-written wallet behind
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bincode::serialize(&file)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, password: &str) -> Result<Self, WalletFileError> {
+        let file: WalletFile = bincode::deserialize(&std::fs::read(path)?)?;
+        if file.version != WALLET_FILE_VERSION {
+            return Err(WalletFileError::UnsupportedVersion(file.version));
+        }
+        let key = derive_key(password, &file.salt, file.kdf)?;
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(Nonce::from_slice(&file.nonce), file.ciphertext.as_ref())
+            .map_err(|_| WalletFileError::Decryption)?;
+        let state: WalletState = bincode::deserialize(&plaintext)?;
+        Ok(Self {
+            seed: state.seed,
+            nonce: state.nonce,
+            balance: state.balance,
+            locked: state.locked,
+            pending: state.pending,
+            address_book: state.address_book,
+            used_indices: state.used_indices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Account;
+
+    // Cheap costs, the defaults are slow in debug builds
+    const TEST_KDF: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_wallet_file() {
+        let path = std::env::temp_dir().join(format!("bazuka-wallet-{}", rand::random::<u64>()));
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        wallet.update_account(&Account {
+            balance: 1000,
+            nonce: 3,
+            locked: None,
+        });
+        let dst = Wallet::new(b"other seed".to_vec()).get_address();
+        wallet.create_transaction(dst, 100, 1);
+        wallet.next_derived();
+        wallet.save_with(&path, "password", TEST_KDF).unwrap();
+
+        let loaded = Wallet::load(&path, "password").unwrap();
+        assert_eq!(loaded.seed, wallet.seed);
+        assert_eq!(loaded.next_nonce(), wallet.next_nonce());
+        assert_eq!(loaded.balance(), wallet.balance());
+        assert_eq!(loaded.pending(), wallet.pending());
+        assert_eq!(loaded.used_indices(), wallet.used_indices());
+
+        assert!(matches!(
+            Wallet::load(&path, "wrong password"),
+            Err(WalletFileError::Decryption)
+        ));
+
+        // Flipping a single bit of the ciphertext is detected
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            Wallet::load(&path, "password"),
+            Err(WalletFileError::Decryption)
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
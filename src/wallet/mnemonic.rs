@@ -0,0 +1,56 @@
+use bip39::Mnemonic;
+use rand::RngCore;
+use thiserror::Error;
+
+// Words of the generated mnemonics, 11 bits each: 256 bits of entropy plus an
+// 8-bit checksum
+pub const MNEMONIC_WORDS: usize = 24;
+const MNEMONIC_ENTROPY: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum MnemonicError {
+    #[error("mnemonic invalid: {0}")]
+    Invalid(#[from] bip39::Error),
+}
+
+// A fresh BIP39 mnemonic, from the English wordlist
+pub fn generate_mnemonic() -> String {
+    let mut entropy = [0u8; MNEMONIC_ENTROPY];
+    rand::thread_rng().fill_bytes(&mut entropy);
+    Mnemonic::from_entropy(&entropy)
+        .expect("entropy has a valid length")
+        .to_string()
+}
+
+// Checks the words and the checksum, and stretches the mnemonic into a 64-byte
+// seed. Every passphrase leads to a different (But valid) seed, so a wrong one
+// can't be detected.
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> Result<Vec<u8>, MnemonicError> {
+    Ok(Mnemonic::parse(mnemonic)?.to_seed(passphrase).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mnemonic_seed() {
+        // Test vector of the BIP39 reference implementation
+        let mnemonic = format!("{} art", ["abandon"; 23].join(" "));
+        assert_eq!(
+            hex::encode(mnemonic_to_seed(&mnemonic, "TREZOR").unwrap()),
+            "bda85446c68413707090a52022edd26a1c9462295029f2e60cd7c4f2bbd3097170af7a4d73245cafa9c3cca8d561a7c3de6f5d4a10be8ed2a5e608d68f92fcc8"
+        );
+
+        let generated = generate_mnemonic();
+        assert_eq!(generated.split(' ').count(), MNEMONIC_WORDS);
+        assert_ne!(
+            mnemonic_to_seed(&generated, "").unwrap(),
+            mnemonic_to_seed(&generated, "TREZOR").unwrap()
+        );
+
+        // Bad checksum
+        let mnemonic = ["abandon"; 24].join(" ");
+        assert!(mnemonic_to_seed(&mnemonic, "").is_err());
+    }
+}
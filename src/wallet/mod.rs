@@ -1,31 +1,624 @@
-use crate::core::{Address, Money, Signature, Transaction, TransactionData};
+use crate::core::hash::domains;
+use crate::core::{
+    message_signing_bytes, Account, Address, Alert, Checkpoint, CheckpointSignature, ContractId,
+    ContractPayment, GovernanceParam, Hasher, Header, Money, PaymentDirection, ProposalId,
+    Signature, Transaction, TransactionData, TransactionId,
+};
 use crate::crypto::{EdDSA, SignatureScheme};
+use crate::zk::{ZkProof, ZkScalar, ZkStateData, ZkStateModel, ZkVerifierKey};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+mod address_book;
+pub use address_book::*;
+mod keystore;
+pub use keystore::*;
+mod mnemonic;
+pub use mnemonic::*;
+mod payment_request;
+pub use payment_request::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletBalance {
+    pub confirmed: Money,
+    // Confirmed balance minus the locked funds and the in-flight (Pending)
+    // spends and their fees
+    pub spendable: Money,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum WalletError {
+    #[error("transaction is not pending")]
+    TransactionNotPending,
+    #[error("new fee must be higher than {0}")]
+    FeeNotHigher(Money),
+    #[error("balance insufficient")]
+    BalanceInsufficient,
+}
 
 #[derive(Debug, Clone)]
 pub struct Wallet {
     seed: Vec<u8>,
+    // Last confirmed account state
     nonce: u32,
+    balance: Money,
+    // Immature block rewards and unbonding stake, which can't be spent yet
+    locked: Money,
+    // Transactions sent but not yet confirmed
+    pending: Vec<Transaction>,
+    address_book: AddressBook,
+    // Indices of the derived wallets that have been handed out
+    used_indices: BTreeSet<u32>,
 }
 
 impl Wallet {
     pub fn new(seed: Vec<u8>) -> Self {
-        Self { seed, nonce: 0 }
+        Self {
+            seed,
+            nonce: 0,
+            balance: 0,
+            locked: 0,
+            pending: Vec::new(),
+            address_book: AddressBook::new(),
+            used_indices: BTreeSet::new(),
+        }
+    }
+    // Child wallet at the given index, i.e. one seed for many addresses. Only
+    // hardened derivation is possible, every child needs the parent seed.
+    pub fn derive(&self, index: u32) -> Wallet {
+        let data = [&self.seed[..], &index.to_le_bytes()].concat();
+        Wallet::new(domains::hash_hd_derivation::<Hasher>(&data).to_vec())
+    }
+    // Hands out the lowest index not in use yet, e.g. for a fresh deposit
+    // address per customer
+    pub fn next_derived(&mut self) -> (u32, Wallet) {
+        let index = (0..=u32::MAX)
+            .find(|i| !self.used_indices.contains(i))
+            .expect("all indices are in use");
+        self.used_indices.insert(index);
+        (index, self.derive(index))
+    }
+    // Indices handed out elsewhere, e.g. restored from a backup
+    pub fn mark_used(&mut self, index: u32) {
+        self.used_indices.insert(index);
+    }
+    pub fn used_indices(&self) -> &BTreeSet<u32> {
+        &self.used_indices
+    }
+    // Index of the derived wallet owning the address, among the used ones
+    pub fn find_derived(&self, addr: &Address) -> Option<u32> {
+        self.used_indices
+            .iter()
+            .copied()
+            .find(|i| self.derive(*i).get_address() == *addr)
+    }
+    // Restores a wallet backed up as a BIP39 mnemonic
+    pub fn from_mnemonic(mnemonic: &str, passphrase: &str) -> Result<Self, MnemonicError> {
+        Ok(Self::new(mnemonic_to_seed(mnemonic, passphrase)?))
+    }
+    pub fn address_book(&self) -> &AddressBook {
+        &self.address_book
+    }
+    pub fn address_book_mut(&mut self) -> &mut AddressBook {
+        &mut self.address_book
+    }
+    pub fn payment_request(&self, amount: Option<Money>, memo: Option<String>) -> PaymentRequest {
+        PaymentRequest {
+            address: self.get_address(),
+            amount,
+            memo,
+        }
+    }
+    // Sync the wallet with the confirmed account state on the chain,
+    // pending transactions which got confirmed are forgotten.
+    pub fn update_account(&mut self, account: &Account) {
+        self.nonce = account.nonce;
+        self.balance = account.balance;
+        self.pending.retain(|tx| tx.nonce > account.nonce);
+    }
+    pub fn update_locked(&mut self, locked: Money) {
+        self.locked = locked;
+    }
+    // Syncs with the account state served by a node (`GET /account`). The
+    // pending transactions are returned as they were signed, for resubmission.
+    // They are never signed again with other nonces, both versions would be
+    // valid and the payment could be executed twice. Ones stuck behind a gap
+    // (Confirmations undone by a reorg, not coming back) have to be replaced
+    // explicitly.
+    pub fn sync(&mut self, account: &Account, locked: Money) -> Vec<Transaction> {
+        self.update_account(account);
+        self.update_locked(locked);
+        self.pending.clone()
+    }
+    pub fn pending(&self) -> &Vec<Transaction> {
+        &self.pending
+    }
+    pub fn balance(&self) -> WalletBalance {
+        let in_flight: Money = self
+            .pending
+            .iter()
+            .map(|tx| {
+                tx.fee
+                    + match &tx.data {
+                        TransactionData::RegularSend { amount, .. }
+                        | TransactionData::LockedSend { amount, .. }
+                        | TransactionData::RegisterValidator { amount, .. }
+                        | TransactionData::Stake { amount } => *amount,
+                        TransactionData::RegularSendMany { outputs } => {
+                            outputs.iter().map(|(_, amount)| amount).sum()
+                        }
+                        _ => 0,
+                    }
+            })
+            .sum();
+        WalletBalance {
+            confirmed: self.balance,
+            spendable: self
+                .balance
+                .saturating_sub(self.locked)
+                .saturating_sub(in_flight),
+        }
+    }
+    // Nonce of the next transaction, taking in-flight transactions into account
+    pub fn next_nonce(&self) -> u32 {
+        self.pending.last().map(|tx| tx.nonce).unwrap_or(self.nonce) + 1
     }
     pub fn get_address(&self) -> Address {
         let (pk, _) = EdDSA::generate_keys(&self.seed);
         Address::PublicKey(pk)
     }
-    pub fn create_transaction(&self, dst: Address, amount: Money, fee: Money) -> Transaction {
-        let (_, sk) = EdDSA::generate_keys(&self.seed);
+    fn sign_transaction(&self, dst: Address, amount: Money, fee: Money) -> Transaction {
+        self.sign_transaction_data(TransactionData::RegularSend { dst, amount }, fee)
+    }
+    fn sign_transaction_data(&self, data: TransactionData, fee: Money) -> Transaction {
         let mut tx = Transaction {
             src: self.get_address(),
-            data: TransactionData::RegularSend { dst, amount },
-            nonce: self.nonce,
+            data,
+            nonce: self.next_nonce(),
             fee,
             sig: Signature::Unsigned,
         };
+        self.sign(&mut tx);
+        tx
+    }
+    fn sign(&self, tx: &mut Transaction) {
+        let (_, sk) = EdDSA::generate_keys(&self.seed);
+        tx.sig = Signature::Unsigned;
         let bytes = bincode::serialize(&tx).unwrap();
         tx.sig = Signature::Signed(EdDSA::sign(&sk, &bytes));
+    }
+    // Re-signs a stuck pending transaction with a higher fee. It keeps the
+    // nonce, so that the network replaces the original (If it hasn't been
+    // confirmed in the meantime) instead of applying both.
+    pub fn bump_fee(
+        &mut self,
+        txid: &TransactionId,
+        new_fee: Money,
+    ) -> Result<Transaction, WalletError> {
+        let spendable = self.balance().spendable;
+        let index = self
+            .pending
+            .iter()
+            .position(|tx| tx.txid::<Hasher>() == *txid)
+            .ok_or(WalletError::TransactionNotPending)?;
+        let mut bumped = self.pending[index].clone();
+        if new_fee <= bumped.fee {
+            return Err(WalletError::FeeNotHigher(bumped.fee));
+        }
+        if new_fee - bumped.fee > spendable {
+            return Err(WalletError::BalanceInsufficient);
+        }
+        bumped.fee = new_fee;
+        self.sign(&mut bumped);
+        self.pending[index] = bumped.clone();
+        Ok(bumped)
+    }
+    pub fn create_transaction(&mut self, dst: Address, amount: Money, fee: Money) -> Transaction {
+        let tx = self.sign_transaction(dst, amount, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // The id of the contract is known before it's even submitted, since it's
+    // the hash of the creation transaction
+    pub fn create_contract(
+        &mut self,
+        deposit_withdraw_circuit: ZkVerifierKey,
+        update_circuits: Vec<ZkVerifierKey>,
+        initial_state: ZkStateData,
+        state_model: ZkStateModel,
+        fee: Money,
+    ) -> (Transaction, ContractId) {
+        let tx = self.sign_transaction_data(
+            TransactionData::CreateContract {
+                deposit_withdraw_circuit,
+                update_circuits,
+                initial_state,
+                state_model,
+            },
+            fee,
+        );
+        self.pending.push(tx.clone());
+        let contract_id = ContractId::new(&tx);
+        (tx, contract_id)
+    }
+    // A deposit into (Or withdrawal from) a contract, signed by this wallet as
+    // the initiator. It doesn't go on-chain by itself, but gets batched by an
+    // executor into a DepositWithdraw transaction. `nonce` is per contract and
+    // initiator, one more than the one of the last payment.
+    pub fn create_contract_payment(
+        &self,
+        contract_id: ContractId,
+        nonce: usize,
+        amount: Money,
+        fee: Money,
+        direction: PaymentDirection,
+    ) -> ContractPayment {
+        let (_, sk) = EdDSA::generate_keys(&self.seed);
+        let mut payment = ContractPayment {
+            initiator: self.get_address(),
+            contract_id,
+            nonce,
+            amount,
+            fee,
+            direction,
+            sig: Signature::Unsigned,
+        };
+        let bytes = bincode::serialize(&payment).unwrap();
+        payment.sig = Signature::Signed(EdDSA::sign(&sk, &bytes));
+        payment
+    }
+    // Executes the payments on the contract, proving the transition of its
+    // state. The fees of the payments go to this wallet.
+    pub fn deposit_withdraw(
+        &mut self,
+        contract_id: ContractId,
+        deposit_withdraws: Vec<ContractPayment>,
+        next_state: ZkScalar,
+        proof: ZkProof,
+        fee: Money,
+    ) -> Transaction {
+        let tx = self.sign_transaction_data(
+            TransactionData::DepositWithdraw {
+                contract_id,
+                deposit_withdraws,
+                next_state,
+                proof,
+            },
+            fee,
+        );
+        self.pending.push(tx.clone());
+        tx
+    }
+    pub fn update_contract(
+        &mut self,
+        contract_id: ContractId,
+        circuit_index: u32,
+        next_state: ZkScalar,
+        proof: ZkProof,
+        fee: Money,
+    ) -> Transaction {
+        let tx = self.sign_transaction_data(
+            TransactionData::Update {
+                contract_id,
+                circuit_index,
+                next_state,
+                proof,
+            },
+            fee,
+        );
+        self.pending.push(tx.clone());
         tx
     }
+    // One transaction paying all of the outputs, for a single fee and nonce
+    pub fn create_batch_transaction(
+        &mut self,
+        outputs: Vec<(Address, Money)>,
+        fee: Money,
+    ) -> Transaction {
+        let tx = self.sign_transaction_data(TransactionData::RegularSendMany { outputs }, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Sends money that stays locked until the given height
+    pub fn create_locked_transaction(
+        &mut self,
+        dst: Address,
+        amount: Money,
+        until: u64,
+        fee: Money,
+    ) -> Transaction {
+        let tx =
+            self.sign_transaction_data(TransactionData::LockedSend { dst, amount, until }, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Stakes `amount` for proposing blocks with the given VRF key (PoS)
+    pub fn register_validator(
+        &mut self,
+        vrf_public_key: Vec<u8>,
+        amount: Money,
+        fee: Money,
+    ) -> Transaction {
+        let tx = self.sign_transaction_data(
+            TransactionData::RegisterValidator {
+                vrf_public_key,
+                amount,
+            },
+            fee,
+        );
+        self.pending.push(tx.clone());
+        tx
+    }
+    pub fn stake(&mut self, amount: Money, fee: Money) -> Transaction {
+        let tx = self.sign_transaction_data(TransactionData::Stake { amount }, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Unstaked funds are only spendable after the unbonding period
+    pub fn unstake(&mut self, amount: Money, fee: Money) -> Transaction {
+        let tx = self.sign_transaction_data(TransactionData::Unstake { amount }, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Governance is for validators only, the id of the proposal is what the
+    // others vote on
+    pub fn propose(
+        &mut self,
+        param: GovernanceParam,
+        value: u64,
+        fee: Money,
+    ) -> (Transaction, ProposalId) {
+        let tx = self.sign_transaction_data(TransactionData::Propose { param, value }, fee);
+        self.pending.push(tx.clone());
+        let proposal_id = ProposalId::new(&tx);
+        (tx, proposal_id)
+    }
+    pub fn vote(&mut self, proposal: ProposalId, approve: bool, fee: Money) -> Transaction {
+        let tx = self.sign_transaction_data(TransactionData::Vote { proposal, approve }, fee);
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Reports a validator who sealed both of the headers
+    pub fn report_double_signing(
+        &mut self,
+        offender: Address,
+        first: Header,
+        second: Header,
+        fee: Money,
+    ) -> Transaction {
+        let tx = self.sign_transaction_data(
+            TransactionData::SlashEvidence {
+                offender,
+                first,
+                second,
+            },
+            fee,
+        );
+        self.pending.push(tx.clone());
+        tx
+    }
+    // Moves the whole spendable balance to the address of another wallet, e.g.
+    // when the key of this one is suspected to be compromised. The fee depends
+    // on the size of the transaction, which in turn depends on the fee, so it's
+    // recalculated until it settles. None if the balance can't even pay the fee.
+    pub fn sweep_to(&mut self, new_wallet: &Wallet, fee_per_byte: Money) -> Option<Transaction> {
+        let dst = new_wallet.get_address();
+        let spendable = self.balance().spendable;
+        let mut fee = 0;
+        loop {
+            let amount = spendable.checked_sub(fee)?;
+            let tx = self.sign_transaction(dst.clone(), amount, fee);
+            let required = bincode::serialized_size(&tx).unwrap() * fee_per_byte;
+            if required <= fee {
+                if amount == 0 {
+                    return None;
+                }
+                self.pending.push(tx.clone());
+                return Some(tx);
+            }
+            fee = required;
+        }
+    }
+    pub fn sign_checkpoint(&self, checkpoint: &mut Checkpoint) {
+        let (pk, sk) = EdDSA::generate_keys(&self.seed);
+        let sig = EdDSA::sign(&sk, &checkpoint.signing_bytes());
+        checkpoint
+            .signatures
+            .push(CheckpointSignature { authority: pk, sig });
+    }
+    pub fn sign_message(&self, msg: &[u8]) -> Signature {
+        let (_, sk) = EdDSA::generate_keys(&self.seed);
+        Signature::Signed(EdDSA::sign(&sk, &message_signing_bytes(msg)))
+    }
+    pub fn sign_alert(&self, alert: &mut Alert) {
+        let (_, sk) = EdDSA::generate_keys(&self.seed);
+        alert.sig = Signature::Signed(EdDSA::sign(&sk, &alert.signing_bytes()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spendable_balance() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        wallet.update_account(&Account {
+            balance: 1000,
+            nonce: 3,
+            locked: None,
+        });
+        let dst = Wallet::new(b"other seed".to_vec()).get_address();
+
+        let tx1 = wallet.create_transaction(dst.clone(), 100, 1);
+        let tx2 = wallet.create_transaction(dst, 200, 2);
+        assert_eq!(tx1.nonce, 4);
+        assert_eq!(tx2.nonce, 5);
+        assert_eq!(
+            wallet.balance(),
+            WalletBalance {
+                confirmed: 1000,
+                spendable: 697
+            }
+        );
+
+        // First transaction got confirmed
+        wallet.update_account(&Account {
+            balance: 899,
+            nonce: 4,
+            locked: None,
+        });
+        assert_eq!(wallet.pending().len(), 1);
+        assert_eq!(wallet.balance().spendable, 697);
+        assert_eq!(wallet.next_nonce(), 6);
+
+        // A fresh block reward
+        wallet.update_locked(100);
+        assert_eq!(wallet.balance().spendable, 597);
+    }
+
+    #[test]
+    fn test_sweep() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        let new_wallet = Wallet::new(b"new seed".to_vec());
+        wallet.update_account(&Account {
+            balance: 1000,
+            nonce: 0,
+            locked: None,
+        });
+        wallet.create_transaction(new_wallet.get_address(), 100, 1);
+
+        let tx = wallet.sweep_to(&new_wallet, 2).unwrap();
+        let size = bincode::serialized_size(&tx).unwrap();
+        assert_eq!(tx.fee, 2 * size);
+        assert_eq!(tx.nonce, 2);
+        match &tx.data {
+            TransactionData::RegularSend { dst, amount } => {
+                assert_eq!(*dst, new_wallet.get_address());
+                assert_eq!(*amount, 899 - tx.fee);
+            }
+            _ => panic!(),
+        }
+        assert_eq!(wallet.balance().spendable, 0);
+
+        // Nothing left to sweep
+        assert!(wallet.sweep_to(&new_wallet, 2).is_none());
+    }
+
+    #[test]
+    fn test_nonce_sync() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        wallet.sync(
+            &Account {
+                balance: 1000,
+                nonce: 5,
+                locked: None,
+            },
+            0,
+        );
+        let tx1 = wallet.create_transaction(Address::Treasury, 10, 1);
+        let tx2 = wallet.create_transaction(Address::Treasury, 10, 1);
+        assert_eq!((tx1.nonce, tx2.nonce), (6, 7));
+
+        // The first one got confirmed
+        let account = Account {
+            balance: 989,
+            nonce: 6,
+            locked: None,
+        };
+        assert_eq!(wallet.sync(&account, 0), vec![tx2.clone()]);
+        assert_eq!(wallet.pending(), &vec![tx2.clone()]);
+
+        // The confirmations got undone, the pending one is resubmitted as it
+        // was signed
+        let pending = wallet.sync(
+            &Account {
+                balance: 1000,
+                nonce: 3,
+                locked: None,
+            },
+            0,
+        );
+        assert_eq!(pending, vec![tx2]);
+        assert_eq!(wallet.next_nonce(), 8);
+    }
+
+    #[test]
+    fn test_contract_transactions() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        let (tx, contract_id) = wallet.create_contract(
+            serde_json::from_str("[1, 2, 3]").unwrap(),
+            vec![],
+            serde_json::from_str("{}").unwrap(),
+            serde_json::from_str(r#"{"leaf_size": 1, "tree_depth": 8}"#).unwrap(),
+            1,
+        );
+        assert!(tx.verify_signature());
+        assert_eq!(contract_id, ContractId::new(&tx));
+
+        let payment = wallet.create_contract_payment(
+            contract_id.clone(),
+            1,
+            100,
+            2,
+            PaymentDirection::Deposit,
+        );
+        assert!(payment.verify_signature());
+        assert_eq!(payment.initiator, wallet.get_address());
+        assert_eq!(payment.contract_id, contract_id);
+    }
+
+    #[test]
+    fn test_bump_fee() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        wallet.update_account(&Account {
+            balance: 1000,
+            nonce: 0,
+            locked: None,
+        });
+        let tx = wallet.create_transaction(Address::Treasury, 100, 1);
+        let txid = tx.txid::<Hasher>();
+        assert_eq!(wallet.bump_fee(&txid, 1), Err(WalletError::FeeNotHigher(1)));
+        assert_eq!(
+            wallet.bump_fee(&txid, 1000),
+            Err(WalletError::BalanceInsufficient)
+        );
+
+        let bumped = wallet.bump_fee(&txid, 10).unwrap();
+        assert_eq!(bumped.nonce, tx.nonce);
+        assert_eq!(bumped.fee, 10);
+        assert!(bumped.verify_signature());
+        assert_eq!(wallet.pending(), &vec![bumped.clone()]);
+        assert_eq!(wallet.balance().spendable, 890);
+
+        // The original is gone
+        assert_eq!(
+            wallet.bump_fee(&txid, 20),
+            Err(WalletError::TransactionNotPending)
+        );
+        assert!(wallet.bump_fee(&bumped.txid::<Hasher>(), 20).is_ok());
+    }
+
+    #[test]
+    fn test_hd_derivation() {
+        let mut wallet = Wallet::new(b"test seed".to_vec());
+        assert_eq!(wallet.derive(1).seed, wallet.derive(1).seed);
+        assert_ne!(wallet.derive(1).seed, wallet.derive(2).seed);
+        assert_ne!(
+            wallet.derive(1).seed,
+            Wallet::new(b"other seed".to_vec()).derive(1).seed
+        );
+
+        assert_eq!(wallet.next_derived().0, 0);
+        wallet.mark_used(2);
+        assert_eq!(wallet.next_derived().0, 1);
+        let (index, child) = wallet.next_derived();
+        assert_eq!(index, 3);
+        assert_eq!(child.seed, wallet.derive(3).seed);
+        assert_eq!(
+            wallet.used_indices().iter().copied().collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(wallet.find_derived(&child.get_address()).is_some());
+    }
 }
@@ -0,0 +1,95 @@
+use crate::core::{Address, Money};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const PAYMENT_URI_SCHEME: &str = "zeeka";
+
+#[derive(Error, Debug)]
+pub enum PaymentRequestError {
+    #[error("payment uri scheme invalid")]
+    InvalidScheme,
+    #[error("payment address invalid")]
+    InvalidAddress,
+    #[error("payment parameters invalid: {0}")]
+    InvalidParams(#[from] serde_qs::Error),
+}
+
+// A request for payment, encoded as `zeeka:<address>?amount=<amount>&memo=<memo>`
+// so that it can be shared as a link or a QR code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    pub amount: Option<Money>,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PaymentRequestParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    amount: Option<Money>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+}
+
+impl std::fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", PAYMENT_URI_SCHEME, self.address)?;
+        let params = serde_qs::to_string(&PaymentRequestParams {
+            amount: self.amount,
+            memo: self.memo.clone(),
+        })
+        .map_err(|_| std::fmt::Error)?;
+        if !params.is_empty() {
+            write!(f, "?{}", params)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PaymentRequest {
+    type Err = PaymentRequestError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix(PAYMENT_URI_SCHEME)
+            .and_then(|r| r.strip_prefix(':'))
+            .ok_or(PaymentRequestError::InvalidScheme)?;
+        let mut parts = rest.splitn(2, '?');
+        let address = parts
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|_| PaymentRequestError::InvalidAddress)?;
+        let params: PaymentRequestParams = serde_qs::from_str(parts.next().unwrap_or_default())?;
+        Ok(Self {
+            address,
+            amount: params.amount,
+            memo: params.memo,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn test_payment_request_roundtrip() {
+        let address = Wallet::new(b"merchant".to_vec()).get_address();
+        for (amount, memo) in [
+            (None, None),
+            (Some(123), None),
+            (Some(5), Some("Order #12 & more".to_string())),
+        ] {
+            let req = PaymentRequest {
+                address: address.clone(),
+                amount,
+                memo,
+            };
+            let uri = req.to_string();
+            assert!(uri.starts_with("zeeka:0x"));
+            assert_eq!(uri.parse::<PaymentRequest>().unwrap(), req);
+        }
+        assert!("bitcoin:abc".parse::<PaymentRequest>().is_err());
+    }
+}
@@ -31,6 +31,34 @@ pub struct ZkCompressedState {
     state_size: u32,
 }
 
+impl ZkCompressedState {
+    pub fn state_hash(&self) -> &ZkScalar {
+        &self.state_hash
+    }
+    // The state after a transition proven by a circuit
+    pub fn with_state_hash(&self, state_hash: ZkScalar) -> Self {
+        Self {
+            state_hash,
+            state_size: self.state_size,
+        }
+    }
+}
+
+// Checks a proof of `vk` for a transition of a contract from `prev_state` to
+// `next_state`, `aux_data` commits to everything else the circuit depends on.
+// Transitions are only executed by builds with the `zk` feature, others reject
+// them with `ZkProofsUnsupported`.
+pub fn check_proof(
+    _vk: &ZkVerifierKey,
+    _prev_state: &ZkScalar,
+    _aux_data: &[u8],
+    _next_state: &ZkScalar,
+    _proof: &ZkProof,
+) -> bool {
+    // No proving system is integrated yet, so there is no proof to accept
+    false
+}
+
 impl ZkStateData {
     pub fn size(&self) -> u32 {
         self.0.len() as u32
@@ -53,5 +81,11 @@ impl ZkState {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ZkVerifierKey(#[serde(with = "serde_bytes")] Vec<u8>);
 
+impl ZkVerifierKey {
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ZkProof(#[serde(with = "serde_bytes")] Vec<u8>);
@@ -55,8 +55,9 @@ impl ZkRam {
         }
         panic!("Invalid patch!");
     }
+    // Missing nodes are zero, so is the root of an empty tree
     pub fn root(&self) -> Fr {
-        *self.layers[LOG_ZK_RAM_SIZE].get(&0).expect("Tree empty!")
+        self.get(LOG_ZK_RAM_SIZE, 0)
     }
     fn get(&self, level: usize, index: u32) -> Fr {
         self.layers[level]